@@ -0,0 +1,1107 @@
+use actix_web::http::{Method, header::HeaderName};
+use lettre::message::Mailbox;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap, env, fmt::Display, fs, net::SocketAddr, path::Path, str::FromStr,
+};
+
+use crate::utils::jwt::JwtKeys;
+
+// =============================================================================================================================
+
+const CONFIG_FILE_ENV: &str = "CONFIG_FILE";
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+const MIN_SECRET_LENGTH: usize = 16;
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+// =============================================================================================================================
+
+/// Every setting the service needs, loaded once at startup by `Config::load` and injected into handlers as
+/// `web::Data<Config>`. Values come from the optional TOML file, then from environment variables, which win.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub auth: AuthConfig,
+    pub cors: CorsConfig,
+    pub uploads: UploadConfig,
+    pub stories: StoryConfig,
+    pub quotas: QuotaConfig,
+    pub storage: StorageConfig,
+    pub rate_limits: RateLimitConfig,
+    pub mail: MailConfig,
+    pub oidc: OidcConfig,
+    pub audit: AuditConfig,
+}
+
+// =============================================================================================================================
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_address: String,
+    pub environment: Environment,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: "0.0.0.0:8080".to_string(),
+            environment: Environment::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    #[default]
+    Development,
+    Production,
+}
+
+impl FromStr for Environment {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "development" | "dev" => Ok(Self::Development),
+            "production" | "prod" => Ok(Self::Production),
+            _ => Err("expected \"development\" or \"production\"".to_string()),
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub name: String,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            name: "snapshoot".to_string(),
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Directory of `<kid>.pem` signing keys, see `JwtKeys::load`. Required in production, development falls back to
+    /// a key generated at startup.
+    pub keys_dir: String,
+    /// Key that signs new tokens, defaults to the greatest key id. The others only verify.
+    pub active_key_id: Option<String>,
+    pub issuer: String,
+    pub audience: String,
+    /// Loaded from `keys_dir` by `Config::load`.
+    #[serde(skip)]
+    pub keys: JwtKeys,
+    pub token_lifetime_minutes: i64,
+    /// Consecutive failed logins that lock an account, 0 disables the lockout.
+    pub lockout_threshold: u32,
+    pub lockout_minutes: i64,
+    /// Shown by authenticator apps next to the account name.
+    pub two_factor_issuer: String,
+    /// Lifetime of the challenge token returned by login when the account has two-factor authentication.
+    pub two_factor_challenge_minutes: i64,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            keys_dir: String::new(),
+            active_key_id: None,
+            issuer: "snapshoot".to_string(),
+            audience: "snapshoot-api".to_string(),
+            keys: JwtKeys::default(),
+            token_lifetime_minutes: 60,
+            lockout_threshold: 5,
+            lockout_minutes: 15,
+            two_factor_issuer: "Snapshoot".to_string(),
+            two_factor_challenge_minutes: 5,
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Matched exactly. In development, loopback origins on any port are accepted as well.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub allow_credentials: bool,
+    pub max_age_secs: usize,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![
+                "http://localhost".to_string(),
+                "http://localhost:8100".to_string(),
+                "capacitor://localhost".to_string(),
+                "ionic://localhost".to_string(),
+            ],
+            allowed_methods: ["GET", "POST", "PUT", "PATCH", "DELETE"]
+                .map(String::from)
+                .to_vec(),
+            allowed_headers: ["Authorization", "Content-Type"].map(String::from).to_vec(),
+            allow_credentials: true,
+            max_age_secs: 3600,
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UploadConfig {
+    pub max_image_size_mb: u64,
+    pub max_video_size_mb: u64,
+    pub max_video_duration_secs: f64,
+    pub session_lifetime_hours: i64,
+}
+
+impl UploadConfig {
+    pub fn max_image_size(&self) -> usize {
+        (self.max_image_size_mb * BYTES_PER_MB) as usize
+    }
+
+    pub fn max_video_size(&self) -> usize {
+        (self.max_video_size_mb * BYTES_PER_MB) as usize
+    }
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            max_image_size_mb: 5,
+            max_video_size_mb: 10,
+            max_video_duration_secs: 10.0,
+            session_lifetime_hours: 24,
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StoryConfig {
+    pub lifetime_hours: i64,
+}
+
+impl Default for StoryConfig {
+    fn default() -> Self {
+        Self { lifetime_hours: 24 }
+    }
+}
+
+// =============================================================================================================================
+
+/// How long audit events are kept. `0` keeps them forever. A change applies to the events recorded after it.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    pub retention_days: i64,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: 365,
+        }
+    }
+}
+
+// =============================================================================================================================
+
+/// Default quotas per role, used until an admin overrides them through the API.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuotaConfig {
+    pub user_mb: i64,
+    pub admin_mb: i64,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            user_mb: 500,
+            admin_mb: 5 * 1024,
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    #[default]
+    #[serde(alias = "minio")]
+    S3,
+    Local,
+}
+
+impl FromStr for StorageKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "s3" | "minio" => Ok(Self::S3),
+            "local" => Ok(Self::Local),
+            _ => Err("expected \"s3\" or \"local\"".to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: StorageKind,
+    pub s3: S3Config,
+    pub local: LocalStorageConfig,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct S3Config {
+    pub server_url: String,
+    /// Host used in presigned URLs, must be reachable by the mobile app. Defaults to `server_url`.
+    pub public_url: Option<String>,
+    pub region: String,
+    pub bucket: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        Self {
+            server_url: String::new(),
+            public_url: None,
+            region: "us-east-1".to_string(),
+            bucket: "snapshoot-media".to_string(),
+            access_key: String::new(),
+            secret_key: String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocalStorageConfig {
+    pub path: String,
+    pub public_url: String,
+    /// Key for the presigned URL signatures.
+    pub secret: String,
+}
+
+impl Default for LocalStorageConfig {
+    fn default() -> Self {
+        Self {
+            path: "./storage".to_string(),
+            public_url: "http://localhost:8080/api/files".to_string(),
+            secret: String::new(),
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MailerKind {
+    Smtp,
+    #[default]
+    File,
+}
+
+impl FromStr for MailerKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "smtp" => Ok(Self::Smtp),
+            "file" => Ok(Self::File),
+            _ => Err("expected \"smtp\" or \"file\"".to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with STARTTLS, usually on port 587.
+    #[default]
+    StartTls,
+    /// TLS from the first byte, usually on port 465.
+    Tls,
+    /// Unencrypted, only for local catchers such as MailHog.
+    None,
+}
+
+impl FromStr for SmtpSecurity {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "starttls" => Ok(Self::StartTls),
+            "tls" => Ok(Self::Tls),
+            "none" => Ok(Self::None),
+            _ => Err("expected \"starttls\", \"tls\" or \"none\"".to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MailConfig {
+    pub backend: MailerKind,
+    pub from: String,
+    /// Base of the links sent by email, the app handles `/verify-email` and `/reset-password`.
+    pub app_url: String,
+    pub verification_token_hours: i64,
+    pub reset_token_minutes: i64,
+    pub smtp: SmtpConfig,
+    pub file: FileMailerConfig,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        Self {
+            backend: MailerKind::default(),
+            from: "Snapshoot <no-reply@snapshoot.local>".to_string(),
+            app_url: "http://localhost:8100".to_string(),
+            verification_token_hours: 24,
+            reset_token_minutes: 30,
+            smtp: SmtpConfig::default(),
+            file: FileMailerConfig::default(),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: String,
+    pub password: String,
+}
+
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: 587,
+            security: SmtpSecurity::default(),
+            username: String::new(),
+            password: String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileMailerConfig {
+    pub path: String,
+}
+
+impl Default for FileMailerConfig {
+    fn default() -> Self {
+        Self {
+            path: "./mail".to_string(),
+        }
+    }
+}
+
+// =============================================================================================================================
+
+/// Requests allowed per window for a group of routes.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateBudget {
+    pub requests: u32,
+    pub window_secs: u64,
+}
+
+impl RateBudget {
+    const fn new(requests: u32, window_secs: u64) -> Self {
+        Self {
+            requests,
+            window_secs,
+        }
+    }
+}
+
+/// Parses the `requests/window_secs` form used by the environment variables, e.g. `10/60`.
+impl FromStr for RateBudget {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (requests, window_secs) = value
+            .split_once('/')
+            .ok_or("expected requests/window_secs, e.g. 10/60")?;
+        Ok(Self {
+            requests: requests.trim().parse().map_err(|e| format!("{}", e))?,
+            window_secs: window_secs.trim().parse().map_err(|e| format!("{}", e))?,
+        })
+    }
+}
+
+/// Budgets are counted per client IP and, for authenticated requests, per user as well.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Take the client IP from `Forwarded` / `X-Forwarded-For`, only behind a proxy that sets them (Traefik).
+    pub trust_forwarded_for: bool,
+    pub login: RateBudget,
+    pub register: RateBudget,
+    pub friend_requests: RateBudget,
+    pub uploads: RateBudget,
+    /// Verification and password reset emails.
+    pub emails: RateBudget,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            trust_forwarded_for: false,
+            login: RateBudget::new(10, 60),
+            register: RateBudget::new(5, 3600),
+            friend_requests: RateBudget::new(30, 3600),
+            uploads: RateBudget::new(60, 600),
+            emails: RateBudget::new(5, 3600),
+        }
+    }
+}
+
+// =============================================================================================================================
+
+/// Sign-in through OpenID Connect providers, authorization code flow with PKCE.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OidcConfig {
+    /// Keyed by the name used in the routes, e.g. `[oidc.providers.google]`.
+    pub providers: BTreeMap<String, OidcProviderConfig>,
+    /// Time to complete the sign-in at the provider.
+    pub login_minutes: i64,
+}
+
+impl Default for OidcConfig {
+    fn default() -> Self {
+        Self {
+            providers: BTreeMap::new(),
+            login_minutes: 10,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OidcProviderConfig {
+    /// Discovery reads `<issuer>/.well-known/openid-configuration`, and ID tokens must name this issuer.
+    pub issuer: String,
+    pub client_id: String,
+    /// Public clients (PKCE only) leave it unset.
+    pub client_secret: Option<String>,
+    /// Where the provider sends the user back with the code, a page or deep link of the app.
+    pub redirect_uri: String,
+    pub scopes: Vec<String>,
+}
+
+impl Default for OidcProviderConfig {
+    fn default() -> Self {
+        Self {
+            issuer: String::new(),
+            client_id: String::new(),
+            client_secret: None,
+            redirect_uri: String::new(),
+            scopes: vec![
+                "openid".to_string(),
+                "email".to_string(),
+                "profile".to_string(),
+            ],
+        }
+    }
+}
+
+// =============================================================================================================================
+
+impl Config {
+    /// Reads the TOML file named by `CONFIG_FILE` (or `config.toml` when it exists), applies the environment on top
+    /// and validates the result. Every problem is reported at once, one per line.
+    pub fn load() -> Result<Self, String> {
+        let mut config = match optional_env(CONFIG_FILE_ENV) {
+            Some(path) => Self::from_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::from_file(DEFAULT_CONFIG_FILE)?
+            }
+            None => Self::default(),
+        };
+
+        let mut errors = config.apply_env();
+        errors.extend(config.validate());
+        if errors.is_empty() {
+            errors.extend(config.load_keys());
+        }
+
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(format!(
+                "Invalid configuration:\n  - {}",
+                errors.join("\n  - ")
+            ))
+        }
+    }
+
+    /// Dev without `JWT_KEYS_DIR` gets a throwaway key, which logs everyone out on every restart.
+    fn load_keys(&mut self) -> Option<String> {
+        let auth = &mut self.auth;
+        if auth.keys_dir.is_empty() {
+            auth.keys = JwtKeys::generate();
+            return None;
+        }
+
+        match JwtKeys::load(&auth.keys_dir, auth.active_key_id.as_deref()) {
+            Ok(keys) => {
+                auth.keys = keys;
+                None
+            }
+            Err(e) => Some(format!("JWT_KEYS_DIR: {}", e)),
+        }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let content =
+            fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        Self::from_toml(&content).map_err(|e| format!("Invalid {}: {}", path, e))
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|e| e.to_string())
+    }
+
+    /// Overrides the settings with the environment variables that are set and returns the ones that could not be
+    /// parsed. Docker Compose passes unset variables through as empty strings, which count as unset.
+    fn apply_env(&mut self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        set_string(&mut self.server.bind_address, "BIND_ADDRESS");
+        set_parsed(&mut self.server.environment, "APP_ENV", &mut errors);
+
+        set_string(&mut self.database.url, "DATABASE_URL");
+        set_string(&mut self.database.name, "DATABASE_NAME");
+
+        set_string(&mut self.auth.keys_dir, "JWT_KEYS_DIR");
+        if let Some(kid) = optional_env("JWT_ACTIVE_KEY_ID") {
+            self.auth.active_key_id = Some(kid);
+        }
+        set_string(&mut self.auth.issuer, "JWT_ISSUER");
+        set_string(&mut self.auth.audience, "JWT_AUDIENCE");
+        set_parsed(
+            &mut self.auth.token_lifetime_minutes,
+            "JWT_LIFETIME_MINUTES",
+            &mut errors,
+        );
+        set_parsed(
+            &mut self.auth.lockout_threshold,
+            "LOGIN_LOCKOUT_THRESHOLD",
+            &mut errors,
+        );
+        set_parsed(
+            &mut self.auth.lockout_minutes,
+            "LOGIN_LOCKOUT_MINUTES",
+            &mut errors,
+        );
+        set_string(&mut self.auth.two_factor_issuer, "TWO_FACTOR_ISSUER");
+        set_parsed(
+            &mut self.auth.two_factor_challenge_minutes,
+            "TWO_FACTOR_CHALLENGE_MINUTES",
+            &mut errors,
+        );
+
+        set_list(&mut self.cors.allowed_origins, "CORS_ALLOWED_ORIGINS");
+        set_list(&mut self.cors.allowed_methods, "CORS_ALLOWED_METHODS");
+        set_list(&mut self.cors.allowed_headers, "CORS_ALLOWED_HEADERS");
+        set_parsed(
+            &mut self.cors.allow_credentials,
+            "CORS_ALLOW_CREDENTIALS",
+            &mut errors,
+        );
+        set_parsed(
+            &mut self.cors.max_age_secs,
+            "CORS_MAX_AGE_SECS",
+            &mut errors,
+        );
+        // The address the mobile app is served from on the local network, as set up by docker-compose.
+        if let Some(host) = optional_env("EXTERNAL_HOST_IP") {
+            let host = host.trim_end_matches('/');
+            for origin in [host.to_string(), format!("{}:8100", host)] {
+                if !self.cors.allowed_origins.contains(&origin) {
+                    self.cors.allowed_origins.push(origin);
+                }
+            }
+        }
+
+        let uploads = &mut self.uploads;
+        set_parsed(
+            &mut uploads.max_image_size_mb,
+            "UPLOAD_MAX_IMAGE_SIZE_MB",
+            &mut errors,
+        );
+        set_parsed(
+            &mut uploads.max_video_size_mb,
+            "UPLOAD_MAX_VIDEO_SIZE_MB",
+            &mut errors,
+        );
+        set_parsed(
+            &mut uploads.max_video_duration_secs,
+            "UPLOAD_MAX_VIDEO_DURATION_SECS",
+            &mut errors,
+        );
+        set_parsed(
+            &mut uploads.session_lifetime_hours,
+            "UPLOAD_SESSION_LIFETIME_HOURS",
+            &mut errors,
+        );
+
+        set_parsed(
+            &mut self.stories.lifetime_hours,
+            "STORY_LIFETIME_HOURS",
+            &mut errors,
+        );
+
+        set_parsed(
+            &mut self.audit.retention_days,
+            "AUDIT_RETENTION_DAYS",
+            &mut errors,
+        );
+
+        set_parsed(
+            &mut self.quotas.user_mb,
+            "STORAGE_QUOTA_USER_MB",
+            &mut errors,
+        );
+        set_parsed(
+            &mut self.quotas.admin_mb,
+            "STORAGE_QUOTA_ADMIN_MB",
+            &mut errors,
+        );
+
+        let storage = &mut self.storage;
+        set_parsed(&mut storage.backend, "STORAGE_BACKEND", &mut errors);
+        set_string(&mut storage.s3.server_url, "MINIO_SERVER_URL");
+        if let Some(public_url) = optional_env("MINIO_PUBLIC_URL") {
+            storage.s3.public_url = Some(public_url);
+        }
+        set_string(&mut storage.s3.region, "MINIO_REGION");
+        set_string(&mut storage.s3.bucket, "MINIO_BUCKET");
+        set_string(&mut storage.s3.access_key, "MINIO_ROOT_USER");
+        set_string(&mut storage.s3.secret_key, "MINIO_ROOT_PASSWORD");
+        set_string(&mut storage.local.path, "LOCAL_STORAGE_PATH");
+        set_string(&mut storage.local.public_url, "LOCAL_STORAGE_PUBLIC_URL");
+        set_string(&mut storage.local.secret, "LOCAL_STORAGE_SECRET");
+
+        let rate_limits = &mut self.rate_limits;
+        set_parsed(&mut rate_limits.enabled, "RATE_LIMIT_ENABLED", &mut errors);
+        set_parsed(
+            &mut rate_limits.trust_forwarded_for,
+            "RATE_LIMIT_TRUST_FORWARDED_FOR",
+            &mut errors,
+        );
+        set_parsed(&mut rate_limits.login, "RATE_LIMIT_LOGIN", &mut errors);
+        set_parsed(
+            &mut rate_limits.register,
+            "RATE_LIMIT_REGISTER",
+            &mut errors,
+        );
+        set_parsed(
+            &mut rate_limits.friend_requests,
+            "RATE_LIMIT_FRIEND_REQUESTS",
+            &mut errors,
+        );
+        set_parsed(&mut rate_limits.uploads, "RATE_LIMIT_UPLOADS", &mut errors);
+        set_parsed(&mut rate_limits.emails, "RATE_LIMIT_EMAILS", &mut errors);
+
+        let mail = &mut self.mail;
+        set_parsed(&mut mail.backend, "MAILER", &mut errors);
+        set_string(&mut mail.from, "MAIL_FROM");
+        set_string(&mut mail.app_url, "MAIL_APP_URL");
+        set_parsed(
+            &mut mail.verification_token_hours,
+            "MAIL_VERIFICATION_TOKEN_HOURS",
+            &mut errors,
+        );
+        set_parsed(
+            &mut mail.reset_token_minutes,
+            "MAIL_RESET_TOKEN_MINUTES",
+            &mut errors,
+        );
+        set_string(&mut mail.smtp.host, "SMTP_HOST");
+        set_parsed(&mut mail.smtp.port, "SMTP_PORT", &mut errors);
+        set_parsed(&mut mail.smtp.security, "SMTP_SECURITY", &mut errors);
+        set_string(&mut mail.smtp.username, "SMTP_USERNAME");
+        set_string(&mut mail.smtp.password, "SMTP_PASSWORD");
+        set_string(&mut mail.file.path, "MAIL_FILE_PATH");
+
+        // A single provider can be configured from the environment, named by `OIDC_PROVIDER`.
+        let oidc = &mut self.oidc;
+        set_parsed(&mut oidc.login_minutes, "OIDC_LOGIN_MINUTES", &mut errors);
+        if let Some(name) = optional_env("OIDC_PROVIDER") {
+            let provider = oidc.providers.entry(name).or_default();
+            set_string(&mut provider.issuer, "OIDC_ISSUER");
+            set_string(&mut provider.client_id, "OIDC_CLIENT_ID");
+            if let Some(secret) = optional_env("OIDC_CLIENT_SECRET") {
+                provider.client_secret = Some(secret);
+            }
+            set_string(&mut provider.redirect_uri, "OIDC_REDIRECT_URI");
+            set_list(&mut provider.scopes, "OIDC_SCOPES");
+        }
+
+        errors
+    }
+
+    /// Returns every setting that is missing or out of range, named after its environment variable.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut check = |valid: bool, message: &str| {
+            if !valid {
+                errors.push(message.to_string());
+            }
+        };
+
+        check(
+            self.server.bind_address.parse::<SocketAddr>().is_ok(),
+            "BIND_ADDRESS must be an IP address and a port, e.g. 0.0.0.0:8080",
+        );
+
+        check(
+            self.database.url.starts_with("mongodb://")
+                || self.database.url.starts_with("mongodb+srv://"),
+            "DATABASE_URL must be a mongodb:// or mongodb+srv:// connection string",
+        );
+        check(
+            !self.database.name.is_empty()
+                && !self
+                    .database
+                    .name
+                    .contains(['/', '\\', '.', ' ', '"', '$', '*', '<', '>', ':', '|', '?']),
+            "DATABASE_NAME must be a valid MongoDB database name",
+        );
+
+        check(
+            self.server.environment == Environment::Development || !self.auth.keys_dir.is_empty(),
+            "JWT_KEYS_DIR must be set in production",
+        );
+        check(
+            !self.auth.issuer.trim().is_empty(),
+            "JWT_ISSUER must not be empty",
+        );
+        check(
+            !self.auth.audience.trim().is_empty(),
+            "JWT_AUDIENCE must not be empty",
+        );
+        check(
+            self.auth.token_lifetime_minutes > 0,
+            "JWT_LIFETIME_MINUTES must be greater than 0",
+        );
+        check(
+            self.auth.lockout_threshold == 0 || self.auth.lockout_minutes > 0,
+            "LOGIN_LOCKOUT_MINUTES must be greater than 0",
+        );
+        check(
+            !self.auth.two_factor_issuer.trim().is_empty(),
+            "TWO_FACTOR_ISSUER must not be empty",
+        );
+        check(
+            self.auth.two_factor_challenge_minutes > 0,
+            "TWO_FACTOR_CHALLENGE_MINUTES must be greater than 0",
+        );
+
+        for origin in &self.cors.allowed_origins {
+            check(
+                !origin.contains('*'),
+                &format!(
+                    "CORS_ALLOWED_ORIGINS: {:?} is a wildcard, list every origin explicitly",
+                    origin
+                ),
+            );
+            check(
+                origin.contains('*') || is_origin(origin),
+                &format!(
+                    "CORS_ALLOWED_ORIGINS: {:?} is not an origin (scheme://host[:port], without a path)",
+                    origin
+                ),
+            );
+        }
+        check(
+            self.server.environment == Environment::Development
+                || !self.cors.allowed_origins.is_empty(),
+            "CORS_ALLOWED_ORIGINS must list at least one origin in production",
+        );
+        for method in &self.cors.allowed_methods {
+            check(
+                Method::from_bytes(method.as_bytes()).is_ok(),
+                &format!("CORS_ALLOWED_METHODS: {:?} is not an HTTP method", method),
+            );
+        }
+        for header in &self.cors.allowed_headers {
+            check(
+                HeaderName::from_bytes(header.as_bytes()).is_ok(),
+                &format!("CORS_ALLOWED_HEADERS: {:?} is not a header name", header),
+            );
+        }
+
+        check(
+            self.uploads.max_image_size_mb > 0,
+            "UPLOAD_MAX_IMAGE_SIZE_MB must be greater than 0",
+        );
+        check(
+            self.uploads.max_video_size_mb > 0,
+            "UPLOAD_MAX_VIDEO_SIZE_MB must be greater than 0",
+        );
+        check(
+            self.uploads.max_video_duration_secs > 0.0,
+            "UPLOAD_MAX_VIDEO_DURATION_SECS must be greater than 0",
+        );
+        check(
+            self.uploads.session_lifetime_hours > 0,
+            "UPLOAD_SESSION_LIFETIME_HOURS must be greater than 0",
+        );
+
+        check(
+            self.stories.lifetime_hours > 0,
+            "STORY_LIFETIME_HOURS must be greater than 0",
+        );
+
+        check(
+            self.audit.retention_days >= 0,
+            "AUDIT_RETENTION_DAYS must not be negative",
+        );
+
+        check(
+            self.quotas.user_mb >= 0,
+            "STORAGE_QUOTA_USER_MB must not be negative",
+        );
+        check(
+            self.quotas.admin_mb >= 0,
+            "STORAGE_QUOTA_ADMIN_MB must not be negative",
+        );
+
+        match self.storage.backend {
+            StorageKind::S3 => {
+                let s3 = &self.storage.s3;
+                check(
+                    is_http_url(&s3.server_url),
+                    "MINIO_SERVER_URL must be an http:// or https:// URL",
+                );
+                check(
+                    s3.public_url.as_deref().is_none_or(is_http_url),
+                    "MINIO_PUBLIC_URL must be an http:// or https:// URL",
+                );
+                check(!s3.region.is_empty(), "MINIO_REGION must not be empty");
+                check(!s3.bucket.is_empty(), "MINIO_BUCKET must not be empty");
+                check(!s3.access_key.is_empty(), "MINIO_ROOT_USER is not set");
+                check(!s3.secret_key.is_empty(), "MINIO_ROOT_PASSWORD is not set");
+            }
+            StorageKind::Local => {
+                let local = &self.storage.local;
+                check(
+                    !local.path.is_empty(),
+                    "LOCAL_STORAGE_PATH must not be empty",
+                );
+                check(
+                    is_http_url(&local.public_url),
+                    "LOCAL_STORAGE_PUBLIC_URL must be an http:// or https:// URL",
+                );
+                check(
+                    local.secret.len() >= MIN_SECRET_LENGTH,
+                    &format!(
+                        "LOCAL_STORAGE_SECRET must be at least {} characters long",
+                        MIN_SECRET_LENGTH
+                    ),
+                );
+            }
+        }
+
+        let mail = &self.mail;
+        check(
+            mail.from.parse::<Mailbox>().is_ok(),
+            "MAIL_FROM must be an email address, optionally with a name: Name <address>",
+        );
+        check(
+            is_http_url(&mail.app_url) || is_origin(&mail.app_url),
+            "MAIL_APP_URL must be a URL, e.g. https://app.snapshoot.example",
+        );
+        check(
+            mail.verification_token_hours > 0,
+            "MAIL_VERIFICATION_TOKEN_HOURS must be greater than 0",
+        );
+        check(
+            mail.reset_token_minutes > 0,
+            "MAIL_RESET_TOKEN_MINUTES must be greater than 0",
+        );
+        match mail.backend {
+            MailerKind::Smtp => {
+                check(!mail.smtp.host.is_empty(), "SMTP_HOST is not set");
+                check(mail.smtp.port > 0, "SMTP_PORT must be greater than 0");
+            }
+            MailerKind::File => {
+                check(
+                    !mail.file.path.is_empty(),
+                    "MAIL_FILE_PATH must not be empty",
+                );
+            }
+        }
+
+        let rate_limits = &self.rate_limits;
+        for (budget, name) in [
+            (rate_limits.login, "RATE_LIMIT_LOGIN"),
+            (rate_limits.register, "RATE_LIMIT_REGISTER"),
+            (rate_limits.friend_requests, "RATE_LIMIT_FRIEND_REQUESTS"),
+            (rate_limits.uploads, "RATE_LIMIT_UPLOADS"),
+            (rate_limits.emails, "RATE_LIMIT_EMAILS"),
+        ] {
+            check(
+                budget.requests > 0 && budget.window_secs > 0,
+                &format!(
+                    "{} must allow at least 1 request per window of at least 1 second",
+                    name
+                ),
+            );
+        }
+
+        check(
+            self.oidc.login_minutes > 0,
+            "OIDC_LOGIN_MINUTES must be greater than 0",
+        );
+        for (name, provider) in &self.oidc.providers {
+            check(
+                !name.is_empty()
+                    && name
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'),
+                &format!(
+                    "OIDC_PROVIDER: {:?} must be lowercase letters, digits and dashes",
+                    name
+                ),
+            );
+            check(
+                provider.issuer.starts_with("https://")
+                    || (self.server.environment == Environment::Development
+                        && is_http_url(&provider.issuer)),
+                &format!(
+                    "OIDC_ISSUER of {:?} must be an https:// URL (http:// is accepted in development)",
+                    name
+                ),
+            );
+            check(
+                !provider.client_id.is_empty(),
+                &format!("OIDC_CLIENT_ID of {:?} is not set", name),
+            );
+            check(
+                provider.redirect_uri.contains("://"),
+                &format!(
+                    "OIDC_REDIRECT_URI of {:?} must be a URL or an app deep link",
+                    name
+                ),
+            );
+            check(
+                provider.scopes.iter().any(|scope| scope == "openid"),
+                &format!("OIDC_SCOPES of {:?} must include openid", name),
+            );
+        }
+
+        errors
+    }
+}
+
+// =============================================================================================================================
+
+fn optional_env(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+// =============================================================================================================================
+
+fn set_string(target: &mut String, name: &str) {
+    if let Some(value) = optional_env(name) {
+        *target = value;
+    }
+}
+
+// =============================================================================================================================
+
+/// Comma-separated values, blanks ignored.
+fn set_list(target: &mut Vec<String>, name: &str) {
+    if let Some(value) = optional_env(name) {
+        *target = value
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect();
+    }
+}
+
+// =============================================================================================================================
+
+fn set_parsed<T>(target: &mut T, name: &str, errors: &mut Vec<String>)
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(value) = optional_env(name) {
+        match value.trim().parse() {
+            Ok(parsed) => *target = parsed,
+            Err(e) => errors.push(format!("{} has an invalid value {:?}: {}", name, value, e)),
+        }
+    }
+}
+
+// =============================================================================================================================
+
+fn is_http_url(url: &str) -> bool {
+    url.strip_prefix("http://")
+        .or_else(|| url.strip_prefix("https://"))
+        .is_some_and(|rest| !rest.is_empty())
+}
+
+// =============================================================================================================================
+
+fn is_origin(origin: &str) -> bool {
+    match origin.split_once("://") {
+        Some((scheme, host)) => {
+            !scheme.is_empty()
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-')
+                && !host.is_empty()
+                && !host.contains('/')
+        }
+        None => false,
+    }
+}
+
+// =============================================================================================================================
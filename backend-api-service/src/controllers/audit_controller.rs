@@ -0,0 +1,45 @@
+use actix_web::{
+    HttpRequest, HttpResponse, Responder, get,
+    web::{self, Data, Query, ServiceConfig},
+};
+
+use crate::{
+    models::{audit_model::AuditQueryParams, user_model::UserRole},
+    repositories::Repositories,
+    services::audit_service,
+    utils::{api_response::ApiResponse, jwt::user_has_any_of_these_roles},
+};
+
+// =============================================================================================================================
+
+pub fn audit_routes(cfg: &mut ServiceConfig) {
+    let scope = web::scope("/audit").service(get_events);
+
+    cfg.service(scope);
+}
+
+// =============================================================================================================================
+
+#[get("/events")]
+async fn get_events(
+    repos: Data<Repositories>,
+    query: Query<AuditQueryParams>,
+    req: HttpRequest,
+) -> impl Responder {
+    if let Err(err_res) = user_has_any_of_these_roles(&req, &[UserRole::Admin]) {
+        return err_res;
+    }
+
+    match audit_service::get_events(&repos, query.into_inner()).await {
+        Ok(events) => {
+            let response = ApiResponse::success("Audit events retrieved successfully", events);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to retrieve the audit events", e.to_string());
+            HttpResponse::BadRequest().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
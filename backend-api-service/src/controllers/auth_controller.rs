@@ -1,15 +1,34 @@
 use crate::{
-    models::auth_model::{AuthLogin, AuthRegister},
-    services::auth_service,
-    utils::{api_response::ApiResponse, jwt::get_authenticated_user},
+    config::Config,
+    mailer::Mailer,
+    models::{
+        auth_model::{AuthLogin, AuthRegister, EmailRequest, ResetPassword, VerifyEmail},
+        oidc_model::OidcCallback,
+        two_factor_model::{TwoFactorCode, TwoFactorVerify},
+    },
+    repositories::Repositories,
+    services::{
+        auth_service::{self, AccountLocked, EmailNotVerified, LoginOutcome},
+        oidc_service::{self, UnknownProvider},
+        session_service::{self, AccountSuspended},
+        two_factor_service::{self, InvalidTwoFactorCode},
+    },
+    utils::{
+        api_response::ApiResponse,
+        jwt::get_authenticated_user,
+        oidc::OidcClient,
+        rate_limit::{Budget, RateLimit},
+        session::client_info,
+    },
 };
 use actix_web::{
     HttpRequest, HttpResponse, Responder,
-    cookie::Cookie,
-    get, post,
-    web::{self, Data, Json, ServiceConfig},
+    cookie::{Cookie, time::Duration},
+    delete, get,
+    http::header,
+    post,
+    web::{self, Data, Json, Path, ServiceConfig},
 };
-use mongodb::Database;
 
 // =============================================================================================================================
 
@@ -17,7 +36,23 @@ pub fn auth_routes(cfg: &mut ServiceConfig) {
     let scope = web::scope("/auth")
         .service(get_me)
         .service(register)
-        .service(login);
+        .service(verify_email)
+        .service(resend_verification)
+        .service(forgot_password)
+        .service(reset_password)
+        .service(login)
+        .service(get_oidc_providers)
+        .service(authorize_oidc)
+        .service(oidc_callback)
+        .service(get_two_factor)
+        .service(enroll_two_factor)
+        .service(confirm_two_factor)
+        .service(disable_two_factor)
+        .service(verify_two_factor)
+        .service(get_sessions)
+        .service(revoke_other_sessions)
+        .service(revoke_session)
+        .service(logout);
 
     cfg.service(scope);
 }
@@ -37,21 +72,22 @@ async fn get_me(req: HttpRequest) -> impl Responder {
 
 // =============================================================================================================================
 
-#[post("/register")]
-async fn register(db: Data<Database>, data: Json<AuthRegister>) -> impl Responder {
+#[post("/register", wrap = "RateLimit::new(Budget::Register)")]
+async fn register(
+    repos: Data<Repositories>,
+    mailer: Data<dyn Mailer>,
+    config: Data<Config>,
+    data: Json<AuthRegister>,
+) -> impl Responder {
     let data = data.into_inner();
 
-    match auth_service::register(&db, data).await {
-        Ok(auth_response) => {
-            let cookie = Cookie::build("token", auth_response.token.clone())
-                .path("/")
-                .http_only(true)
-                .secure(false)
-                .max_age(actix_web::cookie::time::Duration::hours(6))
-                .finish();
-
-            let res = ApiResponse::success("User created successfully", auth_response);
-            HttpResponse::Ok().cookie(cookie).json(res)
+    match auth_service::register(&repos, mailer.get_ref(), &config, data).await {
+        Ok(pending) => {
+            let res = ApiResponse::success(
+                "User created successfully, check your email to verify the account",
+                pending,
+            );
+            HttpResponse::Ok().json(res)
         }
         Err(e) => {
             let res = ApiResponse::error("Failed to create user", e.to_string());
@@ -62,23 +98,469 @@ async fn register(db: Data<Database>, data: Json<AuthRegister>) -> impl Responde
 
 // =============================================================================================================================
 
-#[post("/login")]
-async fn login(db: Data<Database>, data: Json<AuthLogin>) -> impl Responder {
+#[post("/verify-email")]
+async fn verify_email(
+    req: HttpRequest,
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    data: Json<VerifyEmail>,
+) -> impl Responder {
     let data = data.into_inner();
+    let client = client_info(&req, data.device.clone());
 
-    match auth_service::login(&db, data).await {
-        Ok(auth_response) => {
-            let cookie = Cookie::build("session_token", auth_response.token.clone())
-                .path("/")
-                .http_only(true)
-                .secure(false)
-                .finish();
+    match auth_service::verify_email(&repos, &config, client, data).await {
+        Ok(LoginOutcome::Authenticated(auth_response)) => {
+            let cookie = session_cookie(&config, &auth_response.token);
+            let res = ApiResponse::success("Email verified successfully", auth_response);
+            HttpResponse::Ok().cookie(cookie).json(res)
+        }
+        Ok(LoginOutcome::TwoFactorRequired(challenge)) => {
+            let res = ApiResponse::success(
+                "Email verified successfully, two-factor authentication required",
+                challenge,
+            );
+            HttpResponse::Ok().json(res)
+        }
+        Err(e) => {
+            let res = ApiResponse::error("Failed to verify the email", e.to_string());
+            if e.is::<AccountSuspended>() {
+                HttpResponse::Forbidden().json(res)
+            } else {
+                HttpResponse::BadRequest().json(res)
+            }
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/resend-verification", wrap = "RateLimit::new(Budget::Email)")]
+async fn resend_verification(
+    repos: Data<Repositories>,
+    mailer: Data<dyn Mailer>,
+    config: Data<Config>,
+    data: Json<EmailRequest>,
+) -> impl Responder {
+    let data = data.into_inner();
+
+    match auth_service::resend_verification(&repos, mailer.get_ref(), &config, data).await {
+        Ok(()) => {
+            let res = ApiResponse::success(
+                "If the address belongs to an unverified account, a new link has been sent",
+                (),
+            );
+            HttpResponse::Ok().json(res)
+        }
+        Err(e) => {
+            let res = ApiResponse::error("Failed to send the verification email", e.to_string());
+            HttpResponse::InternalServerError().json(res)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/forgot-password", wrap = "RateLimit::new(Budget::Email)")]
+async fn forgot_password(
+    repos: Data<Repositories>,
+    mailer: Data<dyn Mailer>,
+    config: Data<Config>,
+    data: Json<EmailRequest>,
+) -> impl Responder {
+    let data = data.into_inner();
+
+    match auth_service::forgot_password(&repos, mailer.get_ref(), &config, data).await {
+        Ok(()) => {
+            let res = ApiResponse::success(
+                "If the address belongs to an account, a password reset link has been sent",
+                (),
+            );
+            HttpResponse::Ok().json(res)
+        }
+        Err(e) => {
+            let res = ApiResponse::error("Failed to send the password reset email", e.to_string());
+            HttpResponse::InternalServerError().json(res)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/reset-password")]
+async fn reset_password(repos: Data<Repositories>, data: Json<ResetPassword>) -> impl Responder {
+    let data = data.into_inner();
+
+    match auth_service::reset_password(&repos, data).await {
+        Ok(()) => {
+            let res = ApiResponse::success("Password reset successfully", ());
+            HttpResponse::Ok().json(res)
+        }
+        Err(e) => {
+            let res = ApiResponse::error("Failed to reset the password", e.to_string());
+            HttpResponse::BadRequest().json(res)
+        }
+    }
+}
 
+// =============================================================================================================================
+
+#[post("/login", wrap = "RateLimit::new(Budget::Login)")]
+async fn login(
+    req: HttpRequest,
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    data: Json<AuthLogin>,
+) -> impl Responder {
+    let data = data.into_inner();
+    let client = client_info(&req, data.device.clone());
+
+    match auth_service::login(&repos, &config, client, data).await {
+        Ok(LoginOutcome::Authenticated(auth_response)) => {
+            let cookie = session_cookie(&config, &auth_response.token);
             let res = ApiResponse::success("User connected successfully", auth_response);
             HttpResponse::Ok().cookie(cookie).json(res)
         }
+        Ok(LoginOutcome::TwoFactorRequired(challenge)) => {
+            let res = ApiResponse::success("Two-factor authentication required", challenge);
+            HttpResponse::Ok().json(res)
+        }
         Err(e) => {
             let res = ApiResponse::error("Failed to login the user", e.to_string());
+            if let Some(locked) = e.downcast_ref::<AccountLocked>() {
+                locked_response(locked, res)
+            } else if e.is::<EmailNotVerified>() || e.is::<AccountSuspended>() {
+                HttpResponse::Forbidden().json(res)
+            } else {
+                HttpResponse::InternalServerError().json(res)
+            }
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[get("/oidc/providers")]
+async fn get_oidc_providers(config: Data<Config>) -> impl Responder {
+    let res = ApiResponse::success(
+        "Sign-in providers retrieved successfully",
+        oidc_service::providers(&config),
+    );
+    HttpResponse::Ok().json(res)
+}
+
+// =============================================================================================================================
+
+#[post("/oidc/{provider}/authorize")]
+async fn authorize_oidc(
+    repos: Data<Repositories>,
+    oidc: Data<OidcClient>,
+    config: Data<Config>,
+    provider: Path<String>,
+) -> impl Responder {
+    match oidc_service::authorize(&repos, &oidc, &config, provider.into_inner()).await {
+        Ok(authorization) => {
+            let res = ApiResponse::success("Sign-in started at the provider", authorization);
+            HttpResponse::Ok().json(res)
+        }
+        Err(e) => {
+            let res = ApiResponse::error("Failed to start the sign-in", e.to_string());
+            if e.is::<UnknownProvider>() {
+                HttpResponse::NotFound().json(res)
+            } else {
+                HttpResponse::BadGateway().json(res)
+            }
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/oidc/{provider}/callback", wrap = "RateLimit::new(Budget::Login)")]
+async fn oidc_callback(
+    req: HttpRequest,
+    repos: Data<Repositories>,
+    oidc: Data<OidcClient>,
+    config: Data<Config>,
+    provider: Path<String>,
+    data: Json<OidcCallback>,
+) -> impl Responder {
+    let data = data.into_inner();
+    let client = client_info(&req, data.device.clone());
+
+    match oidc_service::callback(&repos, &oidc, &config, provider.into_inner(), client, data).await
+    {
+        Ok(LoginOutcome::Authenticated(auth_response)) => {
+            let cookie = session_cookie(&config, &auth_response.token);
+            let res = ApiResponse::success("User connected successfully", auth_response);
+            HttpResponse::Ok().cookie(cookie).json(res)
+        }
+        Ok(LoginOutcome::TwoFactorRequired(challenge)) => {
+            let res = ApiResponse::success("Two-factor authentication required", challenge);
+            HttpResponse::Ok().json(res)
+        }
+        Err(e) => {
+            let res = ApiResponse::error("Failed to sign in with the provider", e.to_string());
+            if e.is::<UnknownProvider>() {
+                HttpResponse::NotFound().json(res)
+            } else if e.is::<AccountSuspended>() {
+                HttpResponse::Forbidden().json(res)
+            } else {
+                HttpResponse::Unauthorized().json(res)
+            }
+        }
+    }
+}
+
+// =============================================================================================================================
+
+const SESSION_COOKIE: &str = "session_token";
+
+fn session_cookie(config: &Config, token: &str) -> Cookie<'static> {
+    Cookie::build(SESSION_COOKIE, token.to_string())
+        .path("/")
+        .http_only(true)
+        .secure(false)
+        .max_age(Duration::minutes(config.auth.token_lifetime_minutes))
+        .finish()
+}
+
+// =============================================================================================================================
+
+#[get("/2fa")]
+async fn get_two_factor(req: HttpRequest, repos: Data<Repositories>) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    match two_factor_service::status(&repos, jwt_payload.user_id).await {
+        Ok(status) => {
+            let res = ApiResponse::success("Two-factor status retrieved successfully", status);
+            HttpResponse::Ok().json(res)
+        }
+        Err(e) => {
+            let res = ApiResponse::error("Failed to retrieve the two-factor status", e.to_string());
+            HttpResponse::InternalServerError().json(res)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/2fa/enroll")]
+async fn enroll_two_factor(
+    req: HttpRequest,
+    repos: Data<Repositories>,
+    config: Data<Config>,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    match two_factor_service::enroll(&repos, &config, jwt_payload.user_id).await {
+        Ok(enrollment) => {
+            let res = ApiResponse::success("Two-factor enrolment started", enrollment);
+            HttpResponse::Ok().json(res)
+        }
+        Err(e) => {
+            let res = ApiResponse::error("Failed to start two-factor enrolment", e.to_string());
+            HttpResponse::BadRequest().json(res)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/2fa/confirm")]
+async fn confirm_two_factor(
+    req: HttpRequest,
+    repos: Data<Repositories>,
+    data: Json<TwoFactorCode>,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    match two_factor_service::confirm(&repos, jwt_payload.user_id, data.into_inner()).await {
+        Ok(recovery_codes) => {
+            let res = ApiResponse::success("Two-factor authentication enabled", recovery_codes);
+            HttpResponse::Ok().json(res)
+        }
+        Err(e) => {
+            let res =
+                ApiResponse::error("Failed to enable two-factor authentication", e.to_string());
+            HttpResponse::BadRequest().json(res)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[delete("/2fa", wrap = "RateLimit::new(Budget::Login)")]
+async fn disable_two_factor(
+    req: HttpRequest,
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    data: Json<TwoFactorCode>,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    match two_factor_service::disable(&repos, &config, jwt_payload.user_id, data.into_inner()).await
+    {
+        Ok(()) => {
+            let res = ApiResponse::success("Two-factor authentication disabled", ());
+            HttpResponse::Ok().json(res)
+        }
+        Err(e) => {
+            let res =
+                ApiResponse::error("Failed to disable two-factor authentication", e.to_string());
+            if let Some(locked) = e.downcast_ref::<AccountLocked>() {
+                locked_response(locked, res)
+            } else if e.is::<InvalidTwoFactorCode>() {
+                HttpResponse::Unauthorized().json(res)
+            } else {
+                HttpResponse::BadRequest().json(res)
+            }
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/2fa/verify", wrap = "RateLimit::new(Budget::Login)")]
+async fn verify_two_factor(
+    req: HttpRequest,
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    data: Json<TwoFactorVerify>,
+) -> impl Responder {
+    let data = data.into_inner();
+    let client = client_info(&req, data.device.clone());
+
+    match two_factor_service::verify_challenge(&repos, &config, client, data).await {
+        Ok(auth_response) => {
+            let cookie = session_cookie(&config, &auth_response.token);
+            let res = ApiResponse::success("User connected successfully", auth_response);
+            HttpResponse::Ok().cookie(cookie).json(res)
+        }
+        Err(e) => {
+            let res = ApiResponse::error("Failed to verify the two-factor code", e.to_string());
+            if let Some(locked) = e.downcast_ref::<AccountLocked>() {
+                locked_response(locked, res)
+            } else if e.is::<AccountSuspended>() {
+                HttpResponse::Forbidden().json(res)
+            } else {
+                HttpResponse::Unauthorized().json(res)
+            }
+        }
+    }
+}
+
+// =============================================================================================================================
+
+fn locked_response<T: serde::Serialize>(
+    locked: &AccountLocked,
+    res: ApiResponse<'_, T>,
+) -> HttpResponse {
+    HttpResponse::TooManyRequests()
+        .insert_header((header::RETRY_AFTER, locked.retry_after_secs))
+        .json(res)
+}
+
+// =============================================================================================================================
+
+#[get("/sessions")]
+async fn get_sessions(req: HttpRequest, repos: Data<Repositories>) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    match session_service::list(&repos, jwt_payload.user_id, jwt_payload.session_id).await {
+        Ok(sessions) => {
+            let res = ApiResponse::success("Sessions retrieved successfully", sessions);
+            HttpResponse::Ok().json(res)
+        }
+        Err(e) => {
+            let res = ApiResponse::error("Failed to retrieve the sessions", e.to_string());
+            HttpResponse::InternalServerError().json(res)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[delete("/sessions")]
+async fn revoke_other_sessions(req: HttpRequest, repos: Data<Repositories>) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    match session_service::revoke_others(&repos, jwt_payload.user_id, Some(jwt_payload.session_id))
+        .await
+    {
+        Ok(revoked) => {
+            let res = ApiResponse::success("Other sessions revoked successfully", revoked);
+            HttpResponse::Ok().json(res)
+        }
+        Err(e) => {
+            let res = ApiResponse::error("Failed to revoke the sessions", e.to_string());
+            HttpResponse::InternalServerError().json(res)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[delete("/sessions/{session_id}")]
+async fn revoke_session(
+    req: HttpRequest,
+    repos: Data<Repositories>,
+    session_id: Path<String>,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    match session_service::revoke(&repos, jwt_payload.user_id, session_id.into_inner()).await {
+        Ok(()) => {
+            let res = ApiResponse::success("Session revoked successfully", ());
+            HttpResponse::Ok().json(res)
+        }
+        Err(e) => {
+            let res = ApiResponse::error("Failed to revoke the session", e.to_string());
+            HttpResponse::NotFound().json(res)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+/// Revokes the session of the request and clears the session cookie.
+#[post("/logout")]
+async fn logout(req: HttpRequest, repos: Data<Repositories>) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    match session_service::revoke(&repos, jwt_payload.user_id, jwt_payload.session_id).await {
+        Ok(()) => {
+            let mut cookie = Cookie::new(SESSION_COOKIE, "");
+            cookie.set_path("/");
+            cookie.make_removal();
+            let res = ApiResponse::success("User disconnected successfully", ());
+            HttpResponse::Ok().cookie(cookie).json(res)
+        }
+        Err(e) => {
+            let res = ApiResponse::error("Failed to logout the user", e.to_string());
             HttpResponse::InternalServerError().json(res)
         }
     }
@@ -0,0 +1,91 @@
+use actix_web::{
+    HttpRequest, HttpResponse, Responder, get,
+    http::header,
+    put,
+    web::{self, Data, Path, Payload, Query, ServiceConfig},
+};
+
+use crate::{
+    config::Config, models::media_model::PresignedFileQueryParams, services::file_service,
+    storage::StorageBackend, utils::api_response::ApiResponse,
+};
+
+// =============================================================================================================================
+
+pub fn file_routes(cfg: &mut ServiceConfig) {
+    let scope = web::scope("/files")
+        .service(download_file)
+        .service(upload_file);
+
+    cfg.service(scope);
+}
+
+// =============================================================================================================================
+
+#[get("/{key:.*}")]
+async fn download_file(
+    storage: Data<dyn StorageBackend>,
+    key: Path<String>,
+    query: Query<PresignedFileQueryParams>,
+) -> impl Responder {
+    let key = key.into_inner();
+    let params = query.into_inner();
+
+    match file_service::read_presigned(storage.get_ref(), &key, params.expires, &params.signature)
+        .await
+    {
+        Ok((metadata, data)) => HttpResponse::Ok()
+            .content_type(metadata.content_type)
+            .body(data),
+        Err(e) => {
+            let response = ApiResponse::error("Failed to download the file", e.to_string());
+            HttpResponse::NotFound().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[put("/{key:.*}")]
+async fn upload_file(
+    storage: Data<dyn StorageBackend>,
+    config: Data<Config>,
+    req: HttpRequest,
+    key: Path<String>,
+    query: Query<PresignedFileQueryParams>,
+    payload: Payload,
+) -> impl Responder {
+    let key = key.into_inner();
+    let params = query.into_inner();
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    let content_length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok());
+
+    match file_service::write_presigned(
+        storage.get_ref(),
+        &config,
+        &key,
+        &params,
+        &content_type,
+        content_length,
+        payload,
+    )
+    .await
+    {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(e) => {
+            let response = ApiResponse::error("Failed to upload the file", e.to_string());
+            HttpResponse::BadRequest().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
@@ -1,13 +1,17 @@
 use actix_web::{
     HttpRequest, HttpResponse, Responder, delete, get, patch, post,
-    web::{self, Data, Json, Path, ServiceConfig},
+    web::{self, Data, Json, Path, Query, ServiceConfig},
 };
-use mongodb::Database;
 
 use crate::{
-    models::friend_model::FindFriend,
+    models::friend_model::{FindFriend, SuggestionQueryParams, UserSearchParams},
+    repositories::Repositories,
     services::friend_service,
-    utils::{api_response::ApiResponse, jwt::get_authenticated_user},
+    utils::{
+        api_response::ApiResponse,
+        jwt::get_authenticated_user,
+        rate_limit::{Budget, RateLimit},
+    },
 };
 
 // =============================================================================================================================
@@ -16,6 +20,8 @@ pub fn friend_routes(cfg: &mut ServiceConfig) {
     let scope = web::scope("/friends")
         .service(get_friends)
         .service(get_friend_requests)
+        .service(search_users)
+        .service(get_suggestions)
         .service(find_friend)
         .service(send_friend_request)
         .service(accept_friend_request)
@@ -27,13 +33,13 @@ pub fn friend_routes(cfg: &mut ServiceConfig) {
 // =============================================================================================================================
 
 #[get("")]
-async fn get_friends(db: Data<Database>, req: HttpRequest) -> impl Responder {
+async fn get_friends(repos: Data<Repositories>, req: HttpRequest) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
         Ok(payload) => payload,
         Err(err_res) => return err_res,
     };
 
-    match friend_service::get_friends(&db, jwt_payload.user_id).await {
+    match friend_service::get_friends(&repos, jwt_payload.user_id).await {
         Ok(friends) => {
             let response = ApiResponse::success("Friends retrieved successfully", friends);
             HttpResponse::Ok().json(response)
@@ -48,13 +54,13 @@ async fn get_friends(db: Data<Database>, req: HttpRequest) -> impl Responder {
 // =============================================================================================================================
 
 #[get("/requests")]
-async fn get_friend_requests(db: Data<Database>, req: HttpRequest) -> impl Responder {
+async fn get_friend_requests(repos: Data<Repositories>, req: HttpRequest) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
         Ok(payload) => payload,
         Err(err_res) => return err_res,
     };
 
-    match friend_service::get_friend_requests(&db, jwt_payload.user_id).await {
+    match friend_service::get_friend_requests(&repos, jwt_payload.user_id).await {
         Ok(requests) => {
             let response = ApiResponse::success("Friend requests retrieved successfully", requests);
             HttpResponse::Ok().json(response)
@@ -68,9 +74,59 @@ async fn get_friend_requests(db: Data<Database>, req: HttpRequest) -> impl Respo
 
 // =============================================================================================================================
 
+#[get("/search")]
+async fn search_users(
+    repos: Data<Repositories>,
+    req: HttpRequest,
+    query: Query<UserSearchParams>,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    match friend_service::search_users(&repos, jwt_payload.user_id, query.into_inner()).await {
+        Ok(users) => {
+            let response = ApiResponse::success("Users retrieved successfully", users);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to search users", e.to_string());
+            HttpResponse::BadRequest().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[get("/suggestions")]
+async fn get_suggestions(
+    repos: Data<Repositories>,
+    req: HttpRequest,
+    query: Query<SuggestionQueryParams>,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    match friend_service::get_suggestions(&repos, jwt_payload.user_id, query.into_inner()).await {
+        Ok(suggestions) => {
+            let response = ApiResponse::success("Suggestions retrieved successfully", suggestions);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to retrieve suggestions", e.to_string());
+            HttpResponse::InternalServerError().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
 #[post("/find")]
 async fn find_friend(
-    db: Data<Database>,
+    repos: Data<Repositories>,
     req: HttpRequest,
     data: Json<FindFriend>,
 ) -> impl Responder {
@@ -82,7 +138,7 @@ async fn find_friend(
     let data = data.into_inner();
 
     if let Some(email) = data.email {
-        match friend_service::find_user_by_email(&db, email).await {
+        match friend_service::find_user_by_email(&repos, email).await {
             Ok(user) => {
                 let response = ApiResponse::success("User found successfully", user);
                 HttpResponse::Ok().json(response)
@@ -93,7 +149,7 @@ async fn find_friend(
             }
         }
     } else if let Some(user_id) = data.user_id {
-        match friend_service::find_user_by_id(&db, user_id).await {
+        match friend_service::find_user_by_id(&repos, user_id).await {
             Ok(user) => {
                 let response = ApiResponse::success("User found successfully", user);
                 HttpResponse::Ok().json(response)
@@ -114,9 +170,9 @@ async fn find_friend(
 
 // =============================================================================================================================
 
-#[post("/request/{user_id}")]
+#[post("/request/{user_id}", wrap = "RateLimit::new(Budget::FriendRequest)")]
 async fn send_friend_request(
-    db: Data<Database>,
+    repos: Data<Repositories>,
     req: HttpRequest,
     user_id: Path<String>,
 ) -> impl Responder {
@@ -127,7 +183,7 @@ async fn send_friend_request(
 
     let friend_id = user_id.into_inner();
 
-    match friend_service::send_friend_request(&db, jwt_payload.user_id, friend_id).await {
+    match friend_service::send_friend_request(&repos, jwt_payload.user_id, friend_id).await {
         Ok(request) => {
             let response = ApiResponse::success("Friend request sent successfully", request);
             HttpResponse::Ok().json(response)
@@ -143,7 +199,7 @@ async fn send_friend_request(
 
 #[patch("/accept/{request_id}")]
 async fn accept_friend_request(
-    db: Data<Database>,
+    repos: Data<Repositories>,
     req: HttpRequest,
     request_id: Path<String>,
 ) -> impl Responder {
@@ -154,7 +210,7 @@ async fn accept_friend_request(
 
     let request_id = request_id.into_inner();
 
-    match friend_service::accept_friend_request(&db, request_id, jwt_payload.user_id).await {
+    match friend_service::accept_friend_request(&repos, request_id, jwt_payload.user_id).await {
         Ok(friend) => {
             let response = ApiResponse::success("Friend request accepted successfully", friend);
             HttpResponse::Ok().json(response)
@@ -170,7 +226,7 @@ async fn accept_friend_request(
 
 #[delete("/{user_id}")]
 async fn delete_friend(
-    db: Data<Database>,
+    repos: Data<Repositories>,
     req: HttpRequest,
     user_id: Path<String>,
 ) -> impl Responder {
@@ -181,7 +237,7 @@ async fn delete_friend(
 
     let friend_id = user_id.into_inner();
 
-    match friend_service::delete_friend(&db, jwt_payload.user_id, friend_id).await {
+    match friend_service::delete_friend(&repos, jwt_payload.user_id, friend_id).await {
         Ok(_) => {
             let response = ApiResponse::success("Friend removed successfully", ());
             HttpResponse::Ok().json(response)
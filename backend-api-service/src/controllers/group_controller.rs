@@ -1,16 +1,20 @@
 use actix_web::{
     HttpRequest, HttpResponse, Responder, delete, get, post, put,
-    web::{self, Data, Json, Path, ServiceConfig},
+    web::{self, Data, Json, Path, Query, ServiceConfig},
 };
-use mongodb::Database;
+use mongodb::bson::oid::ObjectId;
 
 use crate::{
-    models::group_model::{AddGroupMembers, CreateGroup, UpdateGroup},
-    services::group_service,
-    utils::{
-        api_response::ApiResponse,
-        jwt::get_authenticated_user,
+    config::Config,
+    models::{
+        audit_model::AuditAction,
+        deletion_model::DeletionQueryParams,
+        group_model::{AddGroupMembers, CreateGroup, UpdateGroup},
     },
+    repositories::Repositories,
+    services::{audit_service, deletion_service, group_service},
+    storage::StorageBackend,
+    utils::{api_response::ApiResponse, jwt::get_authenticated_user, session::actor},
 };
 
 // =============================================================================================================================
@@ -31,17 +35,17 @@ pub fn group_routes(cfg: &mut ServiceConfig) {
 // =============================================================================================================================
 
 #[get("")]
-async fn get_groups(db: Data<Database>, req: HttpRequest) -> impl Responder {
+async fn get_groups(repos: Data<Repositories>, req: HttpRequest) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
         Ok(payload) => payload,
         Err(err_res) => return err_res,
     };
 
-    match group_service::get_user_groups(&db, jwt_payload.user_id).await {
+    match group_service::get_user_groups(&repos, jwt_payload.user_id).await {
         Ok(groups) => {
             let response = ApiResponse::success("Groups retrieved successfully", groups);
             HttpResponse::Ok().json(response)
-        },
+        }
         Err(e) => {
             let response = ApiResponse::error("Failed to retrieve groups", e.to_string());
             HttpResponse::InternalServerError().json(response)
@@ -52,7 +56,11 @@ async fn get_groups(db: Data<Database>, req: HttpRequest) -> impl Responder {
 // =============================================================================================================================
 
 #[get("/{group_id}")]
-async fn get_group_by_id(db: Data<Database>, req: HttpRequest, group_id: Path<String>) -> impl Responder {
+async fn get_group_by_id(
+    repos: Data<Repositories>,
+    req: HttpRequest,
+    group_id: Path<String>,
+) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
         Ok(payload) => payload,
         Err(err_res) => return err_res,
@@ -60,11 +68,11 @@ async fn get_group_by_id(db: Data<Database>, req: HttpRequest, group_id: Path<St
 
     let group_id = group_id.into_inner();
 
-    match group_service::get_group_by_id(&db, group_id, jwt_payload.user_id).await {
+    match group_service::get_group_by_id(&repos, group_id, jwt_payload.user_id).await {
         Ok(group) => {
             let response = ApiResponse::success("Group retrieved successfully", group);
             HttpResponse::Ok().json(response)
-        },
+        }
         Err(e) => {
             let response = ApiResponse::error("Failed to retrieve group", e.to_string());
             HttpResponse::InternalServerError().json(response)
@@ -75,7 +83,11 @@ async fn get_group_by_id(db: Data<Database>, req: HttpRequest, group_id: Path<St
 // =============================================================================================================================
 
 #[post("")]
-async fn create_group(db: Data<Database>, req: HttpRequest, payload: Json<CreateGroup>) -> impl Responder {
+async fn create_group(
+    repos: Data<Repositories>,
+    req: HttpRequest,
+    payload: Json<CreateGroup>,
+) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
         Ok(payload) => payload,
         Err(err_res) => return err_res,
@@ -83,11 +95,11 @@ async fn create_group(db: Data<Database>, req: HttpRequest, payload: Json<Create
 
     let data = payload.into_inner();
 
-    match group_service::create_group(&db, data, jwt_payload.user_id).await {
+    match group_service::create_group(&repos, data, jwt_payload.user_id).await {
         Ok(group) => {
             let response = ApiResponse::success("Group created successfully", group);
             HttpResponse::Created().json(response)
-        },
+        }
         Err(e) => {
             let response = ApiResponse::error("Failed to create group", e.to_string());
             HttpResponse::InternalServerError().json(response)
@@ -99,10 +111,10 @@ async fn create_group(db: Data<Database>, req: HttpRequest, payload: Json<Create
 
 #[put("/{group_id}")]
 async fn update_group(
-    db: Data<Database>,
+    repos: Data<Repositories>,
     req: HttpRequest,
     group_id: Path<String>,
-    payload: Json<UpdateGroup>
+    payload: Json<UpdateGroup>,
 ) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
         Ok(payload) => payload,
@@ -112,11 +124,11 @@ async fn update_group(
     let group_id = group_id.into_inner();
     let data = payload.into_inner();
 
-    match group_service::update_group(&db, group_id, data, jwt_payload.user_id).await {
+    match group_service::update_group(&repos, group_id, data, jwt_payload.user_id).await {
         Ok(group) => {
             let response = ApiResponse::success("Group updated successfully", group);
             HttpResponse::Ok().json(response)
-        },
+        }
         Err(e) => {
             let response = ApiResponse::error("Failed to update group", e.to_string());
             HttpResponse::InternalServerError().json(response)
@@ -128,10 +140,10 @@ async fn update_group(
 
 #[post("/{group_id}/members")]
 async fn add_group_members(
-    db: Data<Database>,
+    repos: Data<Repositories>,
     req: HttpRequest,
     group_id: Path<String>,
-    payload: Json<AddGroupMembers>
+    payload: Json<AddGroupMembers>,
 ) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
         Ok(payload) => payload,
@@ -141,11 +153,11 @@ async fn add_group_members(
     let group_id = group_id.into_inner();
     let data = payload.into_inner();
 
-    match group_service::add_group_members(&db, group_id, data, jwt_payload.user_id).await {
+    match group_service::add_group_members(&repos, group_id, data, jwt_payload.user_id).await {
         Ok(group) => {
             let response = ApiResponse::success("Members added to group successfully", group);
             HttpResponse::Ok().json(response)
-        },
+        }
         Err(e) => {
             let response = ApiResponse::error("Failed to add members to group", e.to_string());
             HttpResponse::InternalServerError().json(response)
@@ -157,9 +169,9 @@ async fn add_group_members(
 
 #[delete("/{group_id}/members/{user_id}")]
 async fn remove_group_member(
-    db: Data<Database>,
+    repos: Data<Repositories>,
     req: HttpRequest,
-    path: Path<(String, String)>
+    path: Path<(String, String)>,
 ) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
         Ok(payload) => payload,
@@ -168,11 +180,12 @@ async fn remove_group_member(
 
     let (group_id, member_id) = path.into_inner();
 
-    match group_service::remove_group_member(&db, group_id, member_id, jwt_payload.user_id).await {
+    match group_service::remove_group_member(&repos, group_id, member_id, jwt_payload.user_id).await
+    {
         Ok(group) => {
             let response = ApiResponse::success("Member removed from group successfully", group);
             HttpResponse::Ok().json(response)
-        },
+        }
         Err(e) => {
             let response = ApiResponse::error("Failed to remove member from group", e.to_string());
             HttpResponse::InternalServerError().json(response)
@@ -183,19 +196,52 @@ async fn remove_group_member(
 // =============================================================================================================================
 
 #[delete("/{group_id}")]
-async fn delete_group(db: Data<Database>, req: HttpRequest, group_id: Path<String>) -> impl Responder {
+async fn delete_group(
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    storage: Data<dyn StorageBackend>,
+    req: HttpRequest,
+    group_id: Path<String>,
+    query: Query<DeletionQueryParams>,
+) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
         Ok(payload) => payload,
         Err(err_res) => return err_res,
     };
 
     let group_id = group_id.into_inner();
-
-    match group_service::delete_group(&db, group_id, jwt_payload.user_id).await {
-        Ok(_) => {
-            let response = ApiResponse::success("Group deleted successfully", ());
+    let target_id = ObjectId::parse_str(&group_id).ok();
+    let dry_run = query.dry_run.unwrap_or(false);
+    let actor = actor(&req, &jwt_payload.user_id);
+
+    match deletion_service::delete_group(
+        &repos,
+        storage.get_ref(),
+        group_id,
+        jwt_payload.user_id,
+        dry_run,
+    )
+    .await
+    {
+        Ok(report) => {
+            if !dry_run {
+                audit_service::record(
+                    &repos,
+                    &config,
+                    &actor,
+                    AuditAction::GroupDeleted,
+                    target_id,
+                    None,
+                )
+                .await;
+            }
+            let message = match dry_run {
+                true => "Dry run, the group was not deleted",
+                false => "Group deleted successfully",
+            };
+            let response = ApiResponse::success(message, report);
             HttpResponse::Ok().json(response)
-        },
+        }
         Err(e) => {
             let response = ApiResponse::error("Failed to delete group", e.to_string());
             HttpResponse::InternalServerError().json(response)
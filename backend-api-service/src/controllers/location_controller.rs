@@ -2,10 +2,10 @@ use actix_web::{
     HttpRequest, HttpResponse, Responder, get, post,
     web::{self, Data, Json, Query, ServiceConfig},
 };
-use mongodb::Database;
 
 use crate::{
     models::location_model::{NearbyUsersQueryParams, UpdateLocationPayload},
+    repositories::Repositories,
     services::location_service,
     utils::{api_response::ApiResponse, jwt::get_authenticated_user},
 };
@@ -24,7 +24,7 @@ pub fn location_routes(cfg: &mut ServiceConfig) {
 
 #[post("/update")]
 async fn update_location(
-    db: Data<Database>,
+    repos: Data<Repositories>,
     req: HttpRequest,
     payload: Json<UpdateLocationPayload>,
 ) -> impl Responder {
@@ -35,7 +35,7 @@ async fn update_location(
 
     let data = payload.into_inner();
 
-    match location_service::update_user_location(&db, jwt_payload.user_id, data).await {
+    match location_service::update_user_location(&repos, jwt_payload.user_id, data).await {
         Ok(user) => {
             let response = ApiResponse::success("User location updated successfully", user);
             HttpResponse::Ok().json(response)
@@ -51,7 +51,7 @@ async fn update_location(
 
 #[get("/nearby/users")]
 async fn find_nearby_users(
-    db: Data<Database>,
+    repos: Data<Repositories>,
     req: HttpRequest,
     query: Query<NearbyUsersQueryParams>,
 ) -> impl Responder {
@@ -62,7 +62,7 @@ async fn find_nearby_users(
 
     let params = query.into_inner();
 
-    match location_service::find_nearby_users(&db, jwt_payload.user_id, params).await {
+    match location_service::find_nearby_users(&repos, jwt_payload.user_id, params).await {
         Ok(users) => {
             let response = ApiResponse::success("Nearby users found successfully", users);
             HttpResponse::Ok().json(response)
@@ -0,0 +1,124 @@
+use actix_multipart::Multipart;
+use actix_web::{
+    HttpRequest, HttpResponse, Responder, delete, get, post,
+    web::{self, Data, Path, ServiceConfig},
+};
+
+use crate::{
+    config::Config,
+    repositories::Repositories,
+    services::{media_service, upload_service},
+    storage::StorageBackend,
+    utils::{
+        api_response::ApiResponse,
+        jwt::get_authenticated_user,
+        rate_limit::{Budget, RateLimit},
+    },
+};
+
+// =============================================================================================================================
+
+pub fn media_routes(cfg: &mut ServiceConfig) {
+    let scope = web::scope("/media")
+        .service(upload_media)
+        .service(get_media)
+        .service(delete_media);
+
+    cfg.service(scope);
+}
+
+// =============================================================================================================================
+
+#[post("", wrap = "RateLimit::new(Budget::Upload)")]
+async fn upload_media(
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    config: Data<Config>,
+    req: HttpRequest,
+    form: Multipart,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    match upload_service::upload_form(
+        &repos,
+        storage.get_ref(),
+        &config,
+        jwt_payload.user_id,
+        form,
+        true,
+    )
+    .await
+    {
+        Ok(form) => {
+            let response = ApiResponse::success("Media uploaded successfully", form.media);
+            HttpResponse::Created().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to upload media", e.to_string());
+            HttpResponse::BadRequest().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[get("/{media_id}")]
+async fn get_media(
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    req: HttpRequest,
+    media_id: Path<String>,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    let media_id = media_id.into_inner();
+
+    match media_service::get_media(&repos, storage.get_ref(), media_id, jwt_payload.user_id).await {
+        Ok(media) => {
+            let response = ApiResponse::success("Media retrieved successfully", media);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to retrieve media", e.to_string());
+            HttpResponse::NotFound().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[delete("/{media_id}")]
+async fn delete_media(
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    req: HttpRequest,
+    media_id: Path<String>,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    let media_id = media_id.into_inner();
+
+    match media_service::delete_media(&repos, storage.get_ref(), media_id, jwt_payload.user_id)
+        .await
+    {
+        Ok(_) => {
+            let response = ApiResponse::success("Media deleted successfully", ());
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to delete media", e.to_string());
+            HttpResponse::InternalServerError().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
@@ -1,15 +1,24 @@
+use actix_multipart::Multipart;
 use actix_web::{
-    HttpRequest, HttpResponse, Responder, delete, get,
-    http::header::ContentType,
-    post,
-    web::{self, Bytes, Data, Json, Path, Query, ServiceConfig},
+    HttpRequest, HttpResponse, Responder, delete, get, post,
+    web::{self, Data, Json, Path, Query, ServiceConfig},
 };
-use mongodb::Database;
 
 use crate::{
-    models::message_model::{CreateMessage, Media, MediaType, MessageQueryParams},
-    services::{file_service, message_service},
-    utils::{api_response::ApiResponse, jwt::get_authenticated_user},
+    config::Config,
+    models::{
+        audit_model::AuditAction,
+        message_model::{CreateMessage, MessageQueryParams},
+    },
+    repositories::Repositories,
+    services::{audit_service, media_service, message_service, upload_service},
+    storage::StorageBackend,
+    utils::{
+        api_response::ApiResponse,
+        jwt::get_authenticated_user,
+        rate_limit::{Budget, RateLimit},
+        session::actor,
+    },
 };
 
 // =============================================================================================================================
@@ -31,7 +40,8 @@ pub fn message_routes(cfg: &mut ServiceConfig) {
 
 #[get("/{recipient_id}")]
 async fn get_direct_messages(
-    db: Data<Database>,
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
     req: HttpRequest,
     recipient_id: Path<String>,
     query: Query<MessageQueryParams>,
@@ -44,7 +54,14 @@ async fn get_direct_messages(
     let recipient_id = recipient_id.into_inner();
     let params = query.into_inner();
 
-    match message_service::get_direct_messages(&db, jwt_payload.user_id, recipient_id, params).await
+    match message_service::get_direct_messages(
+        &repos,
+        storage.get_ref(),
+        jwt_payload.user_id,
+        recipient_id,
+        params,
+    )
+    .await
     {
         Ok(messages) => {
             let response = ApiResponse::success("Messages retrieved successfully", messages);
@@ -61,7 +78,8 @@ async fn get_direct_messages(
 
 #[post("/{recipient_id}")]
 async fn send_direct_message(
-    db: Data<Database>,
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
     req: HttpRequest,
     recipient_id: Path<String>,
     payload: Json<CreateMessage>,
@@ -74,7 +92,15 @@ async fn send_direct_message(
     let recipient_id = recipient_id.into_inner();
     let data = payload.into_inner();
 
-    match message_service::send_direct_message(&db, jwt_payload.user_id, recipient_id, data).await {
+    match message_service::send_direct_message(
+        &repos,
+        storage.get_ref(),
+        jwt_payload.user_id,
+        recipient_id,
+        data,
+    )
+    .await
+    {
         Ok(message) => {
             let response = ApiResponse::success("Message sent successfully", message);
             HttpResponse::Created().json(response)
@@ -88,14 +114,14 @@ async fn send_direct_message(
 
 // =============================================================================================================================
 
-#[post("/{recipient_id}/media")]
+#[post("/{recipient_id}/media", wrap = "RateLimit::new(Budget::Upload)")]
 async fn send_direct_message_with_media(
-    db: Data<Database>,
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    config: Data<Config>,
     req: HttpRequest,
     recipient_id: Path<String>,
-    content_type: web::Header<ContentType>,
-    body: Bytes,
-    text_content: Query<Option<String>>,
+    form: Multipart,
 ) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
         Ok(payload) => payload,
@@ -103,74 +129,49 @@ async fn send_direct_message_with_media(
     };
 
     let recipient_id = recipient_id.into_inner();
-    let content_type_str = content_type.to_string();
-
-    // Validate file type
-    if !content_type_str.starts_with("image/") && !content_type_str.starts_with("video/") {
-        let response = ApiResponse::error(
-            "Invalid content type",
-            "Only image and video files are allowed",
-        );
-        return HttpResponse::BadRequest().json(response);
-    }
 
-    // Validate file size
-    if let Err(e) = file_service::validate_file_size(&content_type_str, body.len()) {
-        let response = ApiResponse::error("File size validation failed", e);
-        return HttpResponse::BadRequest().json(response);
-    }
+    let form = match upload_service::upload_form(
+        &repos,
+        storage.get_ref(),
+        &config,
+        jwt_payload.user_id.clone(),
+        form,
+        false,
+    )
+    .await
+    {
+        Ok(form) => form,
+        Err(e) => {
+            let response = ApiResponse::error("Failed to upload media", e.to_string());
+            return HttpResponse::BadRequest().json(response);
+        }
+    };
+    let media = form.media;
 
-    // Upload file to MinIO
-    match file_service::upload_file(&body, &content_type_str).await {
-        Ok(url) => {
-            // Create message with media
-            let media_type = if content_type_str.starts_with("image/") {
-                MediaType::Image
-            } else {
-                MediaType::Video
-            };
-
-            let duration = if content_type_str.starts_with("video/") {
-                Some(10.0) // Default to 10 seconds max
-            } else {
-                None
-            };
-
-            let media = Media {
-                media_type,
-                url,
-                duration,
-            };
-
-            let message = CreateMessage {
-                content: text_content
-                    .as_ref()
-                    .map_or_else(|| "".to_string(), |q| q.clone()),
-                media: Some(media),
-            };
-
-            match message_service::send_direct_message(
-                &db,
-                jwt_payload.user_id,
-                recipient_id,
-                message,
-            )
-            .await
-            {
-                Ok(message) => {
-                    let response =
-                        ApiResponse::success("Message with media sent successfully", message);
-                    HttpResponse::Created().json(response)
-                }
-                Err(e) => {
-                    let response =
-                        ApiResponse::error("Failed to send message with media", e.to_string());
-                    HttpResponse::InternalServerError().json(response)
-                }
-            }
+    let message = CreateMessage {
+        content: form.fields.get("text_content").cloned().unwrap_or_default(),
+        media_id: media.id.map(|id| id.to_hex()),
+    };
+
+    match message_service::send_direct_message(
+        &repos,
+        storage.get_ref(),
+        jwt_payload.user_id,
+        recipient_id,
+        message,
+    )
+    .await
+    {
+        Ok(message) => {
+            let response = ApiResponse::success("Message with media sent successfully", message);
+            HttpResponse::Created().json(response)
         }
         Err(e) => {
-            let response = ApiResponse::error("Failed to upload media", e.to_string());
+            if let Some(media_id) = media.id {
+                let _ = media_service::delete_if_unreferenced(&repos, storage.get_ref(), media_id)
+                    .await;
+            }
+            let response = ApiResponse::error("Failed to send message with media", e.to_string());
             HttpResponse::InternalServerError().json(response)
         }
     }
@@ -180,7 +181,8 @@ async fn send_direct_message_with_media(
 
 #[get("/groups/{group_id}")]
 async fn get_group_messages(
-    db: Data<Database>,
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
     req: HttpRequest,
     group_id: Path<String>,
     query: Query<MessageQueryParams>,
@@ -193,7 +195,15 @@ async fn get_group_messages(
     let group_id = group_id.into_inner();
     let params = query.into_inner();
 
-    match message_service::get_group_messages(&db, jwt_payload.user_id, group_id, params).await {
+    match message_service::get_group_messages(
+        &repos,
+        storage.get_ref(),
+        jwt_payload.user_id,
+        group_id,
+        params,
+    )
+    .await
+    {
         Ok(messages) => {
             let response = ApiResponse::success("Group messages retrieved successfully", messages);
             HttpResponse::Ok().json(response)
@@ -209,7 +219,8 @@ async fn get_group_messages(
 
 #[post("/groups/{group_id}")]
 async fn send_group_message(
-    db: Data<Database>,
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
     req: HttpRequest,
     group_id: Path<String>,
     payload: Json<CreateMessage>,
@@ -222,7 +233,15 @@ async fn send_group_message(
     let group_id = group_id.into_inner();
     let data = payload.into_inner();
 
-    match message_service::send_group_message(&db, jwt_payload.user_id, group_id, data).await {
+    match message_service::send_group_message(
+        &repos,
+        storage.get_ref(),
+        jwt_payload.user_id,
+        group_id,
+        data,
+    )
+    .await
+    {
         Ok(message) => {
             let response = ApiResponse::success("Group message sent successfully", message);
             HttpResponse::Created().json(response)
@@ -236,14 +255,14 @@ async fn send_group_message(
 
 // =============================================================================================================================
 
-#[post("/groups/{group_id}/media")]
+#[post("/groups/{group_id}/media", wrap = "RateLimit::new(Budget::Upload)")]
 async fn send_group_message_with_media(
-    db: Data<Database>,
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    config: Data<Config>,
     req: HttpRequest,
     group_id: Path<String>,
-    content_type: web::Header<ContentType>,
-    body: Bytes,
-    text_content: Query<Option<String>>,
+    form: Multipart,
 ) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
         Ok(payload) => payload,
@@ -251,71 +270,51 @@ async fn send_group_message_with_media(
     };
 
     let group_id = group_id.into_inner();
-    let content_type_str = content_type.to_string();
-
-    // Validate file type
-    if !content_type_str.starts_with("image/") && !content_type_str.starts_with("video/") {
-        let response = ApiResponse::error(
-            "Invalid content type",
-            "Only image and video files are allowed",
-        );
-        return HttpResponse::BadRequest().json(response);
-    }
 
-    // Validate file size
-    if let Err(e) = file_service::validate_file_size(&content_type_str, body.len()) {
-        let response = ApiResponse::error("File size validation failed", e);
-        return HttpResponse::BadRequest().json(response);
-    }
+    let form = match upload_service::upload_form(
+        &repos,
+        storage.get_ref(),
+        &config,
+        jwt_payload.user_id.clone(),
+        form,
+        false,
+    )
+    .await
+    {
+        Ok(form) => form,
+        Err(e) => {
+            let response = ApiResponse::error("Failed to upload media", e.to_string());
+            return HttpResponse::BadRequest().json(response);
+        }
+    };
+    let media = form.media;
 
-    // Upload file to MinIO
-    match file_service::upload_file(&body, &content_type_str).await {
-        Ok(url) => {
-            // Create message with media
-            let media_type = if content_type_str.starts_with("image/") {
-                MediaType::Image
-            } else {
-                MediaType::Video
-            };
-
-            let duration = if content_type_str.starts_with("video/") {
-                Some(10.0) // Default to 10 seconds max
-            } else {
-                None
-            };
-
-            let media = Media {
-                media_type,
-                url,
-                duration,
-            };
-
-            let message = CreateMessage {
-                content: text_content
-                    .as_ref()
-                    .map_or_else(|| "".to_string(), |q| q.clone()),
-                media: Some(media),
-            };
-
-            match message_service::send_group_message(&db, jwt_payload.user_id, group_id, message)
-                .await
-            {
-                Ok(message) => {
-                    let response =
-                        ApiResponse::success("Group message with media sent successfully", message);
-                    HttpResponse::Created().json(response)
-                }
-                Err(e) => {
-                    let response = ApiResponse::error(
-                        "Failed to send group message with media",
-                        e.to_string(),
-                    );
-                    HttpResponse::InternalServerError().json(response)
-                }
-            }
+    let message = CreateMessage {
+        content: form.fields.get("text_content").cloned().unwrap_or_default(),
+        media_id: media.id.map(|id| id.to_hex()),
+    };
+
+    match message_service::send_group_message(
+        &repos,
+        storage.get_ref(),
+        jwt_payload.user_id,
+        group_id,
+        message,
+    )
+    .await
+    {
+        Ok(message) => {
+            let response =
+                ApiResponse::success("Group message with media sent successfully", message);
+            HttpResponse::Created().json(response)
         }
         Err(e) => {
-            let response = ApiResponse::error("Failed to upload media", e.to_string());
+            if let Some(media_id) = media.id {
+                let _ = media_service::delete_if_unreferenced(&repos, storage.get_ref(), media_id)
+                    .await;
+            }
+            let response =
+                ApiResponse::error("Failed to send group message with media", e.to_string());
             HttpResponse::InternalServerError().json(response)
         }
     }
@@ -325,7 +324,9 @@ async fn send_group_message_with_media(
 
 #[delete("/{message_id}")]
 async fn delete_message(
-    db: Data<Database>,
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    storage: Data<dyn StorageBackend>,
     req: HttpRequest,
     message_id: Path<String>,
 ) -> impl Responder {
@@ -335,9 +336,26 @@ async fn delete_message(
     };
 
     let message_id = message_id.into_inner();
-
-    match message_service::delete_message(&db, message_id, jwt_payload.user_id).await {
-        Ok(_) => {
+    let actor = actor(&req, &jwt_payload.user_id);
+
+    match message_service::delete_message(
+        &repos,
+        storage.get_ref(),
+        message_id,
+        jwt_payload.user_id,
+    )
+    .await
+    {
+        Ok(message) => {
+            audit_service::record(
+                &repos,
+                &config,
+                &actor,
+                AuditAction::MessageDeleted,
+                message.id,
+                None,
+            )
+            .await;
             let response = ApiResponse::success("Message deleted successfully", ());
             HttpResponse::Ok().json(response)
         }
@@ -1,28 +1,45 @@
-use crate::utils::api_response::ApiResponse;
+use crate::{
+    config::Config,
+    utils::{api_response::ApiResponse, session::SessionGuard},
+};
 use actix_web::{
     HttpResponse, Responder, get,
+    http::header,
     web::{self},
 };
+use audit_controller::audit_routes;
 use auth_controller::auth_routes;
+use file_controller::file_routes;
 use friend_controller::friend_routes;
 use group_controller::group_routes;
 use location_controller::location_routes;
+use media_controller::media_routes;
 use message_controller::message_routes;
+use moderation_controller::moderation_routes;
+use storage_controller::storage_routes;
 use story_controller::story_routes;
+use upload_controller::upload_routes;
 use user_controller::user_routes;
 
+pub mod audit_controller;
 pub mod auth_controller;
+pub mod file_controller;
 pub mod friend_controller;
 pub mod group_controller;
 pub mod location_controller;
+pub mod media_controller;
 pub mod message_controller;
+pub mod moderation_controller;
+pub mod storage_controller;
 pub mod story_controller;
+pub mod upload_controller;
 pub mod user_controller;
 
 // =============================================================================================================================
 
 pub fn routes(cfg: &mut web::ServiceConfig) {
     let scope = web::scope("/api")
+        .wrap(SessionGuard)
         .service(healthcheck)
         .configure(auth_routes)
         .configure(user_routes)
@@ -30,9 +47,15 @@ pub fn routes(cfg: &mut web::ServiceConfig) {
         .configure(group_routes)
         .configure(message_routes)
         .configure(story_routes)
-        .configure(location_routes);
+        .configure(location_routes)
+        .configure(media_routes)
+        .configure(upload_routes)
+        .configure(storage_routes)
+        .configure(file_routes)
+        .configure(moderation_routes)
+        .configure(audit_routes);
 
-    cfg.service(scope);
+    cfg.service(scope).service(jwks);
 }
 
 // =============================================================================================================================
@@ -46,3 +69,14 @@ async fn healthcheck() -> impl Responder {
 }
 
 // =============================================================================================================================
+
+/// Public keys other services verify our tokens with. Cached briefly so a newly added key is picked up before it
+/// becomes the active one.
+#[get("/.well-known/jwks.json")]
+async fn jwks(config: web::Data<Config>) -> impl Responder {
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "public, max-age=300"))
+        .json(config.auth.keys.jwks())
+}
+
+// =============================================================================================================================
@@ -0,0 +1,336 @@
+use actix_web::{
+    HttpRequest, HttpResponse, Responder, get, post,
+    web::{self, Data, Json, Path, Query, ServiceConfig},
+};
+use std::error::Error;
+
+use crate::{
+    config::Config,
+    models::{
+        audit_model::AuditAction,
+        moderation_model::{
+            CreateReport, DismissReport, ModerationActionQueryParams, ReportQueryParams,
+            ResolveReport, Sanction,
+        },
+        user_model::UserRole,
+    },
+    repositories::Repositories,
+    services::{
+        audit_service,
+        moderation_service::{self, ModerationTargetNotFound},
+    },
+    storage::StorageBackend,
+    utils::{
+        api_response::ApiResponse,
+        jwt::{get_authenticated_user, user_has_any_of_these_roles},
+        session::actor,
+    },
+};
+
+// =============================================================================================================================
+
+pub fn moderation_routes(cfg: &mut ServiceConfig) {
+    let reports = web::scope("/reports").service(create_report);
+
+    let moderation = web::scope("/moderation")
+        .service(get_reports)
+        .service(resolve_report)
+        .service(dismiss_report)
+        .service(suspend_user)
+        .service(ban_user)
+        .service(reinstate_user)
+        .service(get_actions);
+
+    cfg.service(reports).service(moderation);
+}
+
+// =============================================================================================================================
+
+#[post("")]
+async fn create_report(
+    repos: Data<Repositories>,
+    payload: Json<CreateReport>,
+    req: HttpRequest,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    match moderation_service::create_report(&repos, jwt_payload.user_id, payload.into_inner()).await
+    {
+        Ok(report) => {
+            let response = ApiResponse::success("Report submitted for review", report);
+            HttpResponse::Created().json(response)
+        }
+        Err(e) => moderation_error("Failed to submit the report", e),
+    }
+}
+
+// =============================================================================================================================
+
+#[get("/reports")]
+async fn get_reports(
+    repos: Data<Repositories>,
+    query: Query<ReportQueryParams>,
+    req: HttpRequest,
+) -> impl Responder {
+    if let Err(err_res) = user_has_any_of_these_roles(&req, &[UserRole::Admin]) {
+        return err_res;
+    }
+
+    match moderation_service::get_reports(&repos, query.into_inner().status).await {
+        Ok(reports) => {
+            let response = ApiResponse::success("Reports retrieved successfully", reports);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to retrieve the reports", e.to_string());
+            HttpResponse::InternalServerError().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/reports/{report_id}/resolve")]
+async fn resolve_report(
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    storage: Data<dyn StorageBackend>,
+    report_id: Path<String>,
+    payload: Json<ResolveReport>,
+    req: HttpRequest,
+) -> impl Responder {
+    let claims = match user_has_any_of_these_roles(&req, &[UserRole::Admin]) {
+        Ok(claims) => claims,
+        Err(err_res) => return err_res,
+    };
+
+    let actor = actor(&req, &claims.user_id);
+    let remove_content = payload.remove_content;
+
+    match moderation_service::resolve_report(
+        &repos,
+        storage.get_ref(),
+        claims.user_id,
+        report_id.into_inner(),
+        payload.into_inner(),
+    )
+    .await
+    {
+        Ok(report) => {
+            if remove_content {
+                audit_service::record(
+                    &repos,
+                    &config,
+                    &actor,
+                    AuditAction::ContentRemoved,
+                    Some(report.target_id),
+                    Some(format!("{:?} taken down", report.target)),
+                )
+                .await;
+            }
+            let response = ApiResponse::success("Report resolved", report);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => moderation_error("Failed to resolve the report", e),
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/reports/{report_id}/dismiss")]
+async fn dismiss_report(
+    repos: Data<Repositories>,
+    report_id: Path<String>,
+    payload: Json<DismissReport>,
+    req: HttpRequest,
+) -> impl Responder {
+    let claims = match user_has_any_of_these_roles(&req, &[UserRole::Admin]) {
+        Ok(claims) => claims,
+        Err(err_res) => return err_res,
+    };
+
+    match moderation_service::dismiss_report(
+        &repos,
+        claims.user_id,
+        report_id.into_inner(),
+        payload.into_inner(),
+    )
+    .await
+    {
+        Ok(report) => {
+            let response = ApiResponse::success("Report dismissed", report);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => moderation_error("Failed to dismiss the report", e),
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/users/{user_id}/suspend")]
+async fn suspend_user(
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    user_id: Path<String>,
+    payload: Json<Sanction>,
+    req: HttpRequest,
+) -> impl Responder {
+    let claims = match user_has_any_of_these_roles(&req, &[UserRole::Admin]) {
+        Ok(claims) => claims,
+        Err(err_res) => return err_res,
+    };
+
+    let actor = actor(&req, &claims.user_id);
+    let reason = payload.reason.clone();
+
+    match moderation_service::suspend_user(
+        &repos,
+        claims.user_id,
+        user_id.into_inner(),
+        payload.into_inner(),
+    )
+    .await
+    {
+        Ok(user) => {
+            audit_service::record(
+                &repos,
+                &config,
+                &actor,
+                AuditAction::UserSuspended,
+                user.id,
+                Some(reason),
+            )
+            .await;
+            let response = ApiResponse::success("User suspended", user);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => moderation_error("Failed to suspend the user", e),
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/users/{user_id}/ban")]
+async fn ban_user(
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    user_id: Path<String>,
+    payload: Json<Sanction>,
+    req: HttpRequest,
+) -> impl Responder {
+    let claims = match user_has_any_of_these_roles(&req, &[UserRole::Admin]) {
+        Ok(claims) => claims,
+        Err(err_res) => return err_res,
+    };
+
+    let actor = actor(&req, &claims.user_id);
+    let reason = payload.reason.clone();
+
+    match moderation_service::ban_user(
+        &repos,
+        claims.user_id,
+        user_id.into_inner(),
+        payload.into_inner(),
+    )
+    .await
+    {
+        Ok(user) => {
+            audit_service::record(
+                &repos,
+                &config,
+                &actor,
+                AuditAction::UserBanned,
+                user.id,
+                Some(reason),
+            )
+            .await;
+            let response = ApiResponse::success("User banned", user);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => moderation_error("Failed to ban the user", e),
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/users/{user_id}/reinstate")]
+async fn reinstate_user(
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    user_id: Path<String>,
+    payload: Json<Sanction>,
+    req: HttpRequest,
+) -> impl Responder {
+    let claims = match user_has_any_of_these_roles(&req, &[UserRole::Admin]) {
+        Ok(claims) => claims,
+        Err(err_res) => return err_res,
+    };
+
+    let actor = actor(&req, &claims.user_id);
+    let reason = payload.reason.clone();
+
+    match moderation_service::reinstate_user(
+        &repos,
+        claims.user_id,
+        user_id.into_inner(),
+        payload.into_inner(),
+    )
+    .await
+    {
+        Ok(user) => {
+            audit_service::record(
+                &repos,
+                &config,
+                &actor,
+                AuditAction::UserReinstated,
+                user.id,
+                Some(reason),
+            )
+            .await;
+            let response = ApiResponse::success("User reinstated", user);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => moderation_error("Failed to reinstate the user", e),
+    }
+}
+
+// =============================================================================================================================
+
+#[get("/actions")]
+async fn get_actions(
+    repos: Data<Repositories>,
+    query: Query<ModerationActionQueryParams>,
+    req: HttpRequest,
+) -> impl Responder {
+    if let Err(err_res) = user_has_any_of_these_roles(&req, &[UserRole::Admin]) {
+        return err_res;
+    }
+
+    match moderation_service::get_actions(&repos, query.into_inner()).await {
+        Ok(actions) => {
+            let response = ApiResponse::success("Moderation log retrieved successfully", actions);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response =
+                ApiResponse::error("Failed to retrieve the moderation log", e.to_string());
+            HttpResponse::BadRequest().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+fn moderation_error(message: &str, e: Box<dyn Error>) -> HttpResponse {
+    let response = ApiResponse::error(message, e.to_string());
+    if e.is::<ModerationTargetNotFound>() {
+        HttpResponse::NotFound().json(response)
+    } else {
+        HttpResponse::BadRequest().json(response)
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,80 @@
+use actix_web::{
+    HttpRequest, HttpResponse, Responder, get, put,
+    web::{self, Data, Json, Path, ServiceConfig},
+};
+
+use crate::{
+    config::Config,
+    models::{storage_model::UpdateStorageQuota, user_model::UserRole},
+    repositories::Repositories,
+    services::storage_service,
+    utils::{api_response::ApiResponse, jwt::user_has_any_of_these_roles},
+};
+
+// =============================================================================================================================
+
+pub fn storage_routes(cfg: &mut ServiceConfig) {
+    let scope = web::scope("/storage")
+        .service(get_quotas)
+        .service(update_quota);
+
+    cfg.service(scope);
+}
+
+// =============================================================================================================================
+
+#[get("/quotas")]
+async fn get_quotas(
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    req: HttpRequest,
+) -> impl Responder {
+    let required_roles = &[UserRole::Admin];
+    match user_has_any_of_these_roles(&req, required_roles) {
+        Ok(claims) => claims,
+        Err(err_res) => return err_res,
+    };
+
+    match storage_service::get_quotas(&repos, &config).await {
+        Ok(quotas) => {
+            let response = ApiResponse::success("Storage quotas successfully retrieved", quotas);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to retrieve storage quotas", e.to_string());
+            HttpResponse::InternalServerError().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[put("/quotas/{role}")]
+async fn update_quota(
+    repos: Data<Repositories>,
+    req: HttpRequest,
+    role: Path<UserRole>,
+    payload: Json<UpdateStorageQuota>,
+) -> impl Responder {
+    let required_roles = &[UserRole::Admin];
+    match user_has_any_of_these_roles(&req, required_roles) {
+        Ok(claims) => claims,
+        Err(err_res) => return err_res,
+    };
+
+    let role = role.into_inner();
+    let data = payload.into_inner();
+
+    match storage_service::update_quota(&repos, role, data).await {
+        Ok(quota) => {
+            let response = ApiResponse::success("Storage quota successfully updated", quota);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to update the storage quota", e.to_string());
+            HttpResponse::BadRequest().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
@@ -1,18 +1,20 @@
+use actix_multipart::Multipart;
 use actix_web::{
-    HttpRequest, HttpResponse, Responder, delete, get,
-    http::header::ContentType,
-    post,
-    web::{self, Bytes, Data, Json, Path, Query, ServiceConfig},
+    HttpRequest, HttpResponse, Responder, delete, get, post,
+    web::{self, Data, Json, Path, Query, ServiceConfig},
 };
-use mongodb::Database;
 
 use crate::{
-    models::{
-        message_model::{Media, MediaType},
-        story_model::{CreateStory, Location, NearbyQueryParams},
+    config::Config,
+    models::story_model::{CreateStory, NearbyQueryParams, StoryLocationQueryParams},
+    repositories::Repositories,
+    services::{media_service, story_service, upload_service},
+    storage::StorageBackend,
+    utils::{
+        api_response::ApiResponse,
+        jwt::get_authenticated_user,
+        rate_limit::{Budget, RateLimit},
     },
-    services::{file_service, story_service},
-    utils::{api_response::ApiResponse, jwt::get_authenticated_user},
 };
 
 // =============================================================================================================================
@@ -32,13 +34,17 @@ pub fn story_routes(cfg: &mut ServiceConfig) {
 // =============================================================================================================================
 
 #[get("")]
-async fn get_stories(db: Data<Database>, req: HttpRequest) -> impl Responder {
+async fn get_stories(
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    req: HttpRequest,
+) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
         Ok(payload) => payload,
         Err(err_res) => return err_res,
     };
 
-    match story_service::get_friend_stories(&db, jwt_payload.user_id).await {
+    match story_service::get_friend_stories(&repos, storage.get_ref(), jwt_payload.user_id).await {
         Ok(stories) => {
             let response = ApiResponse::success("Stories retrieved successfully", stories);
             HttpResponse::Ok().json(response)
@@ -54,7 +60,8 @@ async fn get_stories(db: Data<Database>, req: HttpRequest) -> impl Responder {
 
 #[get("/nearby")]
 async fn get_nearby_stories(
-    db: Data<Database>,
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
     req: HttpRequest,
     query: Query<NearbyQueryParams>,
 ) -> impl Responder {
@@ -65,7 +72,9 @@ async fn get_nearby_stories(
 
     let params = query.into_inner();
 
-    match story_service::get_nearby_stories(&db, jwt_payload.user_id, params).await {
+    match story_service::get_nearby_stories(&repos, storage.get_ref(), jwt_payload.user_id, params)
+        .await
+    {
         Ok(stories) => {
             let response = ApiResponse::success("Nearby stories retrieved successfully", stories);
             HttpResponse::Ok().json(response)
@@ -81,7 +90,9 @@ async fn get_nearby_stories(
 
 #[post("")]
 async fn create_story(
-    db: Data<Database>,
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    config: Data<Config>,
     req: HttpRequest,
     payload: Json<CreateStory>,
 ) -> impl Responder {
@@ -92,7 +103,15 @@ async fn create_story(
 
     let data = payload.into_inner();
 
-    match story_service::create_story(&db, jwt_payload.user_id, data).await {
+    match story_service::create_story(
+        &repos,
+        storage.get_ref(),
+        &config,
+        jwt_payload.user_id,
+        data,
+    )
+    .await
+    {
         Ok(story) => {
             let response = ApiResponse::success("Story created successfully", story);
             HttpResponse::Created().json(response)
@@ -106,77 +125,69 @@ async fn create_story(
 
 // =============================================================================================================================
 
-#[post("/media")]
+#[post("/media", wrap = "RateLimit::new(Budget::Upload)")]
 async fn create_story_with_media(
-    db: Data<Database>,
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    config: Data<Config>,
     req: HttpRequest,
-    content_type: web::Header<ContentType>,
-    body: Bytes,
-    location: Query<Location>,
+    form: Multipart,
+    location: Query<StoryLocationQueryParams>,
 ) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
         Ok(payload) => payload,
         Err(err_res) => return err_res,
     };
 
-    let content_type_str = content_type.to_string();
+    let location = match location.into_inner().into_location() {
+        Ok(location) => location,
+        Err(e) => {
+            let response = ApiResponse::error("Invalid story location", e);
+            return HttpResponse::BadRequest().json(response);
+        }
+    };
 
-    // Validate file type
-    if !content_type_str.starts_with("image/") && !content_type_str.starts_with("video/") {
-        let response = ApiResponse::error(
-            "Invalid content type",
-            "Only image and video files are allowed",
-        );
-        return HttpResponse::BadRequest().json(response);
-    }
+    let media = match upload_service::upload_form(
+        &repos,
+        storage.get_ref(),
+        &config,
+        jwt_payload.user_id.clone(),
+        form,
+        false,
+    )
+    .await
+    {
+        Ok(form) => form.media,
+        Err(e) => {
+            let response = ApiResponse::error("Failed to upload media", e.to_string());
+            return HttpResponse::BadRequest().json(response);
+        }
+    };
 
-    // Validate file size
-    if let Err(e) = file_service::validate_file_size(&content_type_str, body.len()) {
-        let response = ApiResponse::error("File size validation failed", e);
-        return HttpResponse::BadRequest().json(response);
-    }
+    let story = CreateStory {
+        media_id: media.id.map(|id| id.to_hex()).unwrap_or_default(),
+        location,
+    };
 
-    // Upload file to MinIO
-    match file_service::upload_file(&body, &content_type_str).await {
-        Ok(url) => {
-            let media_type = if content_type_str.starts_with("image/") {
-                MediaType::Image
-            } else {
-                MediaType::Video
-            };
-
-            let duration = if content_type_str.starts_with("video/") {
-                Some(10.0) // Default to 10 seconds max
-            } else {
-                None
-            };
-
-            let media = Media {
-                media_type,
-                url,
-                duration,
-            };
-
-            let story = CreateStory {
-                media,
-                location: location.into_inner(),
-            };
-
-            match story_service::create_story(&db, jwt_payload.user_id, story).await {
-                Ok(story) => {
-                    let response =
-                        ApiResponse::success("Story with media created successfully", story);
-                    HttpResponse::Created().json(response)
-                }
-                Err(e) => {
-                    let response =
-                        ApiResponse::error("Failed to create story with media", e.to_string());
-                    HttpResponse::InternalServerError().json(response)
-                }
-            }
+    match story_service::create_story(
+        &repos,
+        storage.get_ref(),
+        &config,
+        jwt_payload.user_id,
+        story,
+    )
+    .await
+    {
+        Ok(story) => {
+            let response = ApiResponse::success("Story with media created successfully", story);
+            HttpResponse::Created().json(response)
         }
         Err(e) => {
-            let response = ApiResponse::error("Failed to upload media", e.to_string());
+            if let Some(media_id) = media.id {
+                let _ = media_service::delete_if_unreferenced(&repos, storage.get_ref(), media_id)
+                    .await;
+            }
+            let response = ApiResponse::error("Failed to create story with media", e.to_string());
             HttpResponse::InternalServerError().json(response)
         }
     }
@@ -186,7 +197,8 @@ async fn create_story_with_media(
 
 #[get("/{story_id}")]
 async fn get_story_by_id(
-    db: Data<Database>,
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
     req: HttpRequest,
     story_id: Path<String>,
 ) -> impl Responder {
@@ -197,7 +209,9 @@ async fn get_story_by_id(
 
     let story_id = story_id.into_inner();
 
-    match story_service::get_story_by_id(&db, story_id, jwt_payload.user_id).await {
+    match story_service::get_story_by_id(&repos, storage.get_ref(), story_id, jwt_payload.user_id)
+        .await
+    {
         Ok(story) => {
             let response = ApiResponse::success("Story retrieved successfully", story);
             HttpResponse::Ok().json(response)
@@ -213,7 +227,8 @@ async fn get_story_by_id(
 
 #[delete("/{story_id}")]
 async fn delete_story(
-    db: Data<Database>,
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
     req: HttpRequest,
     story_id: Path<String>,
 ) -> impl Responder {
@@ -224,7 +239,9 @@ async fn delete_story(
 
     let story_id = story_id.into_inner();
 
-    match story_service::delete_story(&db, story_id, jwt_payload.user_id).await {
+    match story_service::delete_story(&repos, storage.get_ref(), story_id, jwt_payload.user_id)
+        .await
+    {
         Ok(_) => {
             let response = ApiResponse::success("Story deleted successfully", ());
             HttpResponse::Ok().json(response)
@@ -0,0 +1,280 @@
+use actix_web::{
+    HttpRequest, HttpResponse, Responder, delete, get, post, put,
+    web::{self, Data, Json, Path, Payload, ServiceConfig},
+};
+
+use crate::{
+    config::Config,
+    models::media_model::{ConfirmUpload, CreateUploadSession, PresignUpload},
+    repositories::Repositories,
+    services::upload_service,
+    storage::StorageBackend,
+    utils::{
+        api_response::ApiResponse,
+        jwt::get_authenticated_user,
+        rate_limit::{Budget, RateLimit},
+    },
+};
+
+// =============================================================================================================================
+
+pub fn upload_routes(cfg: &mut ServiceConfig) {
+    let scope = web::scope("/uploads")
+        .service(presign_upload)
+        .service(confirm_upload)
+        .service(create_session)
+        .service(get_session)
+        .service(upload_session_part)
+        .service(complete_session)
+        .service(cancel_session);
+
+    cfg.service(scope);
+}
+
+// =============================================================================================================================
+
+#[post("/presign", wrap = "RateLimit::new(Budget::Upload)")]
+async fn presign_upload(
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    config: Data<Config>,
+    req: HttpRequest,
+    payload: Json<PresignUpload>,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    let data = payload.into_inner();
+
+    match upload_service::presign_upload(
+        &repos,
+        storage.get_ref(),
+        &config,
+        jwt_payload.user_id,
+        data,
+    )
+    .await
+    {
+        Ok(upload) => {
+            let response = ApiResponse::success("Upload URL generated successfully", upload);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to generate upload URL", e.to_string());
+            HttpResponse::BadRequest().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/confirm")]
+async fn confirm_upload(
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    config: Data<Config>,
+    req: HttpRequest,
+    payload: Json<ConfirmUpload>,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    let data = payload.into_inner();
+
+    match upload_service::confirm_upload(
+        &repos,
+        storage.get_ref(),
+        &config,
+        jwt_payload.user_id,
+        data,
+    )
+    .await
+    {
+        Ok(upload) => {
+            let response = ApiResponse::success("Upload confirmed successfully", upload);
+            HttpResponse::Created().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to confirm upload", e.to_string());
+            HttpResponse::BadRequest().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/sessions", wrap = "RateLimit::new(Budget::Upload)")]
+async fn create_session(
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    config: Data<Config>,
+    req: HttpRequest,
+    payload: Json<CreateUploadSession>,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    let data = payload.into_inner();
+
+    match upload_service::create_session(
+        &repos,
+        storage.get_ref(),
+        &config,
+        jwt_payload.user_id,
+        data,
+    )
+    .await
+    {
+        Ok(session) => {
+            let response = ApiResponse::success("Upload session created successfully", session);
+            HttpResponse::Created().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to create upload session", e.to_string());
+            HttpResponse::BadRequest().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[get("/sessions/{session_id}")]
+async fn get_session(
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    req: HttpRequest,
+    session_id: Path<String>,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    let session_id = session_id.into_inner();
+
+    match upload_service::get_session(&repos, storage.get_ref(), session_id, jwt_payload.user_id)
+        .await
+    {
+        Ok(session) => {
+            let response = ApiResponse::success("Upload session retrieved successfully", session);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to retrieve upload session", e.to_string());
+            HttpResponse::NotFound().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[put("/sessions/{session_id}/parts/{part_number}")]
+async fn upload_session_part(
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    req: HttpRequest,
+    path: Path<(String, i32)>,
+    payload: Payload,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    let (session_id, part_number) = path.into_inner();
+
+    match upload_service::upload_session_part(
+        &repos,
+        storage.get_ref(),
+        session_id,
+        jwt_payload.user_id,
+        part_number,
+        payload,
+    )
+    .await
+    {
+        Ok(session) => {
+            let response = ApiResponse::success("Part uploaded successfully", session);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to upload part", e.to_string());
+            HttpResponse::BadRequest().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/sessions/{session_id}/complete")]
+async fn complete_session(
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    config: Data<Config>,
+    req: HttpRequest,
+    session_id: Path<String>,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    let session_id = session_id.into_inner();
+
+    match upload_service::complete_session(
+        &repos,
+        storage.get_ref(),
+        &config,
+        session_id,
+        jwt_payload.user_id,
+    )
+    .await
+    {
+        Ok(upload) => {
+            let response = ApiResponse::success("Upload completed successfully", upload);
+            HttpResponse::Created().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to complete upload", e.to_string());
+            HttpResponse::BadRequest().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[delete("/sessions/{session_id}")]
+async fn cancel_session(
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    req: HttpRequest,
+    session_id: Path<String>,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    let session_id = session_id.into_inner();
+
+    match upload_service::cancel_session(&repos, storage.get_ref(), session_id, jwt_payload.user_id)
+        .await
+    {
+        Ok(_) => {
+            let response = ApiResponse::success("Upload session cancelled successfully", ());
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to cancel upload session", e.to_string());
+            HttpResponse::NotFound().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
@@ -1,15 +1,37 @@
 use actix_web::{
-    HttpRequest, HttpResponse, Responder, delete, get, post, put,
-    web::{self, Data, Json, Path},
+    HttpRequest, HttpResponse, Responder, delete, get,
+    http::header,
+    patch, post, put,
+    web::{self, Data, Json, Path, Query},
 };
-use mongodb::Database;
+use bson::oid::ObjectId;
+use std::error::Error;
 
 use crate::{
-    models::user_model::{CreateUser, UpdateUser, UserRole},
-    services::user_service,
+    config::Config,
+    mailer::Mailer,
+    models::{
+        audit_model::AuditAction,
+        deletion_model::DeletionQueryParams,
+        export_model::ExportDownloadQueryParams,
+        user_model::{
+            ChangeEmail, ChangePassword, CreateUser, UpdateProfile, UpdateRole, UserQueryParams,
+            UserRole,
+        },
+    },
+    repositories::Repositories,
+    services::{
+        audit_service,
+        auth_service::AccountLocked,
+        deletion_service, export_service, storage_service,
+        user_service::{self, IncorrectPassword},
+    },
+    storage::StorageBackend,
     utils::{
         api_response::ApiResponse,
         jwt::{get_authenticated_user, user_has_any_of_these_roles},
+        rate_limit::{Budget, RateLimit},
+        session::actor,
     },
 };
 
@@ -19,10 +41,17 @@ pub fn user_routes(cfg: &mut web::ServiceConfig) {
     let scope = web::scope("/users")
         .service(get_users)
         .service(get_me)
+        .service(get_my_storage)
+        .service(request_export)
+        .service(get_export)
+        .service(download_export)
         .service(get_user_by_id)
         .service(create_user)
         .service(update_me)
+        .service(change_password)
+        .service(change_email)
         .service(update_user_by_id)
+        .service(update_user_role)
         .service(delete_me)
         .service(delete_user_by_id);
 
@@ -32,22 +61,26 @@ pub fn user_routes(cfg: &mut web::ServiceConfig) {
 // =============================================================================================================================
 
 #[get("")]
-async fn get_users(db: Data<Database>, req: HttpRequest) -> impl Responder {
+async fn get_users(
+    repos: Data<Repositories>,
+    query: Query<UserQueryParams>,
+    req: HttpRequest,
+) -> impl Responder {
     let required_roles = &[UserRole::Admin];
     match user_has_any_of_these_roles(&req, required_roles) {
         Ok(claims) => claims,
         Err(err_res) => return err_res,
     };
 
-    match user_service::get_users(&db).await {
-        Ok(users) => {
-            let response = ApiResponse::success("Users have been successfully recovered", users);
+    match user_service::get_users(&repos, query.into_inner()).await {
+        Ok(page) => {
+            let response = ApiResponse::success("Users have been successfully recovered", page);
             HttpResponse::Ok().json(response)
         }
         Err(e) => {
             let response =
                 ApiResponse::error("An error occured while retrieving users.", e.to_string());
-            HttpResponse::InternalServerError().json(response)
+            HttpResponse::BadRequest().json(response)
         }
     }
 }
@@ -55,14 +88,14 @@ async fn get_users(db: Data<Database>, req: HttpRequest) -> impl Responder {
 // =============================================================================================================================
 
 #[get("/me")]
-async fn get_me(db: Data<Database>, req: HttpRequest) -> impl Responder {
+async fn get_me(repos: Data<Repositories>, req: HttpRequest) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
         Ok(payload) => payload,
         Err(err_res) => return err_res,
     };
     let id = jwt_payload.user_id;
 
-    match user_service::get_user_by_id(&db, id).await {
+    match user_service::get_user_by_id(&repos, id).await {
         Ok(user) => {
             let response = ApiResponse::success("User successfully retrieved", user);
             HttpResponse::Ok().json(response)
@@ -76,15 +109,120 @@ async fn get_me(db: Data<Database>, req: HttpRequest) -> impl Responder {
 
 // =============================================================================================================================
 
+#[get("/me/storage")]
+async fn get_my_storage(
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    req: HttpRequest,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    match storage_service::get_usage(&repos, &config, jwt_payload.user_id).await {
+        Ok(usage) => {
+            let response = ApiResponse::success("Storage usage successfully retrieved", usage);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response =
+                ApiResponse::error("Failed to retrieve the storage usage", e.to_string());
+            HttpResponse::InternalServerError().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[post("/me/export")]
+async fn request_export(
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    req: HttpRequest,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    match export_service::request_export(&repos, storage.into_inner(), jwt_payload.user_id).await {
+        Ok(export) => {
+            let response = ApiResponse::success("Data export successfully requested", export);
+            HttpResponse::Accepted().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to request the data export", e.to_string());
+            HttpResponse::InternalServerError().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[get("/me/export/{export_id}")]
+async fn get_export(
+    repos: Data<Repositories>,
+    req: HttpRequest,
+    export_id: Path<String>,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    let export_id = export_id.into_inner();
+
+    match export_service::get_export(&repos, export_id, jwt_payload.user_id).await {
+        Ok(export) => {
+            let response = ApiResponse::success("Data export successfully retrieved", export);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("Failed to retrieve the data export", e.to_string());
+            HttpResponse::NotFound().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[get("/me/export/{export_id}/download")]
+async fn download_export(
+    repos: Data<Repositories>,
+    storage: Data<dyn StorageBackend>,
+    export_id: Path<String>,
+    query: Query<ExportDownloadQueryParams>,
+) -> impl Responder {
+    let export_id = export_id.into_inner();
+    let token = query.into_inner().token;
+
+    match export_service::download_export(&repos, storage.get_ref(), export_id, token).await {
+        Ok(archive_url) => HttpResponse::SeeOther()
+            .insert_header((header::LOCATION, archive_url))
+            .finish(),
+        Err(e) => {
+            let response = ApiResponse::error("Failed to download the data export", e.to_string());
+            HttpResponse::NotFound().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
 #[get("/{id}")]
-async fn get_user_by_id(db: Data<Database>, req: HttpRequest, id: Path<String>) -> impl Responder {
+async fn get_user_by_id(
+    repos: Data<Repositories>,
+    req: HttpRequest,
+    id: Path<String>,
+) -> impl Responder {
     match get_authenticated_user(&req) {
         Ok(payload) => payload,
         Err(err_res) => return err_res,
     };
     let id = id.into_inner();
 
-    match user_service::get_user_by_id(&db, id).await {
+    match user_service::get_user_by_id(&repos, id).await {
         Ok(user) => {
             let response = ApiResponse::success("User successfully retrieved", user);
             HttpResponse::Ok().json(response)
@@ -100,7 +238,7 @@ async fn get_user_by_id(db: Data<Database>, req: HttpRequest, id: Path<String>)
 
 #[post("")]
 async fn create_user(
-    db: Data<Database>,
+    repos: Data<Repositories>,
     payload: Json<CreateUser>,
     req: HttpRequest,
 ) -> impl Responder {
@@ -111,7 +249,7 @@ async fn create_user(
         Err(err_res) => return err_res,
     };
 
-    match user_service::create_user(&db, data).await {
+    match user_service::create_user(&repos, data).await {
         Ok(user) => {
             let response = ApiResponse::success("User created successfully", user);
             HttpResponse::Ok().json(response)
@@ -125,10 +263,10 @@ async fn create_user(
 
 // =============================================================================================================================
 
-#[put("/me")]
+#[patch("/me")]
 async fn update_me(
-    db: Data<Database>,
-    payload: Json<UpdateUser>,
+    repos: Data<Repositories>,
+    payload: Json<UpdateProfile>,
     req: HttpRequest,
 ) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
@@ -139,7 +277,7 @@ async fn update_me(
     let id = jwt_payload.user_id;
     let data = payload.into_inner();
 
-    match user_service::update_user_by_id(&db, id, data).await {
+    match user_service::update_profile(&repos, id, data).await {
         Ok(user) => {
             let response = ApiResponse::success("User successfully updated.", user);
             HttpResponse::Ok().json(response)
@@ -153,24 +291,91 @@ async fn update_me(
 
 // =============================================================================================================================
 
-#[put("/{id}")]
+#[put("/me/password")]
+async fn change_password(
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    payload: Json<ChangePassword>,
+    req: HttpRequest,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    let id = jwt_payload.user_id;
+    let data = payload.into_inner();
+
+    let session_id = jwt_payload.session_id;
+
+    match user_service::change_password(&repos, &config, id, session_id, data).await {
+        Ok(()) => {
+            let response = ApiResponse::success("Password successfully changed.", ());
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => reauthentication_error("Failed to change the password", e),
+    }
+}
+
+// =============================================================================================================================
+
+#[put("/me/email", wrap = "RateLimit::new(Budget::Email)")]
+async fn change_email(
+    repos: Data<Repositories>,
+    mailer: Data<dyn Mailer>,
+    config: Data<Config>,
+    payload: Json<ChangeEmail>,
+    req: HttpRequest,
+) -> impl Responder {
+    let jwt_payload = match get_authenticated_user(&req) {
+        Ok(payload) => payload,
+        Err(err_res) => return err_res,
+    };
+
+    let id = jwt_payload.user_id;
+    let data = payload.into_inner();
+
+    match user_service::change_email(&repos, mailer.get_ref(), &config, id, data).await {
+        Ok(pending) => {
+            let response =
+                ApiResponse::success("A verification link was sent to the new address.", pending);
+            HttpResponse::Accepted().json(response)
+        }
+        Err(e) => reauthentication_error("Failed to change the email address", e),
+    }
+}
+
+// =============================================================================================================================
+
+#[patch("/{id}")]
 async fn update_user_by_id(
-    db: Data<Database>,
+    repos: Data<Repositories>,
+    config: Data<Config>,
     id: Path<String>,
-    payload: Json<UpdateUser>,
+    payload: Json<UpdateProfile>,
     req: HttpRequest,
 ) -> impl Responder {
     let required_roles = &[UserRole::Admin];
-    match user_has_any_of_these_roles(&req, required_roles) {
+    let claims = match user_has_any_of_these_roles(&req, required_roles) {
         Ok(claims) => claims,
         Err(err_res) => return err_res,
     };
 
     let id = id.into_inner();
     let data = payload.into_inner();
+    let fields = data.fields().join(", ");
 
-    match user_service::update_user_by_id(&db, id, data).await {
+    match user_service::update_profile(&repos, id, data).await {
         Ok(user) => {
+            audit_service::record(
+                &repos,
+                &config,
+                &actor(&req, &claims.user_id),
+                AuditAction::UserUpdated,
+                user.id,
+                Some(fields),
+            )
+            .await;
             let response = ApiResponse::success("User successfully updated.", user);
             HttpResponse::Ok().json(response)
         }
@@ -183,16 +388,83 @@ async fn update_user_by_id(
 
 // =============================================================================================================================
 
+#[put("/{id}/role")]
+async fn update_user_role(
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    id: Path<String>,
+    payload: Json<UpdateRole>,
+    req: HttpRequest,
+) -> impl Responder {
+    let required_roles = &[UserRole::Admin];
+    let claims = match user_has_any_of_these_roles(&req, required_roles) {
+        Ok(claims) => claims,
+        Err(err_res) => return err_res,
+    };
+
+    let id = id.into_inner();
+    let data = payload.into_inner();
+
+    match user_service::update_role(&repos, id, data).await {
+        Ok(user) => {
+            audit_service::record(
+                &repos,
+                &config,
+                &actor(&req, &claims.user_id),
+                AuditAction::RoleChanged,
+                user.id,
+                Some(format!("Role set to {:?}", user.role)),
+            )
+            .await;
+            let response = ApiResponse::success("User role successfully updated.", user);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            let response = ApiResponse::error("An error occurred.", e.to_string());
+            HttpResponse::InternalServerError().json(response)
+        }
+    }
+}
+
+// =============================================================================================================================
+
 #[delete("/me")]
-async fn delete_me(db: Data<Database>, req: HttpRequest) -> impl Responder {
+async fn delete_me(
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    storage: Data<dyn StorageBackend>,
+    req: HttpRequest,
+    query: Query<DeletionQueryParams>,
+) -> impl Responder {
     let jwt_payload = match get_authenticated_user(&req) {
         Ok(payload) => payload,
         Err(e) => return e,
     };
 
-    match user_service::delete_user_by_id(&db, jwt_payload.user_id).await {
-        Ok(user) => {
-            let res = ApiResponse::success("User successfully deleted", user);
+    let dry_run = query.dry_run.unwrap_or(false);
+    let actor = actor(&req, &jwt_payload.user_id);
+
+    match deletion_service::delete_user(&repos, storage.get_ref(), jwt_payload.user_id, dry_run)
+        .await
+    {
+        Ok(report) => {
+            if !dry_run {
+                let target_id = actor.user_id;
+                audit_service::record(
+                    &repos,
+                    &config,
+                    &actor,
+                    AuditAction::UserDeleted,
+                    target_id,
+                    None,
+                )
+                .await;
+            }
+            let message = match dry_run {
+                true => "Dry run, the user was not deleted",
+                false => "User successfully deleted",
+            };
+            let res = ApiResponse::success(message, report);
             HttpResponse::Ok().json(res)
         }
         Err(e) => {
@@ -206,21 +478,41 @@ async fn delete_me(db: Data<Database>, req: HttpRequest) -> impl Responder {
 
 #[delete("/{id}")]
 async fn delete_user_by_id(
-    db: Data<Database>,
+    repos: Data<Repositories>,
+    config: Data<Config>,
+    storage: Data<dyn StorageBackend>,
     id: Path<String>,
     req: HttpRequest,
+    query: Query<DeletionQueryParams>,
 ) -> impl Responder {
     let required_roles = &[UserRole::Admin];
-    match user_has_any_of_these_roles(&req, required_roles) {
+    let claims = match user_has_any_of_these_roles(&req, required_roles) {
         Ok(claims) => claims,
         Err(err_res) => return err_res,
     };
 
     let id = id.into_inner();
-
-    match user_service::delete_user_by_id(&db, id).await {
-        Ok(user) => {
-            let response = ApiResponse::success("User was successfully deleted.", user);
+    let target_id = ObjectId::parse_str(&id).ok();
+    let dry_run = query.dry_run.unwrap_or(false);
+
+    match deletion_service::delete_user(&repos, storage.get_ref(), id, dry_run).await {
+        Ok(report) => {
+            if !dry_run {
+                audit_service::record(
+                    &repos,
+                    &config,
+                    &actor(&req, &claims.user_id),
+                    AuditAction::UserDeleted,
+                    target_id,
+                    None,
+                )
+                .await;
+            }
+            let message = match dry_run {
+                true => "Dry run, the user was not deleted",
+                false => "User was successfully deleted.",
+            };
+            let response = ApiResponse::success(message, report);
             HttpResponse::Ok().json(response)
         }
         Err(e) => {
@@ -231,3 +523,18 @@ async fn delete_user_by_id(
 }
 
 // =============================================================================================================================
+
+fn reauthentication_error(message: &str, e: Box<dyn Error>) -> HttpResponse {
+    let response = ApiResponse::error(message, e.to_string());
+    if let Some(locked) = e.downcast_ref::<AccountLocked>() {
+        HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, locked.retry_after_secs))
+            .json(response)
+    } else if e.is::<IncorrectPassword>() {
+        HttpResponse::Unauthorized().json(response)
+    } else {
+        HttpResponse::BadRequest().json(response)
+    }
+}
+
+// =============================================================================================================================
@@ -1,5 +1,28 @@
-use mongodb::{Client, Database, options::ClientOptions};
-use std::error::Error;
+use crate::config::DatabaseConfig;
+use bson::{Bson, Document, doc};
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{
+    Client, Database, IndexModel,
+    error::ErrorKind,
+    options::{ClientOptions, IndexOptions},
+};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, time::Duration};
+
+// =============================================================================================================================
+
+const MIGRATIONS_COLLECTION: &str = "schema_migrations";
+
+// Server error codes for dropping an index or a collection that does not exist.
+const NAMESPACE_NOT_FOUND: i32 = 26;
+const INDEX_NOT_FOUND: i32 = 27;
+
+// bcrypt hashes of "admin" and "test", the passwords of the accounts seeded by the former mongo-init-scripts.
+const WELL_KNOWN_PASSWORD_HASHES: [&str; 2] = [
+    "$2a$12$gF17hZWhsqAMHnBWRaK25e1F4RTVGCDNKjEMZrtfo6NsZP3TE5z6G",
+    "$2a$12$EDofS6cpfTs5.vmigyIUU.Jvzm3Q8Ww32EHyu4fIzFQZxhduHq0t6",
+];
 
 // =============================================================================================================================
 
@@ -7,17 +30,433 @@ use std::error::Error;
 pub struct Db {}
 
 impl Db {
-    pub async fn init(uri: &str) -> Result<Database, Box<dyn Error>> {
-        let client_options = ClientOptions::parse(
-            std::env::var(uri)
-                .expect("❌ No env variable called {uri} was found in the .env file."),
-        )
-        .await?;
+    pub async fn init(config: &DatabaseConfig) -> Result<Database, Box<dyn Error>> {
+        let client_options = ClientOptions::parse(&config.url).await?;
 
         let client = Client::with_options(client_options)?;
 
-        Ok(client.database("snapshoot"))
+        Ok(client.database(&config.name))
     }
+
+    /// Applies, in order, the migrations that are not yet recorded in `schema_migrations` and returns their versions.
+    /// Steps are idempotent, so a migration interrupted half-way is simply replayed on the next start.
+    pub async fn migrate(db: &Database) -> Result<Vec<i32>, Box<dyn Error>> {
+        let records = db.collection::<MigrationRecord>(MIGRATIONS_COLLECTION);
+        let applied: Vec<i32> = records
+            .find(doc! {})
+            .await?
+            .try_collect::<Vec<MigrationRecord>>()
+            .await?
+            .into_iter()
+            .map(|record| record.version)
+            .collect();
+
+        let mut newly_applied = Vec::new();
+        for migration in MIGRATIONS {
+            if applied.contains(&migration.version) {
+                continue;
+            }
+
+            for step in migration.steps {
+                step.apply(db)
+                    .await
+                    .map_err(|e| format!("Migration {} failed: {}", migration.version, e))?;
+            }
+
+            records
+                .insert_one(MigrationRecord {
+                    version: migration.version,
+                    description: migration.description.to_string(),
+                    applied_at: Utc::now(),
+                })
+                .await?;
+            newly_applied.push(migration.version);
+        }
+
+        Ok(newly_applied)
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+struct MigrationRecord {
+    #[serde(rename = "_id")]
+    version: i32,
+    description: String,
+    applied_at: DateTime<Utc>,
+}
+
+// =============================================================================================================================
+
+struct Migration {
+    version: i32,
+    description: &'static str,
+    steps: &'static [Step],
 }
 
+enum IndexKey {
+    Ascending,
+    Sphere,
+}
+
+enum Step {
+    CreateIndex {
+        collection: &'static str,
+        keys: &'static [(&'static str, IndexKey)],
+        unique: bool,
+    },
+    /// Documents are deleted by the server once the date in `field` has passed.
+    CreateTtlIndex {
+        collection: &'static str,
+        field: &'static str,
+    },
+    DropIndex {
+        collection: &'static str,
+        name: &'static str,
+    },
+    UpdateMany {
+        collection: &'static str,
+        filter: fn() -> Document,
+        update: fn() -> Document,
+    },
+    /// An update computed from the fields of each document by an aggregation pipeline.
+    UpdateManyPipeline {
+        collection: &'static str,
+        filter: fn() -> Document,
+        pipeline: fn() -> Vec<Document>,
+    },
+}
+
+impl Step {
+    async fn apply(&self, db: &Database) -> Result<(), Box<dyn Error>> {
+        match self {
+            Step::CreateIndex {
+                collection,
+                keys,
+                unique,
+            } => {
+                let mut key_document = Document::new();
+                for (field, key) in keys.iter() {
+                    let value = match key {
+                        IndexKey::Ascending => Bson::Int32(1),
+                        IndexKey::Sphere => Bson::String("2dsphere".to_string()),
+                    };
+                    key_document.insert(*field, value);
+                }
+
+                // Only unique indexes carry options, so the others match those created by mongo-init-scripts.
+                let options = unique.then(|| IndexOptions::builder().unique(true).build());
+                let index = IndexModel::builder()
+                    .keys(key_document)
+                    .options(options)
+                    .build();
+                db.collection::<Document>(collection)
+                    .create_index(index)
+                    .await?;
+            }
+            Step::CreateTtlIndex { collection, field } => {
+                let options = IndexOptions::builder().expire_after(Duration::ZERO).build();
+                let index = IndexModel::builder()
+                    .keys(doc! { *field: 1 })
+                    .options(options)
+                    .build();
+                db.collection::<Document>(collection)
+                    .create_index(index)
+                    .await?;
+            }
+            Step::DropIndex { collection, name } => {
+                if let Err(e) = db
+                    .collection::<Document>(collection)
+                    .drop_index(*name)
+                    .await
+                {
+                    match *e.kind {
+                        ErrorKind::Command(ref command)
+                            if command.code == INDEX_NOT_FOUND
+                                || command.code == NAMESPACE_NOT_FOUND => {}
+                        _ => return Err(e.into()),
+                    }
+                }
+            }
+            Step::UpdateMany {
+                collection,
+                filter,
+                update,
+            } => {
+                db.collection::<Document>(collection)
+                    .update_many(filter(), update())
+                    .await?;
+            }
+            Step::UpdateManyPipeline {
+                collection,
+                filter,
+                pipeline,
+            } => {
+                db.collection::<Document>(collection)
+                    .update_many(filter(), pipeline())
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
+
+const fn index(collection: &'static str, keys: &'static [(&'static str, IndexKey)]) -> Step {
+    Step::CreateIndex {
+        collection,
+        keys,
+        unique: false,
+    }
+}
+
+const fn unique_index(collection: &'static str, keys: &'static [(&'static str, IndexKey)]) -> Step {
+    Step::CreateIndex {
+        collection,
+        keys,
+        unique: true,
+    }
+}
+
+// Append new migrations at the end, never edit or renumber one that has shipped.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Create collection indexes",
+        steps: &[
+            unique_index("users", &[("email", IndexKey::Ascending)]),
+            unique_index("users", &[("username", IndexKey::Ascending)]),
+            index("users", &[("location", IndexKey::Sphere)]),
+            index("users", &[("role", IndexKey::Ascending)]),
+            unique_index(
+                "friends",
+                &[
+                    ("user_id", IndexKey::Ascending),
+                    ("friend_id", IndexKey::Ascending),
+                ],
+            ),
+            index("friends", &[("friend_id", IndexKey::Ascending)]),
+            index("friends", &[("status", IndexKey::Ascending)]),
+            index(
+                "messages",
+                &[
+                    ("sender_id", IndexKey::Ascending),
+                    ("recipient_id", IndexKey::Ascending),
+                    ("is_group", IndexKey::Ascending),
+                ],
+            ),
+            index(
+                "messages",
+                &[
+                    ("recipient_id", IndexKey::Ascending),
+                    ("is_group", IndexKey::Ascending),
+                ],
+            ),
+            index("groups", &[("creator_id", IndexKey::Ascending)]),
+            index("groups", &[("members", IndexKey::Ascending)]),
+            index("stories", &[("user_id", IndexKey::Ascending)]),
+            index("stories", &[("expires_at", IndexKey::Ascending)]),
+            index("stories", &[("location", IndexKey::Sphere)]),
+            index("media", &[("owner_id", IndexKey::Ascending)]),
+            index("media", &[("key", IndexKey::Ascending)]),
+            index(
+                "exports",
+                &[
+                    ("user_id", IndexKey::Ascending),
+                    ("status", IndexKey::Ascending),
+                ],
+            ),
+            index("upload_sessions", &[("owner_id", IndexKey::Ascending)]),
+            index("storage_quotas", &[("role", IndexKey::Ascending)]),
+        ],
+    },
+    Migration {
+        version: 2,
+        description: "Drop the mongo-init-scripts indexes on fields that do not exist",
+        steps: &[
+            Step::DropIndex {
+                collection: "users",
+                name: "location.coordinates_2dsphere",
+            },
+            Step::DropIndex {
+                collection: "stories",
+                name: "location.coordinates_2dsphere",
+            },
+            Step::DropIndex {
+                collection: "messages",
+                name: "media.type_1",
+            },
+            Step::DropIndex {
+                collection: "stories",
+                name: "media.type_1",
+            },
+        ],
+    },
+    Migration {
+        version: 3,
+        description: "Disable sign-in for seeded accounts that still use a well-known password",
+        steps: &[Step::UpdateMany {
+            collection: "users",
+            filter: || doc! { "password": { "$in": WELL_KNOWN_PASSWORD_HASHES.to_vec() } },
+            // Not a bcrypt hash, so every login attempt fails until the password is reset with `seed-admin`.
+            update: || doc! { "$set": { "password": "!" } },
+        }],
+    },
+    Migration {
+        version: 4,
+        description: "Mark existing accounts as verified and index auth tokens",
+        steps: &[
+            // Accounts created before email verification keep working.
+            Step::UpdateMany {
+                collection: "users",
+                filter: || doc! { "verified": { "$exists": false } },
+                update: || doc! { "$set": { "verified": true } },
+            },
+            index(
+                "auth_tokens",
+                &[
+                    ("user_id", IndexKey::Ascending),
+                    ("purpose", IndexKey::Ascending),
+                ],
+            ),
+        ],
+    },
+    Migration {
+        version: 5,
+        description: "Index sessions and expire them with their token",
+        steps: &[
+            index("sessions", &[("user_id", IndexKey::Ascending)]),
+            Step::CreateTtlIndex {
+                collection: "sessions",
+                field: "expires_at",
+            },
+        ],
+    },
+    Migration {
+        version: 6,
+        description: "Index linked identities and expire pending OIDC sign-ins",
+        steps: &[
+            unique_index(
+                "identities",
+                &[
+                    ("provider", IndexKey::Ascending),
+                    ("subject", IndexKey::Ascending),
+                ],
+            ),
+            index("identities", &[("user_id", IndexKey::Ascending)]),
+            Step::CreateTtlIndex {
+                collection: "oidc_logins",
+                field: "expires_at",
+            },
+        ],
+    },
+    Migration {
+        version: 7,
+        description: "Index the moderation queue, the moderation log and banned users",
+        steps: &[
+            index(
+                "reports",
+                &[
+                    ("status", IndexKey::Ascending),
+                    ("created_at", IndexKey::Ascending),
+                ],
+            ),
+            index(
+                "reports",
+                &[
+                    ("target_id", IndexKey::Ascending),
+                    ("status", IndexKey::Ascending),
+                ],
+            ),
+            index("reports", &[("reporter_id", IndexKey::Ascending)]),
+            index("moderation_actions", &[("target_id", IndexKey::Ascending)]),
+            index("users", &[("status", IndexKey::Ascending)]),
+        ],
+    },
+    Migration {
+        version: 8,
+        description: "Index the audit log and expire its events",
+        steps: &[
+            index("audit_events", &[("actor_id", IndexKey::Ascending)]),
+            index("audit_events", &[("target_id", IndexKey::Ascending)]),
+            index("audit_events", &[("action", IndexKey::Ascending)]),
+            Step::CreateTtlIndex {
+                collection: "audit_events",
+                field: "expires_at",
+            },
+        ],
+    },
+    Migration {
+        version: 9,
+        description: "Index the verification status of users for the admin listing",
+        steps: &[index("users", &[("verified", IndexKey::Ascending)])],
+    },
+    Migration {
+        version: 10,
+        description: "Index the expiry of export archives for the sweeper",
+        steps: &[index(
+            "exports",
+            &[
+                ("status", IndexKey::Ascending),
+                ("expires_at", IndexKey::Ascending),
+            ],
+        )],
+    },
+    Migration {
+        version: 11,
+        description: "Index the three-letter grams of usernames for the fuzzy user search",
+        steps: &[
+            // The distinct windows of three characters of each username, as written by the user repository.
+            Step::UpdateManyPipeline {
+                collection: "users",
+                filter: || doc! { "username_grams": { "$exists": false } },
+                pipeline: || {
+                    vec![doc! {
+                        "$set": {
+                            "username_grams": {
+                                "$setUnion": [{
+                                    "$map": {
+                                        "input": {
+                                            "$range": [
+                                                0,
+                                                { "$max": [0, { "$subtract": [{ "$strLenCP": "$username" }, 2] }] }
+                                            ]
+                                        },
+                                        "as": "start",
+                                        "in": { "$substrCP": ["$username", "$$start", 3] }
+                                    }
+                                }]
+                            }
+                        }
+                    }]
+                },
+            },
+            index("users", &[("username_grams", IndexKey::Ascending)]),
+        ],
+    },
+    Migration {
+        version: 12,
+        description: "Make the storage key of media unique so an upload is registered once",
+        steps: &[
+            Step::DropIndex {
+                collection: "media",
+                name: "key_1",
+            },
+            unique_index("media", &[("key", IndexKey::Ascending)]),
+        ],
+    },
+    Migration {
+        version: 13,
+        description: "Index pending uploads for their confirmation, the quota and the sweeper",
+        steps: &[
+            unique_index("pending_uploads", &[("key", IndexKey::Ascending)]),
+            index("pending_uploads", &[("owner_id", IndexKey::Ascending)]),
+            index("pending_uploads", &[("expires_at", IndexKey::Ascending)]),
+        ],
+    },
+];
+
 // =============================================================================================================================
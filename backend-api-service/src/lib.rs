@@ -0,0 +1,10 @@
+pub mod config;
+pub mod controllers;
+pub mod db;
+pub mod extractor;
+pub mod mailer;
+pub mod models;
+pub mod repositories;
+pub mod services;
+pub mod storage;
+pub mod utils;
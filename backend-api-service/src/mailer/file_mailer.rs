@@ -0,0 +1,59 @@
+use crate::{
+    config::MailConfig,
+    mailer::{Email, Mailer},
+};
+use actix_web::web;
+use async_trait::async_trait;
+use chrono::Utc;
+use std::{error::Error, fs, path::PathBuf};
+use uuid::Uuid;
+
+// =============================================================================================================================
+
+/// Writes every email to a text file instead of sending it, for local development and tests. Files are named
+/// after the time they were sent, so listing the directory gives them in order.
+pub struct FileMailer {
+    root: PathBuf,
+    from: String,
+}
+
+impl FileMailer {
+    pub fn new(root: impl Into<PathBuf>, from: &str) -> Self {
+        Self {
+            root: root.into(),
+            from: from.to_string(),
+        }
+    }
+
+    pub fn from_config(config: &MailConfig) -> Self {
+        Self::new(&config.file.path, &config.from)
+    }
+}
+
+#[async_trait(?Send)]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), Box<dyn Error>> {
+        let path = self.root.join(format!(
+            "{}-{}.eml",
+            Utc::now().format("%Y%m%dT%H%M%S%.6f"),
+            Uuid::new_v4().simple()
+        ));
+        let content = format!(
+            "From: {}\nTo: {}\nSubject: {}\n\n{}\n",
+            self.from, email.to, email.subject, email.body
+        );
+
+        let root = self.root.clone();
+        let file = path.clone();
+        web::block(move || {
+            fs::create_dir_all(root)?;
+            fs::write(file, content)
+        })
+        .await??;
+
+        println!("📧 Email to {} written to {}", email.to, path.display());
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,37 @@
+use crate::config::{MailConfig, MailerKind};
+use async_trait::async_trait;
+use file_mailer::FileMailer;
+use smtp_mailer::SmtpMailer;
+use std::{error::Error, sync::Arc};
+
+pub mod file_mailer;
+pub mod smtp_mailer;
+
+// =============================================================================================================================
+
+/// A plain text email.
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// =============================================================================================================================
+
+/// Outgoing email, injected into handlers as `web::Data<dyn Mailer>` like the storage backend.
+#[async_trait(?Send)]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+/// Builds the mailer selected by `mail.backend` (`MAILER`).
+pub fn from_config(config: &MailConfig) -> Result<Arc<dyn Mailer>, Box<dyn Error>> {
+    Ok(match config.backend {
+        MailerKind::Smtp => Arc::new(SmtpMailer::from_config(config)?),
+        MailerKind::File => Arc::new(FileMailer::from_config(config)),
+    })
+}
+
+// =============================================================================================================================
@@ -0,0 +1,63 @@
+use crate::{
+    config::{MailConfig, SmtpSecurity},
+    mailer::{Email, Mailer},
+};
+use async_trait::async_trait;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{Mailbox, header::ContentType},
+    transport::smtp::authentication::Credentials,
+};
+use std::error::Error;
+
+// =============================================================================================================================
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn from_config(config: &MailConfig) -> Result<Self, Box<dyn Error>> {
+        let smtp = &config.smtp;
+        let builder = match smtp.security {
+            SmtpSecurity::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host)?
+            }
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&smtp.host)?,
+            SmtpSecurity::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&smtp.host)
+            }
+        };
+        let builder = builder.port(smtp.port);
+        let builder = match smtp.username.is_empty() {
+            true => builder,
+            false => builder.credentials(Credentials::new(
+                smtp.username.clone(),
+                smtp.password.clone(),
+            )),
+        };
+
+        Ok(Self {
+            transport: builder.build(),
+            from: config.from.parse()?,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: Email) -> Result<(), Box<dyn Error>> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(email.to.parse()?)
+            .subject(email.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body)?;
+
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
@@ -1,15 +1,27 @@
-use actix_cors::Cors;
-use actix_web::{App, HttpServer, http, web};
-use controllers::routes;
-use db::Db;
-use extractor::deserialize_error_extractor;
-
-mod controllers;
-mod db;
-mod extractor;
-mod models;
-mod services;
-mod utils;
+use actix_web::{App, HttpServer, web};
+use backend_api_service::{
+    config::Config,
+    controllers::routes,
+    db::Db,
+    extractor::deserialize_error_extractor,
+    mailer,
+    repositories::Repositories,
+    services::{export_service, upload_service, user_service},
+    storage,
+    utils::{
+        cors::{build_cors, describe_cors},
+        jwt,
+        oidc::OidcClient,
+        rate_limit::RateLimiter,
+    },
+};
+use mongodb::Database;
+use std::{env, io, process, time::Duration};
+
+// =============================================================================================================================
+
+const EXPORT_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const UPLOAD_SWEEP_INTERVAL: Duration = Duration::from_secs(15 * 60);
 
 // =============================================================================================================================
 
@@ -17,40 +29,152 @@ mod utils;
 async fn main() -> std::io::Result<()> {
     dotenv::dotenv().ok();
 
-    let db = Db::init("DATABASE_URL")
+    // Runs before the configuration is loaded, which fails in production until a key exists.
+    if env::args().nth(1).as_deref() == Some("generate-jwt-key") {
+        return generate_jwt_key(env::args().nth(2));
+    }
+
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            process::exit(1);
+        }
+    };
+
+    let db = Db::init(&config.database)
         .await
         .expect("❌ Failed to connect to database");
 
-    let external_host_ip =
-        std::env::var("EXTERNAL_HOST_IP").expect("Missing EXTERNAL_HOST_IP env.");
+    let applied = Db::migrate(&db)
+        .await
+        .expect("❌ Failed to run database migrations");
+    if !applied.is_empty() {
+        println!("✅ Applied database migrations {:?}", applied);
+    }
+
+    match env::args().nth(1).as_deref() {
+        None | Some("serve") => {}
+        Some("migrate") => return Ok(()),
+        Some("seed-admin") => return seed_admin(&db).await,
+        Some(command) => {
+            eprintln!(
+                "❌ Unknown command {:?}, expected serve, migrate, seed-admin or generate-jwt-key",
+                command
+            );
+            process::exit(2);
+        }
+    }
 
-    HttpServer::new(move || {
-        let cors = Cors::default()
-            .allowed_origin("http://localhost:8100")
-            .allowed_origin(&format!("http://{}:8100", external_host_ip.clone()))
-            .allowed_origin(&format!("http://{}", external_host_ip.clone()))
-            .allowed_origin(&external_host_ip)
-            .allowed_origin("capacitor://localhost")
-            .allowed_origin("http://localhost")
-            .allowed_origin_fn(|origin, _req_head| origin.as_bytes().starts_with(b"http://"))
-            .allowed_methods(vec!["GET", "POST", "PUT", "DELETE", "PATCH"])
-            .allowed_headers(vec![
-                http::header::AUTHORIZATION,
-                http::header::CONTENT_TYPE,
-            ])
-            .allowed_header(http::header::CONTENT_TYPE)
-            .supports_credentials()
-            .max_age(3600);
+    let repositories = web::Data::new(Repositories::mongo(&db));
+    let storage = web::Data::from(storage::from_config(&config));
+    let rate_limiter = web::Data::new(RateLimiter::default());
+    let oidc = web::Data::new(OidcClient::default());
+    let mailer =
+        web::Data::from(mailer::from_config(&config.mail).expect("❌ Failed to set up the mailer"));
+    let bind_address = config.server.bind_address.clone();
+    println!("🔒 CORS: {}", describe_cors(&config));
+    match config.auth.keys_dir.is_empty() {
+        true => println!("⚠️ JWT_KEYS_DIR is not set, tokens are signed with a throwaway key"),
+        false => println!(
+            "🔑 Signing tokens with key {}",
+            config.auth.keys.active_kid().unwrap_or_default()
+        ),
+    }
+    if !config.rate_limits.enabled {
+        println!("⚠️ Rate limiting is disabled");
+    }
+    let config = web::Data::new(config);
 
+    actix_web::rt::spawn(sweep_expired_exports(repositories.clone(), storage.clone()));
+    actix_web::rt::spawn(sweep_expired_uploads(repositories.clone(), storage.clone()));
+
+    HttpServer::new(move || {
         App::new()
-            .wrap(cors)
-            .app_data(web::Data::new(db.clone()))
+            .wrap(build_cors(&config))
+            .app_data(config.clone())
+            .app_data(repositories.clone())
+            .app_data(storage.clone())
+            .app_data(rate_limiter.clone())
+            .app_data(oidc.clone())
+            .app_data(mailer.clone())
             .configure(routes)
             .app_data(deserialize_error_extractor())
     })
-    .bind(("0.0.0.0", 8080))?
+    .bind(bind_address)?
     .run()
     .await
 }
 
 // =============================================================================================================================
+
+/// Removes expired export archives, including those whose link was never opened, until the server stops.
+async fn sweep_expired_exports(
+    repositories: web::Data<Repositories>,
+    storage: web::Data<dyn storage::StorageBackend>,
+) {
+    let mut interval = actix_web::rt::time::interval(EXPORT_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = export_service::sweep_expired(&repositories, storage.get_ref()).await {
+            eprintln!("❌ Failed to sweep expired exports: {}", e);
+        }
+    }
+}
+
+// =============================================================================================================================
+
+/// Removes the presigned uploads that were never confirmed, until the server stops.
+async fn sweep_expired_uploads(
+    repositories: web::Data<Repositories>,
+    storage: web::Data<dyn storage::StorageBackend>,
+) {
+    let mut interval = actix_web::rt::time::interval(UPLOAD_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = upload_service::sweep_expired(&repositories, storage.get_ref()).await {
+            eprintln!("❌ Failed to sweep unconfirmed uploads: {}", e);
+        }
+    }
+}
+
+// =============================================================================================================================
+
+/// Creates the admin account described by `ADMIN_USERNAME`, `ADMIN_EMAIL` and `ADMIN_PASSWORD`, or promotes the
+/// account with that email and resets its password.
+async fn seed_admin(db: &Database) -> io::Result<()> {
+    let var =
+        |name: &str| env::var(name).map_err(|_| io::Error::other(format!("Missing {} env.", name)));
+    let (username, email, password) = (
+        var("ADMIN_USERNAME")?,
+        var("ADMIN_EMAIL")?,
+        var("ADMIN_PASSWORD")?,
+    );
+
+    let admin = user_service::seed_admin(&Repositories::mongo(db), username, email, password)
+        .await
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    println!(
+        "✅ Admin account ready: {} <{}>",
+        admin.username, admin.email
+    );
+
+    Ok(())
+}
+
+// =============================================================================================================================
+
+/// Writes a new signing key to the given directory, `JWT_KEYS_DIR` or `./keys`, and prints its key id.
+fn generate_jwt_key(dir: Option<String>) -> io::Result<()> {
+    let dir = dir
+        .or_else(|| env::var("JWT_KEYS_DIR").ok())
+        .filter(|dir| !dir.trim().is_empty())
+        .unwrap_or_else(|| "./keys".to_string());
+
+    let kid = jwt::write_new_key(&dir).map_err(io::Error::other)?;
+    println!("✅ Key {} written to {}", kid, dir);
+
+    Ok(())
+}
+
+// =============================================================================================================================
@@ -0,0 +1,80 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::{oid::ObjectId, serde_helpers::chrono_datetime_as_bson_datetime_optional};
+use serde::{Deserialize, Serialize};
+
+// =============================================================================================================================
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum AuditAction {
+    Login,
+    LoginFailed,
+    RoleChanged,
+    UserUpdated,
+    UserDeleted,
+    GroupDeleted,
+    MessageDeleted,
+    ContentRemoved,
+    UserSuspended,
+    UserBanned,
+    UserReinstated,
+}
+
+// =============================================================================================================================
+
+/// One security-sensitive action. Never updated: events only go away once `expires_at` has passed.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AuditEvent {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub action: AuditAction,
+    /// Who did it, none for a failed login with an unknown account.
+    pub actor_id: Option<ObjectId>,
+    /// The user, group, message or story acted upon.
+    pub target_id: Option<ObjectId>,
+    pub ip: Option<String>,
+    pub details: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Stored as a BSON date so a TTL index removes the event then. None when the retention is unlimited.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// =============================================================================================================================
+
+/// The user behind a request and where it came from.
+#[derive(Clone, Default)]
+pub struct Actor {
+    pub user_id: Option<ObjectId>,
+    pub ip: Option<String>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct AuditQueryParams {
+    pub action: Option<AuditAction>,
+    pub actor_id: Option<String>,
+    pub target_id: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// Id of the last event of the previous page.
+    pub before: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Criteria of an audit query, all optional.
+#[derive(Default)]
+pub struct AuditFilter {
+    pub action: Option<AuditAction>,
+    pub actor_id: Option<ObjectId>,
+    pub target_id: Option<ObjectId>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub before: Option<ObjectId>,
+}
+
+// =============================================================================================================================
@@ -1,8 +1,10 @@
 use crate::utils::utils_fn::{LETTERS_REGEX, trim, trim_lowercase};
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
-use super::user_model::Location;
+use super::{session_model::DeviceInfo, user_model::Location};
 
 // =============================================================================================================================
 
@@ -10,6 +12,8 @@ use super::user_model::Location;
 pub struct AuthLogin {
     pub credential: String,
     pub password: String,
+    #[serde(flatten)]
+    pub device: DeviceInfo,
 }
 
 // =============================================================================================================================
@@ -61,3 +65,75 @@ pub struct AuthRegister {
 }
 
 // =============================================================================================================================
+
+/// Consecutive failed logins of an account, keyed by user id. Cleared by a successful login.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LoginAttempts {
+    #[serde(rename = "_id")]
+    pub user_id: ObjectId,
+    pub failures: u32,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+// =============================================================================================================================
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TokenPurpose {
+    EmailVerification,
+    PasswordReset,
+}
+
+/// Single-use token sent by email. Only its SHA-256 is stored, the token itself exists in the email alone.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AuthToken {
+    #[serde(rename = "_id")]
+    pub token_hash: String,
+    pub user_id: ObjectId,
+    pub purpose: TokenPurpose,
+    /// The address the token was sent to, which is the one verified.
+    pub email: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct PendingVerification {
+    pub email: String,
+    pub verified: bool,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct VerifyEmail {
+    pub token: String,
+    #[serde(flatten)]
+    pub device: DeviceInfo,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct EmailRequest {
+    #[serde(deserialize_with = "trim_lowercase")]
+    #[validate(email(message = "Email must be valid"))]
+    pub email: String,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct ResetPassword {
+    pub token: String,
+
+    #[serde(deserialize_with = "trim")]
+    #[validate(length(
+        min = 12,
+        max = 32,
+        message = "password must be between 12 and 32 characters"
+    ))]
+    pub password: String,
+}
+
+// =============================================================================================================================
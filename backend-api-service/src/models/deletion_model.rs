@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct DeletionReport {
+    pub dry_run: bool,
+    pub transactional: bool,
+    pub users: u64,
+    pub friendships: u64,
+    pub groups: u64,
+    pub group_memberships: u64,
+    pub messages: u64,
+    pub stories: u64,
+    pub sessions: u64,
+    pub identities: u64,
+    pub two_factor: u64,
+    pub auth_tokens: u64,
+    pub login_attempts: u64,
+    pub upload_sessions: u64,
+    pub pending_uploads: u64,
+    pub exports: u64,
+    pub reports: u64,
+    pub media: Vec<String>,
+    pub media_failures: Vec<String>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct DeletionQueryParams {
+    pub dry_run: Option<bool>,
+}
+
+// =============================================================================================================================
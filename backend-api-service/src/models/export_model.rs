@@ -0,0 +1,91 @@
+use crate::{
+    models::user_model::{Location, UserRole},
+    utils::utils_fn::serialize_option_object_id_as_hex_string,
+};
+use chrono::{DateTime, Utc};
+use mongodb::bson::{oid::ObjectId, serde_helpers::chrono_datetime_as_bson_datetime_optional};
+use serde::{Deserialize, Serialize};
+
+// =============================================================================================================================
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ExportStatus {
+    Pending,
+    Ready,
+    Failed,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DataExport {
+    #[serde(
+        rename = "_id",
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_option_object_id_as_hex_string"
+    )]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub status: ExportStatus,
+    pub archive_url: Option<String>,
+    pub download_token: Option<String>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// When the download link stops working. Stored as a BSON date so expired archives can be swept.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportResponse {
+    pub id: String,
+    pub status: ExportStatus,
+    pub download_url: Option<String>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportDownloadQueryParams {
+    pub token: String,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportedProfile {
+    pub id: String,
+    pub username: String,
+    pub email: String,
+    pub role: UserRole,
+    pub bio: String,
+    pub avatar: Option<String>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportedLocation {
+    pub location: Location,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportedGroup {
+    pub id: String,
+    pub name: String,
+    pub is_creator: bool,
+    pub member_count: usize,
+}
+
+// =============================================================================================================================
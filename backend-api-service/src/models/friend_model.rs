@@ -1,11 +1,13 @@
-use crate::utils::utils_fn::serialize_option_object_id_as_hex_string;
+use crate::{
+    models::user_model::PublicProfile, utils::utils_fn::serialize_option_object_id_as_hex_string,
+};
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
 // =============================================================================================================================
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum FriendStatus {
     Pending,
     Accepted,
@@ -13,7 +15,7 @@ pub enum FriendStatus {
 
 // =============================================================================================================================
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Friend {
     #[serde(
         rename = "_id",
@@ -36,6 +38,34 @@ pub struct FindFriend {
 
 // =============================================================================================================================
 
+#[derive(Serialize, Deserialize)]
+pub struct UserSearchParams {
+    /// Start of a username, or a username with a typo.
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct SuggestionQueryParams {
+    pub limit: Option<i64>,
+}
+
+/// Someone the user may know, with what the ranking is based on.
+#[derive(Serialize, Deserialize)]
+pub struct FriendSuggestion {
+    pub user: PublicProfile,
+    pub mutual_friends: u64,
+    pub shared_groups: u64,
+    /// Rounded up to the kilometre so the exact location of the user cannot be worked out. None beyond the radius
+    /// of the suggestions.
+    pub distance_km: Option<u32>,
+    pub score: f64,
+}
+
+// =============================================================================================================================
+
 #[derive(Serialize, Deserialize)]
 pub struct FriendRequest {
     pub user_id: String,
@@ -5,7 +5,7 @@ use validator::Validate;
 
 // =============================================================================================================================
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Group {
     #[serde(
         rename = "_id",
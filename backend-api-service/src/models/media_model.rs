@@ -0,0 +1,207 @@
+use crate::{
+    models::message_model::MediaType, utils::utils_fn::serialize_option_object_id_as_hex_string,
+};
+use chrono::{DateTime, Utc};
+use mongodb::bson::{
+    oid::ObjectId,
+    serde_helpers::{chrono_datetime_as_bson_datetime, chrono_datetime_as_bson_datetime_optional},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use validator::Validate;
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MediaRecord {
+    #[serde(
+        rename = "_id",
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_option_object_id_as_hex_string"
+    )]
+    pub id: Option<ObjectId>,
+    pub owner_id: ObjectId,
+    pub key: String,
+    pub url: String,
+    pub feed_url: Option<String>,
+    pub thumbnail_url: Option<String>,
+    pub content_type: String,
+    pub media_type: MediaType,
+    /// Bytes counted against the quota of the owner, the feed and thumbnail renditions of an image included.
+    pub size: i64,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub duration: Option<f64>,
+    pub orientation: Option<i32>,
+    pub ref_count: i32,
+    pub in_library: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+// =============================================================================================================================
+
+pub struct MediaInfo {
+    pub content_type: String,
+    pub media_type: MediaType,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub duration: Option<f64>,
+    pub orientation: Option<i32>,
+}
+
+// =============================================================================================================================
+
+pub struct StoredImage {
+    pub url: String,
+    pub feed_url: String,
+    pub thumbnail_url: String,
+    pub content_type: String,
+    /// The original and its renditions together.
+    pub size: usize,
+    pub width: i32,
+    pub height: i32,
+}
+
+// =============================================================================================================================
+
+pub struct ObjectMetadata {
+    pub size: u64,
+    pub content_type: String,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct PresignedFileQueryParams {
+    pub expires: i64,
+    pub signature: String,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct PresignUpload {
+    #[validate(length(min = 1, message = "content_type is required"))]
+    pub content_type: String,
+    #[validate(range(min = 1, message = "size must be greater than 0"))]
+    pub size: usize,
+}
+
+// =============================================================================================================================
+
+/// The upload must be sent with exactly `content_type` as its `Content-Type` and `size` as its `Content-Length`.
+#[derive(Serialize, Deserialize)]
+pub struct PresignedUpload {
+    pub key: String,
+    pub upload_url: String,
+    pub method: String,
+    pub content_type: String,
+    pub size: usize,
+    pub expires_in: u64,
+}
+
+// =============================================================================================================================
+
+/// An object presigned for upload and not confirmed yet. Its size counts toward the quota of the owner until it is
+/// confirmed, and the object is deleted once it expires unconfirmed.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PendingUpload {
+    #[serde(
+        rename = "_id",
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_option_object_id_as_hex_string"
+    )]
+    pub id: Option<ObjectId>,
+    pub owner_id: ObjectId,
+    pub key: String,
+    pub size: i64,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+    /// Set while a confirmation processes the upload, so that no other confirmation or sweep touches it meanwhile.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub confirming_until: Option<DateTime<Utc>>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct ConfirmUpload {
+    #[validate(length(min = 1, message = "key is required"))]
+    pub key: String,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct ConfirmedUpload {
+    pub media: MediaRecord,
+    pub download_url: String,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UploadSession {
+    #[serde(
+        rename = "_id",
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_option_object_id_as_hex_string"
+    )]
+    pub id: Option<ObjectId>,
+    pub owner_id: ObjectId,
+    pub key: String,
+    pub upload_id: String,
+    pub content_type: String,
+    pub size: i64,
+    pub part_size: i64,
+    pub parts: Vec<UploadedPart>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UploadedPart {
+    pub part_number: i32,
+    pub etag: String,
+    pub size: i64,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct CreateUploadSession {
+    #[validate(length(min = 1, message = "content_type is required"))]
+    pub content_type: String,
+    #[validate(range(min = 1, message = "size must be greater than 0"))]
+    pub size: usize,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct UploadSessionResponse {
+    pub id: String,
+    pub key: String,
+    pub content_type: String,
+    pub size: i64,
+    pub part_size: i64,
+    pub total_parts: i32,
+    pub uploaded_parts: Vec<i32>,
+    pub received: i64,
+    pub expires_at: DateTime<Utc>,
+}
+
+// =============================================================================================================================
+
+pub struct MediaForm {
+    pub media: MediaRecord,
+    pub fields: HashMap<String, String>,
+}
+
+// =============================================================================================================================
@@ -5,7 +5,7 @@ use validator::Validate;
 
 // =============================================================================================================================
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MediaType {
     Image,
     Video,
@@ -13,17 +13,23 @@ pub enum MediaType {
 
 // =============================================================================================================================
 
-#[derive(Serialize, Deserialize, Validate)]
+#[derive(Serialize, Deserialize, Validate, Clone)]
 pub struct Media {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
     pub media_type: MediaType,
     #[validate(url)]
     pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feed_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
     pub duration: Option<f64>,
 }
 
 // =============================================================================================================================
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Message {
     #[serde(
         rename = "_id",
@@ -48,7 +54,7 @@ pub struct CreateMessage {
         message = "Message content must be less than 1000 characters"
     ))]
     pub content: String,
-    pub media: Option<Media>,
+    pub media_id: Option<String>,
 }
 
 // =============================================================================================================================
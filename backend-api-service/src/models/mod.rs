@@ -1,7 +1,16 @@
+pub mod audit_model;
 pub mod auth_model;
+pub mod deletion_model;
+pub mod export_model;
 pub mod friend_model;
 pub mod group_model;
 pub mod location_model;
+pub mod media_model;
 pub mod message_model;
+pub mod moderation_model;
+pub mod oidc_model;
+pub mod session_model;
+pub mod storage_model;
 pub mod story_model;
+pub mod two_factor_model;
 pub mod user_model;
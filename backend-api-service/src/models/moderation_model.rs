@@ -0,0 +1,138 @@
+use crate::utils::utils_fn::{trim, trim_option};
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+// =============================================================================================================================
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ReportTarget {
+    User,
+    Message,
+    Story,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ReportStatus {
+    Open,
+    Resolved,
+    Dismissed,
+}
+
+// =============================================================================================================================
+
+/// A user, message or story reported by a user, waiting in the review queue while `Open`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Report {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub reporter_id: ObjectId,
+    pub target: ReportTarget,
+    pub target_id: ObjectId,
+    /// The user who posted the reported content, or the reported user.
+    pub target_user_id: ObjectId,
+    pub reason: String,
+    pub status: ReportStatus,
+    pub created_at: DateTime<Utc>,
+    pub reviewed_by: Option<ObjectId>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub note: Option<String>,
+}
+
+// =============================================================================================================================
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ModerationActionKind {
+    RemoveContent,
+    ResolveReport,
+    DismissReport,
+    SuspendUser,
+    BanUser,
+    ReinstateUser,
+}
+
+/// One entry of the moderation log, written for every decision an admin takes. Never updated nor deleted.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ModerationAction {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub moderator_id: ObjectId,
+    pub action: ModerationActionKind,
+    pub target: ReportTarget,
+    pub target_id: ObjectId,
+    pub report_id: Option<ObjectId>,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct CreateReport {
+    pub target: ReportTarget,
+    pub target_id: String,
+
+    #[serde(deserialize_with = "trim")]
+    #[validate(length(
+        min = 3,
+        max = 500,
+        message = "reason must be between 3 and 500 characters"
+    ))]
+    pub reason: String,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Validate, Default)]
+pub struct ResolveReport {
+    /// Deletes the reported message or story and its media. Reports of users are resolved without removal.
+    #[serde(default)]
+    pub remove_content: bool,
+
+    #[serde(default, deserialize_with = "trim_option")]
+    #[validate(length(max = 500, message = "note must be less than 500 characters"))]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Validate, Default)]
+pub struct DismissReport {
+    #[serde(default, deserialize_with = "trim_option")]
+    #[validate(length(max = 500, message = "note must be less than 500 characters"))]
+    pub note: Option<String>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct Sanction {
+    /// End of a suspension, none to suspend until reinstated. Ignored for bans and reinstatements.
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+
+    #[serde(deserialize_with = "trim")]
+    #[validate(length(
+        min = 3,
+        max = 500,
+        message = "reason must be between 3 and 500 characters"
+    ))]
+    pub reason: String,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct ReportQueryParams {
+    /// Defaults to the open reports.
+    pub status: Option<ReportStatus>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct ModerationActionQueryParams {
+    pub target_id: Option<String>,
+    pub limit: Option<i64>,
+}
+
+// =============================================================================================================================
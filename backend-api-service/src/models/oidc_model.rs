@@ -0,0 +1,64 @@
+use super::session_model::DeviceInfo;
+use chrono::{DateTime, Utc};
+use mongodb::bson::{oid::ObjectId, serde_helpers::chrono_datetime_as_bson_datetime};
+use serde::{Deserialize, Serialize};
+
+// =============================================================================================================================
+
+/// An account at an OpenID Connect provider, linked to a user. The provider's `sub` never changes, unlike the email.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Identity {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub provider: String,
+    pub subject: String,
+    pub user_id: ObjectId,
+    /// The email the provider reported when the identity was linked.
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// =============================================================================================================================
+
+/// A sign-in started at a provider, keyed by the SHA-256 of its `state`. Consumed by the callback.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OidcLogin {
+    #[serde(rename = "_id")]
+    pub state_hash: String,
+    pub provider: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct OidcAuthorization {
+    /// Opened in a browser. The provider then redirects to the configured redirect URI with `code` and `state`.
+    pub authorization_url: String,
+    pub state: String,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct OidcCallback {
+    pub code: String,
+    pub state: String,
+    #[serde(flatten)]
+    pub device: DeviceInfo,
+}
+
+// =============================================================================================================================
+
+/// The claims of a verified ID token that sign-in relies on.
+pub struct OidcClaims {
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub name: Option<String>,
+}
+
+// =============================================================================================================================
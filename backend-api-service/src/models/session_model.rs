@@ -0,0 +1,77 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::{oid::ObjectId, serde_helpers::chrono_datetime_as_bson_datetime};
+use serde::{Deserialize, Serialize};
+
+// =============================================================================================================================
+
+/// A signed-in device. Every access token names its session, and a token whose session is gone is rejected.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Session {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub user_id: ObjectId,
+    pub device_name: String,
+    pub platform: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used: DateTime<Utc>,
+    /// When the token of the session expires. Stored as a BSON date so a TTL index removes the session then.
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+}
+
+// =============================================================================================================================
+
+/// Optional description of the device signing in, sent with the credentials. The user agent stands in for a missing
+/// device name.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct DeviceInfo {
+    pub device_name: Option<String>,
+    pub platform: Option<String>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub id: String,
+    pub device_name: String,
+    pub platform: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used: DateTime<Utc>,
+    /// Whether this is the session of the token that made the request.
+    pub current: bool,
+}
+
+impl SessionInfo {
+    pub fn new(session: Session, current_session_id: &str) -> Self {
+        Self {
+            current: session.id.to_hex() == current_session_id,
+            id: session.id.to_hex(),
+            device_name: session.device_name,
+            platform: session.platform,
+            ip: session.ip,
+            created_at: session.created_at,
+            last_used: session.last_used,
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct RevokedSessions {
+    pub revoked: u64,
+}
+
+// =============================================================================================================================
+
+/// Where a sign-in comes from, as recorded on its session.
+pub struct ClientInfo {
+    pub device_name: String,
+    pub platform: Option<String>,
+    pub ip: Option<String>,
+}
+
+// =============================================================================================================================
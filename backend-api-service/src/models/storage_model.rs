@@ -0,0 +1,53 @@
+use crate::models::user_model::UserRole;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StorageQuota {
+    pub role: UserRole,
+    pub quota_bytes: i64,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct UpdateStorageQuota {
+    #[validate(range(min = 0, message = "quota_bytes must be a positive number of bytes"))]
+    pub quota_bytes: i64,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct StorageCategory {
+    pub count: u64,
+    pub bytes: i64,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct StorageBreakdown {
+    pub stories: StorageCategory,
+    pub direct_messages: StorageCategory,
+    pub groups: StorageCategory,
+    pub library: StorageCategory,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct StorageUsage {
+    pub role: UserRole,
+    pub quota_bytes: i64,
+    pub used_bytes: i64,
+    pub reserved_bytes: i64,
+    pub remaining_bytes: i64,
+    pub breakdown: StorageBreakdown,
+}
+
+// =============================================================================================================================
@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 
 // =============================================================================================================================
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Location {
     #[serde(rename = "type")]
     pub location_type: String,
@@ -16,7 +16,7 @@ pub struct Location {
 
 // =============================================================================================================================
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Story {
     #[serde(
         rename = "_id",
@@ -34,12 +34,33 @@ pub struct Story {
 
 #[derive(Serialize, Deserialize)]
 pub struct CreateStory {
-    pub media: Media,
+    pub media_id: String,
     pub location: Location,
 }
 
 // =============================================================================================================================
 
+/// The location of a story posted with its upload, as `?type=Point&coordinates=[longitude,latitude]`.
+#[derive(Serialize, Deserialize)]
+pub struct StoryLocationQueryParams {
+    #[serde(rename = "type")]
+    pub location_type: String,
+    pub coordinates: String,
+}
+
+impl StoryLocationQueryParams {
+    pub fn into_location(self) -> Result<Location, String> {
+        let coordinates = serde_json::from_str(&self.coordinates)
+            .map_err(|_| "coordinates must be [longitude, latitude]".to_string())?;
+        Ok(Location {
+            location_type: self.location_type,
+            coordinates,
+        })
+    }
+}
+
+// =============================================================================================================================
+
 #[derive(Serialize, Deserialize)]
 pub struct NearbyQueryParams {
     pub latitude: f64,
@@ -0,0 +1,71 @@
+use super::session_model::DeviceInfo;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+// =============================================================================================================================
+
+/// TOTP settings of an account, keyed by user id. The secret is pending until the first code confirms it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TwoFactor {
+    #[serde(rename = "_id")]
+    pub user_id: ObjectId,
+    pub secret: String,
+    pub enabled: bool,
+    /// SHA-256 of the recovery codes that are still unused.
+    pub recovery_codes: Vec<String>,
+    /// The latest time step accepted, so a code cannot be replayed.
+    pub last_used_step: Option<i64>,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub recovery_codes_left: usize,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct TwoFactorEnrollment {
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
+// =============================================================================================================================
+
+/// A TOTP code, or a recovery code where the endpoint accepts one.
+#[derive(Serialize, Deserialize)]
+pub struct TwoFactorCode {
+    pub code: String,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+// =============================================================================================================================
+
+/// Returned by login instead of the access token when the account has two-factor authentication enabled.
+#[derive(Serialize, Deserialize)]
+pub struct TwoFactorChallenge {
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+    pub expires_in: i64,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct TwoFactorVerify {
+    pub challenge_token: String,
+    pub code: String,
+    #[serde(flatten)]
+    pub device: DeviceInfo,
+}
+
+// =============================================================================================================================
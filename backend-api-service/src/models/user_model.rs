@@ -1,13 +1,15 @@
 use crate::utils::utils_fn::{
     LETTERS_REGEX, serialize_option_object_id_as_hex_string, trim, trim_lowercase,
+    trim_lowercase_option, trim_option,
 };
-use mongodb::bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use mongodb::bson::{oid::ObjectId, serde_helpers::chrono_datetime_as_bson_datetime_optional};
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
 // =============================================================================================================================
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub enum UserRole {
     User,
     Admin,
@@ -15,7 +17,18 @@ pub enum UserRole {
 
 // =============================================================================================================================
 
-#[derive(Serialize, Deserialize)]
+/// Set by moderators. Suspended and banned accounts cannot sign in, banned accounts also disappear from public feeds.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum AccountStatus {
+    #[default]
+    Active,
+    Suspended,
+    Banned,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Location {
     #[serde(rename = "type")]
     pub location_type: String,
@@ -24,7 +37,7 @@ pub struct Location {
 
 // =============================================================================================================================
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct User {
     #[serde(
         rename = "_id",
@@ -39,6 +52,52 @@ pub struct User {
     pub bio: String,
     pub avatar: Option<String>,
     pub location: Location,
+    /// Whether `email` was confirmed through the link sent at registration. Unverified accounts cannot log in.
+    #[serde(default)]
+    pub verified: bool,
+    #[serde(default)]
+    pub status: AccountStatus,
+    /// End of a suspension, none for a suspension until further notice.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub suspended_until: Option<DateTime<Utc>>,
+}
+
+impl User {
+    /// Whether a moderator keeps the account from signing in at `now`. Suspensions lift by themselves once over.
+    pub fn is_restricted(&self, now: DateTime<Utc>) -> bool {
+        match self.status {
+            AccountStatus::Active => false,
+            AccountStatus::Suspended => self.suspended_until.is_none_or(|until| until > now),
+            AccountStatus::Banned => true,
+        }
+    }
+}
+
+// =============================================================================================================================
+
+/// What anyone may see of an account: no email, password hash, location or moderation status.
+#[derive(Serialize, Deserialize)]
+pub struct PublicProfile {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub username: String,
+    pub bio: String,
+    pub avatar: Option<String>,
+}
+
+impl From<User> for PublicProfile {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id.map(|id| id.to_hex()).unwrap_or_default(),
+            username: user.username,
+            bio: user.bio,
+            avatar: user.avatar,
+        }
+    }
 }
 
 // =============================================================================================================================
@@ -85,9 +144,10 @@ pub struct CreateUser {
 
 // =============================================================================================================================
 
-#[derive(Serialize, Deserialize, Validate)]
-pub struct UpdateUser {
-    #[serde(deserialize_with = "trim_lowercase")]
+/// Partial profile update: fields left out are kept.
+#[derive(Serialize, Deserialize, Validate, Default)]
+pub struct UpdateProfile {
+    #[serde(default, deserialize_with = "trim_lowercase_option")]
     #[validate(length(
         min = 2,
         max = 25,
@@ -97,20 +157,39 @@ pub struct UpdateUser {
         path = "*LETTERS_REGEX",
         message = "username contains invalid characters"
     ))]
-    pub username: String,
+    pub username: Option<String>,
 
-    #[serde(deserialize_with = "trim_lowercase")]
-    #[validate(email(message = "Email must be valid"))]
-    pub email: String,
-
-    #[serde(deserialize_with = "trim")]
+    #[serde(default, deserialize_with = "trim_option")]
     #[validate(length(
         min = 12,
         max = 32,
-        message = "password must be between 12 and 32 characters"
+        message = "bio must be between 12 and 32 characters"
     ))]
-    pub password: String,
-    pub role: UserRole,
+    pub bio: Option<String>,
+
+    #[validate(url)]
+    pub avatar: Option<String>,
+}
+
+impl UpdateProfile {
+    /// Names of the fields the update sets.
+    pub fn fields(&self) -> Vec<&'static str> {
+        [
+            ("username", self.username.is_some()),
+            ("bio", self.bio.is_some()),
+            ("avatar", self.avatar.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(field, set)| set.then_some(field))
+        .collect()
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct ChangePassword {
+    pub current_password: String,
 
     #[serde(deserialize_with = "trim")]
     #[validate(length(
@@ -118,11 +197,105 @@ pub struct UpdateUser {
         max = 32,
         message = "password must be between 12 and 32 characters"
     ))]
-    pub bio: String,
+    pub new_password: String,
+}
 
-    #[validate(url)]
-    pub avatar: Option<String>,
-    pub location: Location,
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct ChangeEmail {
+    pub current_password: String,
+
+    #[serde(deserialize_with = "trim_lowercase")]
+    #[validate(email(message = "Email must be valid"))]
+    pub email: String,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct UpdateRole {
+    pub role: UserRole,
+}
+
+// =============================================================================================================================
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum UserSort {
+    /// Order of creation, the order of the ids.
+    #[default]
+    Created,
+    Username,
+    Email,
+}
+
+impl UserSort {
+    /// The value of the sort field a cursor records. Empty when sorting by creation, the id already tells.
+    pub fn key(self, user: &User) -> &str {
+        match self {
+            UserSort::Created => "",
+            UserSort::Username => &user.username,
+            UserSort::Email => &user.email,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UserQueryParams {
+    pub role: Option<UserRole>,
+    pub verified: Option<bool>,
+    pub status: Option<AccountStatus>,
+    /// Start of a username or email, whatever the case.
+    pub search: Option<String>,
+    pub sort: Option<UserSort>,
+    pub order: Option<SortOrder>,
+    /// `next_cursor` of the previous page, with the same sort.
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Criteria of a user listing, all optional. `search` is a lowercase prefix of the username or email.
+#[derive(Default, Clone)]
+pub struct UserFilter {
+    pub role: Option<UserRole>,
+    pub verified: Option<bool>,
+    pub status: Option<AccountStatus>,
+    pub search: Option<String>,
+}
+
+/// Where a page of users starts: after the user with this id and this value of the sort field.
+pub struct UserCursor {
+    pub id: ObjectId,
+    pub key: String,
+}
+
+// =============================================================================================================================
+
+#[derive(Serialize, Deserialize)]
+pub struct UserPage {
+    pub users: Vec<User>,
+    /// Users matching the filters, across all pages.
+    pub total: u64,
+    /// None on the last page.
+    pub next_cursor: Option<String>,
+    pub counts: UserCounts,
+}
+
+/// Totals over every user, whatever the filters.
+#[derive(Serialize, Deserialize)]
+pub struct UserCounts {
+    pub users: u64,
+    pub admins: u64,
+    pub unverified: u64,
+    pub suspended: u64,
+    pub banned: u64,
 }
 
 // =============================================================================================================================
@@ -0,0 +1,136 @@
+use crate::models::audit_model::{AuditEvent, AuditFilter};
+use async_trait::async_trait;
+use bson::{Document, oid::ObjectId, to_bson};
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{Collection, Database, bson::doc};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "audit_events";
+
+// =============================================================================================================================
+
+/// The audit log. Append only: events are never changed, a TTL index removes them once their retention is over.
+#[async_trait(?Send)]
+pub trait AuditEventRepo: Send + Sync {
+    async fn insert(&self, event: AuditEvent) -> Result<(), Box<dyn Error>>;
+
+    /// The events matching the filter, newest first.
+    async fn find(
+        &self,
+        filter: &AuditFilter,
+        limit: i64,
+    ) -> Result<Vec<AuditEvent>, Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoAuditEventRepo {
+    collection: Collection<AuditEvent>,
+}
+
+impl MongoAuditEventRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl AuditEventRepo for MongoAuditEventRepo {
+    async fn insert(&self, event: AuditEvent) -> Result<(), Box<dyn Error>> {
+        self.collection.insert_one(event).await?;
+        Ok(())
+    }
+
+    async fn find(
+        &self,
+        filter: &AuditFilter,
+        limit: i64,
+    ) -> Result<Vec<AuditEvent>, Box<dyn Error>> {
+        let mut query = Document::new();
+        if let Some(action) = filter.action {
+            query.insert("action", to_bson(&action)?);
+        }
+        if let Some(actor_id) = filter.actor_id {
+            query.insert("actor_id", actor_id);
+        }
+        if let Some(target_id) = filter.target_id {
+            query.insert("target_id", target_id);
+        }
+        // Ids start with their creation time, so they bound the period without a date index.
+        let mut id = Document::new();
+        if let Some(from) = filter.from {
+            id.insert("$gte", object_id_at(from));
+        }
+        let upper = [filter.to.map(object_id_at), filter.before]
+            .into_iter()
+            .flatten()
+            .min();
+        if let Some(upper) = upper {
+            id.insert("$lt", upper);
+        }
+        if !id.is_empty() {
+            query.insert("_id", id);
+        }
+
+        let cursor = self
+            .collection
+            .find(query)
+            .sort(doc! { "_id": -1 })
+            .limit(limit)
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+}
+
+/// The smallest id created at `at`, to the second.
+fn object_id_at(at: DateTime<Utc>) -> ObjectId {
+    let mut bytes = [0; 12];
+    bytes[..4].copy_from_slice(&(at.timestamp() as u32).to_be_bytes());
+    ObjectId::from_bytes(bytes)
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryAuditEventRepo {
+    events: RwLock<Vec<AuditEvent>>,
+}
+
+#[async_trait(?Send)]
+impl AuditEventRepo for InMemoryAuditEventRepo {
+    async fn insert(&self, event: AuditEvent) -> Result<(), Box<dyn Error>> {
+        self.events.write().unwrap().push(event);
+        Ok(())
+    }
+
+    async fn find(
+        &self,
+        filter: &AuditFilter,
+        limit: i64,
+    ) -> Result<Vec<AuditEvent>, Box<dyn Error>> {
+        let events = self.events.read().unwrap();
+        Ok(events
+            .iter()
+            .rev()
+            .filter(|event| filter.action.is_none_or(|action| event.action == action))
+            .filter(|event| filter.actor_id.is_none_or(|id| event.actor_id == Some(id)))
+            .filter(|event| {
+                filter
+                    .target_id
+                    .is_none_or(|id| event.target_id == Some(id))
+            })
+            .filter(|event| filter.from.is_none_or(|from| event.created_at >= from))
+            .filter(|event| filter.to.is_none_or(|to| event.created_at < to))
+            .filter(|event| filter.before.is_none_or(|before| event.id < before))
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,102 @@
+use crate::models::auth_model::{AuthToken, TokenPurpose};
+use async_trait::async_trait;
+use bson::to_bson;
+use chrono::{DateTime, Utc};
+use mongodb::{Collection, Database, bson::doc};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "auth_tokens";
+
+// =============================================================================================================================
+
+#[async_trait(?Send)]
+pub trait AuthTokenRepo: Send + Sync {
+    /// Stores the token and invalidates the ones previously issued to the user for the same purpose.
+    async fn replace(&self, token: AuthToken) -> Result<(), Box<dyn Error>>;
+
+    /// Deletes the token and returns it, unless it had already expired at `now`.
+    async fn consume(
+        &self,
+        token_hash: &str,
+        purpose: TokenPurpose,
+        now: DateTime<Utc>,
+    ) -> Result<Option<AuthToken>, Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoAuthTokenRepo {
+    collection: Collection<AuthToken>,
+}
+
+impl MongoAuthTokenRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl AuthTokenRepo for MongoAuthTokenRepo {
+    async fn replace(&self, token: AuthToken) -> Result<(), Box<dyn Error>> {
+        self.collection
+            .delete_many(doc! {
+                "user_id": token.user_id,
+                "purpose": to_bson(&token.purpose)?
+            })
+            .await?;
+        self.collection.insert_one(&token).await?;
+        Ok(())
+    }
+
+    async fn consume(
+        &self,
+        token_hash: &str,
+        purpose: TokenPurpose,
+        now: DateTime<Utc>,
+    ) -> Result<Option<AuthToken>, Box<dyn Error>> {
+        let token = self
+            .collection
+            .find_one_and_delete(doc! { "_id": token_hash, "purpose": to_bson(&purpose)? })
+            .await?;
+        Ok(token.filter(|token| token.expires_at > now))
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryAuthTokenRepo {
+    pub(super) tokens: RwLock<Vec<AuthToken>>,
+}
+
+#[async_trait(?Send)]
+impl AuthTokenRepo for InMemoryAuthTokenRepo {
+    async fn replace(&self, token: AuthToken) -> Result<(), Box<dyn Error>> {
+        let mut tokens = self.tokens.write().unwrap();
+        tokens.retain(|existing| {
+            !(existing.user_id == token.user_id && existing.purpose == token.purpose)
+        });
+        tokens.push(token);
+        Ok(())
+    }
+
+    async fn consume(
+        &self,
+        token_hash: &str,
+        purpose: TokenPurpose,
+        now: DateTime<Utc>,
+    ) -> Result<Option<AuthToken>, Box<dyn Error>> {
+        let mut tokens = self.tokens.write().unwrap();
+        Ok(tokens
+            .iter()
+            .position(|token| token.token_hash == token_hash && token.purpose == purpose)
+            .map(|index| tokens.remove(index))
+            .filter(|token| token.expires_at > now))
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,554 @@
+use crate::{
+    models::{
+        deletion_model::DeletionReport,
+        group_model::Group,
+        message_model::{Media, Message},
+        story_model::Story,
+    },
+    repositories::{
+        auth_token_repository::InMemoryAuthTokenRepo, export_repository::InMemoryExportRepo,
+        friend_repository::InMemoryFriendRepo, group_repository::InMemoryGroupRepo,
+        identity_repository::InMemoryIdentityRepo,
+        login_attempt_repository::InMemoryLoginAttemptRepo,
+        message_repository::InMemoryMessageRepo,
+        pending_upload_repository::InMemoryPendingUploadRepo,
+        report_repository::InMemoryReportRepo, session_repository::InMemorySessionRepo,
+        story_repository::InMemoryStoryRepo, two_factor_repository::InMemoryTwoFactorRepo,
+        upload_session_repository::InMemoryUploadSessionRepo, user_repository::InMemoryUserRepo,
+    },
+};
+use async_trait::async_trait;
+use bson::{Document, oid::ObjectId};
+use futures_util::TryStreamExt;
+use mongodb::{ClientSession, Collection, Database, bson::doc};
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{Arc, RwLock},
+};
+
+// =============================================================================================================================
+
+/// Removes a user or a group with everything that belongs to them, within a MongoDB transaction where the deployment
+/// supports it. Storage objects are left to the caller, which releases the media, aborts the multipart uploads and
+/// deletes the export archives and unconfirmed uploads it listed beforehand. OIDC logins in progress are not tied to a
+/// user and expire on their own.
+#[async_trait(?Send)]
+pub trait DeletionRepo: Send + Sync {
+    /// Media of the stories and messages that deleting the user removes.
+    async fn user_media(&self, user_id: ObjectId) -> Result<Vec<Media>, Box<dyn Error>>;
+
+    /// Deletes the user, their friendships, stories, sessions, identities, two-factor settings, auth tokens, login
+    /// attempts, upload sessions, unconfirmed uploads, exports and the reports they filed or that were filed about
+    /// them, the groups they created, their messages and those of their groups, and takes them out of the other groups.
+    /// A dry run only counts.
+    async fn delete_user(
+        &self,
+        user_id: ObjectId,
+        dry_run: bool,
+    ) -> Result<DeletionReport, Box<dyn Error>>;
+
+    /// Media of the messages posted to the group.
+    async fn group_media(&self, group_id: ObjectId) -> Result<Vec<Media>, Box<dyn Error>>;
+
+    /// Deletes the group and its messages. A dry run only counts.
+    async fn delete_group(
+        &self,
+        group_id: ObjectId,
+        dry_run: bool,
+    ) -> Result<DeletionReport, Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoDeletionRepo {
+    db: Database,
+    users: Collection<Document>,
+    friends: Collection<Document>,
+    groups: Collection<Group>,
+    messages: Collection<Message>,
+    stories: Collection<Story>,
+    sessions: Collection<Document>,
+    identities: Collection<Document>,
+    two_factor: Collection<Document>,
+    auth_tokens: Collection<Document>,
+    login_attempts: Collection<Document>,
+    upload_sessions: Collection<Document>,
+    pending_uploads: Collection<Document>,
+    exports: Collection<Document>,
+    reports: Collection<Document>,
+}
+
+impl MongoDeletionRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            db: db.clone(),
+            users: db.collection("users"),
+            friends: db.collection("friends"),
+            groups: db.collection("groups"),
+            messages: db.collection("messages"),
+            stories: db.collection("stories"),
+            sessions: db.collection("sessions"),
+            identities: db.collection("identities"),
+            two_factor: db.collection("two_factor"),
+            auth_tokens: db.collection("auth_tokens"),
+            login_attempts: db.collection("login_attempts"),
+            upload_sessions: db.collection("upload_sessions"),
+            pending_uploads: db.collection("pending_uploads"),
+            exports: db.collection("exports"),
+            reports: db.collection("reports"),
+        }
+    }
+
+    async fn owned_groups(&self, user_id: ObjectId) -> Result<Vec<ObjectId>, Box<dyn Error>> {
+        Ok(self
+            .groups
+            .find(doc! { "creator_id": user_id })
+            .await?
+            .try_collect::<Vec<Group>>()
+            .await?
+            .into_iter()
+            .filter_map(|group| group.id)
+            .collect())
+    }
+
+    async fn message_media(&self, filter: Document) -> Result<Vec<Media>, Box<dyn Error>> {
+        let mut media = Vec::new();
+        let mut cursor = self.messages.find(filter).await?;
+        while let Some(message) = cursor.try_next().await? {
+            if let Some(message_media) = message.media {
+                media.push(message_media);
+            }
+        }
+        Ok(media)
+    }
+}
+
+fn user_messages_filter(user_id: ObjectId, owned_groups: &[ObjectId]) -> Document {
+    doc! {
+        "$or": [
+            { "sender_id": user_id },
+            { "is_group": false, "recipient_id": user_id },
+            { "is_group": true, "recipient_id": { "$in": owned_groups } }
+        ]
+    }
+}
+
+#[async_trait(?Send)]
+impl DeletionRepo for MongoDeletionRepo {
+    async fn user_media(&self, user_id: ObjectId) -> Result<Vec<Media>, Box<dyn Error>> {
+        let owned_groups = self.owned_groups(user_id).await?;
+        let mut media = self
+            .message_media(user_messages_filter(user_id, &owned_groups))
+            .await?;
+
+        let mut cursor = self.stories.find(doc! { "user_id": user_id }).await?;
+        while let Some(story) = cursor.try_next().await? {
+            media.push(story.media);
+        }
+
+        Ok(media)
+    }
+
+    async fn delete_user(
+        &self,
+        user_id: ObjectId,
+        dry_run: bool,
+    ) -> Result<DeletionReport, Box<dyn Error>> {
+        let owned_groups = self.owned_groups(user_id).await?;
+
+        let user_filter = doc! { "_id": user_id };
+        let friends_filter = doc! {
+            "$or": [
+                { "user_id": user_id },
+                { "friend_id": user_id }
+            ]
+        };
+        let groups_filter = doc! { "_id": { "$in": &owned_groups } };
+        let memberships_filter = doc! {
+            "members": user_id,
+            "creator_id": { "$ne": user_id }
+        };
+        let messages_filter = user_messages_filter(user_id, &owned_groups);
+        let owned_filter = doc! { "user_id": user_id };
+        let keyed_filter = doc! { "_id": user_id };
+        let uploads_filter = doc! { "owner_id": user_id };
+        let reports_filter = doc! {
+            "$or": [
+                { "reporter_id": user_id },
+                { "target_user_id": user_id }
+            ]
+        };
+
+        let mut report = DeletionReport {
+            dry_run,
+            ..Default::default()
+        };
+
+        if dry_run {
+            report.users = self.users.count_documents(user_filter).await?;
+            report.friendships = self.friends.count_documents(friends_filter).await?;
+            report.groups = self.groups.count_documents(groups_filter).await?;
+            report.group_memberships = self.groups.count_documents(memberships_filter).await?;
+            report.messages = self.messages.count_documents(messages_filter).await?;
+            report.stories = self.stories.count_documents(owned_filter.clone()).await?;
+            report.sessions = self.sessions.count_documents(owned_filter.clone()).await?;
+            report.identities = self
+                .identities
+                .count_documents(owned_filter.clone())
+                .await?;
+            report.two_factor = self
+                .two_factor
+                .count_documents(keyed_filter.clone())
+                .await?;
+            report.auth_tokens = self
+                .auth_tokens
+                .count_documents(owned_filter.clone())
+                .await?;
+            report.login_attempts = self.login_attempts.count_documents(keyed_filter).await?;
+            report.upload_sessions = self
+                .upload_sessions
+                .count_documents(uploads_filter.clone())
+                .await?;
+            report.pending_uploads = self.pending_uploads.count_documents(uploads_filter).await?;
+            report.exports = self.exports.count_documents(owned_filter).await?;
+            report.reports = self.reports.count_documents(reports_filter).await?;
+            return Ok(report);
+        }
+
+        let mut tx = Transaction::begin(&self.db).await?;
+        report.transactional = tx.is_transactional();
+
+        report.messages = tx.delete_many(&self.messages, messages_filter).await?;
+        report.stories = tx.delete_many(&self.stories, owned_filter.clone()).await?;
+        report.friendships = tx.delete_many(&self.friends, friends_filter).await?;
+        report.groups = tx.delete_many(&self.groups, groups_filter).await?;
+        report.group_memberships = tx
+            .update_many(
+                &self.groups,
+                memberships_filter,
+                doc! { "$pull": { "members": user_id } },
+            )
+            .await?;
+        report.sessions = tx.delete_many(&self.sessions, owned_filter.clone()).await?;
+        report.identities = tx
+            .delete_many(&self.identities, owned_filter.clone())
+            .await?;
+        report.two_factor = tx
+            .delete_many(&self.two_factor, keyed_filter.clone())
+            .await?;
+        report.auth_tokens = tx
+            .delete_many(&self.auth_tokens, owned_filter.clone())
+            .await?;
+        report.login_attempts = tx.delete_many(&self.login_attempts, keyed_filter).await?;
+        report.upload_sessions = tx
+            .delete_many(&self.upload_sessions, uploads_filter.clone())
+            .await?;
+        report.pending_uploads = tx
+            .delete_many(&self.pending_uploads, uploads_filter)
+            .await?;
+        report.exports = tx.delete_many(&self.exports, owned_filter).await?;
+        report.reports = tx.delete_many(&self.reports, reports_filter).await?;
+        report.users = tx.delete_many(&self.users, user_filter).await?;
+
+        tx.commit().await?;
+
+        Ok(report)
+    }
+
+    async fn group_media(&self, group_id: ObjectId) -> Result<Vec<Media>, Box<dyn Error>> {
+        self.message_media(doc! { "is_group": true, "recipient_id": group_id })
+            .await
+    }
+
+    async fn delete_group(
+        &self,
+        group_id: ObjectId,
+        dry_run: bool,
+    ) -> Result<DeletionReport, Box<dyn Error>> {
+        let group_filter = doc! { "_id": group_id };
+        let messages_filter = doc! {
+            "is_group": true,
+            "recipient_id": group_id
+        };
+
+        let mut report = DeletionReport {
+            dry_run,
+            ..Default::default()
+        };
+
+        if dry_run {
+            report.groups = self.groups.count_documents(group_filter).await?;
+            report.messages = self.messages.count_documents(messages_filter).await?;
+            return Ok(report);
+        }
+
+        let mut tx = Transaction::begin(&self.db).await?;
+        report.transactional = tx.is_transactional();
+
+        report.messages = tx.delete_many(&self.messages, messages_filter).await?;
+        report.groups = tx.delete_many(&self.groups, group_filter).await?;
+
+        tx.commit().await?;
+
+        Ok(report)
+    }
+}
+
+// =============================================================================================================================
+
+/// Wraps the writes of a cascade in a MongoDB transaction when the deployment supports it
+/// (replica set or sharded cluster), and falls back to plain sequential writes on a standalone server.
+struct Transaction {
+    session: Option<ClientSession>,
+}
+
+impl Transaction {
+    async fn begin(db: &Database) -> Result<Self, Box<dyn Error>> {
+        let hello = db.run_command(doc! { "hello": 1 }).await?;
+        let supports_transactions = hello.contains_key("setName")
+            || hello.get_str("msg").is_ok_and(|msg| msg == "isdbgrid");
+
+        if !supports_transactions {
+            return Ok(Self { session: None });
+        }
+
+        let mut session = db.client().start_session().await?;
+        session.start_transaction().await?;
+
+        Ok(Self {
+            session: Some(session),
+        })
+    }
+
+    fn is_transactional(&self) -> bool {
+        self.session.is_some()
+    }
+
+    async fn delete_many<T: Send + Sync>(
+        &mut self,
+        collection: &Collection<T>,
+        filter: Document,
+    ) -> Result<u64, Box<dyn Error>> {
+        let action = collection.delete_many(filter);
+        let result = match self.session.as_mut() {
+            Some(session) => action.session(session).await?,
+            None => action.await?,
+        };
+        Ok(result.deleted_count)
+    }
+
+    async fn update_many<T: Send + Sync>(
+        &mut self,
+        collection: &Collection<T>,
+        filter: Document,
+        update: Document,
+    ) -> Result<u64, Box<dyn Error>> {
+        let action = collection.update_many(filter, update);
+        let result = match self.session.as_mut() {
+            Some(session) => action.session(session).await?,
+            None => action.await?,
+        };
+        Ok(result.modified_count)
+    }
+
+    async fn commit(mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(session) = self.session.as_mut() {
+            session.commit_transaction().await?;
+        }
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
+
+/// Works on the stores of the other in-memory repositories, so a deletion shows through them.
+pub struct InMemoryDeletionRepo {
+    pub(super) users: Arc<InMemoryUserRepo>,
+    pub(super) friends: Arc<InMemoryFriendRepo>,
+    pub(super) groups: Arc<InMemoryGroupRepo>,
+    pub(super) messages: Arc<InMemoryMessageRepo>,
+    pub(super) stories: Arc<InMemoryStoryRepo>,
+    pub(super) sessions: Arc<InMemorySessionRepo>,
+    pub(super) identities: Arc<InMemoryIdentityRepo>,
+    pub(super) two_factor: Arc<InMemoryTwoFactorRepo>,
+    pub(super) auth_tokens: Arc<InMemoryAuthTokenRepo>,
+    pub(super) login_attempts: Arc<InMemoryLoginAttemptRepo>,
+    pub(super) upload_sessions: Arc<InMemoryUploadSessionRepo>,
+    pub(super) pending_uploads: Arc<InMemoryPendingUploadRepo>,
+    pub(super) exports: Arc<InMemoryExportRepo>,
+    pub(super) reports: Arc<InMemoryReportRepo>,
+}
+
+impl InMemoryDeletionRepo {
+    fn owned_groups(&self, user_id: ObjectId) -> Vec<ObjectId> {
+        let groups = self.groups.groups.read().unwrap();
+        groups
+            .iter()
+            .filter(|group| group.creator_id == user_id)
+            .filter_map(|group| group.id)
+            .collect()
+    }
+}
+
+fn is_user_message(message: &Message, user_id: ObjectId, owned_groups: &[ObjectId]) -> bool {
+    message.sender_id == user_id
+        || (!message.is_group && message.recipient_id == user_id)
+        || (message.is_group && owned_groups.contains(&message.recipient_id))
+}
+
+/// Counts the matching items, and removes them unless it is a dry run.
+fn remove<T>(items: &RwLock<Vec<T>>, predicate: impl Fn(&T) -> bool, dry_run: bool) -> u64 {
+    let mut items = items.write().unwrap();
+    let count = items.iter().filter(|item| predicate(item)).count() as u64;
+    if !dry_run {
+        items.retain(|item| !predicate(item));
+    }
+    count
+}
+
+/// Same as `remove`, for the stores keyed by user.
+fn remove_key<T>(items: &RwLock<HashMap<ObjectId, T>>, user_id: ObjectId, dry_run: bool) -> u64 {
+    let mut items = items.write().unwrap();
+    match dry_run {
+        true => items.contains_key(&user_id) as u64,
+        false => items.remove(&user_id).is_some() as u64,
+    }
+}
+
+#[async_trait(?Send)]
+impl DeletionRepo for InMemoryDeletionRepo {
+    async fn user_media(&self, user_id: ObjectId) -> Result<Vec<Media>, Box<dyn Error>> {
+        let owned_groups = self.owned_groups(user_id);
+        let messages = self.messages.messages.read().unwrap();
+        let stories = self.stories.stories.read().unwrap();
+
+        Ok(messages
+            .iter()
+            .filter(|message| is_user_message(message, user_id, &owned_groups))
+            .filter_map(|message| message.media.clone())
+            .chain(
+                stories
+                    .iter()
+                    .filter(|story| story.user_id == user_id)
+                    .map(|story| story.media.clone()),
+            )
+            .collect())
+    }
+
+    async fn delete_user(
+        &self,
+        user_id: ObjectId,
+        dry_run: bool,
+    ) -> Result<DeletionReport, Box<dyn Error>> {
+        let owned_groups = self.owned_groups(user_id);
+
+        let mut report = DeletionReport {
+            dry_run,
+            ..Default::default()
+        };
+        report.messages = remove(
+            &self.messages.messages,
+            |message| is_user_message(message, user_id, &owned_groups),
+            dry_run,
+        );
+        report.stories = remove(
+            &self.stories.stories,
+            |story| story.user_id == user_id,
+            dry_run,
+        );
+        report.friendships = remove(
+            &self.friends.friends,
+            |friend| friend.user_id == user_id || friend.friend_id == user_id,
+            dry_run,
+        );
+        report.groups = remove(
+            &self.groups.groups,
+            |group| group.creator_id == user_id,
+            dry_run,
+        );
+        {
+            let mut groups = self.groups.groups.write().unwrap();
+            for group in groups.iter_mut() {
+                if group.creator_id != user_id && group.members.contains(&user_id) {
+                    report.group_memberships += 1;
+                    if !dry_run {
+                        group.members.retain(|member| *member != user_id);
+                    }
+                }
+            }
+        }
+        report.sessions = remove(
+            &self.sessions.sessions,
+            |session| session.user_id == user_id,
+            dry_run,
+        );
+        report.identities = remove(
+            &self.identities.identities,
+            |identity| identity.user_id == user_id,
+            dry_run,
+        );
+        report.two_factor = remove_key(&self.two_factor.settings, user_id, dry_run);
+        report.auth_tokens = remove(
+            &self.auth_tokens.tokens,
+            |token| token.user_id == user_id,
+            dry_run,
+        );
+        report.login_attempts = remove_key(&self.login_attempts.attempts, user_id, dry_run);
+        report.upload_sessions = remove(
+            &self.upload_sessions.sessions,
+            |session| session.owner_id == user_id,
+            dry_run,
+        );
+        report.pending_uploads = remove(
+            &self.pending_uploads.uploads,
+            |upload| upload.owner_id == user_id,
+            dry_run,
+        );
+        report.exports = remove(
+            &self.exports.exports,
+            |export| export.user_id == user_id,
+            dry_run,
+        );
+        report.reports = remove(
+            &self.reports.reports,
+            |report| report.reporter_id == user_id || report.target_user_id == user_id,
+            dry_run,
+        );
+        report.users = remove(&self.users.users, |user| user.id == Some(user_id), dry_run);
+
+        Ok(report)
+    }
+
+    async fn group_media(&self, group_id: ObjectId) -> Result<Vec<Media>, Box<dyn Error>> {
+        let messages = self.messages.messages.read().unwrap();
+        Ok(messages
+            .iter()
+            .filter(|message| message.is_group && message.recipient_id == group_id)
+            .filter_map(|message| message.media.clone())
+            .collect())
+    }
+
+    async fn delete_group(
+        &self,
+        group_id: ObjectId,
+        dry_run: bool,
+    ) -> Result<DeletionReport, Box<dyn Error>> {
+        Ok(DeletionReport {
+            dry_run,
+            messages: remove(
+                &self.messages.messages,
+                |message| message.is_group && message.recipient_id == group_id,
+                dry_run,
+            ),
+            groups: remove(
+                &self.groups.groups,
+                |group| group.id == Some(group_id),
+                dry_run,
+            ),
+            ..Default::default()
+        })
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,286 @@
+use crate::models::export_model::{DataExport, ExportStatus};
+use async_trait::async_trait;
+use bson::{DateTime as BsonDateTime, oid::ObjectId, to_bson};
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{Collection, Database, bson::doc};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "exports";
+
+// =============================================================================================================================
+
+#[async_trait(?Send)]
+pub trait ExportRepo: Send + Sync {
+    async fn insert(&self, export: DataExport) -> Result<DataExport, Box<dyn Error>>;
+
+    /// The export of the user still being built, if any.
+    async fn find_pending(&self, user_id: ObjectId) -> Result<Option<DataExport>, Box<dyn Error>>;
+
+    async fn find_by_user(&self, user_id: ObjectId) -> Result<Vec<DataExport>, Box<dyn Error>>;
+
+    /// The export, provided it was requested by `user_id`.
+    async fn find_for_user(
+        &self,
+        id: ObjectId,
+        user_id: ObjectId,
+    ) -> Result<Option<DataExport>, Box<dyn Error>>;
+
+    /// The ready export whose download link carries `token`.
+    async fn find_ready(
+        &self,
+        id: ObjectId,
+        token: &str,
+    ) -> Result<Option<DataExport>, Box<dyn Error>>;
+
+    /// The ready exports whose download link expired at or before `now`.
+    async fn find_expired(&self, now: DateTime<Utc>) -> Result<Vec<DataExport>, Box<dyn Error>>;
+
+    async fn mark_ready(
+        &self,
+        id: ObjectId,
+        archive_url: &str,
+        download_token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Box<dyn Error>>;
+
+    async fn mark_failed(&self, id: ObjectId, error: &str) -> Result<(), Box<dyn Error>>;
+
+    async fn delete(&self, id: ObjectId) -> Result<(), Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoExportRepo {
+    collection: Collection<DataExport>,
+}
+
+impl MongoExportRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl ExportRepo for MongoExportRepo {
+    async fn insert(&self, export: DataExport) -> Result<DataExport, Box<dyn Error>> {
+        let result = self.collection.insert_one(&export).await?;
+        let mut created_export = export;
+        created_export.id = result.inserted_id.as_object_id();
+        Ok(created_export)
+    }
+
+    async fn find_pending(&self, user_id: ObjectId) -> Result<Option<DataExport>, Box<dyn Error>> {
+        let filter = doc! {
+            "user_id": user_id,
+            "status": to_bson(&ExportStatus::Pending)?
+        };
+
+        Ok(self.collection.find_one(filter).await?)
+    }
+
+    async fn find_by_user(&self, user_id: ObjectId) -> Result<Vec<DataExport>, Box<dyn Error>> {
+        let cursor = self.collection.find(doc! { "user_id": user_id }).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_for_user(
+        &self,
+        id: ObjectId,
+        user_id: ObjectId,
+    ) -> Result<Option<DataExport>, Box<dyn Error>> {
+        let filter = doc! {
+            "_id": id,
+            "user_id": user_id
+        };
+
+        Ok(self.collection.find_one(filter).await?)
+    }
+
+    async fn find_ready(
+        &self,
+        id: ObjectId,
+        token: &str,
+    ) -> Result<Option<DataExport>, Box<dyn Error>> {
+        let filter = doc! {
+            "_id": id,
+            "download_token": token,
+            "status": to_bson(&ExportStatus::Ready)?
+        };
+
+        Ok(self.collection.find_one(filter).await?)
+    }
+
+    async fn find_expired(&self, now: DateTime<Utc>) -> Result<Vec<DataExport>, Box<dyn Error>> {
+        let filter = doc! {
+            "status": to_bson(&ExportStatus::Ready)?,
+            "expires_at": { "$lte": BsonDateTime::from_chrono(now) }
+        };
+
+        let cursor = self.collection.find(filter).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn mark_ready(
+        &self,
+        id: ObjectId,
+        archive_url: &str,
+        download_token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Box<dyn Error>> {
+        let update = doc! {
+            "$set": {
+                "status": to_bson(&ExportStatus::Ready)?,
+                "archive_url": archive_url,
+                "download_token": download_token,
+                "expires_at": BsonDateTime::from_chrono(expires_at)
+            }
+        };
+
+        self.collection
+            .update_one(doc! { "_id": id }, update)
+            .await?;
+        Ok(())
+    }
+
+    async fn mark_failed(&self, id: ObjectId, error: &str) -> Result<(), Box<dyn Error>> {
+        let update = doc! {
+            "$set": {
+                "status": to_bson(&ExportStatus::Failed)?,
+                "error": error
+            }
+        };
+
+        self.collection
+            .update_one(doc! { "_id": id }, update)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<(), Box<dyn Error>> {
+        self.collection.delete_one(doc! { "_id": id }).await?;
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryExportRepo {
+    pub(super) exports: RwLock<Vec<DataExport>>,
+}
+
+impl InMemoryExportRepo {
+    fn update(&self, id: ObjectId, apply: impl FnOnce(&mut DataExport)) {
+        let mut exports = self.exports.write().unwrap();
+        if let Some(export) = exports.iter_mut().find(|export| export.id == Some(id)) {
+            apply(export);
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl ExportRepo for InMemoryExportRepo {
+    async fn insert(&self, export: DataExport) -> Result<DataExport, Box<dyn Error>> {
+        let mut exports = self.exports.write().unwrap();
+        let mut created_export = export;
+        created_export.id = Some(ObjectId::new());
+        exports.push(created_export.clone());
+        Ok(created_export)
+    }
+
+    async fn find_pending(&self, user_id: ObjectId) -> Result<Option<DataExport>, Box<dyn Error>> {
+        let exports = self.exports.read().unwrap();
+        Ok(exports
+            .iter()
+            .find(|export| export.user_id == user_id && export.status == ExportStatus::Pending)
+            .cloned())
+    }
+
+    async fn find_by_user(&self, user_id: ObjectId) -> Result<Vec<DataExport>, Box<dyn Error>> {
+        let exports = self.exports.read().unwrap();
+        Ok(exports
+            .iter()
+            .filter(|export| export.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_for_user(
+        &self,
+        id: ObjectId,
+        user_id: ObjectId,
+    ) -> Result<Option<DataExport>, Box<dyn Error>> {
+        let exports = self.exports.read().unwrap();
+        Ok(exports
+            .iter()
+            .find(|export| export.id == Some(id) && export.user_id == user_id)
+            .cloned())
+    }
+
+    async fn find_ready(
+        &self,
+        id: ObjectId,
+        token: &str,
+    ) -> Result<Option<DataExport>, Box<dyn Error>> {
+        let exports = self.exports.read().unwrap();
+        Ok(exports
+            .iter()
+            .find(|export| {
+                export.id == Some(id)
+                    && export.download_token.as_deref() == Some(token)
+                    && export.status == ExportStatus::Ready
+            })
+            .cloned())
+    }
+
+    async fn find_expired(&self, now: DateTime<Utc>) -> Result<Vec<DataExport>, Box<dyn Error>> {
+        let exports = self.exports.read().unwrap();
+        Ok(exports
+            .iter()
+            .filter(|export| {
+                export.status == ExportStatus::Ready
+                    && export
+                        .expires_at
+                        .is_some_and(|expires_at| expires_at <= now)
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn mark_ready(
+        &self,
+        id: ObjectId,
+        archive_url: &str,
+        download_token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.update(id, |export| {
+            export.status = ExportStatus::Ready;
+            export.archive_url = Some(archive_url.to_string());
+            export.download_token = Some(download_token.to_string());
+            export.expires_at = Some(expires_at);
+        });
+        Ok(())
+    }
+
+    async fn mark_failed(&self, id: ObjectId, error: &str) -> Result<(), Box<dyn Error>> {
+        self.update(id, |export| {
+            export.status = ExportStatus::Failed;
+            export.error = Some(error.to_string());
+        });
+        Ok(())
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<(), Box<dyn Error>> {
+        let mut exports = self.exports.write().unwrap();
+        exports.retain(|export| export.id != Some(id));
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,290 @@
+use crate::models::friend_model::{Friend, FriendStatus};
+use async_trait::async_trait;
+use bson::oid::ObjectId;
+use futures_util::TryStreamExt;
+use mongodb::{Collection, Database, bson::doc, options::ReturnDocument};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "friends";
+
+// =============================================================================================================================
+
+#[async_trait(?Send)]
+pub trait FriendRepo: Send + Sync {
+    /// Accepted friendships in which the user is either the requester or the recipient.
+    async fn find_accepted(&self, user_id: ObjectId) -> Result<Vec<Friend>, Box<dyn Error>>;
+
+    /// Every friendship and request, whatever its status, in which one of the users takes part.
+    async fn find_involving(&self, user_ids: &[ObjectId]) -> Result<Vec<Friend>, Box<dyn Error>>;
+
+    /// Pending requests addressed to the user.
+    async fn find_pending_for(&self, user_id: ObjectId) -> Result<Vec<Friend>, Box<dyn Error>>;
+
+    /// The relationship between two users in either direction, whatever its status.
+    async fn find_between(
+        &self,
+        user_id: ObjectId,
+        other_id: ObjectId,
+    ) -> Result<Option<Friend>, Box<dyn Error>>;
+
+    async fn are_friends(
+        &self,
+        user_id: ObjectId,
+        other_id: ObjectId,
+    ) -> Result<bool, Box<dyn Error>>;
+
+    async fn insert(&self, friend: Friend) -> Result<Friend, Box<dyn Error>>;
+
+    /// Accepts a pending request, provided `recipient_id` is the user it was sent to.
+    async fn accept(
+        &self,
+        request_id: ObjectId,
+        recipient_id: ObjectId,
+    ) -> Result<Option<Friend>, Box<dyn Error>>;
+
+    async fn delete_between(
+        &self,
+        user_id: ObjectId,
+        other_id: ObjectId,
+    ) -> Result<Option<Friend>, Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoFriendRepo {
+    collection: Collection<Friend>,
+}
+
+impl MongoFriendRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl FriendRepo for MongoFriendRepo {
+    async fn find_accepted(&self, user_id: ObjectId) -> Result<Vec<Friend>, Box<dyn Error>> {
+        let filter = doc! {
+            "$or": [
+                { "user_id": user_id },
+                { "friend_id": user_id }
+            ],
+            "status": "Accepted"
+        };
+
+        let cursor = self.collection.find(filter).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_involving(&self, user_ids: &[ObjectId]) -> Result<Vec<Friend>, Box<dyn Error>> {
+        let filter = doc! {
+            "$or": [
+                { "user_id": { "$in": user_ids } },
+                { "friend_id": { "$in": user_ids } }
+            ]
+        };
+
+        let cursor = self.collection.find(filter).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_pending_for(&self, user_id: ObjectId) -> Result<Vec<Friend>, Box<dyn Error>> {
+        let filter = doc! {
+            "friend_id": user_id,
+            "status": "Pending"
+        };
+
+        let cursor = self.collection.find(filter).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_between(
+        &self,
+        user_id: ObjectId,
+        other_id: ObjectId,
+    ) -> Result<Option<Friend>, Box<dyn Error>> {
+        let filter = doc! {
+            "$or": [
+                { "user_id": user_id, "friend_id": other_id },
+                { "user_id": other_id, "friend_id": user_id }
+            ]
+        };
+
+        Ok(self.collection.find_one(filter).await?)
+    }
+
+    async fn are_friends(
+        &self,
+        user_id: ObjectId,
+        other_id: ObjectId,
+    ) -> Result<bool, Box<dyn Error>> {
+        let filter = doc! {
+            "$or": [
+                { "user_id": user_id, "friend_id": other_id },
+                { "user_id": other_id, "friend_id": user_id }
+            ],
+            "status": "Accepted"
+        };
+
+        Ok(self.collection.find_one(filter).await?.is_some())
+    }
+
+    async fn insert(&self, friend: Friend) -> Result<Friend, Box<dyn Error>> {
+        let result = self.collection.insert_one(&friend).await?;
+        let mut created_friend = friend;
+        created_friend.id = result.inserted_id.as_object_id();
+        Ok(created_friend)
+    }
+
+    async fn accept(
+        &self,
+        request_id: ObjectId,
+        recipient_id: ObjectId,
+    ) -> Result<Option<Friend>, Box<dyn Error>> {
+        let filter = doc! {
+            "_id": request_id,
+            "friend_id": recipient_id,
+            "status": "Pending"
+        };
+
+        let update = doc! {
+            "$set": {
+                "status": "Accepted"
+            }
+        };
+
+        Ok(self
+            .collection
+            .find_one_and_update(filter, update)
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn delete_between(
+        &self,
+        user_id: ObjectId,
+        other_id: ObjectId,
+    ) -> Result<Option<Friend>, Box<dyn Error>> {
+        let filter = doc! {
+            "$or": [
+                { "user_id": user_id, "friend_id": other_id },
+                { "user_id": other_id, "friend_id": user_id }
+            ]
+        };
+
+        Ok(self.collection.find_one_and_delete(filter).await?)
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryFriendRepo {
+    pub(super) friends: RwLock<Vec<Friend>>,
+}
+
+fn is_between(friend: &Friend, user_id: ObjectId, other_id: ObjectId) -> bool {
+    (friend.user_id == user_id && friend.friend_id == other_id)
+        || (friend.user_id == other_id && friend.friend_id == user_id)
+}
+
+#[async_trait(?Send)]
+impl FriendRepo for InMemoryFriendRepo {
+    async fn find_accepted(&self, user_id: ObjectId) -> Result<Vec<Friend>, Box<dyn Error>> {
+        let friends = self.friends.read().unwrap();
+        Ok(friends
+            .iter()
+            .filter(|friend| friend.status == FriendStatus::Accepted)
+            .filter(|friend| friend.user_id == user_id || friend.friend_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_involving(&self, user_ids: &[ObjectId]) -> Result<Vec<Friend>, Box<dyn Error>> {
+        let friends = self.friends.read().unwrap();
+        Ok(friends
+            .iter()
+            .filter(|friend| {
+                user_ids.contains(&friend.user_id) || user_ids.contains(&friend.friend_id)
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn find_pending_for(&self, user_id: ObjectId) -> Result<Vec<Friend>, Box<dyn Error>> {
+        let friends = self.friends.read().unwrap();
+        Ok(friends
+            .iter()
+            .filter(|friend| friend.status == FriendStatus::Pending && friend.friend_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_between(
+        &self,
+        user_id: ObjectId,
+        other_id: ObjectId,
+    ) -> Result<Option<Friend>, Box<dyn Error>> {
+        let friends = self.friends.read().unwrap();
+        Ok(friends
+            .iter()
+            .find(|friend| is_between(friend, user_id, other_id))
+            .cloned())
+    }
+
+    async fn are_friends(
+        &self,
+        user_id: ObjectId,
+        other_id: ObjectId,
+    ) -> Result<bool, Box<dyn Error>> {
+        let friends = self.friends.read().unwrap();
+        Ok(friends.iter().any(|friend| {
+            friend.status == FriendStatus::Accepted && is_between(friend, user_id, other_id)
+        }))
+    }
+
+    async fn insert(&self, friend: Friend) -> Result<Friend, Box<dyn Error>> {
+        let mut friends = self.friends.write().unwrap();
+        let mut created_friend = friend;
+        created_friend.id = Some(ObjectId::new());
+        friends.push(created_friend.clone());
+        Ok(created_friend)
+    }
+
+    async fn accept(
+        &self,
+        request_id: ObjectId,
+        recipient_id: ObjectId,
+    ) -> Result<Option<Friend>, Box<dyn Error>> {
+        let mut friends = self.friends.write().unwrap();
+        Ok(friends
+            .iter_mut()
+            .find(|friend| {
+                friend.id == Some(request_id)
+                    && friend.friend_id == recipient_id
+                    && friend.status == FriendStatus::Pending
+            })
+            .map(|friend| {
+                friend.status = FriendStatus::Accepted;
+                friend.clone()
+            }))
+    }
+
+    async fn delete_between(
+        &self,
+        user_id: ObjectId,
+        other_id: ObjectId,
+    ) -> Result<Option<Friend>, Box<dyn Error>> {
+        let mut friends = self.friends.write().unwrap();
+        Ok(friends
+            .iter()
+            .position(|friend| is_between(friend, user_id, other_id))
+            .map(|index| friends.remove(index)))
+    }
+}
+
+// =============================================================================================================================
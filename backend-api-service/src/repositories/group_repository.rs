@@ -0,0 +1,266 @@
+use crate::models::group_model::Group;
+use async_trait::async_trait;
+use bson::oid::ObjectId;
+use futures_util::TryStreamExt;
+use mongodb::{Collection, Database, bson::doc, options::ReturnDocument};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "groups";
+
+// =============================================================================================================================
+
+#[async_trait(?Send)]
+pub trait GroupRepo: Send + Sync {
+    async fn find_by_id(&self, group_id: ObjectId) -> Result<Option<Group>, Box<dyn Error>>;
+
+    async fn find_by_member(&self, user_id: ObjectId) -> Result<Vec<Group>, Box<dyn Error>>;
+
+    /// The group, provided the user is one of its members.
+    async fn find_for_member(
+        &self,
+        group_id: ObjectId,
+        user_id: ObjectId,
+    ) -> Result<Option<Group>, Box<dyn Error>>;
+
+    async fn insert(&self, group: Group) -> Result<Group, Box<dyn Error>>;
+
+    /// Renames the group, provided the user is its creator or one of its members.
+    async fn rename(
+        &self,
+        group_id: ObjectId,
+        user_id: ObjectId,
+        name: String,
+    ) -> Result<Option<Group>, Box<dyn Error>>;
+
+    async fn add_members(
+        &self,
+        group_id: ObjectId,
+        members: Vec<ObjectId>,
+    ) -> Result<Option<Group>, Box<dyn Error>>;
+
+    async fn remove_member(
+        &self,
+        group_id: ObjectId,
+        member_id: ObjectId,
+    ) -> Result<Option<Group>, Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoGroupRepo {
+    collection: Collection<Group>,
+}
+
+impl MongoGroupRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl GroupRepo for MongoGroupRepo {
+    async fn find_by_id(&self, group_id: ObjectId) -> Result<Option<Group>, Box<dyn Error>> {
+        Ok(self.collection.find_one(doc! { "_id": group_id }).await?)
+    }
+
+    async fn find_by_member(&self, user_id: ObjectId) -> Result<Vec<Group>, Box<dyn Error>> {
+        let cursor = self.collection.find(doc! { "members": user_id }).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_for_member(
+        &self,
+        group_id: ObjectId,
+        user_id: ObjectId,
+    ) -> Result<Option<Group>, Box<dyn Error>> {
+        let filter = doc! {
+            "_id": group_id,
+            "members": user_id
+        };
+
+        Ok(self.collection.find_one(filter).await?)
+    }
+
+    async fn insert(&self, group: Group) -> Result<Group, Box<dyn Error>> {
+        let result = self.collection.insert_one(&group).await?;
+        let mut created_group = group;
+        created_group.id = result.inserted_id.as_object_id();
+        Ok(created_group)
+    }
+
+    async fn rename(
+        &self,
+        group_id: ObjectId,
+        user_id: ObjectId,
+        name: String,
+    ) -> Result<Option<Group>, Box<dyn Error>> {
+        let filter = doc! {
+            "_id": group_id,
+            "$or": [
+                { "creator_id": user_id },
+                { "members": user_id }
+            ]
+        };
+
+        let update = doc! {
+            "$set": {
+                "name": name
+            }
+        };
+
+        Ok(self
+            .collection
+            .find_one_and_update(filter, update)
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn add_members(
+        &self,
+        group_id: ObjectId,
+        members: Vec<ObjectId>,
+    ) -> Result<Option<Group>, Box<dyn Error>> {
+        let update = doc! {
+            "$addToSet": {
+                "members": {
+                    "$each": members
+                }
+            }
+        };
+
+        Ok(self
+            .collection
+            .find_one_and_update(doc! { "_id": group_id }, update)
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn remove_member(
+        &self,
+        group_id: ObjectId,
+        member_id: ObjectId,
+    ) -> Result<Option<Group>, Box<dyn Error>> {
+        let filter = doc! {
+            "_id": group_id,
+            "members": member_id
+        };
+
+        let update = doc! {
+            "$pull": {
+                "members": member_id
+            }
+        };
+
+        Ok(self
+            .collection
+            .find_one_and_update(filter, update)
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryGroupRepo {
+    pub(super) groups: RwLock<Vec<Group>>,
+}
+
+#[async_trait(?Send)]
+impl GroupRepo for InMemoryGroupRepo {
+    async fn find_by_id(&self, group_id: ObjectId) -> Result<Option<Group>, Box<dyn Error>> {
+        let groups = self.groups.read().unwrap();
+        Ok(groups
+            .iter()
+            .find(|group| group.id == Some(group_id))
+            .cloned())
+    }
+
+    async fn find_by_member(&self, user_id: ObjectId) -> Result<Vec<Group>, Box<dyn Error>> {
+        let groups = self.groups.read().unwrap();
+        Ok(groups
+            .iter()
+            .filter(|group| group.members.contains(&user_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn find_for_member(
+        &self,
+        group_id: ObjectId,
+        user_id: ObjectId,
+    ) -> Result<Option<Group>, Box<dyn Error>> {
+        let groups = self.groups.read().unwrap();
+        Ok(groups
+            .iter()
+            .find(|group| group.id == Some(group_id) && group.members.contains(&user_id))
+            .cloned())
+    }
+
+    async fn insert(&self, group: Group) -> Result<Group, Box<dyn Error>> {
+        let mut groups = self.groups.write().unwrap();
+        let mut created_group = group;
+        created_group.id = Some(ObjectId::new());
+        groups.push(created_group.clone());
+        Ok(created_group)
+    }
+
+    async fn rename(
+        &self,
+        group_id: ObjectId,
+        user_id: ObjectId,
+        name: String,
+    ) -> Result<Option<Group>, Box<dyn Error>> {
+        let mut groups = self.groups.write().unwrap();
+        Ok(groups
+            .iter_mut()
+            .find(|group| {
+                group.id == Some(group_id)
+                    && (group.creator_id == user_id || group.members.contains(&user_id))
+            })
+            .map(|group| {
+                group.name = name;
+                group.clone()
+            }))
+    }
+
+    async fn add_members(
+        &self,
+        group_id: ObjectId,
+        members: Vec<ObjectId>,
+    ) -> Result<Option<Group>, Box<dyn Error>> {
+        let mut groups = self.groups.write().unwrap();
+        Ok(groups
+            .iter_mut()
+            .find(|group| group.id == Some(group_id))
+            .map(|group| {
+                for member in members {
+                    if !group.members.contains(&member) {
+                        group.members.push(member);
+                    }
+                }
+                group.clone()
+            }))
+    }
+
+    async fn remove_member(
+        &self,
+        group_id: ObjectId,
+        member_id: ObjectId,
+    ) -> Result<Option<Group>, Box<dyn Error>> {
+        let mut groups = self.groups.write().unwrap();
+        Ok(groups
+            .iter_mut()
+            .find(|group| group.id == Some(group_id) && group.members.contains(&member_id))
+            .map(|group| {
+                group.members.retain(|member| *member != member_id);
+                group.clone()
+            }))
+    }
+}
+
+// =============================================================================================================================
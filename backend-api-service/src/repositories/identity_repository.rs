@@ -0,0 +1,86 @@
+use crate::models::oidc_model::Identity;
+use async_trait::async_trait;
+use mongodb::{Collection, Database, bson::doc};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "identities";
+
+// =============================================================================================================================
+
+#[async_trait(?Send)]
+pub trait IdentityRepo: Send + Sync {
+    async fn find(&self, provider: &str, subject: &str)
+    -> Result<Option<Identity>, Box<dyn Error>>;
+
+    async fn insert(&self, identity: Identity) -> Result<(), Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoIdentityRepo {
+    collection: Collection<Identity>,
+}
+
+impl MongoIdentityRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl IdentityRepo for MongoIdentityRepo {
+    async fn find(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<Identity>, Box<dyn Error>> {
+        Ok(self
+            .collection
+            .find_one(doc! { "provider": provider, "subject": subject })
+            .await?)
+    }
+
+    async fn insert(&self, identity: Identity) -> Result<(), Box<dyn Error>> {
+        self.collection.insert_one(&identity).await?;
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryIdentityRepo {
+    pub(super) identities: RwLock<Vec<Identity>>,
+}
+
+#[async_trait(?Send)]
+impl IdentityRepo for InMemoryIdentityRepo {
+    async fn find(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<Identity>, Box<dyn Error>> {
+        let identities = self.identities.read().unwrap();
+        Ok(identities
+            .iter()
+            .find(|identity| identity.provider == provider && identity.subject == subject)
+            .cloned())
+    }
+
+    async fn insert(&self, identity: Identity) -> Result<(), Box<dyn Error>> {
+        let mut identities = self.identities.write().unwrap();
+        if identities.iter().any(|existing| {
+            existing.provider == identity.provider && existing.subject == identity.subject
+        }) {
+            return Err("This identity is already linked".into());
+        }
+        identities.push(identity);
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
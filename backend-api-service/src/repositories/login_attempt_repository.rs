@@ -0,0 +1,132 @@
+use crate::models::auth_model::LoginAttempts;
+use async_trait::async_trait;
+use bson::{oid::ObjectId, to_bson};
+use chrono::{DateTime, Utc};
+use mongodb::{Collection, Database, bson::doc, options::ReturnDocument};
+use std::{collections::HashMap, error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "login_attempts";
+
+// =============================================================================================================================
+
+#[async_trait(?Send)]
+pub trait LoginAttemptRepo: Send + Sync {
+    async fn find(&self, user_id: ObjectId) -> Result<Option<LoginAttempts>, Box<dyn Error>>;
+
+    /// Counts a failed login. The `threshold`-th consecutive failure locks the account until `locked_until` and
+    /// starts a new count; the returned attempts carry the lock when this failure set it.
+    async fn record_failure(
+        &self,
+        user_id: ObjectId,
+        threshold: u32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<LoginAttempts, Box<dyn Error>>;
+
+    async fn clear(&self, user_id: ObjectId) -> Result<(), Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoLoginAttemptRepo {
+    collection: Collection<LoginAttempts>,
+}
+
+impl MongoLoginAttemptRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl LoginAttemptRepo for MongoLoginAttemptRepo {
+    async fn find(&self, user_id: ObjectId) -> Result<Option<LoginAttempts>, Box<dyn Error>> {
+        Ok(self.collection.find_one(doc! { "_id": user_id }).await?)
+    }
+
+    async fn record_failure(
+        &self,
+        user_id: ObjectId,
+        threshold: u32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<LoginAttempts, Box<dyn Error>> {
+        let attempts = self
+            .collection
+            .find_one_and_update(
+                doc! { "_id": user_id },
+                doc! {
+                    "$inc": { "failures": 1 },
+                    "$setOnInsert": { "locked_until": null }
+                },
+            )
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .await?
+            .ok_or("Failed to record the login attempt")?;
+
+        if attempts.failures < threshold {
+            return Ok(attempts);
+        }
+
+        // Only the request that reached the threshold resets the count, concurrent ones keep incrementing it.
+        Ok(self
+            .collection
+            .find_one_and_update(
+                doc! { "_id": user_id, "failures": { "$gte": threshold } },
+                doc! { "$set": { "failures": 0, "locked_until": to_bson(&locked_until)? } },
+            )
+            .return_document(ReturnDocument::After)
+            .await?
+            .unwrap_or(attempts))
+    }
+
+    async fn clear(&self, user_id: ObjectId) -> Result<(), Box<dyn Error>> {
+        self.collection.delete_one(doc! { "_id": user_id }).await?;
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryLoginAttemptRepo {
+    pub(super) attempts: RwLock<HashMap<ObjectId, LoginAttempts>>,
+}
+
+#[async_trait(?Send)]
+impl LoginAttemptRepo for InMemoryLoginAttemptRepo {
+    async fn find(&self, user_id: ObjectId) -> Result<Option<LoginAttempts>, Box<dyn Error>> {
+        Ok(self.attempts.read().unwrap().get(&user_id).cloned())
+    }
+
+    async fn record_failure(
+        &self,
+        user_id: ObjectId,
+        threshold: u32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<LoginAttempts, Box<dyn Error>> {
+        let mut attempts = self.attempts.write().unwrap();
+        let entry = attempts.entry(user_id).or_insert(LoginAttempts {
+            user_id,
+            failures: 0,
+            locked_until: None,
+        });
+
+        entry.failures += 1;
+        if entry.failures >= threshold {
+            entry.failures = 0;
+            entry.locked_until = Some(locked_until);
+        }
+        Ok(entry.clone())
+    }
+
+    async fn clear(&self, user_id: ObjectId) -> Result<(), Box<dyn Error>> {
+        self.attempts.write().unwrap().remove(&user_id);
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,323 @@
+use crate::models::media_model::MediaRecord;
+use async_trait::async_trait;
+use bson::{Bson, oid::ObjectId};
+use futures_util::TryStreamExt;
+use mongodb::{
+    Collection, Database,
+    bson::doc,
+    error::{ErrorKind, WriteFailure},
+    options::ReturnDocument,
+};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "media";
+
+// Server error code for a write that breaks a unique index.
+const DUPLICATE_KEY: i32 = 11000;
+
+// =============================================================================================================================
+
+/// Stored media files. A record counts the stories and messages that reference it, plus one while it is in the
+/// owner's library; the files go once nothing references them anymore.
+#[async_trait(?Send)]
+pub trait MediaRepo: Send + Sync {
+    /// None when another record already holds the key of `media`.
+    async fn insert(&self, media: MediaRecord) -> Result<Option<MediaRecord>, Box<dyn Error>>;
+
+    async fn find_by_owner(&self, owner_id: ObjectId) -> Result<Vec<MediaRecord>, Box<dyn Error>>;
+
+    /// The media, provided `owner_id` uploaded it.
+    async fn find_for_owner(
+        &self,
+        id: ObjectId,
+        owner_id: ObjectId,
+    ) -> Result<Option<MediaRecord>, Box<dyn Error>>;
+
+    /// Counts one more reference to a media of the owner and returns it updated.
+    async fn add_reference(
+        &self,
+        id: ObjectId,
+        owner_id: ObjectId,
+    ) -> Result<Option<MediaRecord>, Box<dyn Error>>;
+
+    /// Counts one reference less, never going below zero.
+    async fn remove_reference(&self, id: ObjectId) -> Result<(), Box<dyn Error>>;
+
+    /// Takes a media of the owner out of their library, dropping the reference the library held.
+    async fn remove_from_library(
+        &self,
+        id: ObjectId,
+        owner_id: ObjectId,
+    ) -> Result<Option<MediaRecord>, Box<dyn Error>>;
+
+    /// Deletes the record if nothing references it anymore and returns it, so its files can be deleted.
+    async fn delete_unreferenced(
+        &self,
+        id: ObjectId,
+    ) -> Result<Option<MediaRecord>, Box<dyn Error>>;
+
+    /// Deletes the record whatever references it and returns it.
+    async fn delete(&self, id: ObjectId) -> Result<Option<MediaRecord>, Box<dyn Error>>;
+
+    /// The sizes of the media of the owner, added up.
+    async fn used_bytes(&self, owner_id: ObjectId) -> Result<i64, Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoMediaRepo {
+    collection: Collection<MediaRecord>,
+}
+
+impl MongoMediaRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl MediaRepo for MongoMediaRepo {
+    async fn insert(&self, media: MediaRecord) -> Result<Option<MediaRecord>, Box<dyn Error>> {
+        let result = match self.collection.insert_one(&media).await {
+            Ok(result) => result,
+            Err(e) => match *e.kind {
+                ErrorKind::Write(WriteFailure::WriteError(ref error))
+                    if error.code == DUPLICATE_KEY =>
+                {
+                    return Ok(None);
+                }
+                _ => return Err(e.into()),
+            },
+        };
+        let mut created_media = media;
+        created_media.id = result.inserted_id.as_object_id();
+        Ok(Some(created_media))
+    }
+
+    async fn find_by_owner(&self, owner_id: ObjectId) -> Result<Vec<MediaRecord>, Box<dyn Error>> {
+        let cursor = self.collection.find(doc! { "owner_id": owner_id }).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_for_owner(
+        &self,
+        id: ObjectId,
+        owner_id: ObjectId,
+    ) -> Result<Option<MediaRecord>, Box<dyn Error>> {
+        let filter = doc! {
+            "_id": id,
+            "owner_id": owner_id
+        };
+
+        Ok(self.collection.find_one(filter).await?)
+    }
+
+    async fn add_reference(
+        &self,
+        id: ObjectId,
+        owner_id: ObjectId,
+    ) -> Result<Option<MediaRecord>, Box<dyn Error>> {
+        let filter = doc! {
+            "_id": id,
+            "owner_id": owner_id
+        };
+
+        Ok(self
+            .collection
+            .find_one_and_update(filter, doc! { "$inc": { "ref_count": 1 } })
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn remove_reference(&self, id: ObjectId) -> Result<(), Box<dyn Error>> {
+        self.collection
+            .update_one(
+                doc! { "_id": id, "ref_count": { "$gt": 0 } },
+                doc! { "$inc": { "ref_count": -1 } },
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_from_library(
+        &self,
+        id: ObjectId,
+        owner_id: ObjectId,
+    ) -> Result<Option<MediaRecord>, Box<dyn Error>> {
+        let filter = doc! {
+            "_id": id,
+            "owner_id": owner_id,
+            "in_library": true
+        };
+        let update = doc! {
+            "$set": { "in_library": false },
+            "$inc": { "ref_count": -1 }
+        };
+
+        Ok(self
+            .collection
+            .find_one_and_update(filter, update)
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn delete_unreferenced(
+        &self,
+        id: ObjectId,
+    ) -> Result<Option<MediaRecord>, Box<dyn Error>> {
+        let filter = doc! {
+            "_id": id,
+            "ref_count": { "$lte": 0 },
+            "in_library": false
+        };
+
+        Ok(self.collection.find_one_and_delete(filter).await?)
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<Option<MediaRecord>, Box<dyn Error>> {
+        Ok(self
+            .collection
+            .find_one_and_delete(doc! { "_id": id })
+            .await?)
+    }
+
+    async fn used_bytes(&self, owner_id: ObjectId) -> Result<i64, Box<dyn Error>> {
+        let pipeline = vec![
+            doc! { "$match": { "owner_id": owner_id } },
+            doc! { "$group": { "_id": null, "used_bytes": { "$sum": "$size" } } },
+        ];
+
+        let mut cursor = self.collection.aggregate(pipeline).await?;
+        match cursor.try_next().await? {
+            Some(result) => Ok(match result.get("used_bytes") {
+                Some(Bson::Int32(value)) => *value as i64,
+                Some(Bson::Int64(value)) => *value,
+                Some(Bson::Double(value)) => *value as i64,
+                _ => 0,
+            }),
+            None => Ok(0),
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryMediaRepo {
+    media: RwLock<Vec<MediaRecord>>,
+}
+
+impl InMemoryMediaRepo {
+    fn remove_where(&self, predicate: impl Fn(&MediaRecord) -> bool) -> Option<MediaRecord> {
+        let mut media = self.media.write().unwrap();
+        media
+            .iter()
+            .position(predicate)
+            .map(|index| media.remove(index))
+    }
+}
+
+#[async_trait(?Send)]
+impl MediaRepo for InMemoryMediaRepo {
+    async fn insert(&self, media: MediaRecord) -> Result<Option<MediaRecord>, Box<dyn Error>> {
+        let mut stored = self.media.write().unwrap();
+        if stored.iter().any(|existing| existing.key == media.key) {
+            return Ok(None);
+        }
+        let mut created_media = media;
+        created_media.id = Some(ObjectId::new());
+        stored.push(created_media.clone());
+        Ok(Some(created_media))
+    }
+
+    async fn find_by_owner(&self, owner_id: ObjectId) -> Result<Vec<MediaRecord>, Box<dyn Error>> {
+        let media = self.media.read().unwrap();
+        Ok(media
+            .iter()
+            .filter(|media| media.owner_id == owner_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_for_owner(
+        &self,
+        id: ObjectId,
+        owner_id: ObjectId,
+    ) -> Result<Option<MediaRecord>, Box<dyn Error>> {
+        let media = self.media.read().unwrap();
+        Ok(media
+            .iter()
+            .find(|media| media.id == Some(id) && media.owner_id == owner_id)
+            .cloned())
+    }
+
+    async fn add_reference(
+        &self,
+        id: ObjectId,
+        owner_id: ObjectId,
+    ) -> Result<Option<MediaRecord>, Box<dyn Error>> {
+        let mut media = self.media.write().unwrap();
+        Ok(media
+            .iter_mut()
+            .find(|media| media.id == Some(id) && media.owner_id == owner_id)
+            .map(|media| {
+                media.ref_count += 1;
+                media.clone()
+            }))
+    }
+
+    async fn remove_reference(&self, id: ObjectId) -> Result<(), Box<dyn Error>> {
+        let mut media = self.media.write().unwrap();
+        if let Some(media) = media
+            .iter_mut()
+            .find(|media| media.id == Some(id) && media.ref_count > 0)
+        {
+            media.ref_count -= 1;
+        }
+        Ok(())
+    }
+
+    async fn remove_from_library(
+        &self,
+        id: ObjectId,
+        owner_id: ObjectId,
+    ) -> Result<Option<MediaRecord>, Box<dyn Error>> {
+        let mut media = self.media.write().unwrap();
+        Ok(media
+            .iter_mut()
+            .find(|media| media.id == Some(id) && media.owner_id == owner_id && media.in_library)
+            .map(|media| {
+                media.in_library = false;
+                media.ref_count -= 1;
+                media.clone()
+            }))
+    }
+
+    async fn delete_unreferenced(
+        &self,
+        id: ObjectId,
+    ) -> Result<Option<MediaRecord>, Box<dyn Error>> {
+        Ok(self.remove_where(|media| {
+            media.id == Some(id) && media.ref_count <= 0 && !media.in_library
+        }))
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<Option<MediaRecord>, Box<dyn Error>> {
+        Ok(self.remove_where(|media| media.id == Some(id)))
+    }
+
+    async fn used_bytes(&self, owner_id: ObjectId) -> Result<i64, Box<dyn Error>> {
+        let media = self.media.read().unwrap();
+        Ok(media
+            .iter()
+            .filter(|media| media.owner_id == owner_id)
+            .map(|media| media.size)
+            .sum())
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,252 @@
+use crate::models::message_model::Message;
+use async_trait::async_trait;
+use bson::oid::ObjectId;
+use futures_util::TryStreamExt;
+use mongodb::{Collection, Database, bson::doc};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "messages";
+
+// =============================================================================================================================
+
+#[async_trait(?Send)]
+pub trait MessageRepo: Send + Sync {
+    /// Direct messages exchanged between two users, newest first.
+    async fn find_direct(
+        &self,
+        user_id: ObjectId,
+        other_id: ObjectId,
+        limit: i64,
+        skip: u64,
+    ) -> Result<Vec<Message>, Box<dyn Error>>;
+
+    /// Messages posted to a group, newest first.
+    async fn find_group(
+        &self,
+        group_id: ObjectId,
+        limit: i64,
+        skip: u64,
+    ) -> Result<Vec<Message>, Box<dyn Error>>;
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Message>, Box<dyn Error>>;
+
+    /// Every message the user sent, direct or to a group, oldest first.
+    async fn find_by_sender(&self, sender_id: ObjectId) -> Result<Vec<Message>, Box<dyn Error>>;
+
+    async fn insert(&self, message: Message) -> Result<Message, Box<dyn Error>>;
+
+    /// Deletes the message, provided `sender_id` is the user who sent it.
+    async fn delete_by_sender(
+        &self,
+        message_id: ObjectId,
+        sender_id: ObjectId,
+    ) -> Result<Option<Message>, Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoMessageRepo {
+    collection: Collection<Message>,
+}
+
+impl MongoMessageRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl MessageRepo for MongoMessageRepo {
+    async fn find_direct(
+        &self,
+        user_id: ObjectId,
+        other_id: ObjectId,
+        limit: i64,
+        skip: u64,
+    ) -> Result<Vec<Message>, Box<dyn Error>> {
+        let filter = doc! {
+            "is_group": false,
+            "$or": [
+                {
+                    "sender_id": user_id,
+                    "recipient_id": other_id
+                },
+                {
+                    "sender_id": other_id,
+                    "recipient_id": user_id
+                }
+            ]
+        };
+
+        let cursor = self
+            .collection
+            .find(filter)
+            .sort(doc! { "_id": -1 })
+            .limit(limit)
+            .skip(skip)
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_group(
+        &self,
+        group_id: ObjectId,
+        limit: i64,
+        skip: u64,
+    ) -> Result<Vec<Message>, Box<dyn Error>> {
+        let filter = doc! {
+            "is_group": true,
+            "recipient_id": group_id
+        };
+
+        let cursor = self
+            .collection
+            .find(filter)
+            .sort(doc! { "_id": -1 })
+            .limit(limit)
+            .skip(skip)
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Message>, Box<dyn Error>> {
+        Ok(self.collection.find_one(doc! { "_id": id }).await?)
+    }
+
+    async fn find_by_sender(&self, sender_id: ObjectId) -> Result<Vec<Message>, Box<dyn Error>> {
+        let cursor = self
+            .collection
+            .find(doc! { "sender_id": sender_id })
+            .sort(doc! { "_id": 1 })
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn insert(&self, message: Message) -> Result<Message, Box<dyn Error>> {
+        let result = self.collection.insert_one(&message).await?;
+        let mut created_message = message;
+        created_message.id = result.inserted_id.as_object_id();
+        Ok(created_message)
+    }
+
+    async fn delete_by_sender(
+        &self,
+        message_id: ObjectId,
+        sender_id: ObjectId,
+    ) -> Result<Option<Message>, Box<dyn Error>> {
+        let filter = doc! {
+            "_id": message_id,
+            "sender_id": sender_id
+        };
+
+        Ok(self.collection.find_one_and_delete(filter).await?)
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryMessageRepo {
+    pub(super) messages: RwLock<Vec<Message>>,
+}
+
+impl InMemoryMessageRepo {
+    fn find_newest_first(
+        &self,
+        predicate: impl Fn(&Message) -> bool,
+        limit: i64,
+        skip: u64,
+    ) -> Vec<Message> {
+        let messages = self.messages.read().unwrap();
+        // ObjectIds are generated in increasing order, so the insertion order is the `_id` order.
+        messages
+            .iter()
+            .rev()
+            .filter(|message| predicate(message))
+            .skip(skip as usize)
+            .take(if limit > 0 {
+                limit as usize
+            } else {
+                usize::MAX
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+#[async_trait(?Send)]
+impl MessageRepo for InMemoryMessageRepo {
+    async fn find_direct(
+        &self,
+        user_id: ObjectId,
+        other_id: ObjectId,
+        limit: i64,
+        skip: u64,
+    ) -> Result<Vec<Message>, Box<dyn Error>> {
+        Ok(self.find_newest_first(
+            |message| {
+                !message.is_group
+                    && ((message.sender_id == user_id && message.recipient_id == other_id)
+                        || (message.sender_id == other_id && message.recipient_id == user_id))
+            },
+            limit,
+            skip,
+        ))
+    }
+
+    async fn find_group(
+        &self,
+        group_id: ObjectId,
+        limit: i64,
+        skip: u64,
+    ) -> Result<Vec<Message>, Box<dyn Error>> {
+        Ok(self.find_newest_first(
+            |message| message.is_group && message.recipient_id == group_id,
+            limit,
+            skip,
+        ))
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Message>, Box<dyn Error>> {
+        let messages = self.messages.read().unwrap();
+        Ok(messages
+            .iter()
+            .find(|message| message.id == Some(id))
+            .cloned())
+    }
+
+    async fn find_by_sender(&self, sender_id: ObjectId) -> Result<Vec<Message>, Box<dyn Error>> {
+        let messages = self.messages.read().unwrap();
+        Ok(messages
+            .iter()
+            .filter(|message| message.sender_id == sender_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn insert(&self, message: Message) -> Result<Message, Box<dyn Error>> {
+        let mut messages = self.messages.write().unwrap();
+        let mut created_message = message;
+        created_message.id = Some(ObjectId::new());
+        messages.push(created_message.clone());
+        Ok(created_message)
+    }
+
+    async fn delete_by_sender(
+        &self,
+        message_id: ObjectId,
+        sender_id: ObjectId,
+    ) -> Result<Option<Message>, Box<dyn Error>> {
+        let mut messages = self.messages.write().unwrap();
+        Ok(messages
+            .iter()
+            .position(|message| message.id == Some(message_id) && message.sender_id == sender_id)
+            .map(|index| messages.remove(index)))
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,181 @@
+use audit_event_repository::{AuditEventRepo, InMemoryAuditEventRepo, MongoAuditEventRepo};
+use auth_token_repository::{AuthTokenRepo, InMemoryAuthTokenRepo, MongoAuthTokenRepo};
+use deletion_repository::{DeletionRepo, InMemoryDeletionRepo, MongoDeletionRepo};
+use export_repository::{ExportRepo, InMemoryExportRepo, MongoExportRepo};
+use friend_repository::{FriendRepo, InMemoryFriendRepo, MongoFriendRepo};
+use group_repository::{GroupRepo, InMemoryGroupRepo, MongoGroupRepo};
+use identity_repository::{IdentityRepo, InMemoryIdentityRepo, MongoIdentityRepo};
+use login_attempt_repository::{InMemoryLoginAttemptRepo, LoginAttemptRepo, MongoLoginAttemptRepo};
+use media_repository::{InMemoryMediaRepo, MediaRepo, MongoMediaRepo};
+use message_repository::{InMemoryMessageRepo, MessageRepo, MongoMessageRepo};
+use moderation_action_repository::{
+    InMemoryModerationActionRepo, ModerationActionRepo, MongoModerationActionRepo,
+};
+use mongodb::Database;
+use oidc_login_repository::{InMemoryOidcLoginRepo, MongoOidcLoginRepo, OidcLoginRepo};
+use pending_upload_repository::{
+    InMemoryPendingUploadRepo, MongoPendingUploadRepo, PendingUploadRepo,
+};
+use report_repository::{InMemoryReportRepo, MongoReportRepo, ReportRepo};
+use session_repository::{InMemorySessionRepo, MongoSessionRepo, SessionRepo};
+use std::sync::Arc;
+use storage_quota_repository::{InMemoryStorageQuotaRepo, MongoStorageQuotaRepo, StorageQuotaRepo};
+use story_repository::{InMemoryStoryRepo, MongoStoryRepo, StoryRepo};
+use two_factor_repository::{InMemoryTwoFactorRepo, MongoTwoFactorRepo, TwoFactorRepo};
+use upload_session_repository::{
+    InMemoryUploadSessionRepo, MongoUploadSessionRepo, UploadSessionRepo,
+};
+use user_repository::{InMemoryUserRepo, MongoUserRepo, UserRepo};
+
+pub mod audit_event_repository;
+pub mod auth_token_repository;
+pub mod deletion_repository;
+pub mod export_repository;
+pub mod friend_repository;
+pub mod group_repository;
+pub mod identity_repository;
+pub mod login_attempt_repository;
+pub mod media_repository;
+pub mod message_repository;
+pub mod moderation_action_repository;
+pub mod oidc_login_repository;
+pub mod pending_upload_repository;
+pub mod report_repository;
+pub mod session_repository;
+pub mod storage_quota_repository;
+pub mod story_repository;
+pub mod two_factor_repository;
+pub mod upload_session_repository;
+pub mod user_repository;
+
+// =============================================================================================================================
+
+/// Typed access to the users, friends, groups, messages, stories, media, upload sessions, pending uploads, storage
+/// quotas, exports, login attempts, auth tokens, two-factor, sessions, identities, OIDC logins, reports, moderation actions and audit events
+/// collections, and to the deletions cascading over them. Services depend on the traits only, so they run against
+/// MongoDB in production and against the in-memory implementations in tests.
+#[derive(Clone)]
+pub struct Repositories {
+    pub users: Arc<dyn UserRepo>,
+    pub friends: Arc<dyn FriendRepo>,
+    pub groups: Arc<dyn GroupRepo>,
+    pub messages: Arc<dyn MessageRepo>,
+    pub stories: Arc<dyn StoryRepo>,
+    pub media: Arc<dyn MediaRepo>,
+    pub upload_sessions: Arc<dyn UploadSessionRepo>,
+    pub pending_uploads: Arc<dyn PendingUploadRepo>,
+    pub storage_quotas: Arc<dyn StorageQuotaRepo>,
+    pub exports: Arc<dyn ExportRepo>,
+    pub deletions: Arc<dyn DeletionRepo>,
+    pub login_attempts: Arc<dyn LoginAttemptRepo>,
+    pub auth_tokens: Arc<dyn AuthTokenRepo>,
+    pub two_factor: Arc<dyn TwoFactorRepo>,
+    pub sessions: Arc<dyn SessionRepo>,
+    pub identities: Arc<dyn IdentityRepo>,
+    pub oidc_logins: Arc<dyn OidcLoginRepo>,
+    pub reports: Arc<dyn ReportRepo>,
+    pub moderation_actions: Arc<dyn ModerationActionRepo>,
+    pub audit_events: Arc<dyn AuditEventRepo>,
+}
+
+impl Repositories {
+    pub fn mongo(db: &Database) -> Self {
+        Self {
+            users: Arc::new(MongoUserRepo::new(db)),
+            friends: Arc::new(MongoFriendRepo::new(db)),
+            groups: Arc::new(MongoGroupRepo::new(db)),
+            messages: Arc::new(MongoMessageRepo::new(db)),
+            stories: Arc::new(MongoStoryRepo::new(db)),
+            media: Arc::new(MongoMediaRepo::new(db)),
+            upload_sessions: Arc::new(MongoUploadSessionRepo::new(db)),
+            pending_uploads: Arc::new(MongoPendingUploadRepo::new(db)),
+            storage_quotas: Arc::new(MongoStorageQuotaRepo::new(db)),
+            exports: Arc::new(MongoExportRepo::new(db)),
+            deletions: Arc::new(MongoDeletionRepo::new(db)),
+            login_attempts: Arc::new(MongoLoginAttemptRepo::new(db)),
+            auth_tokens: Arc::new(MongoAuthTokenRepo::new(db)),
+            two_factor: Arc::new(MongoTwoFactorRepo::new(db)),
+            sessions: Arc::new(MongoSessionRepo::new(db)),
+            identities: Arc::new(MongoIdentityRepo::new(db)),
+            oidc_logins: Arc::new(MongoOidcLoginRepo::new(db)),
+            reports: Arc::new(MongoReportRepo::new(db)),
+            moderation_actions: Arc::new(MongoModerationActionRepo::new(db)),
+            audit_events: Arc::new(MongoAuditEventRepo::new(db)),
+        }
+    }
+
+    /// For tests: everything stays in process and is lost with the instance.
+    pub fn in_memory() -> Self {
+        let users = Arc::new(InMemoryUserRepo::default());
+        let friends = Arc::new(InMemoryFriendRepo::default());
+        let groups = Arc::new(InMemoryGroupRepo::default());
+        let messages = Arc::new(InMemoryMessageRepo::default());
+        let stories = Arc::new(InMemoryStoryRepo::default());
+        let sessions = Arc::new(InMemorySessionRepo::default());
+        let identities = Arc::new(InMemoryIdentityRepo::default());
+        let two_factor = Arc::new(InMemoryTwoFactorRepo::default());
+        let auth_tokens = Arc::new(InMemoryAuthTokenRepo::default());
+        let login_attempts = Arc::new(InMemoryLoginAttemptRepo::default());
+        let upload_sessions = Arc::new(InMemoryUploadSessionRepo::default());
+        let pending_uploads = Arc::new(InMemoryPendingUploadRepo::default());
+        let exports = Arc::new(InMemoryExportRepo::default());
+        let reports = Arc::new(InMemoryReportRepo::default());
+        let deletions = Arc::new(InMemoryDeletionRepo {
+            users: users.clone(),
+            friends: friends.clone(),
+            groups: groups.clone(),
+            messages: messages.clone(),
+            stories: stories.clone(),
+            sessions: sessions.clone(),
+            identities: identities.clone(),
+            two_factor: two_factor.clone(),
+            auth_tokens: auth_tokens.clone(),
+            login_attempts: login_attempts.clone(),
+            upload_sessions: upload_sessions.clone(),
+            pending_uploads: pending_uploads.clone(),
+            exports: exports.clone(),
+            reports: reports.clone(),
+        });
+
+        Self {
+            users,
+            friends,
+            groups,
+            messages,
+            stories,
+            media: Arc::new(InMemoryMediaRepo::default()),
+            upload_sessions,
+            pending_uploads,
+            storage_quotas: Arc::new(InMemoryStorageQuotaRepo::default()),
+            exports,
+            deletions,
+            login_attempts,
+            auth_tokens,
+            two_factor,
+            sessions,
+            identities,
+            oidc_logins: Arc::new(InMemoryOidcLoginRepo::default()),
+            reports,
+            moderation_actions: Arc::new(InMemoryModerationActionRepo::default()),
+            audit_events: Arc::new(InMemoryAuditEventRepo::default()),
+        }
+    }
+}
+
+// =============================================================================================================================
+
+const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+/// Great-circle distance between two `[longitude, latitude]` points, used by the in-memory
+/// implementations to emulate MongoDB `$near` queries.
+pub fn distance_meters(from: [f64; 2], to: [f64; 2]) -> f64 {
+    let (from_lng, from_lat) = (from[0].to_radians(), from[1].to_radians());
+    let (to_lng, to_lat) = (to[0].to_radians(), to[1].to_radians());
+
+    let a = ((to_lat - from_lat) / 2.0).sin().powi(2)
+        + from_lat.cos() * to_lat.cos() * ((to_lng - from_lng) / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_METERS * a.sqrt().asin()
+}
+
+// =============================================================================================================================
@@ -0,0 +1,97 @@
+use crate::models::moderation_model::ModerationAction;
+use async_trait::async_trait;
+use bson::oid::ObjectId;
+use futures_util::TryStreamExt;
+use mongodb::{Collection, Database, bson::doc};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "moderation_actions";
+
+// =============================================================================================================================
+
+/// The moderation log. Append only: there is no way to change or remove an entry.
+#[async_trait(?Send)]
+pub trait ModerationActionRepo: Send + Sync {
+    async fn insert(&self, action: ModerationAction) -> Result<(), Box<dyn Error>>;
+
+    /// The latest actions, newest first, optionally only those taken on one user, message or story.
+    async fn find_latest(
+        &self,
+        target_id: Option<ObjectId>,
+        limit: i64,
+    ) -> Result<Vec<ModerationAction>, Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoModerationActionRepo {
+    collection: Collection<ModerationAction>,
+}
+
+impl MongoModerationActionRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl ModerationActionRepo for MongoModerationActionRepo {
+    async fn insert(&self, action: ModerationAction) -> Result<(), Box<dyn Error>> {
+        self.collection.insert_one(action).await?;
+        Ok(())
+    }
+
+    async fn find_latest(
+        &self,
+        target_id: Option<ObjectId>,
+        limit: i64,
+    ) -> Result<Vec<ModerationAction>, Box<dyn Error>> {
+        let filter = match target_id {
+            Some(target_id) => doc! { "target_id": target_id },
+            None => doc! {},
+        };
+        let cursor = self
+            .collection
+            .find(filter)
+            .sort(doc! { "_id": -1 })
+            .limit(limit)
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryModerationActionRepo {
+    actions: RwLock<Vec<ModerationAction>>,
+}
+
+#[async_trait(?Send)]
+impl ModerationActionRepo for InMemoryModerationActionRepo {
+    async fn insert(&self, action: ModerationAction) -> Result<(), Box<dyn Error>> {
+        self.actions.write().unwrap().push(action);
+        Ok(())
+    }
+
+    async fn find_latest(
+        &self,
+        target_id: Option<ObjectId>,
+        limit: i64,
+    ) -> Result<Vec<ModerationAction>, Box<dyn Error>> {
+        let actions = self.actions.read().unwrap();
+        Ok(actions
+            .iter()
+            .rev()
+            .filter(|action| target_id.is_none_or(|target_id| action.target_id == target_id))
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,87 @@
+use crate::models::oidc_model::OidcLogin;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mongodb::{Collection, Database, bson::doc};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "oidc_logins";
+
+// =============================================================================================================================
+
+#[async_trait(?Send)]
+pub trait OidcLoginRepo: Send + Sync {
+    async fn insert(&self, login: OidcLogin) -> Result<(), Box<dyn Error>>;
+
+    /// Deletes the login and returns it, unless it had already expired at `now`.
+    async fn consume(
+        &self,
+        state_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<OidcLogin>, Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoOidcLoginRepo {
+    collection: Collection<OidcLogin>,
+}
+
+impl MongoOidcLoginRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl OidcLoginRepo for MongoOidcLoginRepo {
+    async fn insert(&self, login: OidcLogin) -> Result<(), Box<dyn Error>> {
+        self.collection.insert_one(&login).await?;
+        Ok(())
+    }
+
+    async fn consume(
+        &self,
+        state_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<OidcLogin>, Box<dyn Error>> {
+        let login = self
+            .collection
+            .find_one_and_delete(doc! { "_id": state_hash })
+            .await?;
+        Ok(login.filter(|login| login.expires_at > now))
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryOidcLoginRepo {
+    logins: RwLock<Vec<OidcLogin>>,
+}
+
+#[async_trait(?Send)]
+impl OidcLoginRepo for InMemoryOidcLoginRepo {
+    async fn insert(&self, login: OidcLogin) -> Result<(), Box<dyn Error>> {
+        self.logins.write().unwrap().push(login);
+        Ok(())
+    }
+
+    async fn consume(
+        &self,
+        state_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<OidcLogin>, Box<dyn Error>> {
+        let mut logins = self.logins.write().unwrap();
+        Ok(logins
+            .iter()
+            .position(|login| login.state_hash == state_hash)
+            .map(|index| logins.remove(index))
+            .filter(|login| login.expires_at > now))
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,255 @@
+use crate::models::media_model::PendingUpload;
+use async_trait::async_trait;
+use bson::{Bson, DateTime as BsonDateTime, Document, oid::ObjectId};
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{Collection, Database, bson::doc, options::ReturnDocument};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "pending_uploads";
+
+// =============================================================================================================================
+
+/// Objects presigned for upload that were not confirmed yet. A confirmation holds its upload until a deadline, so
+/// that a crashed confirmation does not hold it forever.
+#[async_trait(?Send)]
+pub trait PendingUploadRepo: Send + Sync {
+    async fn insert(&self, upload: PendingUpload) -> Result<PendingUpload, Box<dyn Error>>;
+
+    /// Holds the unexpired upload of `key` by `owner_id` until `until`, unless another confirmation holds it at `now`.
+    async fn claim(
+        &self,
+        owner_id: ObjectId,
+        key: &str,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<PendingUpload>, Box<dyn Error>>;
+
+    /// Lets another confirmation, or the sweeper, have the upload.
+    async fn release(&self, id: ObjectId) -> Result<(), Box<dyn Error>>;
+
+    async fn delete(&self, id: ObjectId) -> Result<(), Box<dyn Error>>;
+
+    async fn find_by_owner(&self, owner_id: ObjectId)
+    -> Result<Vec<PendingUpload>, Box<dyn Error>>;
+
+    /// Uploads expired at `now` that no confirmation holds.
+    async fn find_expired(&self, now: DateTime<Utc>) -> Result<Vec<PendingUpload>, Box<dyn Error>>;
+
+    /// Total size of the uploads of the owner that no confirmation holds at `now`.
+    async fn reserved_bytes(
+        &self,
+        owner_id: ObjectId,
+        now: DateTime<Utc>,
+    ) -> Result<i64, Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoPendingUploadRepo {
+    collection: Collection<PendingUpload>,
+}
+
+impl MongoPendingUploadRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl PendingUploadRepo for MongoPendingUploadRepo {
+    async fn insert(&self, upload: PendingUpload) -> Result<PendingUpload, Box<dyn Error>> {
+        let result = self.collection.insert_one(&upload).await?;
+        let mut created_upload = upload;
+        created_upload.id = result.inserted_id.as_object_id();
+        Ok(created_upload)
+    }
+
+    async fn claim(
+        &self,
+        owner_id: ObjectId,
+        key: &str,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<PendingUpload>, Box<dyn Error>> {
+        let mut filter = unclaimed(now);
+        filter.insert("owner_id", owner_id);
+        filter.insert("key", key);
+        filter.insert("expires_at", doc! { "$gt": BsonDateTime::from_chrono(now) });
+
+        Ok(self
+            .collection
+            .find_one_and_update(
+                filter,
+                doc! { "$set": { "confirming_until": BsonDateTime::from_chrono(until) } },
+            )
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn release(&self, id: ObjectId) -> Result<(), Box<dyn Error>> {
+        self.collection
+            .update_one(
+                doc! { "_id": id },
+                doc! { "$unset": { "confirming_until": "" } },
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<(), Box<dyn Error>> {
+        self.collection.delete_one(doc! { "_id": id }).await?;
+        Ok(())
+    }
+
+    async fn find_by_owner(
+        &self,
+        owner_id: ObjectId,
+    ) -> Result<Vec<PendingUpload>, Box<dyn Error>> {
+        let cursor = self.collection.find(doc! { "owner_id": owner_id }).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_expired(&self, now: DateTime<Utc>) -> Result<Vec<PendingUpload>, Box<dyn Error>> {
+        let mut filter = unclaimed(now);
+        filter.insert(
+            "expires_at",
+            doc! { "$lte": BsonDateTime::from_chrono(now) },
+        );
+
+        let cursor = self.collection.find(filter).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn reserved_bytes(
+        &self,
+        owner_id: ObjectId,
+        now: DateTime<Utc>,
+    ) -> Result<i64, Box<dyn Error>> {
+        let mut filter = unclaimed(now);
+        filter.insert("owner_id", owner_id);
+        let pipeline = vec![
+            doc! { "$match": filter },
+            doc! { "$group": { "_id": null, "reserved_bytes": { "$sum": "$size" } } },
+        ];
+
+        let mut cursor = self.collection.aggregate(pipeline).await?;
+        match cursor.try_next().await? {
+            Some(result) => Ok(match result.get("reserved_bytes") {
+                Some(Bson::Int32(value)) => *value as i64,
+                Some(Bson::Int64(value)) => *value,
+                Some(Bson::Double(value)) => *value as i64,
+                _ => 0,
+            }),
+            None => Ok(0),
+        }
+    }
+}
+
+/// Matches the uploads that no confirmation holds at `now`.
+fn unclaimed(now: DateTime<Utc>) -> Document {
+    doc! {
+        "$or": [
+            { "confirming_until": null },
+            { "confirming_until": { "$lte": BsonDateTime::from_chrono(now) } }
+        ]
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryPendingUploadRepo {
+    pub(super) uploads: RwLock<Vec<PendingUpload>>,
+}
+
+#[async_trait(?Send)]
+impl PendingUploadRepo for InMemoryPendingUploadRepo {
+    async fn insert(&self, upload: PendingUpload) -> Result<PendingUpload, Box<dyn Error>> {
+        let mut uploads = self.uploads.write().unwrap();
+        let mut created_upload = upload;
+        created_upload.id = Some(ObjectId::new());
+        uploads.push(created_upload.clone());
+        Ok(created_upload)
+    }
+
+    async fn claim(
+        &self,
+        owner_id: ObjectId,
+        key: &str,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<PendingUpload>, Box<dyn Error>> {
+        let mut uploads = self.uploads.write().unwrap();
+        Ok(uploads
+            .iter_mut()
+            .find(|upload| {
+                upload.owner_id == owner_id
+                    && upload.key == key
+                    && upload.expires_at > now
+                    && is_unclaimed(upload, now)
+            })
+            .map(|upload| {
+                upload.confirming_until = Some(until);
+                upload.clone()
+            }))
+    }
+
+    async fn release(&self, id: ObjectId) -> Result<(), Box<dyn Error>> {
+        let mut uploads = self.uploads.write().unwrap();
+        if let Some(upload) = uploads.iter_mut().find(|upload| upload.id == Some(id)) {
+            upload.confirming_until = None;
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<(), Box<dyn Error>> {
+        let mut uploads = self.uploads.write().unwrap();
+        uploads.retain(|upload| upload.id != Some(id));
+        Ok(())
+    }
+
+    async fn find_by_owner(
+        &self,
+        owner_id: ObjectId,
+    ) -> Result<Vec<PendingUpload>, Box<dyn Error>> {
+        let uploads = self.uploads.read().unwrap();
+        Ok(uploads
+            .iter()
+            .filter(|upload| upload.owner_id == owner_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_expired(&self, now: DateTime<Utc>) -> Result<Vec<PendingUpload>, Box<dyn Error>> {
+        let uploads = self.uploads.read().unwrap();
+        Ok(uploads
+            .iter()
+            .filter(|upload| upload.expires_at <= now && is_unclaimed(upload, now))
+            .cloned()
+            .collect())
+    }
+
+    async fn reserved_bytes(
+        &self,
+        owner_id: ObjectId,
+        now: DateTime<Utc>,
+    ) -> Result<i64, Box<dyn Error>> {
+        let uploads = self.uploads.read().unwrap();
+        Ok(uploads
+            .iter()
+            .filter(|upload| upload.owner_id == owner_id && is_unclaimed(upload, now))
+            .map(|upload| upload.size)
+            .sum())
+    }
+}
+
+fn is_unclaimed(upload: &PendingUpload, now: DateTime<Utc>) -> bool {
+    upload.confirming_until.is_none_or(|until| until <= now)
+}
+
+// =============================================================================================================================
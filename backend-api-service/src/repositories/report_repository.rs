@@ -0,0 +1,233 @@
+use crate::models::moderation_model::{Report, ReportStatus};
+use async_trait::async_trait;
+use bson::{oid::ObjectId, to_bson};
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{Collection, Database, bson::doc, options::ReturnDocument};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "reports";
+
+// =============================================================================================================================
+
+#[async_trait(?Send)]
+pub trait ReportRepo: Send + Sync {
+    async fn insert(&self, report: Report) -> Result<(), Box<dyn Error>>;
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Report>, Box<dyn Error>>;
+
+    /// Reports with the given status, oldest first, so the queue is reviewed in order.
+    async fn find_by_status(&self, status: ReportStatus) -> Result<Vec<Report>, Box<dyn Error>>;
+
+    /// Whether the user already has an open report of this user, message or story.
+    async fn has_open(
+        &self,
+        reporter_id: ObjectId,
+        target_id: ObjectId,
+    ) -> Result<bool, Box<dyn Error>>;
+
+    /// Closes the report with `status`, provided it is still open.
+    async fn review(
+        &self,
+        id: ObjectId,
+        status: ReportStatus,
+        reviewer_id: ObjectId,
+        note: Option<String>,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Report>, Box<dyn Error>>;
+
+    /// Closes every open report of the user, message or story with `status`, and returns how many were closed.
+    async fn review_target(
+        &self,
+        target_id: ObjectId,
+        status: ReportStatus,
+        reviewer_id: ObjectId,
+        now: DateTime<Utc>,
+    ) -> Result<u64, Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoReportRepo {
+    collection: Collection<Report>,
+}
+
+impl MongoReportRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl ReportRepo for MongoReportRepo {
+    async fn insert(&self, report: Report) -> Result<(), Box<dyn Error>> {
+        self.collection.insert_one(report).await?;
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Report>, Box<dyn Error>> {
+        Ok(self.collection.find_one(doc! { "_id": id }).await?)
+    }
+
+    async fn find_by_status(&self, status: ReportStatus) -> Result<Vec<Report>, Box<dyn Error>> {
+        let cursor = self
+            .collection
+            .find(doc! { "status": to_bson(&status)? })
+            .sort(doc! { "created_at": 1 })
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn has_open(
+        &self,
+        reporter_id: ObjectId,
+        target_id: ObjectId,
+    ) -> Result<bool, Box<dyn Error>> {
+        let filter = doc! {
+            "reporter_id": reporter_id,
+            "target_id": target_id,
+            "status": to_bson(&ReportStatus::Open)?
+        };
+        Ok(self.collection.count_documents(filter).await? > 0)
+    }
+
+    async fn review(
+        &self,
+        id: ObjectId,
+        status: ReportStatus,
+        reviewer_id: ObjectId,
+        note: Option<String>,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Report>, Box<dyn Error>> {
+        let filter = doc! { "_id": id, "status": to_bson(&ReportStatus::Open)? };
+        let update = doc! {
+            "$set": {
+                "status": to_bson(&status)?,
+                "reviewed_by": reviewer_id,
+                "reviewed_at": to_bson(&now)?,
+                "note": note
+            }
+        };
+
+        Ok(self
+            .collection
+            .find_one_and_update(filter, update)
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn review_target(
+        &self,
+        target_id: ObjectId,
+        status: ReportStatus,
+        reviewer_id: ObjectId,
+        now: DateTime<Utc>,
+    ) -> Result<u64, Box<dyn Error>> {
+        let filter = doc! { "target_id": target_id, "status": to_bson(&ReportStatus::Open)? };
+        let update = doc! {
+            "$set": {
+                "status": to_bson(&status)?,
+                "reviewed_by": reviewer_id,
+                "reviewed_at": to_bson(&now)?
+            }
+        };
+
+        Ok(self
+            .collection
+            .update_many(filter, update)
+            .await?
+            .modified_count)
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryReportRepo {
+    pub(super) reports: RwLock<Vec<Report>>,
+}
+
+#[async_trait(?Send)]
+impl ReportRepo for InMemoryReportRepo {
+    async fn insert(&self, report: Report) -> Result<(), Box<dyn Error>> {
+        self.reports.write().unwrap().push(report);
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Report>, Box<dyn Error>> {
+        let reports = self.reports.read().unwrap();
+        Ok(reports.iter().find(|report| report.id == id).cloned())
+    }
+
+    async fn find_by_status(&self, status: ReportStatus) -> Result<Vec<Report>, Box<dyn Error>> {
+        let reports = self.reports.read().unwrap();
+        let mut found: Vec<Report> = reports
+            .iter()
+            .filter(|report| report.status == status)
+            .cloned()
+            .collect();
+        found.sort_by_key(|report| report.created_at);
+        Ok(found)
+    }
+
+    async fn has_open(
+        &self,
+        reporter_id: ObjectId,
+        target_id: ObjectId,
+    ) -> Result<bool, Box<dyn Error>> {
+        let reports = self.reports.read().unwrap();
+        Ok(reports.iter().any(|report| {
+            report.reporter_id == reporter_id
+                && report.target_id == target_id
+                && report.status == ReportStatus::Open
+        }))
+    }
+
+    async fn review(
+        &self,
+        id: ObjectId,
+        status: ReportStatus,
+        reviewer_id: ObjectId,
+        note: Option<String>,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Report>, Box<dyn Error>> {
+        let mut reports = self.reports.write().unwrap();
+        Ok(reports
+            .iter_mut()
+            .find(|report| report.id == id && report.status == ReportStatus::Open)
+            .map(|report| {
+                report.status = status;
+                report.reviewed_by = Some(reviewer_id);
+                report.reviewed_at = Some(now);
+                report.note = note;
+                report.clone()
+            }))
+    }
+
+    async fn review_target(
+        &self,
+        target_id: ObjectId,
+        status: ReportStatus,
+        reviewer_id: ObjectId,
+        now: DateTime<Utc>,
+    ) -> Result<u64, Box<dyn Error>> {
+        let mut reports = self.reports.write().unwrap();
+        let mut closed = 0;
+        for report in reports
+            .iter_mut()
+            .filter(|report| report.target_id == target_id && report.status == ReportStatus::Open)
+        {
+            report.status = status;
+            report.reviewed_by = Some(reviewer_id);
+            report.reviewed_at = Some(now);
+            closed += 1;
+        }
+        Ok(closed)
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,175 @@
+use crate::models::session_model::Session;
+use async_trait::async_trait;
+use bson::{DateTime as BsonDateTime, oid::ObjectId, to_bson};
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{Collection, Database, bson::doc};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "sessions";
+
+// =============================================================================================================================
+
+#[async_trait(?Send)]
+pub trait SessionRepo: Send + Sync {
+    async fn insert(&self, session: Session) -> Result<(), Box<dyn Error>>;
+
+    async fn find(&self, id: ObjectId) -> Result<Option<Session>, Box<dyn Error>>;
+
+    /// Sessions of the user whose token has not expired at `now`, most recently used first.
+    async fn find_active(
+        &self,
+        user_id: ObjectId,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, Box<dyn Error>>;
+
+    async fn touch(&self, id: ObjectId, last_used: DateTime<Utc>) -> Result<(), Box<dyn Error>>;
+
+    /// Deletes a session of the user and returns whether it existed.
+    async fn delete(&self, user_id: ObjectId, id: ObjectId) -> Result<bool, Box<dyn Error>>;
+
+    /// Deletes every session of the user but `keep`, and returns how many were deleted.
+    async fn delete_all(
+        &self,
+        user_id: ObjectId,
+        keep: Option<ObjectId>,
+    ) -> Result<u64, Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoSessionRepo {
+    collection: Collection<Session>,
+}
+
+impl MongoSessionRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl SessionRepo for MongoSessionRepo {
+    async fn insert(&self, session: Session) -> Result<(), Box<dyn Error>> {
+        self.collection.insert_one(session).await?;
+        Ok(())
+    }
+
+    async fn find(&self, id: ObjectId) -> Result<Option<Session>, Box<dyn Error>> {
+        Ok(self.collection.find_one(doc! { "_id": id }).await?)
+    }
+
+    async fn find_active(
+        &self,
+        user_id: ObjectId,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, Box<dyn Error>> {
+        let filter = doc! {
+            "user_id": user_id,
+            "expires_at": { "$gt": BsonDateTime::from_chrono(now) }
+        };
+        let cursor = self
+            .collection
+            .find(filter)
+            .sort(doc! { "last_used": -1 })
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn touch(&self, id: ObjectId, last_used: DateTime<Utc>) -> Result<(), Box<dyn Error>> {
+        self.collection
+            .update_one(
+                doc! { "_id": id },
+                doc! { "$set": { "last_used": to_bson(&last_used)? } },
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, user_id: ObjectId, id: ObjectId) -> Result<bool, Box<dyn Error>> {
+        let result = self
+            .collection
+            .delete_one(doc! { "_id": id, "user_id": user_id })
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn delete_all(
+        &self,
+        user_id: ObjectId,
+        keep: Option<ObjectId>,
+    ) -> Result<u64, Box<dyn Error>> {
+        let mut filter = doc! { "user_id": user_id };
+        if let Some(keep) = keep {
+            filter.insert("_id", doc! { "$ne": keep });
+        }
+        Ok(self.collection.delete_many(filter).await?.deleted_count)
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemorySessionRepo {
+    pub(super) sessions: RwLock<Vec<Session>>,
+}
+
+#[async_trait(?Send)]
+impl SessionRepo for InMemorySessionRepo {
+    async fn insert(&self, session: Session) -> Result<(), Box<dyn Error>> {
+        self.sessions.write().unwrap().push(session);
+        Ok(())
+    }
+
+    async fn find(&self, id: ObjectId) -> Result<Option<Session>, Box<dyn Error>> {
+        let sessions = self.sessions.read().unwrap();
+        Ok(sessions.iter().find(|session| session.id == id).cloned())
+    }
+
+    async fn find_active(
+        &self,
+        user_id: ObjectId,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, Box<dyn Error>> {
+        let sessions = self.sessions.read().unwrap();
+        let mut active: Vec<Session> = sessions
+            .iter()
+            .filter(|session| session.user_id == user_id && session.expires_at > now)
+            .cloned()
+            .collect();
+        active.sort_by_key(|session| std::cmp::Reverse(session.last_used));
+        Ok(active)
+    }
+
+    async fn touch(&self, id: ObjectId, last_used: DateTime<Utc>) -> Result<(), Box<dyn Error>> {
+        let mut sessions = self.sessions.write().unwrap();
+        if let Some(session) = sessions.iter_mut().find(|session| session.id == id) {
+            session.last_used = last_used;
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, user_id: ObjectId, id: ObjectId) -> Result<bool, Box<dyn Error>> {
+        let mut sessions = self.sessions.write().unwrap();
+        let before = sessions.len();
+        sessions.retain(|session| !(session.id == id && session.user_id == user_id));
+        Ok(sessions.len() < before)
+    }
+
+    async fn delete_all(
+        &self,
+        user_id: ObjectId,
+        keep: Option<ObjectId>,
+    ) -> Result<u64, Box<dyn Error>> {
+        let mut sessions = self.sessions.write().unwrap();
+        let before = sessions.len();
+        sessions.retain(|session| session.user_id != user_id || Some(session.id) == keep);
+        Ok((before - sessions.len()) as u64)
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,109 @@
+use crate::models::{storage_model::StorageQuota, user_model::UserRole};
+use async_trait::async_trait;
+use bson::to_bson;
+use chrono::{DateTime, Utc};
+use mongodb::{Collection, Database, bson::doc, options::ReturnDocument};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "storage_quotas";
+
+// =============================================================================================================================
+
+/// Quotas set by the admins, one per role. Roles without one fall back to the configured default.
+#[async_trait(?Send)]
+pub trait StorageQuotaRepo: Send + Sync {
+    async fn find(&self, role: UserRole) -> Result<Option<StorageQuota>, Box<dyn Error>>;
+
+    /// Creates or replaces the quota of the role.
+    async fn save(
+        &self,
+        role: UserRole,
+        quota_bytes: i64,
+        updated_at: DateTime<Utc>,
+    ) -> Result<StorageQuota, Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoStorageQuotaRepo {
+    collection: Collection<StorageQuota>,
+}
+
+impl MongoStorageQuotaRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl StorageQuotaRepo for MongoStorageQuotaRepo {
+    async fn find(&self, role: UserRole) -> Result<Option<StorageQuota>, Box<dyn Error>> {
+        Ok(self
+            .collection
+            .find_one(doc! { "role": to_bson(&role)? })
+            .await?)
+    }
+
+    async fn save(
+        &self,
+        role: UserRole,
+        quota_bytes: i64,
+        updated_at: DateTime<Utc>,
+    ) -> Result<StorageQuota, Box<dyn Error>> {
+        let update = doc! {
+            "$set": {
+                "quota_bytes": quota_bytes,
+                "updated_at": to_bson(&updated_at)?
+            }
+        };
+
+        match self
+            .collection
+            .find_one_and_update(doc! { "role": to_bson(&role)? }, update)
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .await?
+        {
+            Some(quota) => Ok(quota),
+            None => Err("Failed to update the storage quota".into()),
+        }
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryStorageQuotaRepo {
+    quotas: RwLock<Vec<StorageQuota>>,
+}
+
+#[async_trait(?Send)]
+impl StorageQuotaRepo for InMemoryStorageQuotaRepo {
+    async fn find(&self, role: UserRole) -> Result<Option<StorageQuota>, Box<dyn Error>> {
+        let quotas = self.quotas.read().unwrap();
+        Ok(quotas.iter().find(|quota| quota.role == role).cloned())
+    }
+
+    async fn save(
+        &self,
+        role: UserRole,
+        quota_bytes: i64,
+        updated_at: DateTime<Utc>,
+    ) -> Result<StorageQuota, Box<dyn Error>> {
+        let mut quotas = self.quotas.write().unwrap();
+        let quota = StorageQuota {
+            role,
+            quota_bytes,
+            updated_at: Some(updated_at),
+        };
+        quotas.retain(|existing| existing.role != role);
+        quotas.push(quota.clone());
+        Ok(quota)
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,220 @@
+use crate::{models::story_model::Story, repositories::distance_meters};
+use async_trait::async_trait;
+use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{Collection, Database, bson::doc};
+use std::{cmp::Reverse, error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "stories";
+
+// =============================================================================================================================
+
+#[async_trait(?Send)]
+pub trait StoryRepo: Send + Sync {
+    /// Stories posted by any of the users that are still live at `now`, latest expiry first.
+    async fn find_active_by_users(
+        &self,
+        user_ids: &[ObjectId],
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Story>, Box<dyn Error>>;
+
+    /// Stories live at `now` within `radius` meters of `coordinates` (`[longitude, latitude]`),
+    /// latest expiry first.
+    async fn find_active_nearby(
+        &self,
+        coordinates: [f64; 2],
+        radius: f64,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Story>, Box<dyn Error>>;
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Story>, Box<dyn Error>>;
+
+    /// Every story the user posted, expired or not, oldest first.
+    async fn find_by_user(&self, user_id: ObjectId) -> Result<Vec<Story>, Box<dyn Error>>;
+
+    async fn insert(&self, story: Story) -> Result<Story, Box<dyn Error>>;
+
+    /// Deletes the story, provided `user_id` is the user who posted it.
+    async fn delete_by_owner(
+        &self,
+        story_id: ObjectId,
+        user_id: ObjectId,
+    ) -> Result<Option<Story>, Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoStoryRepo {
+    collection: Collection<Story>,
+}
+
+impl MongoStoryRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl StoryRepo for MongoStoryRepo {
+    async fn find_active_by_users(
+        &self,
+        user_ids: &[ObjectId],
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Story>, Box<dyn Error>> {
+        let filter = doc! {
+            "user_id": { "$in": user_ids },
+            "expires_at": { "$gt": now }
+        };
+
+        let cursor = self
+            .collection
+            .find(filter)
+            .sort(doc! { "expires_at": -1 })
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_active_nearby(
+        &self,
+        coordinates: [f64; 2],
+        radius: f64,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Story>, Box<dyn Error>> {
+        let filter = doc! {
+            "location": {
+                "$near": {
+                    "$geometry": {
+                        "type": "Point",
+                        "coordinates": coordinates.to_vec()
+                    },
+                    "$maxDistance": radius
+                }
+            },
+            "expires_at": { "$gt": now }
+        };
+
+        let cursor = self
+            .collection
+            .find(filter)
+            .sort(doc! { "expires_at": -1 })
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Story>, Box<dyn Error>> {
+        Ok(self.collection.find_one(doc! { "_id": id }).await?)
+    }
+
+    async fn find_by_user(&self, user_id: ObjectId) -> Result<Vec<Story>, Box<dyn Error>> {
+        let cursor = self
+            .collection
+            .find(doc! { "user_id": user_id })
+            .sort(doc! { "_id": 1 })
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn insert(&self, story: Story) -> Result<Story, Box<dyn Error>> {
+        let result = self.collection.insert_one(&story).await?;
+        let mut created_story = story;
+        created_story.id = result.inserted_id.as_object_id();
+        Ok(created_story)
+    }
+
+    async fn delete_by_owner(
+        &self,
+        story_id: ObjectId,
+        user_id: ObjectId,
+    ) -> Result<Option<Story>, Box<dyn Error>> {
+        let filter = doc! {
+            "_id": story_id,
+            "user_id": user_id
+        };
+
+        Ok(self.collection.find_one_and_delete(filter).await?)
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryStoryRepo {
+    pub(super) stories: RwLock<Vec<Story>>,
+}
+
+impl InMemoryStoryRepo {
+    fn find_active(&self, predicate: impl Fn(&Story) -> bool, now: DateTime<Utc>) -> Vec<Story> {
+        let stories = self.stories.read().unwrap();
+        let mut active: Vec<Story> = stories
+            .iter()
+            .filter(|story| story.expires_at > now && predicate(story))
+            .cloned()
+            .collect();
+        active.sort_by_key(|story| Reverse(story.expires_at));
+        active
+    }
+}
+
+#[async_trait(?Send)]
+impl StoryRepo for InMemoryStoryRepo {
+    async fn find_active_by_users(
+        &self,
+        user_ids: &[ObjectId],
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Story>, Box<dyn Error>> {
+        Ok(self.find_active(|story| user_ids.contains(&story.user_id), now))
+    }
+
+    async fn find_active_nearby(
+        &self,
+        coordinates: [f64; 2],
+        radius: f64,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Story>, Box<dyn Error>> {
+        Ok(self.find_active(
+            |story| distance_meters(coordinates, story.location.coordinates) <= radius,
+            now,
+        ))
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Story>, Box<dyn Error>> {
+        let stories = self.stories.read().unwrap();
+        Ok(stories.iter().find(|story| story.id == Some(id)).cloned())
+    }
+
+    async fn find_by_user(&self, user_id: ObjectId) -> Result<Vec<Story>, Box<dyn Error>> {
+        let stories = self.stories.read().unwrap();
+        Ok(stories
+            .iter()
+            .filter(|story| story.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn insert(&self, story: Story) -> Result<Story, Box<dyn Error>> {
+        let mut stories = self.stories.write().unwrap();
+        let mut created_story = story;
+        created_story.id = Some(ObjectId::new());
+        stories.push(created_story.clone());
+        Ok(created_story)
+    }
+
+    async fn delete_by_owner(
+        &self,
+        story_id: ObjectId,
+        user_id: ObjectId,
+    ) -> Result<Option<Story>, Box<dyn Error>> {
+        let mut stories = self.stories.write().unwrap();
+        Ok(stories
+            .iter()
+            .position(|story| story.id == Some(story_id) && story.user_id == user_id)
+            .map(|index| stories.remove(index)))
+    }
+}
+
+// =============================================================================================================================
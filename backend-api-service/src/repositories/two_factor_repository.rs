@@ -0,0 +1,152 @@
+use crate::models::two_factor_model::TwoFactor;
+use async_trait::async_trait;
+use bson::oid::ObjectId;
+use mongodb::{Collection, Database, bson::doc, options::ReplaceOptions};
+use std::{collections::HashMap, error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "two_factor";
+
+// =============================================================================================================================
+
+#[async_trait(?Send)]
+pub trait TwoFactorRepo: Send + Sync {
+    async fn find(&self, user_id: ObjectId) -> Result<Option<TwoFactor>, Box<dyn Error>>;
+
+    /// Creates or replaces the settings of `two_factor.user_id`.
+    async fn save(&self, two_factor: TwoFactor) -> Result<(), Box<dyn Error>>;
+
+    /// Records `step` as used, unless a step at least as recent already was. Returns whether it was recorded.
+    async fn use_step(&self, user_id: ObjectId, step: i64) -> Result<bool, Box<dyn Error>>;
+
+    /// Removes the recovery code and returns whether it was still unused.
+    async fn use_recovery_code(
+        &self,
+        user_id: ObjectId,
+        code_hash: &str,
+    ) -> Result<bool, Box<dyn Error>>;
+
+    async fn delete(&self, user_id: ObjectId) -> Result<(), Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoTwoFactorRepo {
+    collection: Collection<TwoFactor>,
+}
+
+impl MongoTwoFactorRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl TwoFactorRepo for MongoTwoFactorRepo {
+    async fn find(&self, user_id: ObjectId) -> Result<Option<TwoFactor>, Box<dyn Error>> {
+        Ok(self.collection.find_one(doc! { "_id": user_id }).await?)
+    }
+
+    async fn save(&self, two_factor: TwoFactor) -> Result<(), Box<dyn Error>> {
+        self.collection
+            .replace_one(doc! { "_id": two_factor.user_id }, &two_factor)
+            .with_options(ReplaceOptions::builder().upsert(true).build())
+            .await?;
+        Ok(())
+    }
+
+    async fn use_step(&self, user_id: ObjectId, step: i64) -> Result<bool, Box<dyn Error>> {
+        let result = self
+            .collection
+            .update_one(
+                doc! {
+                    "_id": user_id,
+                    "$or": [
+                        { "last_used_step": null },
+                        { "last_used_step": { "$lt": step } }
+                    ]
+                },
+                doc! { "$set": { "last_used_step": step } },
+            )
+            .await?;
+        Ok(result.modified_count == 1)
+    }
+
+    async fn use_recovery_code(
+        &self,
+        user_id: ObjectId,
+        code_hash: &str,
+    ) -> Result<bool, Box<dyn Error>> {
+        let result = self
+            .collection
+            .update_one(
+                doc! { "_id": user_id, "recovery_codes": code_hash },
+                doc! { "$pull": { "recovery_codes": code_hash } },
+            )
+            .await?;
+        Ok(result.modified_count == 1)
+    }
+
+    async fn delete(&self, user_id: ObjectId) -> Result<(), Box<dyn Error>> {
+        self.collection.delete_one(doc! { "_id": user_id }).await?;
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryTwoFactorRepo {
+    pub(super) settings: RwLock<HashMap<ObjectId, TwoFactor>>,
+}
+
+#[async_trait(?Send)]
+impl TwoFactorRepo for InMemoryTwoFactorRepo {
+    async fn find(&self, user_id: ObjectId) -> Result<Option<TwoFactor>, Box<dyn Error>> {
+        Ok(self.settings.read().unwrap().get(&user_id).cloned())
+    }
+
+    async fn save(&self, two_factor: TwoFactor) -> Result<(), Box<dyn Error>> {
+        self.settings
+            .write()
+            .unwrap()
+            .insert(two_factor.user_id, two_factor);
+        Ok(())
+    }
+
+    async fn use_step(&self, user_id: ObjectId, step: i64) -> Result<bool, Box<dyn Error>> {
+        let mut settings = self.settings.write().unwrap();
+        Ok(match settings.get_mut(&user_id) {
+            Some(two_factor) if two_factor.last_used_step.is_none_or(|last| last < step) => {
+                two_factor.last_used_step = Some(step);
+                true
+            }
+            _ => false,
+        })
+    }
+
+    async fn use_recovery_code(
+        &self,
+        user_id: ObjectId,
+        code_hash: &str,
+    ) -> Result<bool, Box<dyn Error>> {
+        let mut settings = self.settings.write().unwrap();
+        let Some(two_factor) = settings.get_mut(&user_id) else {
+            return Ok(false);
+        };
+
+        let count = two_factor.recovery_codes.len();
+        two_factor.recovery_codes.retain(|hash| hash != code_hash);
+        Ok(two_factor.recovery_codes.len() < count)
+    }
+
+    async fn delete(&self, user_id: ObjectId) -> Result<(), Box<dyn Error>> {
+        self.settings.write().unwrap().remove(&user_id);
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,178 @@
+use crate::models::media_model::{UploadSession, UploadedPart};
+use async_trait::async_trait;
+use bson::{oid::ObjectId, to_bson};
+use futures_util::TryStreamExt;
+use mongodb::{Collection, Database, bson::doc, options::ReturnDocument};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "upload_sessions";
+
+// =============================================================================================================================
+
+/// Resumable uploads in progress, each backed by a multipart upload in storage.
+#[async_trait(?Send)]
+pub trait UploadSessionRepo: Send + Sync {
+    async fn insert(&self, session: UploadSession) -> Result<UploadSession, Box<dyn Error>>;
+
+    async fn find_by_owner(&self, owner_id: ObjectId)
+    -> Result<Vec<UploadSession>, Box<dyn Error>>;
+
+    /// The session, provided `owner_id` started it.
+    async fn find_for_owner(
+        &self,
+        id: ObjectId,
+        owner_id: ObjectId,
+    ) -> Result<Option<UploadSession>, Box<dyn Error>>;
+
+    /// Records an uploaded part, replacing a previous upload of the same part, and returns the session updated.
+    async fn save_part(
+        &self,
+        id: ObjectId,
+        part: UploadedPart,
+    ) -> Result<Option<UploadSession>, Box<dyn Error>>;
+
+    async fn delete(&self, id: ObjectId) -> Result<(), Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoUploadSessionRepo {
+    collection: Collection<UploadSession>,
+}
+
+impl MongoUploadSessionRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl UploadSessionRepo for MongoUploadSessionRepo {
+    async fn insert(&self, session: UploadSession) -> Result<UploadSession, Box<dyn Error>> {
+        let result = self.collection.insert_one(&session).await?;
+        let mut created_session = session;
+        created_session.id = result.inserted_id.as_object_id();
+        Ok(created_session)
+    }
+
+    async fn find_by_owner(
+        &self,
+        owner_id: ObjectId,
+    ) -> Result<Vec<UploadSession>, Box<dyn Error>> {
+        let cursor = self.collection.find(doc! { "owner_id": owner_id }).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_for_owner(
+        &self,
+        id: ObjectId,
+        owner_id: ObjectId,
+    ) -> Result<Option<UploadSession>, Box<dyn Error>> {
+        let filter = doc! {
+            "_id": id,
+            "owner_id": owner_id
+        };
+
+        Ok(self.collection.find_one(filter).await?)
+    }
+
+    async fn save_part(
+        &self,
+        id: ObjectId,
+        part: UploadedPart,
+    ) -> Result<Option<UploadSession>, Box<dyn Error>> {
+        // A single update cannot both pull and push on the same array.
+        self.collection
+            .update_one(
+                doc! { "_id": id },
+                doc! { "$pull": { "parts": { "part_number": part.part_number } } },
+            )
+            .await?;
+
+        Ok(self
+            .collection
+            .find_one_and_update(
+                doc! { "_id": id },
+                doc! { "$push": { "parts": to_bson(&part)? } },
+            )
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<(), Box<dyn Error>> {
+        self.collection.delete_one(doc! { "_id": id }).await?;
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryUploadSessionRepo {
+    pub(super) sessions: RwLock<Vec<UploadSession>>,
+}
+
+#[async_trait(?Send)]
+impl UploadSessionRepo for InMemoryUploadSessionRepo {
+    async fn insert(&self, session: UploadSession) -> Result<UploadSession, Box<dyn Error>> {
+        let mut sessions = self.sessions.write().unwrap();
+        let mut created_session = session;
+        created_session.id = Some(ObjectId::new());
+        sessions.push(created_session.clone());
+        Ok(created_session)
+    }
+
+    async fn find_by_owner(
+        &self,
+        owner_id: ObjectId,
+    ) -> Result<Vec<UploadSession>, Box<dyn Error>> {
+        let sessions = self.sessions.read().unwrap();
+        Ok(sessions
+            .iter()
+            .filter(|session| session.owner_id == owner_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_for_owner(
+        &self,
+        id: ObjectId,
+        owner_id: ObjectId,
+    ) -> Result<Option<UploadSession>, Box<dyn Error>> {
+        let sessions = self.sessions.read().unwrap();
+        Ok(sessions
+            .iter()
+            .find(|session| session.id == Some(id) && session.owner_id == owner_id)
+            .cloned())
+    }
+
+    async fn save_part(
+        &self,
+        id: ObjectId,
+        part: UploadedPart,
+    ) -> Result<Option<UploadSession>, Box<dyn Error>> {
+        let mut sessions = self.sessions.write().unwrap();
+        Ok(sessions
+            .iter_mut()
+            .find(|session| session.id == Some(id))
+            .map(|session| {
+                session
+                    .parts
+                    .retain(|uploaded| uploaded.part_number != part.part_number);
+                session.parts.push(part);
+                session.clone()
+            }))
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<(), Box<dyn Error>> {
+        let mut sessions = self.sessions.write().unwrap();
+        sessions.retain(|session| session.id != Some(id));
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,760 @@
+use crate::{
+    models::user_model::{
+        AccountStatus, Location, SortOrder, UpdateProfile, User, UserCursor, UserFilter, UserRole,
+        UserSort,
+    },
+    repositories::distance_meters,
+};
+use async_trait::async_trait;
+use bson::{
+    DateTime as BsonDateTime, Document, from_document, oid::ObjectId, to_bson, to_document,
+};
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{Collection, Database, bson::doc, options::ReturnDocument};
+use std::{error::Error, sync::RwLock};
+
+// =============================================================================================================================
+
+const COLLECTION_NAME: &str = "users";
+
+// =============================================================================================================================
+
+#[async_trait(?Send)]
+pub trait UserRepo: Send + Sync {
+    /// A page of the users matching `filter`, ordered by `sort` then by id, starting after `after`.
+    async fn find_page(
+        &self,
+        filter: &UserFilter,
+        sort: UserSort,
+        order: SortOrder,
+        after: Option<&UserCursor>,
+        limit: i64,
+    ) -> Result<Vec<User>, Box<dyn Error>>;
+
+    async fn count(&self, filter: &UserFilter) -> Result<u64, Box<dyn Error>>;
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<User>, Box<dyn Error>>;
+
+    async fn find_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<User>, Box<dyn Error>>;
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, Box<dyn Error>>;
+
+    /// Users whose username starts with `prefix`, in alphabetical order. Usernames are stored in lowercase.
+    async fn find_by_username_prefix(
+        &self,
+        prefix: &str,
+        limit: i64,
+    ) -> Result<Vec<User>, Box<dyn Error>>;
+
+    /// Users whose username contains any of the three-letter `grams`, those sharing the most first: the candidates
+    /// of a fuzzy search.
+    async fn find_by_username_grams(
+        &self,
+        grams: &[String],
+        limit: i64,
+    ) -> Result<Vec<User>, Box<dyn Error>>;
+
+    /// Looks a user up by username or email, as accepted by the login form.
+    async fn find_by_credential(&self, credential: &str) -> Result<Option<User>, Box<dyn Error>>;
+
+    async fn insert(&self, user: User) -> Result<User, Box<dyn Error>>;
+
+    /// Sets the fields of `profile` that are present and leaves the others.
+    async fn update_profile(
+        &self,
+        id: ObjectId,
+        profile: UpdateProfile,
+    ) -> Result<Option<User>, Box<dyn Error>>;
+
+    async fn update_role(
+        &self,
+        id: ObjectId,
+        role: UserRole,
+    ) -> Result<Option<User>, Box<dyn Error>>;
+
+    async fn update_location(
+        &self,
+        id: ObjectId,
+        location: Location,
+    ) -> Result<Option<User>, Box<dyn Error>>;
+
+    /// Sets the email to the address that was just confirmed and marks the account verified.
+    async fn set_verified_email(
+        &self,
+        id: ObjectId,
+        email: &str,
+    ) -> Result<Option<User>, Box<dyn Error>>;
+
+    async fn update_password(
+        &self,
+        id: ObjectId,
+        password_hash: &str,
+    ) -> Result<Option<User>, Box<dyn Error>>;
+
+    /// Sets the moderation status. `suspended_until` only applies to suspensions and is cleared otherwise.
+    async fn update_status(
+        &self,
+        id: ObjectId,
+        status: AccountStatus,
+        suspended_until: Option<DateTime<Utc>>,
+    ) -> Result<Option<User>, Box<dyn Error>>;
+
+    /// The banned users among `ids`.
+    async fn find_banned(&self, ids: &[ObjectId]) -> Result<Vec<ObjectId>, Box<dyn Error>>;
+
+    /// Users within `radius` meters of `coordinates` (`[longitude, latitude]`), nearest first.
+    async fn find_nearby(
+        &self,
+        exclude_id: ObjectId,
+        coordinates: [f64; 2],
+        radius: f64,
+        limit: i64,
+    ) -> Result<Vec<User>, Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+pub struct MongoUserRepo {
+    collection: Collection<User>,
+}
+
+impl MongoUserRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection(COLLECTION_NAME),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl UserRepo for MongoUserRepo {
+    async fn find_page(
+        &self,
+        filter: &UserFilter,
+        sort: UserSort,
+        order: SortOrder,
+        after: Option<&UserCursor>,
+        limit: i64,
+    ) -> Result<Vec<User>, Box<dyn Error>> {
+        let field = sort_field(sort);
+        let (direction, operator) = match order {
+            SortOrder::Asc => (1, "$gt"),
+            SortOrder::Desc => (-1, "$lt"),
+        };
+
+        let mut clauses = vec![filter_document(filter)?];
+        if let Some(after) = after {
+            clauses.push(match sort {
+                UserSort::Created => doc! { "_id": { operator: after.id } },
+                _ => doc! {
+                    "$or": [
+                        { field: { operator: &after.key } },
+                        { field: &after.key, "_id": { operator: after.id } },
+                    ]
+                },
+            });
+        }
+
+        let mut sort_document = doc! { field: direction };
+        sort_document.insert("_id", direction);
+        let cursor = self
+            .collection
+            .find(doc! { "$and": clauses })
+            .sort(sort_document)
+            .limit(limit)
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn count(&self, filter: &UserFilter) -> Result<u64, Box<dyn Error>> {
+        Ok(self
+            .collection
+            .count_documents(filter_document(filter)?)
+            .await?)
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<User>, Box<dyn Error>> {
+        Ok(self.collection.find_one(doc! { "_id": id }).await?)
+    }
+
+    async fn find_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<User>, Box<dyn Error>> {
+        let cursor = self.collection.find(doc! { "_id": { "$in": ids } }).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, Box<dyn Error>> {
+        Ok(self.collection.find_one(doc! { "email": email }).await?)
+    }
+
+    async fn find_by_username_prefix(
+        &self,
+        prefix: &str,
+        limit: i64,
+    ) -> Result<Vec<User>, Box<dyn Error>> {
+        let pattern = format!("^{}", regex::escape(prefix));
+        let cursor = self
+            .collection
+            .find(doc! { "username": { "$regex": pattern } })
+            .sort(doc! { "username": 1 })
+            .limit(limit)
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_by_username_grams(
+        &self,
+        grams: &[String],
+        limit: i64,
+    ) -> Result<Vec<User>, Box<dyn Error>> {
+        if grams.is_empty() {
+            return Ok(Vec::new());
+        }
+        let pipeline = vec![
+            doc! { "$match": { "username_grams": { "$in": grams } } },
+            doc! { "$addFields": { "shared_grams": { "$size": { "$setIntersection": ["$username_grams", grams] } } } },
+            doc! { "$sort": { "shared_grams": -1, "username": 1 } },
+            doc! { "$limit": limit },
+        ];
+        let cursor = self.collection.aggregate(pipeline).await?;
+        let users: Vec<Document> = cursor.try_collect().await?;
+
+        Ok(users
+            .into_iter()
+            .map(from_document)
+            .collect::<Result<_, _>>()?)
+    }
+
+    async fn find_by_credential(&self, credential: &str) -> Result<Option<User>, Box<dyn Error>> {
+        let filter = doc! {
+            "$or": [
+                { "username": credential },
+                { "email": credential }
+            ]
+        };
+        Ok(self.collection.find_one(filter).await?)
+    }
+
+    async fn insert(&self, user: User) -> Result<User, Box<dyn Error>> {
+        let mut document = to_document(&user)?;
+        document.insert("username_grams", username_grams(&user.username));
+        let result = self
+            .collection
+            .clone_with_type::<Document>()
+            .insert_one(document)
+            .await?;
+        let mut created_user = user;
+        created_user.id = result.inserted_id.as_object_id();
+        Ok(created_user)
+    }
+
+    async fn update_profile(
+        &self,
+        id: ObjectId,
+        profile: UpdateProfile,
+    ) -> Result<Option<User>, Box<dyn Error>> {
+        let mut update_doc = Document::new();
+        if let Some(username) = profile.username {
+            update_doc.insert("username_grams", username_grams(&username));
+            update_doc.insert("username", username);
+        }
+        if let Some(bio) = profile.bio {
+            update_doc.insert("bio", bio);
+        }
+        if let Some(avatar) = profile.avatar {
+            update_doc.insert("avatar", avatar);
+        }
+
+        if update_doc.is_empty() {
+            return self.find_by_id(id).await;
+        }
+
+        Ok(self
+            .collection
+            .find_one_and_update(doc! { "_id": id }, doc! { "$set": update_doc })
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn update_role(
+        &self,
+        id: ObjectId,
+        role: UserRole,
+    ) -> Result<Option<User>, Box<dyn Error>> {
+        Ok(self
+            .collection
+            .find_one_and_update(
+                doc! { "_id": id },
+                doc! { "$set": { "role": to_bson(&role)? } },
+            )
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn update_location(
+        &self,
+        id: ObjectId,
+        location: Location,
+    ) -> Result<Option<User>, Box<dyn Error>> {
+        let update = doc! {
+            "$set": {
+                "location": {
+                    "type": location.location_type,
+                    "coordinates": location.coordinates.to_vec()
+                }
+            }
+        };
+
+        Ok(self
+            .collection
+            .find_one_and_update(doc! { "_id": id }, update)
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn set_verified_email(
+        &self,
+        id: ObjectId,
+        email: &str,
+    ) -> Result<Option<User>, Box<dyn Error>> {
+        Ok(self
+            .collection
+            .find_one_and_update(
+                doc! { "_id": id },
+                doc! { "$set": { "email": email, "verified": true } },
+            )
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn update_password(
+        &self,
+        id: ObjectId,
+        password_hash: &str,
+    ) -> Result<Option<User>, Box<dyn Error>> {
+        Ok(self
+            .collection
+            .find_one_and_update(
+                doc! { "_id": id },
+                doc! { "$set": { "password": password_hash } },
+            )
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn update_status(
+        &self,
+        id: ObjectId,
+        status: AccountStatus,
+        suspended_until: Option<DateTime<Utc>>,
+    ) -> Result<Option<User>, Box<dyn Error>> {
+        let update = match suspended_until.filter(|_| status == AccountStatus::Suspended) {
+            Some(until) => doc! {
+                "$set": { "status": to_bson(&status)?, "suspended_until": BsonDateTime::from_chrono(until) }
+            },
+            None => doc! {
+                "$set": { "status": to_bson(&status)? },
+                "$unset": { "suspended_until": "" }
+            },
+        };
+
+        Ok(self
+            .collection
+            .find_one_and_update(doc! { "_id": id }, update)
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn find_banned(&self, ids: &[ObjectId]) -> Result<Vec<ObjectId>, Box<dyn Error>> {
+        let filter = doc! {
+            "_id": { "$in": ids },
+            "status": to_bson(&AccountStatus::Banned)?
+        };
+        let cursor = self
+            .collection
+            .clone_with_type::<Document>()
+            .find(filter)
+            .projection(doc! { "_id": 1 })
+            .await?;
+        let banned: Vec<Document> = cursor.try_collect().await?;
+
+        Ok(banned
+            .iter()
+            .filter_map(|user| user.get_object_id("_id").ok())
+            .collect())
+    }
+
+    async fn find_nearby(
+        &self,
+        exclude_id: ObjectId,
+        coordinates: [f64; 2],
+        radius: f64,
+        limit: i64,
+    ) -> Result<Vec<User>, Box<dyn Error>> {
+        let filter = doc! {
+            "_id": { "$ne": exclude_id },
+            "location": {
+                "$near": {
+                    "$geometry": {
+                        "type": "Point",
+                        "coordinates": coordinates.to_vec()
+                    },
+                    "$maxDistance": radius
+                }
+            }
+        };
+
+        let cursor = self.collection.find(filter).limit(limit).await?;
+        Ok(cursor.try_collect().await?)
+    }
+}
+
+/// The distinct three-letter windows of a username, indexed for the fuzzy search.
+pub(crate) fn username_grams(username: &str) -> Vec<String> {
+    let chars: Vec<char> = username.chars().collect();
+    let mut grams: Vec<String> = chars.windows(3).map(|gram| gram.iter().collect()).collect();
+    grams.sort();
+    grams.dedup();
+    grams
+}
+
+fn sort_field(sort: UserSort) -> &'static str {
+    match sort {
+        UserSort::Created => "_id",
+        UserSort::Username => "username",
+        UserSort::Email => "email",
+    }
+}
+
+fn filter_document(filter: &UserFilter) -> Result<Document, Box<dyn Error>> {
+    let mut document = Document::new();
+    if let Some(role) = filter.role {
+        document.insert("role", to_bson(&role)?);
+    }
+    // Accounts created before verification existed have no `verified` field.
+    match filter.verified {
+        Some(true) => document.insert("verified", true),
+        Some(false) => document.insert("verified", doc! { "$ne": true }),
+        None => None,
+    };
+    // Nor a `status` before moderation.
+    match filter.status {
+        Some(AccountStatus::Active) => {
+            document.insert("status", doc! { "$nin": ["Suspended", "Banned"] })
+        }
+        Some(status) => document.insert("status", to_bson(&status)?),
+        None => None,
+    };
+    // Usernames and emails are stored in lowercase, so an anchored case-sensitive pattern is case-insensitive and
+    // can use their indexes.
+    if let Some(search) = &filter.search {
+        let pattern = format!("^{}", regex::escape(search));
+        document.insert(
+            "$or",
+            vec![
+                doc! { "username": { "$regex": &pattern } },
+                doc! { "email": { "$regex": &pattern } },
+            ],
+        );
+    }
+    Ok(document)
+}
+
+// =============================================================================================================================
+
+#[derive(Default)]
+pub struct InMemoryUserRepo {
+    pub(super) users: RwLock<Vec<User>>,
+}
+
+#[async_trait(?Send)]
+impl UserRepo for InMemoryUserRepo {
+    async fn find_page(
+        &self,
+        filter: &UserFilter,
+        sort: UserSort,
+        order: SortOrder,
+        after: Option<&UserCursor>,
+        limit: i64,
+    ) -> Result<Vec<User>, Box<dyn Error>> {
+        let users = self.users.read().unwrap();
+        let position = |user: &User| (sort.key(user).to_string(), user.id.unwrap_or_default());
+
+        let mut page: Vec<User> = users
+            .iter()
+            .filter(|user| matches_filter(user, filter))
+            .filter(|user| {
+                after.is_none_or(|after| {
+                    let after = (after.key.clone(), after.id);
+                    match order {
+                        SortOrder::Asc => position(user) > after,
+                        SortOrder::Desc => position(user) < after,
+                    }
+                })
+            })
+            .cloned()
+            .collect();
+        page.sort_by_cached_key(position);
+        if order == SortOrder::Desc {
+            page.reverse();
+        }
+        page.truncate(limit.max(0) as usize);
+        Ok(page)
+    }
+
+    async fn count(&self, filter: &UserFilter) -> Result<u64, Box<dyn Error>> {
+        let users = self.users.read().unwrap();
+        Ok(users
+            .iter()
+            .filter(|user| matches_filter(user, filter))
+            .count() as u64)
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<User>, Box<dyn Error>> {
+        let users = self.users.read().unwrap();
+        Ok(users.iter().find(|user| user.id == Some(id)).cloned())
+    }
+
+    async fn find_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<User>, Box<dyn Error>> {
+        let users = self.users.read().unwrap();
+        Ok(users
+            .iter()
+            .filter(|user| user.id.is_some_and(|id| ids.contains(&id)))
+            .cloned()
+            .collect())
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, Box<dyn Error>> {
+        let users = self.users.read().unwrap();
+        Ok(users.iter().find(|user| user.email == email).cloned())
+    }
+
+    async fn find_by_username_prefix(
+        &self,
+        prefix: &str,
+        limit: i64,
+    ) -> Result<Vec<User>, Box<dyn Error>> {
+        let users = self.users.read().unwrap();
+        let mut matching: Vec<User> = users
+            .iter()
+            .filter(|user| user.username.starts_with(prefix))
+            .cloned()
+            .collect();
+        matching.sort_by(|a, b| a.username.cmp(&b.username));
+        matching.truncate(limit.max(0) as usize);
+        Ok(matching)
+    }
+
+    async fn find_by_username_grams(
+        &self,
+        grams: &[String],
+        limit: i64,
+    ) -> Result<Vec<User>, Box<dyn Error>> {
+        let users = self.users.read().unwrap();
+        let mut matching: Vec<(usize, User)> = users
+            .iter()
+            .map(|user| {
+                let shared = username_grams(&user.username)
+                    .iter()
+                    .filter(|gram| grams.contains(gram))
+                    .count();
+                (shared, user)
+            })
+            .filter(|(shared, _)| *shared > 0)
+            .map(|(shared, user)| (shared, user.clone()))
+            .collect();
+        matching.sort_by(|(a, a_user), (b, b_user)| {
+            b.cmp(a).then_with(|| a_user.username.cmp(&b_user.username))
+        });
+        matching.truncate(limit.max(0) as usize);
+        Ok(matching.into_iter().map(|(_, user)| user).collect())
+    }
+
+    async fn find_by_credential(&self, credential: &str) -> Result<Option<User>, Box<dyn Error>> {
+        let users = self.users.read().unwrap();
+        Ok(users
+            .iter()
+            .find(|user| user.username == credential || user.email == credential)
+            .cloned())
+    }
+
+    async fn insert(&self, user: User) -> Result<User, Box<dyn Error>> {
+        let mut users = self.users.write().unwrap();
+        let mut created_user = user;
+        created_user.id = Some(ObjectId::new());
+        users.push(created_user.clone());
+        Ok(created_user)
+    }
+
+    async fn update_profile(
+        &self,
+        id: ObjectId,
+        profile: UpdateProfile,
+    ) -> Result<Option<User>, Box<dyn Error>> {
+        let mut users = self.users.write().unwrap();
+        if let Some(username) = &profile.username
+            && users
+                .iter()
+                .any(|user| &user.username == username && user.id != Some(id))
+        {
+            return Err("Username already exists".into());
+        }
+
+        Ok(users
+            .iter_mut()
+            .find(|existing| existing.id == Some(id))
+            .map(|existing| {
+                if let Some(username) = profile.username {
+                    existing.username = username;
+                }
+                if let Some(bio) = profile.bio {
+                    existing.bio = bio;
+                }
+                if let Some(avatar) = profile.avatar {
+                    existing.avatar = Some(avatar);
+                }
+                existing.clone()
+            }))
+    }
+
+    async fn update_role(
+        &self,
+        id: ObjectId,
+        role: UserRole,
+    ) -> Result<Option<User>, Box<dyn Error>> {
+        let mut users = self.users.write().unwrap();
+        Ok(users
+            .iter_mut()
+            .find(|existing| existing.id == Some(id))
+            .map(|existing| {
+                existing.role = role;
+                existing.clone()
+            }))
+    }
+
+    async fn update_location(
+        &self,
+        id: ObjectId,
+        location: Location,
+    ) -> Result<Option<User>, Box<dyn Error>> {
+        let mut users = self.users.write().unwrap();
+        Ok(users
+            .iter_mut()
+            .find(|existing| existing.id == Some(id))
+            .map(|existing| {
+                existing.location = location;
+                existing.clone()
+            }))
+    }
+
+    async fn set_verified_email(
+        &self,
+        id: ObjectId,
+        email: &str,
+    ) -> Result<Option<User>, Box<dyn Error>> {
+        let mut users = self.users.write().unwrap();
+        if users
+            .iter()
+            .any(|user| user.email == email && user.id != Some(id))
+        {
+            return Err("Email already exists".into());
+        }
+
+        Ok(users
+            .iter_mut()
+            .find(|existing| existing.id == Some(id))
+            .map(|existing| {
+                existing.email = email.to_string();
+                existing.verified = true;
+                existing.clone()
+            }))
+    }
+
+    async fn update_password(
+        &self,
+        id: ObjectId,
+        password_hash: &str,
+    ) -> Result<Option<User>, Box<dyn Error>> {
+        let mut users = self.users.write().unwrap();
+        Ok(users
+            .iter_mut()
+            .find(|existing| existing.id == Some(id))
+            .map(|existing| {
+                existing.password = password_hash.to_string();
+                existing.clone()
+            }))
+    }
+
+    async fn update_status(
+        &self,
+        id: ObjectId,
+        status: AccountStatus,
+        suspended_until: Option<DateTime<Utc>>,
+    ) -> Result<Option<User>, Box<dyn Error>> {
+        let mut users = self.users.write().unwrap();
+        Ok(users
+            .iter_mut()
+            .find(|existing| existing.id == Some(id))
+            .map(|existing| {
+                existing.status = status;
+                existing.suspended_until =
+                    suspended_until.filter(|_| status == AccountStatus::Suspended);
+                existing.clone()
+            }))
+    }
+
+    async fn find_banned(&self, ids: &[ObjectId]) -> Result<Vec<ObjectId>, Box<dyn Error>> {
+        let users = self.users.read().unwrap();
+        Ok(users
+            .iter()
+            .filter(|user| user.status == AccountStatus::Banned)
+            .filter_map(|user| user.id)
+            .filter(|id| ids.contains(id))
+            .collect())
+    }
+
+    async fn find_nearby(
+        &self,
+        exclude_id: ObjectId,
+        coordinates: [f64; 2],
+        radius: f64,
+        limit: i64,
+    ) -> Result<Vec<User>, Box<dyn Error>> {
+        let users = self.users.read().unwrap();
+
+        let mut nearby: Vec<(f64, User)> = users
+            .iter()
+            .filter(|user| user.id != Some(exclude_id))
+            .map(|user| {
+                (
+                    distance_meters(coordinates, user.location.coordinates),
+                    user,
+                )
+            })
+            .filter(|(distance, _)| *distance <= radius)
+            .map(|(distance, user)| (distance, user.clone()))
+            .collect();
+        nearby.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        Ok(nearby
+            .into_iter()
+            .take(limit.max(0) as usize)
+            .map(|(_, user)| user)
+            .collect())
+    }
+}
+
+// =============================================================================================================================
+
+fn matches_filter(user: &User, filter: &UserFilter) -> bool {
+    filter.role.is_none_or(|role| user.role == role)
+        && filter
+            .verified
+            .is_none_or(|verified| user.verified == verified)
+        && filter.status.is_none_or(|status| user.status == status)
+        && filter.search.as_ref().is_none_or(|search| {
+            user.username.starts_with(search.as_str()) || user.email.starts_with(search.as_str())
+        })
+}
+
+// =============================================================================================================================
@@ -0,0 +1,69 @@
+use crate::{
+    config::Config,
+    models::audit_model::{Actor, AuditAction, AuditEvent, AuditFilter, AuditQueryParams},
+    repositories::Repositories,
+};
+use bson::oid::ObjectId;
+use chrono::{Duration, Utc};
+use std::{error::Error, str::FromStr};
+
+// =============================================================================================================================
+
+const DEFAULT_EVENTS_LIMIT: i64 = 50;
+const MAX_EVENTS_LIMIT: i64 = 200;
+
+// =============================================================================================================================
+
+/// Appends an event to the audit log. The action already happened, so a failure to record it is reported in the
+/// logs rather than to the client.
+pub async fn record(
+    repos: &Repositories,
+    config: &Config,
+    actor: &Actor,
+    action: AuditAction,
+    target_id: Option<ObjectId>,
+    details: Option<String>,
+) {
+    let now = Utc::now();
+    let retention_days = config.audit.retention_days;
+    let event = AuditEvent {
+        id: ObjectId::new(),
+        action,
+        actor_id: actor.user_id,
+        target_id,
+        ip: actor.ip.clone(),
+        details,
+        created_at: now,
+        expires_at: (retention_days > 0).then(|| now + Duration::days(retention_days)),
+    };
+
+    if let Err(e) = repos.audit_events.insert(event).await {
+        eprintln!("❌ Failed to record the {:?} audit event: {}", action, e);
+    }
+}
+
+// =============================================================================================================================
+
+/// The latest events matching the query, newest first. Pass the id of the last event as `before` for the next page.
+pub async fn get_events(
+    repos: &Repositories,
+    params: AuditQueryParams,
+) -> Result<Vec<AuditEvent>, Box<dyn Error>> {
+    let parse = |id: Option<String>| id.as_deref().map(ObjectId::from_str).transpose();
+    let filter = AuditFilter {
+        action: params.action,
+        actor_id: parse(params.actor_id)?,
+        target_id: parse(params.target_id)?,
+        from: params.from,
+        to: params.to,
+        before: parse(params.before)?,
+    };
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_EVENTS_LIMIT)
+        .clamp(1, MAX_EVENTS_LIMIT);
+
+    repos.audit_events.find(&filter, limit).await
+}
+
+// =============================================================================================================================
@@ -1,71 +1,429 @@
 use crate::{
+    config::Config,
+    mailer::{Email, Mailer},
     models::{
-        auth_model::{AuthLogin, AuthRegister, AuthResponse},
-        user_model::{User, UserRole},
+        audit_model::{Actor, AuditAction},
+        auth_model::{
+            AuthLogin, AuthRegister, AuthResponse, AuthToken, EmailRequest, PendingVerification,
+            ResetPassword, TokenPurpose, VerifyEmail,
+        },
+        session_model::ClientInfo,
+        two_factor_model::TwoFactorChallenge,
+        user_model::{AccountStatus, User, UserRole},
     },
-    utils::jwt::encode_external_jwt,
+    repositories::Repositories,
+    services::{audit_service, session_service},
+    utils::jwt::encode_challenge_jwt,
 };
+use actix_web::web;
 use bcrypt::{DEFAULT_COST, hash, verify};
-use mongodb::{Collection, Database, bson::doc};
-use std::{error::Error, thread, time::Duration};
+use bson::oid::ObjectId;
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+use std::{error::Error, fmt};
+use uuid::Uuid;
 use validator::Validate;
 
 // =============================================================================================================================
 
+const INVALID_CREDENTIALS: &str = "Invalid credential or password";
+
+static DUMMY_PASSWORD_HASH: Lazy<String> =
+    Lazy::new(|| hash("dummy-password-for-timing", DEFAULT_COST).unwrap());
+
+// =============================================================================================================================
+
+pub enum LoginOutcome {
+    Authenticated(AuthResponse),
+    TwoFactorRequired(TwoFactorChallenge),
+}
+
+// =============================================================================================================================
+
+/// Returned by `login` while the account is locked after too many failed attempts.
+#[derive(Debug)]
+pub struct AccountLocked {
+    pub retry_after_secs: i64,
+}
+
+impl AccountLocked {
+    fn until(locked_until: DateTime<Utc>, now: DateTime<Utc>) -> Self {
+        Self {
+            retry_after_secs: (locked_until - now).num_seconds().max(1),
+        }
+    }
+}
+
+impl fmt::Display for AccountLocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Too many failed login attempts, try again in {} seconds",
+            self.retry_after_secs
+        )
+    }
+}
+
+impl Error for AccountLocked {}
+
+/// Returned by `login` for the right password of an account whose email is not verified yet.
+#[derive(Debug)]
+pub struct EmailNotVerified;
+
+impl fmt::Display for EmailNotVerified {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The email address is not verified, follow the link sent by email"
+        )
+    }
+}
+
+impl Error for EmailNotVerified {}
+
+// =============================================================================================================================
+
+/// Creates an unverified account and emails the verification link. The account can log in once verified.
 pub async fn register(
-    db: &Database,
+    repos: &Repositories,
+    mailer: &dyn Mailer,
+    config: &Config,
     payload: AuthRegister,
-) -> Result<AuthResponse, Box<dyn Error>> {
-    let user = create_basic_user(db, payload).await?;
-    let user_id = user.id.unwrap().to_hex();
-    let token = encode_external_jwt(user_id, user.role)?;
+) -> Result<PendingVerification, Box<dyn Error>> {
+    let user = create_basic_user(repos, payload).await?;
+    let user_id = user.id.ok_or("The user has no id")?;
 
-    Ok(AuthResponse { token })
+    // The account exists either way, a failed email can be sent again through resend-verification.
+    if let Err(e) = send_verification_email(repos, mailer, config, user_id, &user.email).await {
+        eprintln!(
+            "❌ Failed to send the verification email to {}: {}",
+            user.email, e
+        );
+    }
+
+    Ok(PendingVerification {
+        email: user.email,
+        verified: false,
+    })
+}
+
+// =============================================================================================================================
+
+/// Issues a verification token for `email` and sends the link. A new token invalidates the previous one.
+pub async fn send_verification_email(
+    repos: &Repositories,
+    mailer: &dyn Mailer,
+    config: &Config,
+    user_id: ObjectId,
+    email: &str,
+) -> Result<(), Box<dyn Error>> {
+    let token = issue_token(
+        repos,
+        user_id,
+        TokenPurpose::EmailVerification,
+        email,
+        Duration::hours(config.mail.verification_token_hours),
+    )
+    .await?;
+
+    mailer
+        .send(Email {
+            to: email.to_string(),
+            subject: "Confirm your Snapshoot email address".to_string(),
+            body: format!(
+                "Welcome to Snapshoot!\n\nConfirm your email address by opening this link:\n{}/verify-email?token={}\n\nThe link expires in {} hours. If you did not sign up, you can ignore this email.",
+                config.mail.app_url.trim_end_matches('/'),
+                token,
+                config.mail.verification_token_hours
+            ),
+        })
+        .await
+}
+
+// =============================================================================================================================
+
+/// Marks the address the token was sent to as verified and signs the user in, asking for the second factor first
+/// when it is enabled.
+pub async fn verify_email(
+    repos: &Repositories,
+    config: &Config,
+    client: ClientInfo,
+    payload: VerifyEmail,
+) -> Result<LoginOutcome, Box<dyn Error>> {
+    let token = repos
+        .auth_tokens
+        .consume(
+            &hash_token(&payload.token),
+            TokenPurpose::EmailVerification,
+            Utc::now(),
+        )
+        .await?
+        .ok_or("Invalid or expired verification token")?;
+
+    let user = repos
+        .users
+        .set_verified_email(token.user_id, &token.email)
+        .await?
+        .ok_or("No user found for this token")?;
+
+    complete_login(repos, config, &user, client).await
+}
+
+// =============================================================================================================================
+
+/// Succeeds whether or not the address belongs to an unverified account, so it cannot be used to probe emails.
+pub async fn resend_verification(
+    repos: &Repositories,
+    mailer: &dyn Mailer,
+    config: &Config,
+    payload: EmailRequest,
+) -> Result<(), Box<dyn Error>> {
+    payload.validate()?;
+
+    if let Some(user) = repos.users.find_by_email(&payload.email).await?
+        && !user.verified
+    {
+        let user_id = user.id.ok_or("The user has no id")?;
+        send_verification_email(repos, mailer, config, user_id, &user.email).await?;
+    }
+
+    Ok(())
 }
 
 // =============================================================================================================================
 
-pub async fn login(db: &Database, payload: AuthLogin) -> Result<AuthResponse, Box<dyn Error>> {
+/// Emails a password reset link. Succeeds whether or not the address has an account.
+pub async fn forgot_password(
+    repos: &Repositories,
+    mailer: &dyn Mailer,
+    config: &Config,
+    payload: EmailRequest,
+) -> Result<(), Box<dyn Error>> {
     payload.validate()?;
 
-    let collection: Collection<User> = db.collection("users");
-    let filter = doc! {
-      "$or": vec![
-        doc! {"username": &payload.credential},
-        doc! {"email": &payload.credential},
-      ]
+    let Some(user) = repos.users.find_by_email(&payload.email).await? else {
+        return Ok(());
     };
 
-    let user_result = collection.find_one(filter).await;
+    let token = issue_token(
+        repos,
+        user.id.ok_or("The user has no id")?,
+        TokenPurpose::PasswordReset,
+        &user.email,
+        Duration::minutes(config.mail.reset_token_minutes),
+    )
+    .await?;
 
-    let user = match user_result {
-        Ok(Some(user)) => user,
-        Ok(None) => {
-            thread::sleep(Duration::from_millis(300));
-            return Err("Invalid credential or password".into());
-        }
-        Err(_) => return Err("Database error occurred".into()),
+    mailer
+        .send(Email {
+            to: user.email,
+            subject: "Reset your Snapshoot password".to_string(),
+            body: format!(
+                "Someone asked to reset the password of your Snapshoot account.\n\nChoose a new password by opening this link:\n{}/reset-password?token={}\n\nThe link expires in {} minutes and works once. If you did not ask for it, you can ignore this email.",
+                config.mail.app_url.trim_end_matches('/'),
+                token,
+                config.mail.reset_token_minutes
+            ),
+        })
+        .await
+}
+
+// =============================================================================================================================
+
+/// Sets a new password with a token from `forgot_password`, and lifts a lockout from failed logins.
+pub async fn reset_password(
+    repos: &Repositories,
+    payload: ResetPassword,
+) -> Result<(), Box<dyn Error>> {
+    payload.validate()?;
+
+    let token = repos
+        .auth_tokens
+        .consume(
+            &hash_token(&payload.token),
+            TokenPurpose::PasswordReset,
+            Utc::now(),
+        )
+        .await?
+        .ok_or("Invalid or expired password reset token")?;
+
+    let password = payload.password;
+    let password_hash = web::block(move || hash(password, DEFAULT_COST)).await??;
+    repos
+        .users
+        .update_password(token.user_id, &password_hash)
+        .await?
+        .ok_or("No user found for this token")?;
+    repos.login_attempts.clear(token.user_id).await?;
+    // Whoever knew the old password is signed out everywhere.
+    repos.sessions.delete_all(token.user_id, None).await?;
+
+    Ok(())
+}
+
+// =============================================================================================================================
+
+/// Checks the credentials. Accounts with two-factor authentication get a challenge to complete with
+/// `two_factor_service::verify_challenge` instead of the access token.
+pub async fn login(
+    repos: &Repositories,
+    config: &Config,
+    client: ClientInfo,
+    payload: AuthLogin,
+) -> Result<LoginOutcome, Box<dyn Error>> {
+    payload.validate()?;
+
+    let user = repos
+        .users
+        .find_by_credential(&payload.credential)
+        .await
+        .map_err(|_| "Database error occurred")?;
+
+    let password_hash = user.as_ref().map(|user| user.password.clone());
+    let password_matches = verify_password(payload.password, password_hash).await?;
+
+    let Some(user) = user else {
+        audit_failed_login(repos, config, &client, None, "Unknown account").await;
+        return Err(INVALID_CREDENTIALS.into());
     };
+    let user_id = user.id.ok_or("The user has no id")?;
+
+    let now = Utc::now();
+    if let Err(e) = ensure_not_locked(repos, user_id, now).await {
+        audit_failed_login(repos, config, &client, Some(user_id), "Account locked").await;
+        return Err(e);
+    }
+    if !password_matches {
+        audit_failed_login(repos, config, &client, Some(user_id), "Wrong password").await;
+        return Err(
+            record_failed_login(repos, config, user_id, now, INVALID_CREDENTIALS.into()).await,
+        );
+    }
+    repos.login_attempts.clear(user_id).await?;
 
-    if let Err(_) | Ok(false) = verify(&payload.password, &user.password) {
-        thread::sleep(Duration::from_millis(300));
-        return Err("Invalid email or password".into());
+    if !user.verified {
+        return Err(Box::new(EmailNotVerified));
     }
 
-    let user_id = user.id.unwrap().to_hex();
+    complete_login(repos, config, &user, client).await
+}
+
+/// Signs in a user whose first factor was accepted: a challenge when the account has two-factor authentication,
+/// a new session otherwise.
+pub async fn complete_login(
+    repos: &Repositories,
+    config: &Config,
+    user: &User,
+    client: ClientInfo,
+) -> Result<LoginOutcome, Box<dyn Error>> {
+    let user_id = user.id.ok_or("The user has no id")?;
+    // Checked before the second factor too, so suspended users are not asked for a code in vain.
+    session_service::ensure_not_restricted(user, Utc::now())?;
 
-    let token = encode_external_jwt(user_id, user.role)?;
+    if repos
+        .two_factor
+        .find(user_id)
+        .await?
+        .is_some_and(|two_factor| two_factor.enabled)
+    {
+        return Ok(LoginOutcome::TwoFactorRequired(TwoFactorChallenge {
+            two_factor_required: true,
+            challenge_token: encode_challenge_jwt(&config.auth, user_id.to_hex())?,
+            expires_in: config.auth.two_factor_challenge_minutes * 60,
+        }));
+    }
+
+    let auth_response = session_service::start(repos, config, user, client).await?;
 
-    Ok(AuthResponse { token })
+    Ok(LoginOutcome::Authenticated(auth_response))
 }
 
 // =============================================================================================================================
 
-async fn create_basic_user(db: &Database, payload: AuthRegister) -> Result<User, Box<dyn Error>> {
+/// Fails with `AccountLocked` while failed logins keep the account locked.
+pub async fn ensure_not_locked(
+    repos: &Repositories,
+    user_id: ObjectId,
+    now: DateTime<Utc>,
+) -> Result<(), Box<dyn Error>> {
+    let locked_until = repos
+        .login_attempts
+        .find(user_id)
+        .await?
+        .and_then(|attempts| attempts.locked_until)
+        .filter(|locked_until| *locked_until > now);
+
+    match locked_until {
+        Some(locked_until) => Err(Box::new(AccountLocked::until(locked_until, now))),
+        None => Ok(()),
+    }
+}
+
+/// Counts a failed password or second factor towards the lockout and returns the error to report: `AccountLocked`
+/// when this failure locked the account, `error` otherwise.
+pub async fn record_failed_login(
+    repos: &Repositories,
+    config: &Config,
+    user_id: ObjectId,
+    now: DateTime<Utc>,
+    error: Box<dyn Error>,
+) -> Box<dyn Error> {
+    if config.auth.lockout_threshold == 0 {
+        return error;
+    }
+
+    let locked_until = now + Duration::minutes(config.auth.lockout_minutes);
+    match repos
+        .login_attempts
+        .record_failure(user_id, config.auth.lockout_threshold, locked_until)
+        .await
+    {
+        // The count restarts when the lock is set.
+        Ok(attempts)
+            if attempts.failures == 0 && attempts.locked_until.is_some_and(|until| until > now) =>
+        {
+            Box::new(AccountLocked::until(locked_until, now))
+        }
+        Ok(_) => error,
+        Err(e) => e,
+    }
+}
+
+/// Records a failed sign-in in the audit log. Nobody is signed in yet, so the account tried is the target.
+pub async fn audit_failed_login(
+    repos: &Repositories,
+    config: &Config,
+    client: &ClientInfo,
+    user_id: Option<ObjectId>,
+    reason: &str,
+) {
+    let actor = Actor {
+        user_id: None,
+        ip: client.ip.clone(),
+    };
+    audit_service::record(
+        repos,
+        config,
+        &actor,
+        AuditAction::LoginFailed,
+        user_id,
+        Some(reason.to_string()),
+    )
+    .await;
+}
+
+// =============================================================================================================================
+
+async fn create_basic_user(
+    repos: &Repositories,
+    payload: AuthRegister,
+) -> Result<User, Box<dyn Error>> {
     payload.validate()?;
 
-    let hashed_password = hash(&payload.password, DEFAULT_COST)?;
-    let mut user = User {
+    let password = payload.password;
+    let hashed_password = web::block(move || hash(password, DEFAULT_COST)).await??;
+    let user = User {
         id: None,
         username: payload.username,
         email: payload.email,
@@ -74,13 +432,61 @@ async fn create_basic_user(db: &Database, payload: AuthRegister) -> Result<User,
         bio: payload.bio,
         role: UserRole::User,
         location: payload.location,
+        verified: false,
+        status: AccountStatus::Active,
+        suspended_until: None,
     };
 
-    let collection: Collection<User> = db.collection("users");
-    let res = collection.insert_one(&user).await?;
-    user.id = res.inserted_id.as_object_id();
+    repos.users.insert(user).await
+}
+
+// =============================================================================================================================
+
+/// bcrypt is CPU bound, so it runs on the blocking thread pool instead of stalling an actix worker. Without a hash
+/// (unknown credential) the password is checked against a dummy one, so it takes as long as a wrong password.
+pub(crate) async fn verify_password(
+    password: String,
+    password_hash: Option<String>,
+) -> Result<bool, Box<dyn Error>> {
+    Ok(web::block(move || match password_hash {
+        // Hashes that are not bcrypt (disabled accounts) never match.
+        Some(password_hash) => verify(password, &password_hash).unwrap_or(false),
+        None => {
+            let _ = verify(password, &DUMMY_PASSWORD_HASH);
+            false
+        }
+    })
+    .await?)
+}
+
+// =============================================================================================================================
+
+/// Stores the hash of a new random token and returns the token itself, which only ever leaves in an email.
+async fn issue_token(
+    repos: &Repositories,
+    user_id: ObjectId,
+    purpose: TokenPurpose,
+    email: &str,
+    lifetime: Duration,
+) -> Result<String, Box<dyn Error>> {
+    let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+
+    repos
+        .auth_tokens
+        .replace(AuthToken {
+            token_hash: hash_token(&token),
+            user_id,
+            purpose,
+            email: email.to_string(),
+            expires_at: Utc::now() + lifetime,
+        })
+        .await?;
+
+    Ok(token)
+}
 
-    Ok(user)
+pub(crate) fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
 }
 
 // =============================================================================================================================
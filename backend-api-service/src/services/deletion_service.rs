@@ -0,0 +1,143 @@
+use crate::{
+    models::{deletion_model::DeletionReport, message_model::Media},
+    repositories::Repositories,
+    services::{file_service, media_service},
+    storage::StorageBackend,
+};
+use bson::oid::ObjectId;
+use std::{error::Error, str::FromStr};
+
+// =============================================================================================================================
+
+pub async fn delete_user(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    user_id: String,
+    dry_run: bool,
+) -> Result<DeletionReport, Box<dyn Error>> {
+    let user_id = ObjectId::from_str(&user_id)?;
+
+    if repos.users.find_by_id(user_id).await?.is_none() {
+        return Err("No user found with the given id".into());
+    }
+
+    let referenced_media = repos.deletions.user_media(user_id).await?;
+    let owned_media = repos.media.find_by_owner(user_id).await?;
+    let upload_sessions = repos.upload_sessions.find_by_owner(user_id).await?;
+    let pending_uploads = repos.pending_uploads.find_by_owner(user_id).await?;
+    let archive_urls: Vec<String> = repos
+        .exports
+        .find_by_user(user_id)
+        .await?
+        .into_iter()
+        .filter_map(|export| export.archive_url)
+        .collect();
+
+    let mut media_urls: Vec<String> = referenced_media
+        .iter()
+        .map(|media| media.url.clone())
+        .chain(owned_media.iter().map(|media| media.url.clone()))
+        .chain(archive_urls.iter().cloned())
+        .chain(
+            pending_uploads
+                .iter()
+                .map(|upload| storage.object_url(&upload.key)),
+        )
+        .collect();
+    media_urls.sort();
+    media_urls.dedup();
+
+    let mut report = repos.deletions.delete_user(user_id, dry_run).await?;
+    report.media = media_urls;
+
+    if dry_run {
+        return Ok(report);
+    }
+
+    report.media_failures = release_media(repos, storage, &referenced_media).await;
+    for media in owned_media {
+        if media_service::delete_record(repos, storage, &media)
+            .await
+            .is_err()
+        {
+            report.media_failures.push(media.url);
+        }
+    }
+    for archive_url in archive_urls {
+        if file_service::delete_file(storage, &archive_url)
+            .await
+            .is_err()
+        {
+            report.media_failures.push(archive_url);
+        }
+    }
+    for upload in pending_uploads {
+        if storage.delete(&upload.key).await.is_err() {
+            report.media_failures.push(storage.object_url(&upload.key));
+        }
+    }
+    for session in upload_sessions {
+        let _ = storage
+            .abort_multipart_upload(&session.key, &session.upload_id)
+            .await;
+    }
+
+    Ok(report)
+}
+
+// =============================================================================================================================
+
+pub async fn delete_group(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    group_id: String,
+    user_id: String,
+    dry_run: bool,
+) -> Result<DeletionReport, Box<dyn Error>> {
+    let group_id = ObjectId::from_str(&group_id)?;
+    let user_id = ObjectId::from_str(&user_id)?;
+
+    match repos.groups.find_by_id(group_id).await? {
+        Some(group) if group.creator_id == user_id => {}
+        _ => return Err("Group not found or user is not the creator".into()),
+    }
+
+    let referenced_media = repos.deletions.group_media(group_id).await?;
+
+    let mut report = repos.deletions.delete_group(group_id, dry_run).await?;
+    report.media = referenced_media
+        .iter()
+        .map(|media| media.url.clone())
+        .collect();
+
+    if dry_run {
+        return Ok(report);
+    }
+
+    report.media_failures = release_media(repos, storage, &referenced_media).await;
+
+    Ok(report)
+}
+
+// =============================================================================================================================
+
+async fn release_media(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    media: &[Media],
+) -> Vec<String> {
+    let mut failures = Vec::new();
+
+    for item in media {
+        if media_service::release_media(repos, storage, item)
+            .await
+            .is_err()
+        {
+            failures.push(item.url.clone());
+        }
+    }
+
+    failures
+}
+
+// =============================================================================================================================
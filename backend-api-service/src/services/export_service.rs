@@ -0,0 +1,441 @@
+use crate::{
+    models::export_model::{
+        DataExport, ExportResponse, ExportStatus, ExportedGroup, ExportedLocation, ExportedProfile,
+    },
+    repositories::Repositories,
+    services::file_service::{self, MULTIPART_PART_SIZE},
+    storage::StorageBackend,
+};
+use actix_web::web;
+use bson::oid::ObjectId;
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+use uuid::Uuid;
+use zip::{ZipWriter, result::ZipResult, write::SimpleFileOptions};
+
+// =============================================================================================================================
+
+const DOWNLOAD_LINK_LIFETIME_HOURS: i64 = 24;
+
+// A pending export older than this was abandoned by its build task, e.g. when the server restarted.
+const BUILD_TIMEOUT_MINUTES: i64 = 30;
+
+const ARCHIVE_CONTENT_TYPE: &str = "application/zip";
+
+// =============================================================================================================================
+
+pub async fn request_export(
+    repos: &Repositories,
+    storage: Arc<dyn StorageBackend>,
+    user_id: String,
+) -> Result<ExportResponse, Box<dyn Error>> {
+    let user_id = ObjectId::from_str(&user_id)?;
+
+    if let Some(pending) = repos.exports.find_pending(user_id).await? {
+        if !is_stale(&pending) {
+            return Err("An export is already being prepared for this user".into());
+        }
+        fail_stale(repos, pending).await?;
+    }
+
+    let export = DataExport {
+        id: None,
+        user_id,
+        status: ExportStatus::Pending,
+        archive_url: None,
+        download_token: None,
+        error: None,
+        created_at: Utc::now(),
+        expires_at: None,
+    };
+
+    let created_export = repos.exports.insert(export).await?;
+
+    let export_id = created_export.id.ok_or("Failed to create the export")?;
+    let repos = repos.clone();
+    actix_web::rt::spawn(async move {
+        build_export(&repos, storage.as_ref(), export_id, user_id).await;
+    });
+
+    Ok(to_response(created_export))
+}
+
+// =============================================================================================================================
+
+pub async fn get_export(
+    repos: &Repositories,
+    export_id: String,
+    user_id: String,
+) -> Result<ExportResponse, Box<dyn Error>> {
+    let export_id = ObjectId::from_str(&export_id)?;
+    let user_id = ObjectId::from_str(&user_id)?;
+
+    match repos.exports.find_for_user(export_id, user_id).await? {
+        Some(export) if is_stale(&export) => Ok(to_response(fail_stale(repos, export).await?)),
+        Some(export) => Ok(to_response(export)),
+        None => Err("Export not found".into()),
+    }
+}
+
+// =============================================================================================================================
+
+/// Returns a presigned URL of the archive, so that it is downloaded straight from storage.
+pub async fn download_export(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    export_id: String,
+    token: String,
+) -> Result<String, Box<dyn Error>> {
+    let export_id = ObjectId::from_str(&export_id)?;
+
+    let export = match repos.exports.find_ready(export_id, &token).await? {
+        Some(export) => export,
+        None => return Err("Export not found or link is invalid".into()),
+    };
+
+    let archive_url = export.archive_url.ok_or("Export archive is missing")?;
+
+    if export
+        .expires_at
+        .is_none_or(|expires_at| expires_at <= Utc::now())
+    {
+        repos.exports.delete(export_id).await?;
+        file_service::delete_file(storage, &archive_url).await?;
+        return Err("Export link has expired".into());
+    }
+
+    file_service::presign_download(storage, &archive_url)
+}
+
+// =============================================================================================================================
+
+/// Deletes the archives whose download link expired, claimed or not, and returns how many were removed.
+pub async fn sweep_expired(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+) -> Result<usize, Box<dyn Error>> {
+    let expired = repos.exports.find_expired(Utc::now()).await?;
+    let mut swept = 0;
+
+    for export in expired {
+        let Some(export_id) = export.id else {
+            continue;
+        };
+        if let Some(archive_url) = &export.archive_url {
+            if let Err(e) = file_service::delete_file(storage, archive_url).await {
+                eprintln!(
+                    "❌ Failed to delete the archive of export {}: {}",
+                    export_id, e
+                );
+                continue;
+            }
+        }
+        repos.exports.delete(export_id).await?;
+        swept += 1;
+    }
+
+    Ok(swept)
+}
+
+// =============================================================================================================================
+
+async fn build_export(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    export_id: ObjectId,
+    user_id: ObjectId,
+) {
+    let archive_path = std::env::temp_dir().join(format!("export-{}.zip", export_id.to_hex()));
+    let archive_url = match build_archive(repos, storage, user_id, archive_path.clone()).await {
+        Ok(()) => upload_archive(storage, &archive_path).await,
+        Err(e) => Err(e),
+    };
+    let removed: Result<(), Box<dyn Error>> =
+        match web::block(move || fs::remove_file(archive_path)).await {
+            Ok(Err(e)) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Ok(result) => result.map_err(Into::into),
+            Err(e) => Err(e.into()),
+        };
+    if let Err(e) = removed {
+        eprintln!(
+            "❌ Failed to remove the temporary archive of export {}: {}",
+            export_id, e
+        );
+    }
+
+    let result = match archive_url {
+        Ok(archive_url) => {
+            repos
+                .exports
+                .mark_ready(
+                    export_id,
+                    &archive_url,
+                    &Uuid::new_v4().simple().to_string(),
+                    Utc::now() + Duration::hours(DOWNLOAD_LINK_LIFETIME_HOURS),
+                )
+                .await
+        }
+        Err(e) => repos.exports.mark_failed(export_id, &e.to_string()).await,
+    };
+
+    if let Err(e) = result {
+        eprintln!(
+            "❌ Failed to record the outcome of export {}: {}",
+            export_id, e
+        );
+    }
+}
+
+// =============================================================================================================================
+
+fn is_stale(export: &DataExport) -> bool {
+    export.status == ExportStatus::Pending
+        && export.created_at + Duration::minutes(BUILD_TIMEOUT_MINUTES) <= Utc::now()
+}
+
+async fn fail_stale(
+    repos: &Repositories,
+    mut export: DataExport,
+) -> Result<DataExport, Box<dyn Error>> {
+    let error = "The export did not complete in time";
+    let export_id = export.id.ok_or("Export is missing its id")?;
+
+    repos.exports.mark_failed(export_id, error).await?;
+    export.status = ExportStatus::Failed;
+    export.error = Some(error.to_string());
+
+    Ok(export)
+}
+
+// =============================================================================================================================
+
+/// Writes the archive to a temporary file, media included, so that its size is bounded by the disk rather than by the
+/// memory of the worker. Media files are copied in ranged reads of `MULTIPART_PART_SIZE` bytes.
+async fn build_archive(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    user_id: ObjectId,
+    archive_path: PathBuf,
+) -> Result<(), Box<dyn Error>> {
+    let user = match repos.users.find_by_id(user_id).await? {
+        Some(user) => user,
+        None => return Err("No user found with the given id".into()),
+    };
+
+    let friendships = repos.friends.find_involving(&[user_id]).await?;
+
+    let memberships: Vec<ExportedGroup> = repos
+        .groups
+        .find_by_member(user_id)
+        .await?
+        .into_iter()
+        .map(|group| ExportedGroup {
+            id: group.id.map(|id| id.to_hex()).unwrap_or_default(),
+            name: group.name,
+            is_creator: group.creator_id == user_id,
+            member_count: group.members.len(),
+        })
+        .collect();
+
+    let sent_messages = repos.messages.find_by_sender(user_id).await?;
+    let user_stories = repos.stories.find_by_user(user_id).await?;
+    let library = repos.media.find_by_owner(user_id).await?;
+
+    let mut media_urls: Vec<String> = sent_messages
+        .iter()
+        .filter_map(|message| message.media.as_ref().map(|media| media.url.clone()))
+        .chain(user_stories.iter().map(|story| story.media.url.clone()))
+        .chain(library.iter().map(|media| media.url.clone()))
+        .collect();
+    media_urls.sort();
+    media_urls.dedup();
+
+    let profile = ExportedProfile {
+        id: user_id.to_hex(),
+        username: user.username,
+        email: user.email,
+        role: user.role,
+        bio: user.bio,
+        avatar: user.avatar,
+    };
+    let location = ExportedLocation {
+        location: user.location,
+    };
+
+    let file = web::block(move || File::create(archive_path)).await??;
+    let mut zip = ZipWriter::new(file);
+
+    zip = write_json(zip, "profile.json", &profile).await?;
+    zip = write_json(zip, "friendships.json", &friendships).await?;
+    zip = write_json(zip, "groups.json", &memberships).await?;
+    zip = write_json(zip, "messages.json", &sent_messages).await?;
+    zip = write_json(zip, "stories.json", &user_stories).await?;
+    zip = write_json(zip, "location.json", &location).await?;
+
+    for url in media_urls {
+        let Some(key) = storage.object_key_from_url(&url) else {
+            continue;
+        };
+        let Ok(metadata) = storage.head(key).await else {
+            continue;
+        };
+        let file_name = format!("media/{}", url.rsplit('/').next().unwrap_or_default());
+        zip = write_archive(zip, move |zip| {
+            zip.start_file(file_name, SimpleFileOptions::default())
+        })
+        .await?;
+
+        let mut offset = 0;
+        while offset < metadata.size {
+            let chunk = match storage
+                .get_range(key, offset, MULTIPART_PART_SIZE as u64)
+                .await
+            {
+                Ok(chunk) if !chunk.is_empty() => chunk,
+                // A file that cannot be read whole is left out rather than truncated.
+                _ => {
+                    zip = write_archive(zip, |zip| zip.abort_file()).await?;
+                    break;
+                }
+            };
+            offset += chunk.len() as u64;
+            zip = write_archive(zip, move |zip| Ok(zip.write_all(&chunk)?)).await?;
+        }
+    }
+
+    web::block(move || zip.finish().map(|_| ())).await??;
+
+    Ok(())
+}
+
+// =============================================================================================================================
+
+async fn write_json<T: Serialize>(
+    zip: ZipWriter<File>,
+    name: &'static str,
+    value: &T,
+) -> Result<ZipWriter<File>, Box<dyn Error>> {
+    let data = serde_json::to_vec_pretty(value)?;
+    write_archive(zip, move |zip| {
+        zip.start_file(name, SimpleFileOptions::default())?;
+        Ok(zip.write_all(&data)?)
+    })
+    .await
+}
+
+/// Runs a write to the archive off the async workers, since it goes to disk.
+async fn write_archive<F>(
+    mut zip: ZipWriter<File>,
+    write: F,
+) -> Result<ZipWriter<File>, Box<dyn Error>>
+where
+    F: FnOnce(&mut ZipWriter<File>) -> ZipResult<()> + Send + 'static,
+{
+    let (zip, result) = web::block(move || {
+        let result = write(&mut zip);
+        (zip, result)
+    })
+    .await?;
+    result?;
+    Ok(zip)
+}
+
+// =============================================================================================================================
+
+/// Sends the archive to storage as a multipart upload, one part in memory at a time, and returns its URL.
+async fn upload_archive(
+    storage: &dyn StorageBackend,
+    archive_path: &Path,
+) -> Result<String, Box<dyn Error>> {
+    let key = format!(
+        "{}.{}",
+        Uuid::new_v4(),
+        file_service::file_extension_from_type(ARCHIVE_CONTENT_TYPE)
+    );
+    let upload_id = storage
+        .create_multipart_upload(&key, ARCHIVE_CONTENT_TYPE)
+        .await?;
+
+    let uploaded = match upload_parts(storage, &key, &upload_id, archive_path).await {
+        Ok(parts) => {
+            storage
+                .complete_multipart_upload(&key, &upload_id, &parts)
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    if uploaded.is_err() {
+        let _ = storage.abort_multipart_upload(&key, &upload_id).await;
+    }
+
+    uploaded
+}
+
+async fn upload_parts(
+    storage: &dyn StorageBackend,
+    key: &str,
+    upload_id: &str,
+    archive_path: &Path,
+) -> Result<Vec<(i32, String)>, Box<dyn Error>> {
+    let archive_path = archive_path.to_path_buf();
+    let mut file = web::block(move || File::open(archive_path)).await??;
+    let mut parts = Vec::new();
+
+    loop {
+        let (returned, data) = web::block(move || {
+            let mut data = Vec::with_capacity(MULTIPART_PART_SIZE);
+            let result = (&mut file)
+                .take(MULTIPART_PART_SIZE as u64)
+                .read_to_end(&mut data)
+                .map(|_| data);
+            (file, result)
+        })
+        .await?;
+        file = returned;
+        let data = data?;
+
+        if data.is_empty() && !parts.is_empty() {
+            return Ok(parts);
+        }
+        let last = data.len() < MULTIPART_PART_SIZE;
+
+        let part_number = parts.len() as i32 + 1;
+        let etag = storage
+            .upload_part(key, upload_id, part_number, data)
+            .await?;
+        parts.push((part_number, etag));
+
+        if last {
+            return Ok(parts);
+        }
+    }
+}
+
+// =============================================================================================================================
+
+fn to_response(export: DataExport) -> ExportResponse {
+    let id = export.id.map(|id| id.to_hex()).unwrap_or_default();
+    let download_url = export
+        .download_token
+        .as_ref()
+        .map(|token| format!("/api/users/me/export/{}/download?token={}", id, token));
+
+    ExportResponse {
+        id,
+        status: export.status,
+        download_url,
+        error: export.error,
+        created_at: export.created_at,
+        expires_at: export.expires_at,
+    }
+}
+
+// =============================================================================================================================
@@ -1,75 +1,246 @@
-use once_cell::sync::Lazy;
-use reqwest::{Client, StatusCode};
-use std::{env, error::Error};
+use crate::{
+    config::Config,
+    models::{
+        media_model::{ObjectMetadata, PresignedFileQueryParams, StoredImage},
+        message_model::Media,
+    },
+    storage::{PresignMethod, StorageBackend},
+    utils::image_processing::{self, ProcessedImage},
+};
+use actix_web::web::{self, Bytes};
+use futures_util::{Stream, StreamExt};
+use std::{error::Error, fmt::Display};
 use uuid::Uuid;
 
 // =============================================================================================================================
 
-static MINIO_URL: Lazy<String> =
-    Lazy::new(|| env::var("MINIO_SERVER_URL").expect("MINIO_SERVER_URL not set"));
-static MINIO_ACCESS_KEY: Lazy<String> =
-    Lazy::new(|| env::var("MINIO_ROOT_USER").expect("MINIO_ROOT_USER not set"));
-static MINIO_SECRET_KEY: Lazy<String> =
-    Lazy::new(|| env::var("MINIO_ROOT_PASSWORD").expect("MINIO_ROOT_PASSWORD not set"));
-const BUCKET_NAME: &str = "snapshoot-media";
+pub const PRESIGNED_UPLOAD_LIFETIME_SECS: u64 = 15 * 60;
+pub const PRESIGNED_DOWNLOAD_LIFETIME_SECS: u64 = 15 * 60;
+pub const MULTIPART_PART_SIZE: usize = 5 * 1024 * 1024;
 
 // =============================================================================================================================
 
-pub async fn upload_file(file_data: &[u8], content_type: &str) -> Result<String, Box<dyn Error>> {
-    let file_name = format!(
+pub async fn upload_file(
+    storage: &dyn StorageBackend,
+    file_data: &[u8],
+    content_type: &str,
+) -> Result<String, Box<dyn Error>> {
+    let key = format!(
         "{}.{}",
         Uuid::new_v4(),
         file_extension_from_type(content_type)
     );
-    let url = format!("{}/{}/{}", *MINIO_URL, BUCKET_NAME, file_name);
-
-    let client = Client::new();
-    let response = client
-        .put(&url)
-        .header("Content-Type", content_type)
-        .body(file_data.to_vec())
-        .basic_auth(&*MINIO_ACCESS_KEY, Some(&*MINIO_SECRET_KEY))
-        .send()
-        .await?;
+    storage.put(&key, file_data.to_vec(), content_type).await
+}
 
-    if response.status() != StatusCode::OK
-        && response.status() != StatusCode::CREATED
-        && response.status() != StatusCode::NO_CONTENT
-    {
-        return Err(format!(
-            "Failed to upload file: {} - {}",
-            response.status(),
-            response.text().await?
+// =============================================================================================================================
+
+pub async fn process_image(
+    file_data: &[u8],
+    orientation: Option<i32>,
+) -> Result<ProcessedImage, Box<dyn Error>> {
+    let data = file_data.to_vec();
+    let processed = web::block(move || image_processing::process_image(&data, orientation))
+        .await?
+        .map_err(|e| format!("Failed to process image: {}", e))?;
+
+    Ok(processed)
+}
+
+// =============================================================================================================================
+
+pub async fn upload_image(
+    storage: &dyn StorageBackend,
+    processed: &ProcessedImage,
+) -> Result<StoredImage, Box<dyn Error>> {
+    let id = Uuid::new_v4();
+    let original = &processed.original;
+
+    let url = storage
+        .put(
+            &format!("{}.{}", id, file_extension_from_type(original.content_type)),
+            original.data.clone(),
+            original.content_type,
         )
-        .into());
+        .await?;
+
+    let mut rendition_urls = Vec::new();
+    for (suffix, rendition) in [("feed", &processed.feed), ("thumb", &processed.thumbnail)] {
+        let key = format!(
+            "{}_{}.{}",
+            id,
+            suffix,
+            file_extension_from_type(rendition.content_type)
+        );
+        match storage
+            .put(&key, rendition.data.clone(), rendition.content_type)
+            .await
+        {
+            Ok(rendition_url) => rendition_urls.push(rendition_url),
+            Err(e) => {
+                for uploaded in std::iter::once(&url).chain(&rendition_urls) {
+                    let _ = delete_file(storage, uploaded).await;
+                }
+                return Err(e);
+            }
+        }
     }
+    let thumbnail_url = rendition_urls.pop().unwrap_or_default();
+    let feed_url = rendition_urls.pop().unwrap_or_default();
+
+    Ok(StoredImage {
+        url,
+        feed_url,
+        thumbnail_url,
+        content_type: original.content_type.to_string(),
+        size: processed.size(),
+        width: original.width as i32,
+        height: original.height as i32,
+    })
+}
+
+// =============================================================================================================================
+
+pub async fn download_file(
+    storage: &dyn StorageBackend,
+    file_url: &str,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let key = storage
+        .object_key_from_url(file_url)
+        .ok_or("Invalid file URL")?;
+    storage.get(key).await
+}
+
+// =============================================================================================================================
+
+pub async fn delete_file(
+    storage: &dyn StorageBackend,
+    file_url: &str,
+) -> Result<(), Box<dyn Error>> {
+    let key = storage
+        .object_key_from_url(file_url)
+        .ok_or("Invalid file URL")?;
+    storage.delete(key).await
+}
+
+// =============================================================================================================================
+
+pub fn new_object_key(owner_id: &str, content_type: &str) -> String {
+    format!(
+        "{}/{}.{}",
+        owner_id,
+        Uuid::new_v4(),
+        file_extension_from_type(content_type)
+    )
+}
+
+// =============================================================================================================================
+
+/// The URL only accepts a body of exactly `size` bytes sent with `content_type`.
+pub fn presign_upload(
+    storage: &dyn StorageBackend,
+    owner_id: &str,
+    content_type: &str,
+    size: usize,
+) -> Result<(String, String), Box<dyn Error>> {
+    let key = new_object_key(owner_id, content_type);
+    let method = PresignMethod::Put {
+        content_type,
+        content_length: size as u64,
+    };
+    let url = storage.presign(method, &key, PRESIGNED_UPLOAD_LIFETIME_SECS)?;
+
+    Ok((key, url))
+}
+
+// =============================================================================================================================
 
-    Ok(url)
+pub fn presign_download(
+    storage: &dyn StorageBackend,
+    file_url: &str,
+) -> Result<String, Box<dyn Error>> {
+    let key = storage
+        .object_key_from_url(file_url)
+        .ok_or("Invalid file URL")?;
+    storage.presign(PresignMethod::Get, key, PRESIGNED_DOWNLOAD_LIFETIME_SECS)
 }
 
 // =============================================================================================================================
 
-pub async fn delete_file(file_url: &str) -> Result<(), Box<dyn Error>> {
-    if !file_url.starts_with(&*MINIO_URL) {
-        return Err("Invalid file URL".into());
+pub fn presign_media(storage: &dyn StorageBackend, media: &mut Media) {
+    if let Ok(url) = presign_download(storage, &media.url) {
+        media.url = url;
     }
+    for rendition in [&mut media.feed_url, &mut media.thumbnail_url]
+        .into_iter()
+        .flatten()
+    {
+        if let Ok(url) = presign_download(storage, rendition) {
+            *rendition = url;
+        }
+    }
+}
 
-    let client = Client::new();
-    let response = client
-        .delete(file_url)
-        .basic_auth(&*MINIO_ACCESS_KEY, Some(&*MINIO_SECRET_KEY))
-        .send()
-        .await?;
+// =============================================================================================================================
 
-    if response.status() != StatusCode::OK && response.status() != StatusCode::NO_CONTENT {
-        return Err(format!(
-            "Failed to delete file: {} - {}",
-            response.status(),
-            response.text().await?
-        )
-        .into());
+/// Serves a presigned download for backends whose presigned URLs point at the API (`/api/files`).
+pub async fn read_presigned(
+    storage: &dyn StorageBackend,
+    key: &str,
+    expires: i64,
+    signature: &str,
+) -> Result<(ObjectMetadata, Vec<u8>), Box<dyn Error>> {
+    if !storage.verify_presigned(PresignMethod::Get, key, expires, signature) {
+        return Err("The link is invalid or has expired".into());
     }
 
+    let metadata = storage.head(key).await?;
+    let data = storage.get(key).await?;
+
+    Ok((metadata, data))
+}
+
+// =============================================================================================================================
+
+pub async fn write_presigned<S, E>(
+    storage: &dyn StorageBackend,
+    config: &Config,
+    key: &str,
+    params: &PresignedFileQueryParams,
+    content_type: &str,
+    content_length: Option<u64>,
+    mut stream: S,
+) -> Result<(), Box<dyn Error>>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Display,
+{
+    let content_length = content_length.ok_or("The Content-Length header is required")?;
+    let method = PresignMethod::Put {
+        content_type,
+        content_length,
+    };
+    if !storage.verify_presigned(method, key, params.expires, &params.signature) {
+        return Err("The link is invalid or has expired".into());
+    }
+    // The signed length was checked at presign time, but the limits may have been lowered since.
+    let content_length_bytes =
+        usize::try_from(content_length).map_err(|_| "The file is too large")?;
+    validate_file_size(config, content_type, content_length_bytes)?;
+
+    let mut data = Vec::with_capacity(content_length_bytes);
+    while let Some(chunk) = stream.next().await {
+        data.extend_from_slice(&chunk.map_err(|e| e.to_string())?);
+        if data.len() > content_length_bytes {
+            return Err("The file is too large".into());
+        }
+    }
+    if data.len() as u64 != content_length {
+        return Err("The file does not match its Content-Length".into());
+    }
+
+    storage.put(key, data, content_type).await?;
+
     Ok(())
 }
 
@@ -80,22 +251,33 @@ pub fn file_extension_from_type(content_type: &str) -> &str {
         "image/jpeg" => "jpg",
         "image/png" => "png",
         "image/gif" => "gif",
+        "image/webp" => "webp",
         "video/mp4" => "mp4",
+        "video/quicktime" => "mov",
         "video/webm" => "webm",
         "video/ogg" => "ogv",
+        "application/zip" => "zip",
         _ => "bin",
     }
 }
 
 // =============================================================================================================================
 
-pub fn validate_file_size(content_type: &str, size: usize) -> Result<(), String> {
-    if content_type.starts_with("video/") && size > 10 * 1024 * 1024 {
-        return Err("Videos must be under 10MB (approximately 10 seconds)".to_string());
+pub fn validate_file_size(config: &Config, content_type: &str, size: usize) -> Result<(), String> {
+    let limits = &config.uploads;
+
+    if content_type.starts_with("video/") && size > limits.max_video_size() {
+        return Err(format!(
+            "Videos must be under {}MB",
+            limits.max_video_size_mb
+        ));
     }
 
-    if content_type.starts_with("image/") && size > 5 * 1024 * 1024 {
-        return Err("Images must be under 5MB".to_string());
+    if content_type.starts_with("image/") && size > limits.max_image_size() {
+        return Err(format!(
+            "Images must be under {}MB",
+            limits.max_image_size_mb
+        ));
     }
 
     Ok(())
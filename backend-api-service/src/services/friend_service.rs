@@ -1,35 +1,47 @@
-use crate::models::{
-    friend_model::{Friend, FriendStatus},
-    user_model::User,
+use crate::{
+    models::{
+        friend_model::{
+            Friend, FriendStatus, FriendSuggestion, SuggestionQueryParams, UserSearchParams,
+        },
+        user_model::{PublicProfile, User},
+    },
+    repositories::{Repositories, distance_meters},
 };
 use bson::oid::ObjectId;
-use futures_util::TryStreamExt;
-use mongodb::{Collection, Database, bson::doc, options::ReturnDocument};
-use std::{error::Error, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    str::FromStr,
+};
 
 // =============================================================================================================================
 
-const COLLECTION_NAME: &str = "friends";
-const USER_COLLECTION_NAME: &str = "users";
+const MIN_SEARCH_CHARS: usize = 2;
+const DEFAULT_SEARCH_LIMIT: i64 = 20;
+const MAX_SEARCH_LIMIT: i64 = 50;
+// Usernames sharing a trigram with the search, ranked in memory.
+const FUZZY_CANDIDATES: i64 = 200;
+// Share of trigrams in common from which a username is a fuzzy match, as in PostgreSQL's pg_trgm.
+const FUZZY_THRESHOLD: f64 = 0.3;
+
+const DEFAULT_SUGGESTIONS_LIMIT: i64 = 20;
+const MAX_SUGGESTIONS_LIMIT: i64 = 50;
+const SUGGESTION_RADIUS_METERS: f64 = 25_000.0;
+const NEARBY_CANDIDATES: i64 = 100;
+const MUTUAL_FRIEND_WEIGHT: f64 = 3.0;
+const SHARED_GROUP_WEIGHT: f64 = 2.0;
+// Earned in full next door, down to nothing at the edge of the radius.
+const PROXIMITY_WEIGHT: f64 = 2.0;
 
 // =============================================================================================================================
 
-pub async fn get_friends(db: &Database, user_id: String) -> Result<Vec<User>, Box<dyn Error>> {
+pub async fn get_friends(
+    repos: &Repositories,
+    user_id: String,
+) -> Result<Vec<User>, Box<dyn Error>> {
     let user_id = ObjectId::from_str(&user_id)?;
-    let collection: Collection<Friend> = db.collection(COLLECTION_NAME);
-
-    let filter = doc! {
-        "$or": [
-            { "user_id": user_id },
-            { "friend_id": user_id }
-        ],
-        "status": "Accepted"
-    };
+    let friend_docs = repos.friends.find_accepted(user_id).await?;
 
-    let friends = collection.find(filter).await?;
-    let friend_docs: Vec<Friend> = friends.try_collect().await?;
-
-    let user_collection: Collection<User> = db.collection(USER_COLLECTION_NAME);
     let mut result = Vec::new();
 
     for friend in friend_docs {
@@ -38,7 +50,7 @@ pub async fn get_friends(db: &Database, user_id: String) -> Result<Vec<User>, Bo
         } else {
             friend.user_id
         };
-        if let Some(user) = user_collection.find_one(doc! { "_id": friend_id }).await? {
+        if let Some(user) = repos.users.find_by_id(friend_id).await? {
             result.push(user);
         }
     }
@@ -49,29 +61,20 @@ pub async fn get_friends(db: &Database, user_id: String) -> Result<Vec<User>, Bo
 // =============================================================================================================================
 
 pub async fn get_friend_requests(
-    db: &Database,
+    repos: &Repositories,
     user_id: String,
 ) -> Result<Vec<Friend>, Box<dyn Error>> {
     let user_id = ObjectId::from_str(&user_id)?;
-    let collection: Collection<Friend> = db.collection(COLLECTION_NAME);
-
-    let filter = doc! {
-        "friend_id": user_id,
-        "status": "Pending"
-    };
-
-    let requests = collection.find(filter).await?;
-    let result: Vec<Friend> = requests.try_collect().await?;
-
-    Ok(result)
+    repos.friends.find_pending_for(user_id).await
 }
 
 // =============================================================================================================================
 
-pub async fn find_user_by_email(db: &Database, email: String) -> Result<User, Box<dyn Error>> {
-    let collection: Collection<User> = db.collection(USER_COLLECTION_NAME);
-
-    match collection.find_one(doc! { "email": email }).await? {
+pub async fn find_user_by_email(
+    repos: &Repositories,
+    email: String,
+) -> Result<User, Box<dyn Error>> {
+    match repos.users.find_by_email(&email).await? {
         Some(user) => Ok(user),
         None => Err("No user found with the given email".into()),
     }
@@ -79,11 +82,13 @@ pub async fn find_user_by_email(db: &Database, email: String) -> Result<User, Bo
 
 // =============================================================================================================================
 
-pub async fn find_user_by_id(db: &Database, user_id: String) -> Result<User, Box<dyn Error>> {
-    let collection: Collection<User> = db.collection(USER_COLLECTION_NAME);
+pub async fn find_user_by_id(
+    repos: &Repositories,
+    user_id: String,
+) -> Result<User, Box<dyn Error>> {
     let id = ObjectId::from_str(&user_id)?;
 
-    match collection.find_one(doc! { "_id": id }).await? {
+    match repos.users.find_by_id(id).await? {
         Some(user) => Ok(user),
         None => Err("No user found with the given id".into()),
     }
@@ -91,8 +96,193 @@ pub async fn find_user_by_id(db: &Database, user_id: String) -> Result<User, Box
 
 // =============================================================================================================================
 
+/// Usernames starting with the search first, alphabetically, then those close to it, most similar first. Banned
+/// users and the user searching are left out.
+pub async fn search_users(
+    repos: &Repositories,
+    user_id: String,
+    params: UserSearchParams,
+) -> Result<Vec<PublicProfile>, Box<dyn Error>> {
+    let user_id = ObjectId::from_str(&user_id)?;
+    let query = params.q.trim().to_lowercase();
+    if query.chars().count() < MIN_SEARCH_CHARS {
+        return Err(format!(
+            "The search must be at least {} characters long",
+            MIN_SEARCH_CHARS
+        )
+        .into());
+    }
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+
+    let mut results = repos
+        .users
+        .find_by_username_prefix(&query, limit + 1)
+        .await?;
+
+    let query_trigrams = trigrams(&query);
+    let inner_trigrams: Vec<String> = query_trigrams
+        .iter()
+        .filter(|gram| !gram.contains(' '))
+        .cloned()
+        .collect();
+    let mut fuzzy: Vec<(f64, User)> = repos
+        .users
+        .find_by_username_grams(&inner_trigrams, FUZZY_CANDIDATES)
+        .await?
+        .into_iter()
+        .filter(|user| !user.username.starts_with(&query))
+        .map(|user| (similarity(&query_trigrams, &trigrams(&user.username)), user))
+        .filter(|(similarity, _)| *similarity >= FUZZY_THRESHOLD)
+        .collect();
+    fuzzy.sort_by(|(a, a_user), (b, b_user)| {
+        b.total_cmp(a)
+            .then_with(|| a_user.username.cmp(&b_user.username))
+    });
+    results.extend(fuzzy.into_iter().map(|(_, user)| user));
+
+    let ids: Vec<ObjectId> = results.iter().filter_map(|user| user.id).collect();
+    let banned = repos.users.find_banned(&ids).await?;
+    results.retain(|user| {
+        user.id
+            .is_some_and(|id| id != user_id && !banned.contains(&id))
+    });
+    results.truncate(limit as usize);
+
+    Ok(results.into_iter().map(PublicProfile::from).collect())
+}
+
+// =============================================================================================================================
+
+/// People the user may know, best first: friends of friends, members of the same groups and users nearby. Friends,
+/// pending requests either way and banned users are not suggested.
+pub async fn get_suggestions(
+    repos: &Repositories,
+    user_id: String,
+    params: SuggestionQueryParams,
+) -> Result<Vec<FriendSuggestion>, Box<dyn Error>> {
+    let user_id = ObjectId::from_str(&user_id)?;
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_SUGGESTIONS_LIMIT)
+        .clamp(1, MAX_SUGGESTIONS_LIMIT);
+    let user = repos
+        .users
+        .find_by_id(user_id)
+        .await?
+        .ok_or("User not found")?;
+
+    let relationships = repos.friends.find_involving(&[user_id]).await?;
+    let friend_ids: Vec<ObjectId> = relationships
+        .iter()
+        .filter(|friend| friend.status == FriendStatus::Accepted)
+        .map(|friend| other_user(friend, user_id))
+        .collect();
+    let mut excluded: HashSet<ObjectId> = relationships
+        .iter()
+        .map(|friend| other_user(friend, user_id))
+        .collect();
+    excluded.insert(user_id);
+
+    // Mutual friends and shared groups of each candidate.
+    let mut candidates: HashMap<ObjectId, (u64, u64)> = HashMap::new();
+
+    if !friend_ids.is_empty() {
+        for friendship in repos.friends.find_involving(&friend_ids).await? {
+            if friendship.status != FriendStatus::Accepted {
+                continue;
+            }
+            let candidate = match (
+                friend_ids.contains(&friendship.user_id),
+                friend_ids.contains(&friendship.friend_id),
+            ) {
+                (true, false) => friendship.friend_id,
+                (false, true) => friendship.user_id,
+                _ => continue,
+            };
+            if !excluded.contains(&candidate) {
+                candidates.entry(candidate).or_default().0 += 1;
+            }
+        }
+    }
+
+    for group in repos.groups.find_by_member(user_id).await? {
+        for member in group.members {
+            if !excluded.contains(&member) {
+                candidates.entry(member).or_default().1 += 1;
+            }
+        }
+    }
+
+    let mut users: HashMap<ObjectId, User> = HashMap::new();
+    for nearby in repos
+        .users
+        .find_nearby(
+            user_id,
+            user.location.coordinates,
+            SUGGESTION_RADIUS_METERS,
+            NEARBY_CANDIDATES,
+        )
+        .await?
+    {
+        if let Some(id) = nearby.id.filter(|id| !excluded.contains(id)) {
+            candidates.entry(id).or_default();
+            users.insert(id, nearby);
+        }
+    }
+
+    let missing: Vec<ObjectId> = candidates
+        .keys()
+        .filter(|id| !users.contains_key(id))
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        for candidate in repos.users.find_by_ids(&missing).await? {
+            if let Some(id) = candidate.id {
+                users.insert(id, candidate);
+            }
+        }
+    }
+
+    let ids: Vec<ObjectId> = candidates.keys().copied().collect();
+    let banned = repos.users.find_banned(&ids).await?;
+
+    let mut suggestions: Vec<FriendSuggestion> = candidates
+        .into_iter()
+        .filter(|(id, _)| !banned.contains(id))
+        .filter_map(|(id, (mutual_friends, shared_groups))| {
+            let candidate = users.remove(&id)?;
+            let distance =
+                distance_meters(user.location.coordinates, candidate.location.coordinates);
+            let proximity = (1.0 - distance / SUGGESTION_RADIUS_METERS).max(0.0);
+            Some(FriendSuggestion {
+                user: PublicProfile::from(candidate),
+                mutual_friends,
+                shared_groups,
+                distance_km: (distance <= SUGGESTION_RADIUS_METERS)
+                    .then(|| (distance / 1000.0).ceil().max(1.0) as u32),
+                score: mutual_friends as f64 * MUTUAL_FRIEND_WEIGHT
+                    + shared_groups as f64 * SHARED_GROUP_WEIGHT
+                    + proximity * PROXIMITY_WEIGHT,
+            })
+        })
+        .collect();
+    suggestions.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.user.username.cmp(&b.user.username))
+    });
+    suggestions.truncate(limit as usize);
+
+    Ok(suggestions)
+}
+
+// =============================================================================================================================
+
 pub async fn send_friend_request(
-    db: &Database,
+    repos: &Repositories,
     user_id: String,
     friend_id: String,
 ) -> Result<Friend, Box<dyn Error>> {
@@ -103,16 +293,12 @@ pub async fn send_friend_request(
         return Err("Cannot send friend request to yourself".into());
     }
 
-    let collection: Collection<Friend> = db.collection(COLLECTION_NAME);
-
-    let existing_filter = doc! {
-        "$or": [
-            { "user_id": user_id, "friend_id": friend_id },
-            { "user_id": friend_id, "friend_id": user_id }
-        ]
-    };
-
-    if (collection.find_one(existing_filter).await?).is_some() {
+    if repos
+        .friends
+        .find_between(user_id, friend_id)
+        .await?
+        .is_some()
+    {
         return Err("Friend request already exists or users are already friends".into());
     }
 
@@ -123,42 +309,20 @@ pub async fn send_friend_request(
         friend_id,
     };
 
-    let result = collection.insert_one(&friend).await?;
-    let mut created_friend = friend;
-    created_friend.id = result.inserted_id.as_object_id();
-
-    Ok(created_friend)
+    repos.friends.insert(friend).await
 }
 
 // =============================================================================================================================
 
 pub async fn accept_friend_request(
-    db: &Database,
+    repos: &Repositories,
     request_id: String,
     user_id: String,
 ) -> Result<Friend, Box<dyn Error>> {
     let request_id = ObjectId::from_str(&request_id)?;
     let user_id = ObjectId::from_str(&user_id)?;
 
-    let collection: Collection<Friend> = db.collection(COLLECTION_NAME);
-
-    let filter = doc! {
-        "_id": request_id,
-        "friend_id": user_id,
-        "status": "Pending"
-    };
-
-    let update = doc! {
-        "$set": {
-            "status": "Accepted"
-        }
-    };
-
-    match collection
-        .find_one_and_update(filter, update)
-        .return_document(ReturnDocument::After)
-        .await?
-    {
+    match repos.friends.accept(request_id, user_id).await? {
         Some(friend) => Ok(friend),
         None => Err("Friend request not found or already accepted".into()),
     }
@@ -167,26 +331,45 @@ pub async fn accept_friend_request(
 // =============================================================================================================================
 
 pub async fn delete_friend(
-    db: &Database,
+    repos: &Repositories,
     user_id: String,
     friend_id: String,
 ) -> Result<Friend, Box<dyn Error>> {
     let user_id = ObjectId::from_str(&user_id)?;
     let friend_id = ObjectId::from_str(&friend_id)?;
 
-    let collection: Collection<Friend> = db.collection(COLLECTION_NAME);
-
-    let filter = doc! {
-        "$or": [
-            { "user_id": user_id, "friend_id": friend_id },
-            { "user_id": friend_id, "friend_id": user_id }
-        ]
-    };
-
-    match collection.find_one_and_delete(filter).await? {
+    match repos.friends.delete_between(user_id, friend_id).await? {
         Some(friend) => Ok(friend),
         None => Err("Friend relationship not found".into()),
     }
 }
 
 // =============================================================================================================================
+
+fn other_user(friend: &Friend, user_id: ObjectId) -> ObjectId {
+    if friend.user_id == user_id {
+        friend.friend_id
+    } else {
+        friend.user_id
+    }
+}
+
+/// Trigrams of a word padded as pg_trgm does, so that its first and last letters weigh more.
+fn trigrams(word: &str) -> HashSet<String> {
+    let padded: Vec<char> = format!("  {} ", word).chars().collect();
+    padded
+        .windows(3)
+        .map(|gram| gram.iter().collect())
+        .collect()
+}
+
+/// Trigrams in common over trigrams in either, from 0 to 1.
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+// =============================================================================================================================
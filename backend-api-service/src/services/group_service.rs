@@ -1,47 +1,32 @@
-use crate::models::group_model::{AddGroupMembers, CreateGroup, Group, UpdateGroup};
+use crate::{
+    models::group_model::{AddGroupMembers, CreateGroup, Group, UpdateGroup},
+    repositories::Repositories,
+};
 use bson::oid::ObjectId;
-use futures_util::TryStreamExt;
-use mongodb::{Collection, Database, bson::doc, options::ReturnDocument};
 use std::{error::Error, str::FromStr};
 use validator::Validate;
 
 // =============================================================================================================================
 
-const COLLECTION_NAME: &str = "groups";
-
-// =============================================================================================================================
-
-pub async fn get_user_groups(db: &Database, user_id: String) -> Result<Vec<Group>, Box<dyn Error>> {
+pub async fn get_user_groups(
+    repos: &Repositories,
+    user_id: String,
+) -> Result<Vec<Group>, Box<dyn Error>> {
     let user_id = ObjectId::from_str(&user_id)?;
-    let collection: Collection<Group> = db.collection(COLLECTION_NAME);
-
-    let filter = doc! {
-        "members": user_id
-    };
-
-    let cursor = collection.find(filter).await?;
-    let groups: Vec<Group> = cursor.try_collect().await?;
-
-    Ok(groups)
+    repos.groups.find_by_member(user_id).await
 }
 
 // =============================================================================================================================
 
 pub async fn get_group_by_id(
-    db: &Database,
+    repos: &Repositories,
     group_id: String,
     user_id: String,
 ) -> Result<Group, Box<dyn Error>> {
     let group_id = ObjectId::from_str(&group_id)?;
     let user_id = ObjectId::from_str(&user_id)?;
-    let collection: Collection<Group> = db.collection(COLLECTION_NAME);
 
-    let filter = doc! {
-        "_id": group_id,
-        "members": user_id
-    };
-
-    match collection.find_one(filter).await? {
+    match repos.groups.find_for_member(group_id, user_id).await? {
         Some(group) => Ok(group),
         None => Err("Group not found or user is not a member".into()),
     }
@@ -50,14 +35,13 @@ pub async fn get_group_by_id(
 // =============================================================================================================================
 
 pub async fn create_group(
-    db: &Database,
+    repos: &Repositories,
     payload: CreateGroup,
     creator_id: String,
 ) -> Result<Group, Box<dyn Error>> {
     payload.validate()?;
 
     let creator_id = ObjectId::from_str(&creator_id)?;
-    let collection: Collection<Group> = db.collection(COLLECTION_NAME);
 
     let mut members = vec![creator_id];
     for member_id in payload.members {
@@ -75,17 +59,13 @@ pub async fn create_group(
         members,
     };
 
-    let result = collection.insert_one(&group).await?;
-    let mut created_group = group;
-    created_group.id = result.inserted_id.as_object_id();
-
-    Ok(created_group)
+    repos.groups.insert(group).await
 }
 
 // =============================================================================================================================
 
 pub async fn update_group(
-    db: &Database,
+    repos: &Repositories,
     group_id: String,
     payload: UpdateGroup,
     user_id: String,
@@ -94,27 +74,8 @@ pub async fn update_group(
 
     let group_id = ObjectId::from_str(&group_id)?;
     let user_id = ObjectId::from_str(&user_id)?;
-    let collection: Collection<Group> = db.collection(COLLECTION_NAME);
-
-    let filter = doc! {
-        "_id": group_id,
-        "$or": [
-            { "creator_id": user_id },
-            { "members": user_id }
-        ]
-    };
 
-    let update_doc = doc! {
-        "$set": {
-            "name": payload.name
-        }
-    };
-
-    match collection
-        .find_one_and_update(filter, update_doc)
-        .return_document(ReturnDocument::After)
-        .await?
-    {
+    match repos.groups.rename(group_id, user_id, payload.name).await? {
         Some(group) => Ok(group),
         None => Err("Group not found or user is not authorized to update".into()),
     }
@@ -123,23 +84,17 @@ pub async fn update_group(
 // =============================================================================================================================
 
 pub async fn add_group_members(
-    db: &Database,
+    repos: &Repositories,
     group_id: String,
     payload: AddGroupMembers,
     user_id: String,
 ) -> Result<Group, Box<dyn Error>> {
     let group_id = ObjectId::from_str(&group_id)?;
     let user_id = ObjectId::from_str(&user_id)?;
-    let collection: Collection<Group> = db.collection(COLLECTION_NAME);
-
-    let filter = doc! {
-        "_id": group_id,
-        "creator_id": user_id
-    };
 
-    let group = match collection.find_one(filter.clone()).await? {
-        Some(group) => group,
-        None => return Err("Group not found or user is not the creator".into()),
+    let group = match repos.groups.find_for_member(group_id, user_id).await? {
+        Some(group) if group.creator_id == user_id => group,
+        _ => return Err("Group not found or user is not the creator".into()),
     };
 
     let mut new_members = Vec::new();
@@ -155,19 +110,7 @@ pub async fn add_group_members(
         return Ok(group);
     }
 
-    let update = doc! {
-        "$push": {
-            "members": {
-                "$each": new_members
-            }
-        }
-    };
-
-    match collection
-        .find_one_and_update(filter, update)
-        .return_document(ReturnDocument::After)
-        .await?
-    {
+    match repos.groups.add_members(group_id, new_members).await? {
         Some(updated_group) => Ok(updated_group),
         None => Err("Failed to update group members".into()),
     }
@@ -176,7 +119,7 @@ pub async fn add_group_members(
 // =============================================================================================================================
 
 pub async fn remove_group_member(
-    db: &Database,
+    repos: &Repositories,
     group_id: String,
     member_id: String,
     user_id: String,
@@ -184,14 +127,8 @@ pub async fn remove_group_member(
     let group_id = ObjectId::from_str(&group_id)?;
     let member_id = ObjectId::from_str(&member_id)?;
     let user_id = ObjectId::from_str(&user_id)?;
-    let collection: Collection<Group> = db.collection(COLLECTION_NAME);
-
-    let filter = doc! {
-        "_id": group_id,
-        "members": member_id
-    };
 
-    let group = match collection.find_one(filter.clone()).await? {
+    let group = match repos.groups.find_for_member(group_id, member_id).await? {
         Some(group) => group,
         None => return Err("Group not found or member is not in group".into()),
     };
@@ -211,42 +148,10 @@ pub async fn remove_group_member(
         return Err("The creator cannot be removed from the group".into());
     }
 
-    let update = doc! {
-        "$pull": {
-            "members": member_id
-        }
-    };
-
-    match collection
-        .find_one_and_update(filter, update)
-        .return_document(ReturnDocument::After)
-        .await?
-    {
+    match repos.groups.remove_member(group_id, member_id).await? {
         Some(updated_group) => Ok(updated_group),
         None => Err("Failed to remove member from group".into()),
     }
 }
 
 // =============================================================================================================================
-
-pub async fn delete_group(
-    db: &Database,
-    group_id: String,
-    user_id: String,
-) -> Result<Group, Box<dyn Error>> {
-    let group_id = ObjectId::from_str(&group_id)?;
-    let user_id = ObjectId::from_str(&user_id)?;
-    let collection: Collection<Group> = db.collection(COLLECTION_NAME);
-
-    let filter = doc! {
-        "_id": group_id,
-        "creator_id": user_id
-    };
-
-    match collection.find_one_and_delete(filter).await? {
-        Some(group) => Ok(group),
-        None => Err("Group not found or user is not the creator".into()),
-    }
-}
-
-// =============================================================================================================================
@@ -0,0 +1,355 @@
+use crate::{
+    config::Config,
+    models::{
+        media_model::{MediaInfo, MediaRecord},
+        message_model::{Media, MediaType},
+    },
+    repositories::Repositories,
+    services::{file_service, storage_service},
+    storage::StorageBackend,
+    utils::media_probe,
+};
+use bson::oid::ObjectId;
+use chrono::Utc;
+use std::{error::Error, str::FromStr};
+
+// =============================================================================================================================
+
+const VIDEO_DURATION_TOLERANCE_SECS: f64 = 0.5;
+
+// =============================================================================================================================
+
+pub fn validate_upload(
+    config: &Config,
+    content_type: &str,
+    size: usize,
+) -> Result<MediaType, String> {
+    let media_type = media_type_from_content_type(content_type)
+        .ok_or("Only image and video files are allowed")?;
+    file_service::validate_file_size(config, content_type, size)?;
+    Ok(media_type)
+}
+
+// =============================================================================================================================
+
+pub fn inspect_upload(
+    config: &Config,
+    declared_content_type: &str,
+    file_data: &[u8],
+) -> Result<MediaInfo, String> {
+    let info = media_probe::probe(file_data).ok_or("Unsupported or unrecognized media format")?;
+    check_media(config, declared_content_type, &info, file_data.len())?;
+    Ok(info)
+}
+
+// =============================================================================================================================
+
+pub fn check_media(
+    config: &Config,
+    declared_content_type: &str,
+    info: &MediaInfo,
+    size: usize,
+) -> Result<(), String> {
+    let declared_content_type = media_probe::normalize_content_type(declared_content_type);
+    if declared_content_type != info.content_type {
+        return Err(format!(
+            "Declared content type {} does not match the file content ({})",
+            declared_content_type, info.content_type
+        ));
+    }
+
+    file_service::validate_file_size(config, &info.content_type, size)?;
+
+    if let MediaType::Video = info.media_type {
+        let max_duration = config.uploads.max_video_duration_secs;
+        match info.duration {
+            Some(duration) if !duration.is_finite() || duration < 0.0 => {
+                return Err("The video duration is invalid".to_string());
+            }
+            Some(duration) if duration > max_duration + VIDEO_DURATION_TOLERANCE_SECS => {
+                return Err(format!(
+                    "Videos must be at most {} seconds long (got {:.1} seconds)",
+                    max_duration, duration
+                ));
+            }
+            Some(_) => {}
+            None => return Err("Unable to determine the video duration".to_string()),
+        }
+    }
+
+    Ok(())
+}
+
+// =============================================================================================================================
+
+pub async fn upload_media(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    config: &Config,
+    owner_id: String,
+    file_data: &[u8],
+    info: MediaInfo,
+    in_library: bool,
+) -> Result<MediaRecord, Box<dyn Error>> {
+    let owner_id = ObjectId::from_str(&owner_id)?;
+    storage_service::ensure_quota(repos, config, owner_id, file_data.len()).await?;
+
+    let mut media = MediaRecord {
+        id: None,
+        owner_id,
+        key: String::new(),
+        url: String::new(),
+        feed_url: None,
+        thumbnail_url: None,
+        content_type: info.content_type,
+        media_type: info.media_type,
+        size: file_data.len() as i64,
+        width: info.width,
+        height: info.height,
+        duration: info.duration,
+        orientation: info.orientation,
+        ref_count: if in_library { 1 } else { 0 },
+        in_library,
+        created_at: Utc::now(),
+    };
+
+    match media.media_type {
+        MediaType::Image => {
+            let processed = file_service::process_image(file_data, info.orientation).await?;
+            storage_service::ensure_quota(repos, config, owner_id, processed.size()).await?;
+            let image = file_service::upload_image(storage, &processed).await?;
+            media.url = image.url;
+            media.feed_url = Some(image.feed_url);
+            media.thumbnail_url = Some(image.thumbnail_url);
+            media.content_type = image.content_type;
+            media.size = image.size as i64;
+            media.width = Some(image.width);
+            media.height = Some(image.height);
+            media.orientation = Some(1);
+        }
+        MediaType::Video => {
+            media.url = file_service::upload_file(storage, file_data, &media.content_type).await?;
+        }
+    }
+
+    media.key = storage
+        .object_key_from_url(&media.url)
+        .ok_or("Invalid file URL")?
+        .to_string();
+
+    repos
+        .media
+        .insert(media)
+        .await?
+        .ok_or_else(|| "The media key is already in use".into())
+}
+
+// =============================================================================================================================
+
+pub async fn register_object(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    owner_id: ObjectId,
+    key: String,
+    info: MediaInfo,
+    size: usize,
+    in_library: bool,
+) -> Result<MediaRecord, Box<dyn Error>> {
+    let media = MediaRecord {
+        id: None,
+        owner_id,
+        url: storage.object_url(&key),
+        key,
+        feed_url: None,
+        thumbnail_url: None,
+        content_type: info.content_type,
+        media_type: info.media_type,
+        size: size as i64,
+        width: info.width,
+        height: info.height,
+        duration: info.duration,
+        orientation: info.orientation,
+        ref_count: if in_library { 1 } else { 0 },
+        in_library,
+        created_at: Utc::now(),
+    };
+
+    // Two confirmations of the same upload race up to here, the unique index on the key lets only one through.
+    repos
+        .media
+        .insert(media)
+        .await?
+        .ok_or_else(|| "The upload has already been confirmed".into())
+}
+
+// =============================================================================================================================
+
+pub async fn get_media(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    media_id: String,
+    user_id: String,
+) -> Result<MediaRecord, Box<dyn Error>> {
+    let media_id = ObjectId::from_str(&media_id)?;
+    let user_id = ObjectId::from_str(&user_id)?;
+
+    match repos.media.find_for_owner(media_id, user_id).await? {
+        Some(mut media) => {
+            media.url = file_service::presign_download(storage, &media.url)?;
+            for rendition in [&mut media.feed_url, &mut media.thumbnail_url]
+                .into_iter()
+                .flatten()
+            {
+                *rendition = file_service::presign_download(storage, rendition)?;
+            }
+            Ok(media)
+        }
+        None => Err("Media not found or user is not the owner".into()),
+    }
+}
+
+// =============================================================================================================================
+
+pub async fn delete_media(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    media_id: String,
+    user_id: String,
+) -> Result<MediaRecord, Box<dyn Error>> {
+    let media_id = ObjectId::from_str(&media_id)?;
+    let user_id = ObjectId::from_str(&user_id)?;
+
+    match repos.media.remove_from_library(media_id, user_id).await? {
+        Some(media) => {
+            delete_if_unreferenced(repos, storage, media_id).await?;
+            Ok(media)
+        }
+        None => Err("Media not found or user is not the owner".into()),
+    }
+}
+
+// =============================================================================================================================
+
+pub async fn attach_media(
+    repos: &Repositories,
+    media_id: &str,
+    owner_id: ObjectId,
+) -> Result<Media, Box<dyn Error>> {
+    let media_id = ObjectId::from_str(media_id)?;
+
+    match repos.media.add_reference(media_id, owner_id).await? {
+        Some(media) => Ok(Media {
+            id: media.id,
+            media_type: media.media_type,
+            url: media.url,
+            feed_url: media.feed_url,
+            thumbnail_url: media.thumbnail_url,
+            duration: media.duration,
+        }),
+        None => Err("Media not found or user is not the owner".into()),
+    }
+}
+
+// =============================================================================================================================
+
+pub async fn release_media(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    media: &Media,
+) -> Result<(), Box<dyn Error>> {
+    let media_id = match media.id {
+        Some(media_id) => media_id,
+        None => return file_service::delete_file(storage, &media.url).await,
+    };
+
+    repos.media.remove_reference(media_id).await?;
+
+    delete_if_unreferenced(repos, storage, media_id).await
+}
+
+// =============================================================================================================================
+
+pub async fn delete_if_unreferenced(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    media_id: ObjectId,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(media) = repos.media.delete_unreferenced(media_id).await? {
+        delete_files(storage, &media).await?;
+    }
+
+    Ok(())
+}
+
+// =============================================================================================================================
+
+pub async fn delete_record(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    media: &MediaRecord,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(media_id) = media.id
+        && repos.media.delete(media_id).await?.is_some()
+    {
+        delete_files(storage, media).await?;
+    }
+
+    Ok(())
+}
+
+// =============================================================================================================================
+
+/// Deletes the media of content a moderator took down, with its record, whatever else still references it.
+pub async fn purge_media(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    media: &Media,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(media_id) = media.id
+        && let Some(record) = repos.media.delete(media_id).await?
+    {
+        return delete_files(storage, &record).await;
+    }
+
+    for url in [
+        Some(&media.url),
+        media.feed_url.as_ref(),
+        media.thumbnail_url.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        file_service::delete_file(storage, url).await?;
+    }
+    Ok(())
+}
+
+// =============================================================================================================================
+
+async fn delete_files(
+    storage: &dyn StorageBackend,
+    media: &MediaRecord,
+) -> Result<(), Box<dyn Error>> {
+    file_service::delete_file(storage, &media.url).await?;
+    for rendition in [&media.feed_url, &media.thumbnail_url]
+        .into_iter()
+        .flatten()
+    {
+        file_service::delete_file(storage, rendition).await?;
+    }
+    Ok(())
+}
+
+// =============================================================================================================================
+
+pub fn media_type_from_content_type(content_type: &str) -> Option<MediaType> {
+    if content_type.starts_with("image/") {
+        Some(MediaType::Image)
+    } else if content_type.starts_with("video/") {
+        Some(MediaType::Video)
+    } else {
+        None
+    }
+}
+
+// =============================================================================================================================
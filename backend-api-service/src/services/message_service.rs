@@ -1,55 +1,35 @@
 use crate::{
     models::message_model::{CreateMessage, Message, MessageQueryParams},
-    services::file_service,
+    repositories::Repositories,
+    services::{file_service, media_service},
+    storage::StorageBackend,
 };
 use bson::oid::ObjectId;
-use futures_util::TryStreamExt;
-use mongodb::{Collection, Database, bson::doc};
 use std::{error::Error, str::FromStr};
 use validator::Validate;
 
 // =============================================================================================================================
 
-const COLLECTION_NAME: &str = "messages";
-const GROUPS_COLLECTION: &str = "groups";
-const FRIENDS_COLLECTION: &str = "friends";
-
-// =============================================================================================================================
-
 pub async fn get_direct_messages(
-    db: &Database,
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
     user_id: String,
     recipient_id: String,
     params: MessageQueryParams,
 ) -> Result<Vec<Message>, Box<dyn Error>> {
     let user_id = ObjectId::from_str(&user_id)?;
     let recipient_id = ObjectId::from_str(&recipient_id)?;
-    let collection: Collection<Message> = db.collection(COLLECTION_NAME);
-
-    let filter = doc! {
-        "is_group": false,
-        "$or": [
-            {
-                "sender_id": user_id,
-                "recipient_id": recipient_id
-            },
-            {
-                "sender_id": recipient_id,
-                "recipient_id": user_id
-            }
-        ]
-    };
 
     let limit = params.limit.unwrap_or(50);
     let skip = params.offset.unwrap_or(0);
 
-    let cursor = collection
-        .find(filter)
-        .sort(doc! { "_id": -1 })
-        .limit(limit)
-        .skip(skip as u64)
+    let mut messages = repos
+        .messages
+        .find_direct(user_id, recipient_id, limit, skip as u64)
         .await?;
-    let messages: Vec<Message> = cursor.try_collect().await?;
+    messages
+        .iter_mut()
+        .for_each(|message| presign_message_media(storage, message));
 
     Ok(messages)
 }
@@ -57,40 +37,34 @@ pub async fn get_direct_messages(
 // =============================================================================================================================
 
 pub async fn get_group_messages(
-    db: &Database,
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
     user_id: String,
     group_id: String,
     params: MessageQueryParams,
 ) -> Result<Vec<Message>, Box<dyn Error>> {
     let user_id = ObjectId::from_str(&user_id)?;
     let group_id = ObjectId::from_str(&group_id)?;
-    let collection: Collection<Message> = db.collection(COLLECTION_NAME);
-
-    let groups_collection = db.collection::<serde_json::Value>(GROUPS_COLLECTION);
-    let group_filter = doc! {
-        "_id": group_id,
-        "members": user_id
-    };
 
-    if (groups_collection.find_one(group_filter).await?).is_none() {
+    if repos
+        .groups
+        .find_for_member(group_id, user_id)
+        .await?
+        .is_none()
+    {
         return Err("Group not found or user is not a member".into());
     }
 
-    let filter = doc! {
-        "is_group": true,
-        "recipient_id": group_id
-    };
-
     let limit = params.limit.unwrap_or(50);
     let skip = params.offset.unwrap_or(0);
 
-    let cursor = collection
-        .find(filter)
-        .sort(doc! { "_id": -1 })
-        .limit(limit)
-        .skip(skip as u64)
+    let mut messages = repos
+        .messages
+        .find_group(group_id, limit, skip as u64)
         .await?;
-    let messages: Vec<Message> = cursor.try_collect().await?;
+    messages
+        .iter_mut()
+        .for_each(|message| presign_message_media(storage, message));
 
     Ok(messages)
 }
@@ -98,7 +72,8 @@ pub async fn get_group_messages(
 // =============================================================================================================================
 
 pub async fn send_direct_message(
-    db: &Database,
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
     user_id: String,
     recipient_id: String,
     payload: CreateMessage,
@@ -112,26 +87,14 @@ pub async fn send_direct_message(
         return Err("Cannot send message to yourself".into());
     }
 
-    let friends_collection = db.collection::<serde_json::Value>(FRIENDS_COLLECTION);
-    let friend_filter = doc! {
-        "$or": [
-            {
-                "user_id": user_id,
-                "friend_id": recipient_id
-            },
-            {
-                "user_id": recipient_id,
-                "friend_id": user_id
-            }
-        ],
-        "status": "Accepted"
-    };
-
-    if (friends_collection.find_one(friend_filter).await?).is_none() {
+    if !repos.friends.are_friends(user_id, recipient_id).await? {
         return Err("Recipient is not in your friends list".into());
     }
 
-    let collection: Collection<Message> = db.collection(COLLECTION_NAME);
+    let media = match payload.media_id {
+        Some(media_id) => Some(media_service::attach_media(repos, &media_id, user_id).await?),
+        None => None,
+    };
 
     let message = Message {
         id: None,
@@ -139,13 +102,12 @@ pub async fn send_direct_message(
         sender_id: user_id,
         recipient_id,
         is_group: false,
-        media: payload.media,
+        media,
         read: false,
     };
 
-    let result = collection.insert_one(&message).await?;
-    let mut created_message = message;
-    created_message.id = result.inserted_id.as_object_id();
+    let mut created_message = repos.messages.insert(message).await?;
+    presign_message_media(storage, &mut created_message);
 
     Ok(created_message)
 }
@@ -153,7 +115,8 @@ pub async fn send_direct_message(
 // =============================================================================================================================
 
 pub async fn send_group_message(
-    db: &Database,
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
     user_id: String,
     group_id: String,
     payload: CreateMessage,
@@ -163,17 +126,19 @@ pub async fn send_group_message(
     let user_id = ObjectId::from_str(&user_id)?;
     let group_id = ObjectId::from_str(&group_id)?;
 
-    let groups_collection = db.collection::<serde_json::Value>(GROUPS_COLLECTION);
-    let group_filter = doc! {
-        "_id": group_id,
-        "members": user_id
-    };
-
-    if (groups_collection.find_one(group_filter).await?).is_none() {
+    if repos
+        .groups
+        .find_for_member(group_id, user_id)
+        .await?
+        .is_none()
+    {
         return Err("Group not found or user is not a member".into());
     }
 
-    let collection: Collection<Message> = db.collection(COLLECTION_NAME);
+    let media = match payload.media_id {
+        Some(media_id) => Some(media_service::attach_media(repos, &media_id, user_id).await?),
+        None => None,
+    };
 
     let message = Message {
         id: None,
@@ -181,13 +146,12 @@ pub async fn send_group_message(
         sender_id: user_id,
         recipient_id: group_id,
         is_group: true,
-        media: payload.media,
+        media,
         read: true,
     };
 
-    let result = collection.insert_one(&message).await?;
-    let mut created_message = message;
-    created_message.id = result.inserted_id.as_object_id();
+    let mut created_message = repos.messages.insert(message).await?;
+    presign_message_media(storage, &mut created_message);
 
     Ok(created_message)
 }
@@ -195,23 +159,18 @@ pub async fn send_group_message(
 // =============================================================================================================================
 
 pub async fn delete_message(
-    db: &Database,
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
     message_id: String,
     user_id: String,
 ) -> Result<Message, Box<dyn Error>> {
     let message_id = ObjectId::from_str(&message_id)?;
     let user_id = ObjectId::from_str(&user_id)?;
-    let collection: Collection<Message> = db.collection(COLLECTION_NAME);
-
-    let filter = doc! {
-        "_id": message_id,
-        "sender_id": user_id
-    };
 
-    match collection.find_one_and_delete(filter).await? {
+    match repos.messages.delete_by_sender(message_id, user_id).await? {
         Some(message) => {
             if let Some(media) = &message.media {
-                file_service::delete_file(&media.url).await?;
+                media_service::release_media(repos, storage, media).await?;
             }
             Ok(message)
         }
@@ -220,3 +179,11 @@ pub async fn delete_message(
 }
 
 // =============================================================================================================================
+
+fn presign_message_media(storage: &dyn StorageBackend, message: &mut Message) {
+    if let Some(media) = message.media.as_mut() {
+        file_service::presign_media(storage, media);
+    }
+}
+
+// =============================================================================================================================
@@ -1,8 +1,18 @@
+pub mod audit_service;
 pub mod auth_service;
+pub mod deletion_service;
+pub mod export_service;
 pub mod file_service;
 pub mod friend_service;
 pub mod group_service;
 pub mod location_service;
+pub mod media_service;
 pub mod message_service;
+pub mod moderation_service;
+pub mod oidc_service;
+pub mod session_service;
+pub mod storage_service;
 pub mod story_service;
+pub mod two_factor_service;
+pub mod upload_service;
 pub mod user_service;
@@ -0,0 +1,414 @@
+use crate::{
+    models::{
+        moderation_model::{
+            CreateReport, DismissReport, ModerationAction, ModerationActionKind,
+            ModerationActionQueryParams, Report, ReportStatus, ReportTarget, ResolveReport,
+            Sanction,
+        },
+        user_model::{AccountStatus, User, UserRole},
+    },
+    repositories::Repositories,
+    services::{media_service, story_service},
+    storage::StorageBackend,
+};
+use bson::oid::ObjectId;
+use chrono::Utc;
+use std::{error::Error, fmt, str::FromStr};
+use validator::Validate;
+
+// =============================================================================================================================
+
+const DEFAULT_ACTIONS_LIMIT: i64 = 50;
+const MAX_ACTIONS_LIMIT: i64 = 200;
+
+// =============================================================================================================================
+
+/// Returned when the reported user, message or story, or the report itself, does not exist.
+#[derive(Debug)]
+pub struct ModerationTargetNotFound;
+
+impl fmt::Display for ModerationTargetNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Nothing to moderate with this id")
+    }
+}
+
+impl Error for ModerationTargetNotFound {}
+
+// =============================================================================================================================
+
+/// Queues a report for the admins. Users can report messages they sent or received, stories they can see and other
+/// users, once until the report is reviewed.
+pub async fn create_report(
+    repos: &Repositories,
+    reporter_id: String,
+    payload: CreateReport,
+) -> Result<Report, Box<dyn Error>> {
+    payload.validate()?;
+
+    let reporter_id = ObjectId::from_str(&reporter_id)?;
+    let target_id = ObjectId::from_str(&payload.target_id)?;
+
+    let target_user_id = match payload.target {
+        ReportTarget::User => {
+            repos
+                .users
+                .find_by_id(target_id)
+                .await?
+                .ok_or(ModerationTargetNotFound)?;
+            target_id
+        }
+        ReportTarget::Message => {
+            let message = repos
+                .messages
+                .find_by_id(target_id)
+                .await?
+                .ok_or(ModerationTargetNotFound)?;
+            let visible = if message.is_group {
+                repos
+                    .groups
+                    .find_for_member(message.recipient_id, reporter_id)
+                    .await?
+                    .is_some()
+            } else {
+                message.recipient_id == reporter_id || message.sender_id == reporter_id
+            };
+            if !visible {
+                return Err(Box::new(ModerationTargetNotFound));
+            }
+            message.sender_id
+        }
+        ReportTarget::Story => {
+            let story = repos
+                .stories
+                .find_by_id(target_id)
+                .await?
+                .ok_or(ModerationTargetNotFound)?;
+            if !story_service::is_visible(repos, &story, reporter_id).await? {
+                return Err(Box::new(ModerationTargetNotFound));
+            }
+            story.user_id
+        }
+    };
+
+    if target_user_id == reporter_id {
+        return Err("You cannot report yourself or your own content".into());
+    }
+    if repos.reports.has_open(reporter_id, target_id).await? {
+        return Err("You already reported this, it is waiting for review".into());
+    }
+
+    let report = Report {
+        id: ObjectId::new(),
+        reporter_id,
+        target: payload.target,
+        target_id,
+        target_user_id,
+        reason: payload.reason,
+        status: ReportStatus::Open,
+        created_at: Utc::now(),
+        reviewed_by: None,
+        reviewed_at: None,
+        note: None,
+    };
+    repos.reports.insert(report.clone()).await?;
+
+    Ok(report)
+}
+
+// =============================================================================================================================
+
+pub async fn get_reports(
+    repos: &Repositories,
+    status: Option<ReportStatus>,
+) -> Result<Vec<Report>, Box<dyn Error>> {
+    repos
+        .reports
+        .find_by_status(status.unwrap_or(ReportStatus::Open))
+        .await
+}
+
+// =============================================================================================================================
+
+/// Resolves an open report, taking the reported message or story down when asked. Removing content also resolves
+/// the other reports of it.
+pub async fn resolve_report(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    moderator_id: String,
+    report_id: String,
+    payload: ResolveReport,
+) -> Result<Report, Box<dyn Error>> {
+    payload.validate()?;
+
+    let moderator_id = ObjectId::from_str(&moderator_id)?;
+    let report = find_open_report(repos, &report_id).await?;
+
+    if payload.remove_content {
+        match report.target {
+            ReportTarget::User => {
+                return Err(
+                    "Reported users are not removed, suspend or ban them and resolve the report"
+                        .into(),
+                );
+            }
+            ReportTarget::Message => take_down_message(repos, storage, report.target_id).await?,
+            ReportTarget::Story => take_down_story(repos, storage, report.target_id).await?,
+        }
+    }
+
+    let now = Utc::now();
+    let resolved = repos
+        .reports
+        .review(
+            report.id,
+            ReportStatus::Resolved,
+            moderator_id,
+            payload.note.clone(),
+            now,
+        )
+        .await?
+        .ok_or("The report has already been reviewed")?;
+
+    let action = if payload.remove_content {
+        repos
+            .reports
+            .review_target(report.target_id, ReportStatus::Resolved, moderator_id, now)
+            .await?;
+        ModerationActionKind::RemoveContent
+    } else {
+        ModerationActionKind::ResolveReport
+    };
+    record(repos, moderator_id, action, &resolved, payload.note).await?;
+
+    Ok(resolved)
+}
+
+// =============================================================================================================================
+
+pub async fn dismiss_report(
+    repos: &Repositories,
+    moderator_id: String,
+    report_id: String,
+    payload: DismissReport,
+) -> Result<Report, Box<dyn Error>> {
+    payload.validate()?;
+
+    let moderator_id = ObjectId::from_str(&moderator_id)?;
+    let report = find_open_report(repos, &report_id).await?;
+
+    let dismissed = repos
+        .reports
+        .review(
+            report.id,
+            ReportStatus::Dismissed,
+            moderator_id,
+            payload.note.clone(),
+            Utc::now(),
+        )
+        .await?
+        .ok_or("The report has already been reviewed")?;
+    record(
+        repos,
+        moderator_id,
+        ModerationActionKind::DismissReport,
+        &dismissed,
+        payload.note,
+    )
+    .await?;
+
+    Ok(dismissed)
+}
+
+// =============================================================================================================================
+
+/// Suspends the account until `until`, or until reinstated. The user is signed out everywhere and cannot sign in
+/// while suspended.
+pub async fn suspend_user(
+    repos: &Repositories,
+    moderator_id: String,
+    user_id: String,
+    payload: Sanction,
+) -> Result<User, Box<dyn Error>> {
+    payload.validate()?;
+    if payload.until.is_some_and(|until| until <= Utc::now()) {
+        return Err("The end of the suspension must be in the future".into());
+    }
+
+    sanction(
+        repos,
+        moderator_id,
+        user_id,
+        AccountStatus::Suspended,
+        payload,
+    )
+    .await
+}
+
+/// Bans the account for good. Besides being kept from signing in, the user's stories are hidden from strangers.
+pub async fn ban_user(
+    repos: &Repositories,
+    moderator_id: String,
+    user_id: String,
+    payload: Sanction,
+) -> Result<User, Box<dyn Error>> {
+    payload.validate()?;
+    sanction(repos, moderator_id, user_id, AccountStatus::Banned, payload).await
+}
+
+/// Lifts a suspension or a ban.
+pub async fn reinstate_user(
+    repos: &Repositories,
+    moderator_id: String,
+    user_id: String,
+    payload: Sanction,
+) -> Result<User, Box<dyn Error>> {
+    payload.validate()?;
+    sanction(repos, moderator_id, user_id, AccountStatus::Active, payload).await
+}
+
+// =============================================================================================================================
+
+pub async fn get_actions(
+    repos: &Repositories,
+    params: ModerationActionQueryParams,
+) -> Result<Vec<ModerationAction>, Box<dyn Error>> {
+    let target_id = params
+        .target_id
+        .as_deref()
+        .map(ObjectId::from_str)
+        .transpose()?;
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_ACTIONS_LIMIT)
+        .clamp(1, MAX_ACTIONS_LIMIT);
+
+    repos.moderation_actions.find_latest(target_id, limit).await
+}
+
+// =============================================================================================================================
+
+async fn sanction(
+    repos: &Repositories,
+    moderator_id: String,
+    user_id: String,
+    status: AccountStatus,
+    payload: Sanction,
+) -> Result<User, Box<dyn Error>> {
+    let moderator_id = ObjectId::from_str(&moderator_id)?;
+    let user_id = ObjectId::from_str(&user_id)?;
+
+    let user = repos
+        .users
+        .find_by_id(user_id)
+        .await?
+        .ok_or(ModerationTargetNotFound)?;
+    // Admins are demoted first, so an admin cannot lock the others out.
+    if user.role == UserRole::Admin {
+        return Err("Administrators cannot be suspended or banned".into());
+    }
+
+    let user = repos
+        .users
+        .update_status(user_id, status, payload.until)
+        .await?
+        .ok_or(ModerationTargetNotFound)?;
+    if status != AccountStatus::Active {
+        repos.sessions.delete_all(user_id, None).await?;
+    }
+
+    let action = match status {
+        AccountStatus::Active => ModerationActionKind::ReinstateUser,
+        AccountStatus::Suspended => ModerationActionKind::SuspendUser,
+        AccountStatus::Banned => ModerationActionKind::BanUser,
+    };
+    repos
+        .moderation_actions
+        .insert(ModerationAction {
+            id: ObjectId::new(),
+            moderator_id,
+            action,
+            target: ReportTarget::User,
+            target_id: user_id,
+            report_id: None,
+            reason: Some(payload.reason),
+            created_at: Utc::now(),
+        })
+        .await?;
+
+    Ok(user)
+}
+
+async fn find_open_report(repos: &Repositories, report_id: &str) -> Result<Report, Box<dyn Error>> {
+    let report = repos
+        .reports
+        .find_by_id(ObjectId::from_str(report_id)?)
+        .await?
+        .ok_or(ModerationTargetNotFound)?;
+    if report.status != ReportStatus::Open {
+        return Err("The report has already been reviewed".into());
+    }
+    Ok(report)
+}
+
+/// Content its author already deleted needs no takedown.
+async fn take_down_message(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    message_id: ObjectId,
+) -> Result<(), Box<dyn Error>> {
+    let Some(message) = repos.messages.find_by_id(message_id).await? else {
+        return Ok(());
+    };
+    if let Some(message) = repos
+        .messages
+        .delete_by_sender(message_id, message.sender_id)
+        .await?
+        && let Some(media) = &message.media
+    {
+        media_service::purge_media(repos, storage, media).await?;
+    }
+    Ok(())
+}
+
+async fn take_down_story(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    story_id: ObjectId,
+) -> Result<(), Box<dyn Error>> {
+    let Some(story) = repos.stories.find_by_id(story_id).await? else {
+        return Ok(());
+    };
+    if let Some(story) = repos
+        .stories
+        .delete_by_owner(story_id, story.user_id)
+        .await?
+    {
+        media_service::purge_media(repos, storage, &story.media).await?;
+    }
+    Ok(())
+}
+
+async fn record(
+    repos: &Repositories,
+    moderator_id: ObjectId,
+    action: ModerationActionKind,
+    report: &Report,
+    reason: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    repos
+        .moderation_actions
+        .insert(ModerationAction {
+            id: ObjectId::new(),
+            moderator_id,
+            action,
+            target: report.target,
+            target_id: report.target_id,
+            report_id: Some(report.id),
+            reason,
+            created_at: Utc::now(),
+        })
+        .await
+}
+
+// =============================================================================================================================
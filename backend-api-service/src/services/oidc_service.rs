@@ -0,0 +1,266 @@
+use crate::{
+    config::{Config, OidcProviderConfig},
+    models::{
+        oidc_model::{Identity, OidcAuthorization, OidcCallback, OidcClaims, OidcLogin},
+        session_model::ClientInfo,
+        user_model::{AccountStatus, Location, User, UserRole},
+    },
+    repositories::Repositories,
+    services::auth_service::{self, LoginOutcome, hash_token},
+    utils::{oidc::OidcClient, utils_fn::LETTERS_REGEX},
+};
+use bson::oid::ObjectId;
+use chrono::{Duration, Utc};
+use data_encoding::BASE64URL_NOPAD;
+use sha2::{Digest, Sha256};
+use std::{error::Error, fmt};
+
+// =============================================================================================================================
+
+// Not a bcrypt hash, so password logins fail until a password is set through forgot-password.
+const NO_PASSWORD: &str = "!";
+const RANDOM_BYTES: usize = 32;
+const MAX_USERNAME_LENGTH: usize = 20;
+const USERNAME_SUFFIX_LENGTH: usize = 4;
+const USERNAME_ATTEMPTS: usize = 5;
+
+// =============================================================================================================================
+
+#[derive(Debug)]
+pub struct UnknownProvider;
+
+impl fmt::Display for UnknownProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No sign-in provider with this name")
+    }
+}
+
+impl Error for UnknownProvider {}
+
+// =============================================================================================================================
+
+/// Names of the configured providers, for the app to offer.
+pub fn providers(config: &Config) -> Vec<String> {
+    config.oidc.providers.keys().cloned().collect()
+}
+
+// =============================================================================================================================
+
+/// Starts a sign-in at the provider. The state, nonce and PKCE verifier are kept until the callback, which must come
+/// within `oidc.login_minutes`.
+pub async fn authorize(
+    repos: &Repositories,
+    oidc: &OidcClient,
+    config: &Config,
+    provider_name: String,
+) -> Result<OidcAuthorization, Box<dyn Error>> {
+    let provider = find_provider(config, &provider_name)?;
+    let metadata = oidc.metadata(provider).await?;
+
+    let state = random_token()?;
+    let nonce = random_token()?;
+    let code_verifier = random_token()?;
+    let code_challenge = BASE64URL_NOPAD.encode(&Sha256::digest(code_verifier.as_bytes()));
+
+    let authorization_url =
+        OidcClient::authorization_url(&metadata, provider, &state, &nonce, &code_challenge)?;
+
+    repos
+        .oidc_logins
+        .insert(OidcLogin {
+            state_hash: hash_token(&state),
+            provider: provider_name,
+            nonce,
+            code_verifier,
+            expires_at: Utc::now() + Duration::minutes(config.oidc.login_minutes),
+        })
+        .await?;
+
+    Ok(OidcAuthorization {
+        authorization_url,
+        state,
+    })
+}
+
+// =============================================================================================================================
+
+/// Completes the sign-in with the code the provider redirected back with. The identity signs in the user it is
+/// linked to; otherwise it is linked to the account with the same email, which the provider must have verified, or
+/// to a new account.
+pub async fn callback(
+    repos: &Repositories,
+    oidc: &OidcClient,
+    config: &Config,
+    provider_name: String,
+    client: ClientInfo,
+    payload: OidcCallback,
+) -> Result<LoginOutcome, Box<dyn Error>> {
+    let provider = find_provider(config, &provider_name)?;
+
+    let login = repos
+        .oidc_logins
+        .consume(&hash_token(&payload.state), Utc::now())
+        .await?
+        .filter(|login| login.provider == provider_name)
+        .ok_or("Invalid or expired sign-in state")?;
+
+    let id_token = oidc
+        .exchange_code(provider, &payload.code, &login.code_verifier)
+        .await?;
+    let claims = oidc
+        .verify_id_token(provider, &id_token, &login.nonce)
+        .await?;
+
+    let user = find_or_link_user(repos, &provider_name, claims).await?;
+
+    auth_service::complete_login(repos, config, &user, client).await
+}
+
+// =============================================================================================================================
+
+async fn find_or_link_user(
+    repos: &Repositories,
+    provider: &str,
+    claims: OidcClaims,
+) -> Result<User, Box<dyn Error>> {
+    if let Some(identity) = repos.identities.find(provider, &claims.subject).await? {
+        return Ok(repos
+            .users
+            .find_by_id(identity.user_id)
+            .await?
+            .ok_or("The linked account no longer exists")?);
+    }
+
+    // Matching accounts by email is only safe for an address the provider vouches for.
+    let email = claims
+        .email
+        .clone()
+        .filter(|_| claims.email_verified)
+        .ok_or("The provider did not share a verified email address")?;
+
+    let user = match repos.users.find_by_email(&email).await? {
+        Some(user) if user.verified => user,
+        Some(user) => {
+            // Nobody proved they own the address this account was registered with, so its password, possibly chosen
+            // by someone else, goes along with its sessions.
+            let user_id = user.id.ok_or("The user has no id")?;
+            repos.users.update_password(user_id, NO_PASSWORD).await?;
+            repos.sessions.delete_all(user_id, None).await?;
+            repos
+                .users
+                .set_verified_email(user_id, &email)
+                .await?
+                .ok_or("No user found with the given id")?
+        }
+        None => create_user(repos, &email, claims.name.as_deref()).await?,
+    };
+
+    repos
+        .identities
+        .insert(Identity {
+            id: ObjectId::new(),
+            provider: provider.to_string(),
+            subject: claims.subject,
+            user_id: user.id.ok_or("The user has no id")?,
+            email: Some(email),
+            created_at: Utc::now(),
+        })
+        .await?;
+
+    Ok(user)
+}
+
+// =============================================================================================================================
+
+/// A verified account without a password, named after the provider's profile or the email address.
+async fn create_user(
+    repos: &Repositories,
+    email: &str,
+    name: Option<&str>,
+) -> Result<User, Box<dyn Error>> {
+    let base = username_base(name.unwrap_or_else(|| email.split('@').next().unwrap_or_default()));
+
+    let mut username = base.clone();
+    for attempt in 0..=USERNAME_ATTEMPTS {
+        if repos.users.find_by_credential(&username).await?.is_none() {
+            break;
+        }
+        if attempt == USERNAME_ATTEMPTS {
+            return Err("Could not find a free username".into());
+        }
+        username = format!("{}-{}", base, random_letters(USERNAME_SUFFIX_LENGTH)?);
+    }
+
+    repos
+        .users
+        .insert(User {
+            id: None,
+            username,
+            email: email.to_string(),
+            password: NO_PASSWORD.to_string(),
+            role: UserRole::User,
+            bio: String::new(),
+            avatar: None,
+            // Replaced by the first location update of the app.
+            location: Location {
+                location_type: "Point".to_string(),
+                coordinates: [0.0, 0.0],
+            },
+            verified: true,
+            status: AccountStatus::Active,
+            suspended_until: None,
+        })
+        .await
+}
+
+/// Keeps the characters usernames accept, lowercased like registrations.
+fn username_base(source: &str) -> String {
+    let mut buffer = [0u8; 4];
+    let username: String = source
+        .to_lowercase()
+        .chars()
+        .map(|c| if c == '.' || c == '_' { '-' } else { c })
+        .filter(|c| LETTERS_REGEX.is_match(c.encode_utf8(&mut buffer)))
+        .take(MAX_USERNAME_LENGTH)
+        .collect();
+    let username = username.trim_matches(|c: char| c == '-' || c.is_whitespace());
+
+    match username.chars().count() {
+        0 | 1 => "snapshooter".to_string(),
+        _ => username.to_string(),
+    }
+}
+
+// =============================================================================================================================
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], Box<dyn Error>> {
+    let mut bytes = [0u8; N];
+    getrandom::fill(&mut bytes).map_err(|e| format!("Failed to generate random bytes: {}", e))?;
+    Ok(bytes)
+}
+
+/// 256 random bits, URL safe, as PKCE requires for the verifier.
+fn random_token() -> Result<String, Box<dyn Error>> {
+    Ok(BASE64URL_NOPAD.encode(&random_bytes::<RANDOM_BYTES>()?))
+}
+
+fn random_letters(length: usize) -> Result<String, Box<dyn Error>> {
+    Ok(random_bytes::<RANDOM_BYTES>()?
+        .iter()
+        .take(length)
+        .map(|byte| (b'a' + byte % 26) as char)
+        .collect())
+}
+
+fn find_provider<'a>(
+    config: &'a Config,
+    name: &str,
+) -> Result<&'a OidcProviderConfig, Box<dyn Error>> {
+    config
+        .oidc
+        .providers
+        .get(name)
+        .ok_or_else(|| Box::new(UnknownProvider) as Box<dyn Error>)
+}
+
+// =============================================================================================================================
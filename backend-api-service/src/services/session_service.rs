@@ -0,0 +1,181 @@
+use crate::{
+    config::Config,
+    models::{
+        audit_model::{Actor, AuditAction},
+        auth_model::AuthResponse,
+        session_model::{ClientInfo, RevokedSessions, Session, SessionInfo},
+        user_model::{AccountStatus, User},
+    },
+    repositories::Repositories,
+    services::audit_service,
+    utils::jwt::{ExternalClaims, encode_external_jwt},
+};
+use bson::oid::ObjectId;
+use chrono::{DateTime, Duration, Utc};
+use std::{error::Error, fmt, str::FromStr};
+
+// =============================================================================================================================
+
+// `last_used` is only written when it is older than this, so busy clients do not write on every request.
+const TOUCH_INTERVAL_SECS: i64 = 60;
+
+// =============================================================================================================================
+
+/// Returned instead of a session for accounts a moderator suspended or banned.
+#[derive(Debug)]
+pub struct AccountSuspended {
+    pub status: AccountStatus,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl fmt::Display for AccountSuspended {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.status, self.until) {
+            (AccountStatus::Banned, _) => write!(f, "The account has been banned"),
+            (_, Some(until)) => write!(f, "The account is suspended until {}", until.to_rfc3339()),
+            (_, None) => write!(f, "The account is suspended"),
+        }
+    }
+}
+
+impl Error for AccountSuspended {}
+
+// =============================================================================================================================
+
+/// Records a new session and returns an access token bound to it. Every successful sign-in goes through here, so
+/// suspended and banned accounts are turned away here.
+pub async fn start(
+    repos: &Repositories,
+    config: &Config,
+    user: &User,
+    client: ClientInfo,
+) -> Result<AuthResponse, Box<dyn Error>> {
+    let now = Utc::now();
+    ensure_not_restricted(user, now)?;
+
+    let user_id = user.id.ok_or("The user has no id")?;
+    let actor = Actor {
+        user_id: Some(user_id),
+        ip: client.ip.clone(),
+    };
+    let session = Session {
+        id: ObjectId::new(),
+        user_id,
+        device_name: client.device_name,
+        platform: client.platform,
+        ip: client.ip,
+        created_at: now,
+        last_used: now,
+        expires_at: now + Duration::minutes(config.auth.token_lifetime_minutes),
+    };
+
+    let token = encode_external_jwt(
+        &config.auth,
+        user_id.to_hex(),
+        user.role,
+        session.id.to_hex(),
+        session.expires_at.timestamp(),
+    )?;
+    let device_name = session.device_name.clone();
+    repos.sessions.insert(session).await?;
+    audit_service::record(
+        repos,
+        config,
+        &actor,
+        AuditAction::Login,
+        Some(user_id),
+        Some(device_name),
+    )
+    .await;
+
+    Ok(AuthResponse { token })
+}
+
+/// Fails with `AccountSuspended` while a moderator keeps the user from signing in.
+pub fn ensure_not_restricted(user: &User, now: DateTime<Utc>) -> Result<(), Box<dyn Error>> {
+    if user.is_restricted(now) {
+        return Err(Box::new(AccountSuspended {
+            status: user.status,
+            until: user.suspended_until,
+        }));
+    }
+    Ok(())
+}
+
+// =============================================================================================================================
+
+/// Whether the session of a valid token still exists. Also records the use of the session.
+pub async fn is_active(
+    repos: &Repositories,
+    claims: &ExternalClaims,
+) -> Result<bool, Box<dyn Error>> {
+    let Ok(session_id) = ObjectId::from_str(&claims.session_id) else {
+        return Ok(false);
+    };
+    let Some(session) = repos.sessions.find(session_id).await? else {
+        return Ok(false);
+    };
+    if session.user_id.to_hex() != claims.user_id {
+        return Ok(false);
+    }
+
+    let now = Utc::now();
+    if now - session.last_used >= Duration::seconds(TOUCH_INTERVAL_SECS) {
+        repos.sessions.touch(session_id, now).await?;
+    }
+
+    Ok(true)
+}
+
+// =============================================================================================================================
+
+pub async fn list(
+    repos: &Repositories,
+    user_id: String,
+    current_session_id: String,
+) -> Result<Vec<SessionInfo>, Box<dyn Error>> {
+    let user_id = ObjectId::from_str(&user_id)?;
+    let sessions = repos.sessions.find_active(user_id, Utc::now()).await?;
+
+    Ok(sessions
+        .into_iter()
+        .map(|session| SessionInfo::new(session, &current_session_id))
+        .collect())
+}
+
+// =============================================================================================================================
+
+/// Signs a device out. Its token is rejected from the next request on.
+pub async fn revoke(
+    repos: &Repositories,
+    user_id: String,
+    session_id: String,
+) -> Result<(), Box<dyn Error>> {
+    let user_id = ObjectId::from_str(&user_id)?;
+    let session_id = ObjectId::from_str(&session_id)?;
+
+    match repos.sessions.delete(user_id, session_id).await? {
+        true => Ok(()),
+        false => Err("No session found with the given id".into()),
+    }
+}
+
+// =============================================================================================================================
+
+/// Signs every device out but the one making the request, or all of them without `current_session_id`.
+pub async fn revoke_others(
+    repos: &Repositories,
+    user_id: String,
+    current_session_id: Option<String>,
+) -> Result<RevokedSessions, Box<dyn Error>> {
+    let user_id = ObjectId::from_str(&user_id)?;
+    let keep = current_session_id
+        .map(|id| ObjectId::from_str(&id))
+        .transpose()?;
+
+    let revoked = repos.sessions.delete_all(user_id, keep).await?;
+
+    Ok(RevokedSessions { revoked })
+}
+
+// =============================================================================================================================
@@ -0,0 +1,222 @@
+use crate::{
+    config::Config,
+    models::{
+        message_model::Media,
+        storage_model::{
+            StorageBreakdown, StorageCategory, StorageQuota, StorageUsage, UpdateStorageQuota,
+        },
+        user_model::UserRole,
+    },
+    repositories::Repositories,
+};
+use bson::oid::ObjectId;
+use chrono::Utc;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    str::FromStr,
+};
+use validator::Validate;
+
+// =============================================================================================================================
+
+const BYTES_PER_MB: i64 = 1024 * 1024;
+
+// =============================================================================================================================
+
+fn default_quota(config: &Config, role: &UserRole) -> i64 {
+    match role {
+        UserRole::User => config.quotas.user_mb * BYTES_PER_MB,
+        UserRole::Admin => config.quotas.admin_mb * BYTES_PER_MB,
+    }
+}
+
+// =============================================================================================================================
+
+pub async fn get_quota(
+    repos: &Repositories,
+    config: &Config,
+    role: UserRole,
+) -> Result<StorageQuota, Box<dyn Error>> {
+    match repos.storage_quotas.find(role).await? {
+        Some(quota) => Ok(quota),
+        None => Ok(StorageQuota {
+            quota_bytes: default_quota(config, &role),
+            role,
+            updated_at: None,
+        }),
+    }
+}
+
+// =============================================================================================================================
+
+pub async fn get_quotas(
+    repos: &Repositories,
+    config: &Config,
+) -> Result<Vec<StorageQuota>, Box<dyn Error>> {
+    let mut quotas = Vec::new();
+    for role in [UserRole::User, UserRole::Admin] {
+        quotas.push(get_quota(repos, config, role).await?);
+    }
+    Ok(quotas)
+}
+
+// =============================================================================================================================
+
+pub async fn update_quota(
+    repos: &Repositories,
+    role: UserRole,
+    payload: UpdateStorageQuota,
+) -> Result<StorageQuota, Box<dyn Error>> {
+    payload.validate()?;
+
+    repos
+        .storage_quotas
+        .save(role, payload.quota_bytes, Utc::now())
+        .await
+}
+
+// =============================================================================================================================
+
+pub async fn remaining_bytes(
+    repos: &Repositories,
+    config: &Config,
+    owner_id: ObjectId,
+) -> Result<i64, Box<dyn Error>> {
+    let quota = get_quota(repos, config, user_role(repos, owner_id).await?).await?;
+    let used = used_bytes(repos, owner_id).await?;
+    Ok((quota.quota_bytes - used).max(0))
+}
+
+// =============================================================================================================================
+
+/// Fails when storing `incoming` more bytes would take the owner over the quota of their role.
+pub async fn ensure_quota(
+    repos: &Repositories,
+    config: &Config,
+    owner_id: ObjectId,
+    incoming: usize,
+) -> Result<(), Box<dyn Error>> {
+    let quota = get_quota(repos, config, user_role(repos, owner_id).await?).await?;
+    let used = used_bytes(repos, owner_id).await?;
+
+    if used + incoming as i64 > quota.quota_bytes {
+        return Err(quota_exceeded(used, quota.quota_bytes, incoming).into());
+    }
+
+    Ok(())
+}
+
+// =============================================================================================================================
+
+/// The media of the owner, plus the presigned uploads they may still confirm.
+async fn used_bytes(repos: &Repositories, owner_id: ObjectId) -> Result<i64, Box<dyn Error>> {
+    let stored = repos.media.used_bytes(owner_id).await?;
+    let reserved = repos
+        .pending_uploads
+        .reserved_bytes(owner_id, Utc::now())
+        .await?;
+    Ok(stored + reserved)
+}
+
+// =============================================================================================================================
+
+fn quota_exceeded(used: i64, quota: i64, incoming: usize) -> String {
+    format!(
+        "Storage quota exceeded: {} of {} bytes used, the upload needs {} more bytes",
+        used, quota, incoming
+    )
+}
+
+// =============================================================================================================================
+
+pub async fn get_usage(
+    repos: &Repositories,
+    config: &Config,
+    user_id: String,
+) -> Result<StorageUsage, Box<dyn Error>> {
+    let user_id = ObjectId::from_str(&user_id)?;
+    let role = user_role(repos, user_id).await?;
+    let quota = get_quota(repos, config, role).await?;
+
+    let owned_media = repos.media.find_by_owner(user_id).await?;
+
+    let sizes: HashMap<ObjectId, i64> = owned_media
+        .iter()
+        .filter_map(|media| media.id.map(|id| (id, media.size)))
+        .collect();
+    let used_bytes: i64 = owned_media.iter().map(|media| media.size).sum();
+    let reserved_bytes = repos
+        .pending_uploads
+        .reserved_bytes(user_id, Utc::now())
+        .await?;
+
+    let story_media: Vec<Media> = repos
+        .stories
+        .find_by_user(user_id)
+        .await?
+        .into_iter()
+        .map(|story| story.media)
+        .collect();
+
+    let (group_messages, direct_messages): (Vec<_>, Vec<_>) = repos
+        .messages
+        .find_by_sender(user_id)
+        .await?
+        .into_iter()
+        .partition(|message| message.is_group);
+    let direct_media: Vec<Media> = direct_messages
+        .into_iter()
+        .filter_map(|message| message.media)
+        .collect();
+    let group_media: Vec<Media> = group_messages
+        .into_iter()
+        .filter_map(|message| message.media)
+        .collect();
+
+    let breakdown = StorageBreakdown {
+        stories: summarize(&story_media, &sizes),
+        direct_messages: summarize(&direct_media, &sizes),
+        groups: summarize(&group_media, &sizes),
+        library: StorageCategory {
+            count: owned_media.iter().filter(|media| media.in_library).count() as u64,
+            bytes: owned_media
+                .iter()
+                .filter(|media| media.in_library)
+                .map(|media| media.size)
+                .sum(),
+        },
+    };
+
+    Ok(StorageUsage {
+        role: quota.role,
+        quota_bytes: quota.quota_bytes,
+        used_bytes,
+        reserved_bytes,
+        remaining_bytes: (quota.quota_bytes - used_bytes - reserved_bytes).max(0),
+        breakdown,
+    })
+}
+
+// =============================================================================================================================
+
+async fn user_role(repos: &Repositories, user_id: ObjectId) -> Result<UserRole, Box<dyn Error>> {
+    match repos.users.find_by_id(user_id).await? {
+        Some(user) => Ok(user.role),
+        None => Err("No user found with the given id".into()),
+    }
+}
+
+// =============================================================================================================================
+
+/// Counts each stored media once per category, even when several stories or messages share it.
+fn summarize(media: &[Media], sizes: &HashMap<ObjectId, i64>) -> StorageCategory {
+    let ids: HashSet<ObjectId> = media.iter().filter_map(|media| media.id).collect();
+
+    StorageCategory {
+        count: ids.len() as u64,
+        bytes: ids.iter().filter_map(|id| sizes.get(id)).sum(),
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,275 @@
+use crate::{
+    config::Config,
+    models::{
+        auth_model::AuthResponse,
+        session_model::ClientInfo,
+        two_factor_model::{
+            RecoveryCodes, TwoFactor, TwoFactorCode, TwoFactorEnrollment, TwoFactorStatus,
+            TwoFactorVerify,
+        },
+    },
+    repositories::Repositories,
+    services::{
+        auth_service::{audit_failed_login, ensure_not_locked, hash_token, record_failed_login},
+        session_service,
+    },
+    utils::{jwt::decode_challenge_jwt, totp},
+};
+use bson::oid::ObjectId;
+use chrono::Utc;
+use std::{error::Error, fmt};
+use uuid::Uuid;
+
+// =============================================================================================================================
+
+const RECOVERY_CODE_COUNT: usize = 10;
+
+// =============================================================================================================================
+
+/// Returned when a TOTP or recovery code does not match, so the controller can answer 401.
+#[derive(Debug)]
+pub struct InvalidTwoFactorCode;
+
+impl fmt::Display for InvalidTwoFactorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid two-factor code")
+    }
+}
+
+impl Error for InvalidTwoFactorCode {}
+
+// =============================================================================================================================
+
+pub async fn status(
+    repos: &Repositories,
+    user_id: String,
+) -> Result<TwoFactorStatus, Box<dyn Error>> {
+    let user_id = ObjectId::parse_str(&user_id)?;
+    let two_factor = repos.two_factor.find(user_id).await?;
+
+    Ok(match two_factor {
+        Some(two_factor) if two_factor.enabled => TwoFactorStatus {
+            enabled: true,
+            recovery_codes_left: two_factor.recovery_codes.len(),
+        },
+        _ => TwoFactorStatus {
+            enabled: false,
+            recovery_codes_left: 0,
+        },
+    })
+}
+
+// =============================================================================================================================
+
+/// Starts enrolment with a new secret. It only takes effect once `confirm` receives a code generated from it.
+pub async fn enroll(
+    repos: &Repositories,
+    config: &Config,
+    user_id: String,
+) -> Result<TwoFactorEnrollment, Box<dyn Error>> {
+    let user_id = ObjectId::parse_str(&user_id)?;
+    let user = repos
+        .users
+        .find_by_id(user_id)
+        .await?
+        .ok_or("User not found")?;
+
+    if repos
+        .two_factor
+        .find(user_id)
+        .await?
+        .is_some_and(|two_factor| two_factor.enabled)
+    {
+        return Err("Two-factor authentication is already enabled".into());
+    }
+
+    let secret = totp::generate_secret()?;
+    repos
+        .two_factor
+        .save(TwoFactor {
+            user_id,
+            secret: secret.clone(),
+            enabled: false,
+            recovery_codes: Vec::new(),
+            last_used_step: None,
+        })
+        .await?;
+
+    Ok(TwoFactorEnrollment {
+        provisioning_uri: totp::provisioning_uri(
+            &config.auth.two_factor_issuer,
+            &user.username,
+            &secret,
+        ),
+        secret,
+    })
+}
+
+// =============================================================================================================================
+
+/// Enables two-factor authentication and returns the recovery codes, which are only ever shown here.
+pub async fn confirm(
+    repos: &Repositories,
+    user_id: String,
+    payload: TwoFactorCode,
+) -> Result<RecoveryCodes, Box<dyn Error>> {
+    let user_id = ObjectId::parse_str(&user_id)?;
+    let mut two_factor = repos
+        .two_factor
+        .find(user_id)
+        .await?
+        .ok_or("Two-factor enrolment has not been started")?;
+
+    if two_factor.enabled {
+        return Err("Two-factor authentication is already enabled".into());
+    }
+
+    let step = totp::verify(&two_factor.secret, &payload.code, Utc::now().timestamp())?
+        .ok_or(InvalidTwoFactorCode)?;
+
+    let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect();
+
+    two_factor.enabled = true;
+    two_factor.last_used_step = Some(step);
+    two_factor.recovery_codes = recovery_codes.iter().map(|code| hash_token(code)).collect();
+    repos.two_factor.save(two_factor).await?;
+
+    Ok(RecoveryCodes { recovery_codes })
+}
+
+// =============================================================================================================================
+
+/// Turns two-factor authentication off. Takes a current TOTP code or a recovery code, so a lost device is no obstacle.
+/// Wrong codes count towards the lockout, so a stolen access token cannot be used to guess one.
+pub async fn disable(
+    repos: &Repositories,
+    config: &Config,
+    user_id: String,
+    payload: TwoFactorCode,
+) -> Result<(), Box<dyn Error>> {
+    let user_id = ObjectId::parse_str(&user_id)?;
+
+    let now = Utc::now();
+    ensure_not_locked(repos, user_id, now).await?;
+
+    let two_factor = repos
+        .two_factor
+        .find(user_id)
+        .await?
+        .filter(|two_factor| two_factor.enabled)
+        .ok_or("Two-factor authentication is not enabled")?;
+
+    if !check_code(repos, &two_factor, &payload.code).await? {
+        return Err(record_failed_login(
+            repos,
+            config,
+            user_id,
+            now,
+            Box::new(InvalidTwoFactorCode),
+        )
+        .await);
+    }
+    repos.login_attempts.clear(user_id).await?;
+
+    repos.two_factor.delete(user_id).await
+}
+
+// =============================================================================================================================
+
+/// Completes a login: exchanges the challenge from `auth_service::login` and a TOTP or recovery code for the access
+/// token. Wrong codes count towards the same lockout as wrong passwords.
+pub async fn verify_challenge(
+    repos: &Repositories,
+    config: &Config,
+    client: ClientInfo,
+    payload: TwoFactorVerify,
+) -> Result<AuthResponse, Box<dyn Error>> {
+    let claims = decode_challenge_jwt(&config.auth, &payload.challenge_token)?;
+    let user_id = ObjectId::parse_str(&claims.sub)?;
+
+    let now = Utc::now();
+    if let Err(e) = ensure_not_locked(repos, user_id, now).await {
+        audit_failed_login(repos, config, &client, Some(user_id), "Account locked").await;
+        return Err(e);
+    }
+
+    let two_factor = repos
+        .two_factor
+        .find(user_id)
+        .await?
+        .filter(|two_factor| two_factor.enabled)
+        .ok_or("Two-factor authentication is not enabled")?;
+
+    if !check_code(repos, &two_factor, &payload.code).await? {
+        audit_failed_login(
+            repos,
+            config,
+            &client,
+            Some(user_id),
+            "Wrong two-factor code",
+        )
+        .await;
+        return Err(record_failed_login(
+            repos,
+            config,
+            user_id,
+            now,
+            Box::new(InvalidTwoFactorCode),
+        )
+        .await);
+    }
+    repos.login_attempts.clear(user_id).await?;
+
+    let user = repos
+        .users
+        .find_by_id(user_id)
+        .await?
+        .ok_or("User not found")?;
+    session_service::start(repos, config, &user, client).await
+}
+
+// =============================================================================================================================
+
+/// Accepts a TOTP code once, or consumes a recovery code.
+async fn check_code(
+    repos: &Repositories,
+    two_factor: &TwoFactor,
+    code: &str,
+) -> Result<bool, Box<dyn Error>> {
+    if let Some(step) = totp::verify(&two_factor.secret, code, Utc::now().timestamp())? {
+        return repos.two_factor.use_step(two_factor.user_id, step).await;
+    }
+
+    let code = normalize_recovery_code(code);
+    if code.is_empty() {
+        return Ok(false);
+    }
+    repos
+        .two_factor
+        .use_recovery_code(two_factor.user_id, &hash_token(&code))
+        .await
+}
+
+/// Recovery codes are shown as `xxxxx-xxxxx`. Case and the dash are ignored when typed back.
+fn generate_recovery_code() -> String {
+    let raw = Uuid::new_v4().simple().to_string();
+    format!("{}-{}", &raw[..5], &raw[5..10])
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    let compact: String = code
+        .trim()
+        .to_ascii_lowercase()
+        .chars()
+        .filter(|c| *c != '-')
+        .collect();
+    // Checked before slicing, which would panic in the middle of a multi-byte character.
+    match compact.len() == 10 && compact.chars().all(|c| c.is_ascii_alphanumeric()) {
+        true => format!("{}-{}", &compact[..5], &compact[5..]),
+        false => String::new(),
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,690 @@
+use crate::{
+    config::Config,
+    models::{
+        media_model::{
+            ConfirmUpload, ConfirmedUpload, CreateUploadSession, MediaForm, MediaInfo, MediaRecord,
+            PendingUpload, PresignUpload, PresignedUpload, UploadSession, UploadSessionResponse,
+            UploadedPart,
+        },
+        message_model::MediaType,
+    },
+    repositories::Repositories,
+    services::{file_service, media_service, storage_service},
+    storage::StorageBackend,
+    utils::media_probe::{self, StreamProbe},
+};
+use actix_multipart::Multipart;
+use actix_web::web::Bytes;
+use bson::oid::ObjectId;
+use chrono::{Duration, Utc};
+use futures_util::{Stream, StreamExt};
+use std::{collections::HashMap, error::Error, fmt::Display, str::FromStr};
+use validator::Validate;
+
+// =============================================================================================================================
+
+const MAX_FORM_FIELD_SIZE: usize = 4 * 1024;
+
+// Leaves time to confirm an upload sent at the very end of the lifetime of its presigned URL.
+const PENDING_UPLOAD_LIFETIME_MINUTES: i64 = 60;
+// Longer than any confirmation takes, so that only a crashed one lets the upload go.
+const CONFIRMATION_TIMEOUT_MINUTES: i64 = 5;
+
+// Enough for the top-level boxes of any real MP4 file, while bounding the ranged reads made to find `moov`.
+const MAX_PROBED_BOXES: usize = 32;
+const MP4_BOX_HEADER_SIZE: u64 = 16;
+
+// =============================================================================================================================
+
+pub async fn presign_upload(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    config: &Config,
+    user_id: String,
+    payload: PresignUpload,
+) -> Result<PresignedUpload, Box<dyn Error>> {
+    payload.validate()?;
+
+    media_service::validate_upload(config, &payload.content_type, payload.size)?;
+
+    let user_id = ObjectId::from_str(&user_id)?;
+    storage_service::ensure_quota(repos, config, user_id, payload.size).await?;
+    let (key, upload_url) = file_service::presign_upload(
+        storage,
+        &user_id.to_hex(),
+        &payload.content_type,
+        payload.size,
+    )?;
+
+    repos
+        .pending_uploads
+        .insert(PendingUpload {
+            id: None,
+            owner_id: user_id,
+            key: key.clone(),
+            size: payload.size as i64,
+            expires_at: Utc::now() + Duration::minutes(PENDING_UPLOAD_LIFETIME_MINUTES),
+            confirming_until: None,
+        })
+        .await?;
+
+    Ok(PresignedUpload {
+        key,
+        upload_url,
+        method: "PUT".to_string(),
+        content_type: payload.content_type,
+        size: payload.size,
+        expires_in: file_service::PRESIGNED_UPLOAD_LIFETIME_SECS,
+    })
+}
+
+// =============================================================================================================================
+
+pub async fn confirm_upload(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    config: &Config,
+    user_id: String,
+    payload: ConfirmUpload,
+) -> Result<ConfirmedUpload, Box<dyn Error>> {
+    payload.validate()?;
+
+    let user_id = ObjectId::from_str(&user_id)?;
+    if !payload.key.starts_with(&format!("{}/", user_id.to_hex())) {
+        return Err("The upload does not belong to the current user".into());
+    }
+
+    let now = Utc::now();
+    let timeout = now + Duration::minutes(CONFIRMATION_TIMEOUT_MINUTES);
+    let pending_id = repos
+        .pending_uploads
+        .claim(user_id, &payload.key, now, timeout)
+        .await?
+        .and_then(|pending| pending.id)
+        .ok_or("The upload was not found, has expired or has already been confirmed")?;
+
+    let confirmed = finalize_upload(repos, storage, config, user_id, payload.key).await;
+    match confirmed {
+        Ok(_) => repos.pending_uploads.delete(pending_id).await?,
+        // The object may still be uploaded and confirmed before it expires.
+        Err(_) => repos.pending_uploads.release(pending_id).await?,
+    }
+
+    confirmed
+}
+
+// =============================================================================================================================
+
+/// Deletes the presigned uploads that expired unconfirmed, and returns how many were removed.
+pub async fn sweep_expired(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+) -> Result<usize, Box<dyn Error>> {
+    let expired = repos.pending_uploads.find_expired(Utc::now()).await?;
+    let mut swept = 0;
+
+    for upload in expired {
+        let Some(upload_id) = upload.id else {
+            continue;
+        };
+        if let Err(e) = storage.delete(&upload.key).await {
+            eprintln!(
+                "❌ Failed to delete the unconfirmed upload {}: {}",
+                upload.key, e
+            );
+            continue;
+        }
+        repos.pending_uploads.delete(upload_id).await?;
+        swept += 1;
+    }
+
+    Ok(swept)
+}
+
+// =============================================================================================================================
+
+pub async fn upload_form(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    config: &Config,
+    user_id: String,
+    mut form: Multipart,
+    in_library: bool,
+) -> Result<MediaForm, Box<dyn Error>> {
+    let mut media: Option<MediaRecord> = None;
+    let mut fields = HashMap::new();
+
+    let result: Result<(), Box<dyn Error>> = async {
+        while let Some(field) = form.next().await {
+            let mut field = field?;
+            let name = field.name().unwrap_or_default().to_string();
+
+            if name == "file" {
+                if media.is_some() {
+                    return Err("Only one file can be uploaded per request".into());
+                }
+                let content_type = field
+                    .content_type()
+                    .map(|mime| mime.to_string())
+                    .ok_or("The file part has no content type")?;
+                media = Some(
+                    stream_media(
+                        repos,
+                        storage,
+                        config,
+                        user_id.clone(),
+                        &content_type,
+                        &mut field,
+                        in_library,
+                    )
+                    .await?,
+                );
+            } else {
+                let mut value = Vec::new();
+                while let Some(chunk) = field.next().await {
+                    value.extend_from_slice(&chunk?);
+                    if value.len() > MAX_FORM_FIELD_SIZE {
+                        return Err(format!("The {} field is too large", name).into());
+                    }
+                }
+                fields.insert(name, String::from_utf8(value)?);
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    match (result, media) {
+        (Ok(()), Some(media)) => Ok(MediaForm { media, fields }),
+        (Ok(()), None) => Err("The form has no file part".into()),
+        (Err(e), media) => {
+            // Created by this request, so nothing else references it yet, library uploads included.
+            if let Some(media) = media {
+                let _ = media_service::delete_record(repos, storage, &media).await;
+            }
+            Err(e)
+        }
+    }
+}
+
+// =============================================================================================================================
+
+/// Stores an upload read from a request stream. Images are buffered (they are re-encoded anyway), videos
+/// are forwarded to storage part by part through a multipart upload so only one part is held in memory.
+pub async fn stream_media<S, E>(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    config: &Config,
+    user_id: String,
+    content_type: &str,
+    mut stream: S,
+    in_library: bool,
+) -> Result<MediaRecord, Box<dyn Error>>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Display,
+{
+    let owner_id = ObjectId::from_str(&user_id)?;
+
+    if let MediaType::Image = media_service::validate_upload(config, content_type, 0)? {
+        let mut data = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            data.extend_from_slice(&chunk.map_err(|e| e.to_string())?);
+            file_service::validate_file_size(config, content_type, data.len())?;
+        }
+
+        let info = media_service::inspect_upload(config, content_type, &data)?;
+        return media_service::upload_media(
+            repos, storage, config, user_id, &data, info, in_library,
+        )
+        .await;
+    }
+
+    let remaining = storage_service::remaining_bytes(repos, config, owner_id).await?;
+    let normalized_type = media_probe::normalize_content_type(content_type);
+    let key = file_service::new_object_key(&owner_id.to_hex(), &normalized_type);
+    let upload_id = storage
+        .create_multipart_upload(&key, &normalized_type)
+        .await?;
+
+    let mut parts = Vec::new();
+    let mut size = 0;
+
+    let uploaded: Result<MediaInfo, Box<dyn Error>> = async {
+        let mut probe = StreamProbe::default();
+        let mut buffer = Vec::with_capacity(file_service::MULTIPART_PART_SIZE);
+
+        loop {
+            let chunk = match stream.next().await {
+                Some(chunk) => chunk.map_err(|e| e.to_string())?,
+                None => break,
+            };
+            size += chunk.len();
+            file_service::validate_file_size(config, content_type, size)?;
+            if size as i64 > remaining {
+                storage_service::ensure_quota(repos, config, owner_id, size).await?;
+            }
+            probe.push(&chunk);
+            buffer.extend_from_slice(&chunk);
+
+            if buffer.len() >= file_service::MULTIPART_PART_SIZE {
+                let part_number = parts.len() as i32 + 1;
+                let data = std::mem::take(&mut buffer);
+                let etag = storage
+                    .upload_part(&key, &upload_id, part_number, data)
+                    .await?;
+                parts.push((part_number, etag));
+            }
+        }
+
+        if !buffer.is_empty() || parts.is_empty() {
+            let part_number = parts.len() as i32 + 1;
+            let data = std::mem::take(&mut buffer);
+            let etag = storage
+                .upload_part(&key, &upload_id, part_number, data)
+                .await?;
+            parts.push((part_number, etag));
+        }
+
+        let info = probe
+            .finish()
+            .ok_or("Unsupported or unrecognized media format")?;
+        media_service::check_media(config, content_type, &info, size)?;
+        Ok(info)
+    }
+    .await;
+
+    let info = match uploaded {
+        Ok(info) => info,
+        Err(e) => {
+            let _ = storage.abort_multipart_upload(&key, &upload_id).await;
+            return Err(e);
+        }
+    };
+
+    storage
+        .complete_multipart_upload(&key, &upload_id, &parts)
+        .await?;
+
+    media_service::register_object(repos, storage, owner_id, key, info, size, in_library).await
+}
+
+// =============================================================================================================================
+
+pub async fn create_session(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    config: &Config,
+    user_id: String,
+    payload: CreateUploadSession,
+) -> Result<UploadSessionResponse, Box<dyn Error>> {
+    payload.validate()?;
+    media_service::validate_upload(config, &payload.content_type, payload.size)?;
+
+    let owner_id = ObjectId::from_str(&user_id)?;
+    storage_service::ensure_quota(repos, config, owner_id, payload.size).await?;
+
+    let content_type = media_probe::normalize_content_type(&payload.content_type);
+    let key = file_service::new_object_key(&owner_id.to_hex(), &content_type);
+    let upload_id = storage.create_multipart_upload(&key, &content_type).await?;
+
+    let now = Utc::now();
+    let session = UploadSession {
+        id: None,
+        owner_id,
+        key,
+        upload_id,
+        content_type,
+        size: payload.size as i64,
+        part_size: file_service::MULTIPART_PART_SIZE as i64,
+        parts: Vec::new(),
+        created_at: now,
+        expires_at: now + Duration::hours(config.uploads.session_lifetime_hours),
+    };
+
+    let created_session = repos.upload_sessions.insert(session).await?;
+
+    Ok(to_response(&created_session))
+}
+
+// =============================================================================================================================
+
+pub async fn get_session(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    session_id: String,
+    user_id: String,
+) -> Result<UploadSessionResponse, Box<dyn Error>> {
+    let session = find_session(repos, storage, session_id, user_id).await?;
+    Ok(to_response(&session))
+}
+
+// =============================================================================================================================
+
+pub async fn upload_session_part<S, E>(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    session_id: String,
+    user_id: String,
+    part_number: i32,
+    mut stream: S,
+) -> Result<UploadSessionResponse, Box<dyn Error>>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Display,
+{
+    let session = find_session(repos, storage, session_id, user_id).await?;
+
+    let total_parts = total_parts(&session);
+    if part_number < 1 || part_number > total_parts {
+        return Err(format!("Part number must be between 1 and {}", total_parts).into());
+    }
+
+    let expected_size = if part_number < total_parts {
+        session.part_size
+    } else {
+        session.size - session.part_size * (total_parts as i64 - 1)
+    } as usize;
+
+    let mut data = Vec::with_capacity(expected_size);
+    while let Some(chunk) = stream.next().await {
+        data.extend_from_slice(&chunk.map_err(|e| e.to_string())?);
+        if data.len() > expected_size {
+            break;
+        }
+    }
+    if data.len() != expected_size {
+        return Err(format!(
+            "Part {} must be exactly {} bytes",
+            part_number, expected_size
+        )
+        .into());
+    }
+
+    if part_number == 1 {
+        let detected = media_probe::probe(&data).map(|info| info.content_type);
+        if detected.as_deref() != Some(session.content_type.as_str()) {
+            return Err("The uploaded data does not match the declared content type".into());
+        }
+    }
+
+    let etag = storage
+        .upload_part(&session.key, &session.upload_id, part_number, data)
+        .await?;
+    let part = UploadedPart {
+        part_number,
+        etag,
+        size: expected_size as i64,
+    };
+
+    let session_id = session.id.ok_or("Upload session not found")?;
+    match repos.upload_sessions.save_part(session_id, part).await? {
+        Some(session) => Ok(to_response(&session)),
+        None => Err("Upload session not found".into()),
+    }
+}
+
+// =============================================================================================================================
+
+pub async fn complete_session(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    config: &Config,
+    session_id: String,
+    user_id: String,
+) -> Result<ConfirmedUpload, Box<dyn Error>> {
+    let session = find_session(repos, storage, session_id, user_id).await?;
+
+    let mut parts: Vec<(i32, String)> = session
+        .parts
+        .iter()
+        .map(|part| (part.part_number, part.etag.clone()))
+        .collect();
+    parts.sort_by_key(|(part_number, _)| *part_number);
+
+    let missing: Vec<String> = (1..=total_parts(&session))
+        .filter(|part_number| !parts.iter().any(|(uploaded, _)| uploaded == part_number))
+        .map(|part_number| part_number.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("Missing parts: {}", missing.join(", ")).into());
+    }
+
+    storage
+        .complete_multipart_upload(&session.key, &session.upload_id, &parts)
+        .await?;
+
+    // The object now exists, so it is handed over to a pending upload held by this confirmation: when finalizing
+    // fails, it can still be confirmed through `confirm_upload` or is swept once it expires.
+    let now = Utc::now();
+    let pending_id = repos
+        .pending_uploads
+        .insert(PendingUpload {
+            id: None,
+            owner_id: session.owner_id,
+            key: session.key.clone(),
+            size: session.size,
+            expires_at: now + Duration::minutes(PENDING_UPLOAD_LIFETIME_MINUTES),
+            confirming_until: Some(now + Duration::minutes(CONFIRMATION_TIMEOUT_MINUTES)),
+        })
+        .await?
+        .id
+        .ok_or("Failed to register the completed upload")?;
+
+    if let Some(session_id) = session.id {
+        repos.upload_sessions.delete(session_id).await?;
+    }
+
+    let confirmed = finalize_upload(repos, storage, config, session.owner_id, session.key).await;
+    match confirmed {
+        Ok(_) => repos.pending_uploads.delete(pending_id).await?,
+        Err(_) => repos.pending_uploads.release(pending_id).await?,
+    }
+
+    confirmed
+}
+
+// =============================================================================================================================
+
+pub async fn cancel_session(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    session_id: String,
+    user_id: String,
+) -> Result<(), Box<dyn Error>> {
+    let session = find_session(repos, storage, session_id, user_id).await?;
+
+    storage
+        .abort_multipart_upload(&session.key, &session.upload_id)
+        .await?;
+
+    if let Some(session_id) = session.id {
+        repos.upload_sessions.delete(session_id).await?;
+    }
+
+    Ok(())
+}
+
+// =============================================================================================================================
+
+async fn find_session(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    session_id: String,
+    user_id: String,
+) -> Result<UploadSession, Box<dyn Error>> {
+    let session_id = ObjectId::from_str(&session_id)?;
+    let user_id = ObjectId::from_str(&user_id)?;
+
+    let session = match repos
+        .upload_sessions
+        .find_for_owner(session_id, user_id)
+        .await?
+    {
+        Some(session) => session,
+        None => return Err("Upload session not found".into()),
+    };
+
+    if session.expires_at <= Utc::now() {
+        let _ = storage
+            .abort_multipart_upload(&session.key, &session.upload_id)
+            .await;
+        repos.upload_sessions.delete(session_id).await?;
+        return Err("Upload session has expired".into());
+    }
+
+    Ok(session)
+}
+
+// =============================================================================================================================
+
+/// Validates an object that was uploaded straight to storage and registers it in the media library.
+/// Images are re-processed into renditions and the raw upload is removed; invalid objects are deleted.
+async fn finalize_upload(
+    repos: &Repositories,
+    storage: &dyn StorageBackend,
+    config: &Config,
+    owner_id: ObjectId,
+    key: String,
+) -> Result<ConfirmedUpload, Box<dyn Error>> {
+    let metadata = storage.head(&key).await?;
+    let url = storage.object_url(&key);
+    let size = metadata.size as usize;
+
+    let media_type = match media_service::validate_upload(config, &metadata.content_type, size) {
+        Ok(media_type) => media_type,
+        Err(e) => {
+            file_service::delete_file(storage, &url).await?;
+            return Err(e.into());
+        }
+    };
+
+    if let Err(e) = storage_service::ensure_quota(repos, config, owner_id, size).await {
+        file_service::delete_file(storage, &url).await?;
+        return Err(e);
+    }
+
+    // Images are re-encoded, so they are read whole anyway. Videos are only probed.
+    if let MediaType::Image = media_type {
+        let file_data = file_service::download_file(storage, &url).await?;
+        let info = match media_service::inspect_upload(config, &metadata.content_type, &file_data) {
+            Ok(info) => info,
+            Err(e) => {
+                file_service::delete_file(storage, &url).await?;
+                return Err(e.into());
+            }
+        };
+
+        let uploaded = media_service::upload_media(
+            repos,
+            storage,
+            config,
+            owner_id.to_hex(),
+            &file_data,
+            info,
+            true,
+        )
+        .await;
+        file_service::delete_file(storage, &url).await?;
+        return confirmed(storage, uploaded?);
+    }
+
+    let info = probe_object(storage, &key, metadata.size)
+        .await?
+        .ok_or_else(|| "Unsupported or unrecognized media format".to_string())
+        .and_then(|info| {
+            media_service::check_media(config, &metadata.content_type, &info, size)?;
+            Ok(info)
+        });
+    let info = match info {
+        Ok(info) => info,
+        Err(e) => {
+            file_service::delete_file(storage, &url).await?;
+            return Err(e.into());
+        }
+    };
+
+    let media =
+        media_service::register_object(repos, storage, owner_id, key, info, size, true).await?;
+
+    confirmed(storage, media)
+}
+
+// =============================================================================================================================
+
+fn confirmed(
+    storage: &dyn StorageBackend,
+    media: MediaRecord,
+) -> Result<ConfirmedUpload, Box<dyn Error>> {
+    let download_url = file_service::presign_download(storage, &media.url)?;
+
+    Ok(ConfirmedUpload {
+        media,
+        download_url,
+    })
+}
+
+// =============================================================================================================================
+
+/// Probes an object in storage through ranged reads rather than downloading it. The head holds everything but the
+/// `moov` box of an MP4/QuickTime file, which is found by walking the top-level box headers.
+async fn probe_object(
+    storage: &dyn StorageBackend,
+    key: &str,
+    size: u64,
+) -> Result<Option<MediaInfo>, Box<dyn Error>> {
+    let head = storage
+        .get_range(key, 0, media_probe::STREAM_HEAD_LIMIT as u64)
+        .await?;
+    if !media_probe::is_mp4(&head) {
+        return Ok(media_probe::probe(&head));
+    }
+
+    let mut moov = Vec::new();
+    let mut offset = 0;
+    for _ in 0..MAX_PROBED_BOXES {
+        if offset >= size {
+            break;
+        }
+        let header = storage.get_range(key, offset, MP4_BOX_HEADER_SIZE).await?;
+        let Some((name, box_size)) = media_probe::box_header(&header, size - offset) else {
+            break;
+        };
+        if &name == b"moov" {
+            if box_size <= media_probe::STREAM_MOOV_LIMIT {
+                moov = storage.get_range(key, offset, box_size).await?;
+            }
+            break;
+        }
+        offset = offset.saturating_add(box_size);
+    }
+
+    Ok(media_probe::probe_mp4(&head, &moov))
+}
+
+// =============================================================================================================================
+
+fn total_parts(session: &UploadSession) -> i32 {
+    ((session.size + session.part_size - 1) / session.part_size) as i32
+}
+
+// =============================================================================================================================
+
+fn to_response(session: &UploadSession) -> UploadSessionResponse {
+    let mut uploaded_parts: Vec<i32> = session.parts.iter().map(|part| part.part_number).collect();
+    uploaded_parts.sort();
+
+    UploadSessionResponse {
+        id: session.id.map(|id| id.to_hex()).unwrap_or_default(),
+        key: session.key.clone(),
+        content_type: session.content_type.clone(),
+        size: session.size,
+        part_size: session.part_size,
+        total_parts: total_parts(session),
+        uploaded_parts,
+        received: session.parts.iter().map(|part| part.size).sum(),
+        expires_at: session.expires_at,
+    }
+}
+
+// =============================================================================================================================
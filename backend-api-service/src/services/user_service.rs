@@ -1,33 +1,102 @@
-use crate::models::user_model::{CreateUser, UpdateUser, User};
+use crate::{
+    config::Config,
+    mailer::Mailer,
+    models::{
+        auth_model::PendingVerification,
+        user_model::{
+            AccountStatus, ChangeEmail, ChangePassword, CreateUser, Location, UpdateProfile,
+            UpdateRole, User, UserCounts, UserCursor, UserFilter, UserPage, UserQueryParams,
+            UserRole,
+        },
+    },
+    repositories::Repositories,
+    services::{auth_service, session_service},
+};
+use actix_web::web;
 use bcrypt::{DEFAULT_COST, hash};
-use bson::{oid::ObjectId, to_document};
-use futures_util::TryStreamExt;
-use mongodb::{Collection, Cursor, Database, bson::doc, options::ReturnDocument};
-use std::{error::Error, str::FromStr};
+use bson::oid::ObjectId;
+use chrono::Utc;
+use data_encoding::BASE64URL_NOPAD;
+use std::{error::Error, fmt, str::FromStr};
 use validator::Validate;
 
 // =============================================================================================================================
 
-const COLLECTION_NAME: &str = "users";
+const DEFAULT_USERS_LIMIT: i64 = 50;
+const MAX_USERS_LIMIT: i64 = 200;
 
 // =============================================================================================================================
 
-pub async fn get_users(db: &Database) -> Result<Vec<User>, Box<dyn Error>> {
-    let collection: Collection<User> = db.collection(COLLECTION_NAME);
-    let cursor: Cursor<User> = collection.find(doc! {}).await?;
-    let users: Vec<User> = cursor.try_collect().await?;
-    Ok(users)
+/// A page of the users matching the query, with the counts of the admin dashboard.
+pub async fn get_users(
+    repos: &Repositories,
+    params: UserQueryParams,
+) -> Result<UserPage, Box<dyn Error>> {
+    let sort = params.sort.unwrap_or_default();
+    let order = params.order.unwrap_or_default();
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_USERS_LIMIT)
+        .clamp(1, MAX_USERS_LIMIT);
+    let after = params.cursor.as_deref().map(decode_cursor).transpose()?;
+    let filter = UserFilter {
+        role: params.role,
+        verified: params.verified,
+        status: params.status,
+        search: params
+            .search
+            .map(|search| search.trim().to_lowercase())
+            .filter(|search| !search.is_empty()),
+    };
+
+    // One more than asked tells whether there is a next page.
+    let mut users = repos
+        .users
+        .find_page(&filter, sort, order, after.as_ref(), limit + 1)
+        .await?;
+    let next_cursor = if users.len() as i64 > limit {
+        users.truncate(limit as usize);
+        users.last().map(|user| encode_cursor(user, sort.key(user)))
+    } else {
+        None
+    };
+
+    let count = |filter: UserFilter| async move { repos.users.count(&filter).await };
+    Ok(UserPage {
+        total: count(filter.clone()).await?,
+        next_cursor,
+        counts: UserCounts {
+            users: count(UserFilter::default()).await?,
+            admins: count(UserFilter {
+                role: Some(UserRole::Admin),
+                ..Default::default()
+            })
+            .await?,
+            unverified: count(UserFilter {
+                verified: Some(false),
+                ..Default::default()
+            })
+            .await?,
+            suspended: count(UserFilter {
+                status: Some(AccountStatus::Suspended),
+                ..Default::default()
+            })
+            .await?,
+            banned: count(UserFilter {
+                status: Some(AccountStatus::Banned),
+                ..Default::default()
+            })
+            .await?,
+        },
+        users,
+    })
 }
 
 // =============================================================================================================================
 
-pub async fn get_user_by_id(db: &Database, id: String) -> Result<User, Box<dyn Error>> {
+pub async fn get_user_by_id(repos: &Repositories, id: String) -> Result<User, Box<dyn Error>> {
     let id = ObjectId::from_str(&id)?;
-    let collection: Collection<User> = db.collection(COLLECTION_NAME);
-    let filter = doc! {
-      "_id": id
-    };
-    match collection.find_one(filter).await? {
+    match repos.users.find_by_id(id).await? {
         Some(user) => Ok(user),
         None => Err("No user found with the given id".into()),
     }
@@ -35,12 +104,14 @@ pub async fn get_user_by_id(db: &Database, id: String) -> Result<User, Box<dyn E
 
 // =============================================================================================================================
 
-pub async fn create_user(db: &Database, payload: CreateUser) -> Result<User, Box<dyn Error>> {
+pub async fn create_user(
+    repos: &Repositories,
+    payload: CreateUser,
+) -> Result<User, Box<dyn Error>> {
     payload.validate()?;
     let hashed_password = hash(&payload.password, DEFAULT_COST)?;
-    let collection: Collection<User> = db.collection(COLLECTION_NAME);
 
-    let mut user = User {
+    let user = User {
         id: None,
         username: payload.username,
         email: payload.email,
@@ -49,44 +120,232 @@ pub async fn create_user(db: &Database, payload: CreateUser) -> Result<User, Box
         bio: payload.bio,
         role: payload.role,
         location: payload.location,
+        // Accounts created by an administrator skip the email verification.
+        verified: true,
+        status: AccountStatus::Active,
+        suspended_until: None,
     };
-    let res = collection.insert_one(&user).await?;
-    user.id = res.inserted_id.as_object_id();
 
-    Ok(user)
+    repos.users.insert(user).await
 }
 
 // =============================================================================================================================
 
-pub async fn update_user_by_id(
-    db: &Database,
+pub async fn update_profile(
+    repos: &Repositories,
     id: String,
-    user: UpdateUser,
+    profile: UpdateProfile,
 ) -> Result<User, Box<dyn Error>> {
+    profile.validate()?;
     let id = ObjectId::from_str(&id)?;
-    let collection: Collection<User> = db.collection(COLLECTION_NAME);
 
-    let update_doc = to_document(&user)?;
+    match repos.users.update_profile(id, profile).await? {
+        Some(user) => Ok(user),
+        None => Err("No user found with the given id".into()),
+    }
+}
+
+// =============================================================================================================================
+
+/// Requires the current password. Wrong guesses count towards the login lockout. Other sessions are signed out.
+pub async fn change_password(
+    repos: &Repositories,
+    config: &Config,
+    id: String,
+    session_id: String,
+    payload: ChangePassword,
+) -> Result<(), Box<dyn Error>> {
+    payload.validate()?;
+    let user = confirm_password(repos, config, &id, payload.current_password).await?;
+
+    let new_password = payload.new_password;
+    let password_hash = web::block(move || hash(new_password, DEFAULT_COST)).await??;
+    let user_id = user.id.ok_or("The user has no id")?;
+    repos.users.update_password(user_id, &password_hash).await?;
+    session_service::revoke_others(repos, user_id.to_hex(), Some(session_id)).await?;
+
+    Ok(())
+}
+
+// =============================================================================================================================
+
+/// Requires the current password and sends a verification link to the new address. The email only changes once the
+/// link is opened, through `auth_service::verify_email`.
+pub async fn change_email(
+    repos: &Repositories,
+    mailer: &dyn Mailer,
+    config: &Config,
+    id: String,
+    payload: ChangeEmail,
+) -> Result<PendingVerification, Box<dyn Error>> {
+    payload.validate()?;
+    let user = confirm_password(repos, config, &id, payload.current_password).await?;
+
+    if user.email == payload.email {
+        return Err("This is already the email address of the account".into());
+    }
+    if repos.users.find_by_email(&payload.email).await?.is_some() {
+        return Err("Email already exists".into());
+    }
+
+    auth_service::send_verification_email(
+        repos,
+        mailer,
+        config,
+        user.id.ok_or("The user has no id")?,
+        &payload.email,
+    )
+    .await?;
+
+    Ok(PendingVerification {
+        email: payload.email,
+        verified: false,
+    })
+}
+
+// =============================================================================================================================
+
+/// Signs the user out everywhere: tokens carry the role they were issued with, so none may outlive a role change.
+pub async fn update_role(
+    repos: &Repositories,
+    id: String,
+    payload: UpdateRole,
+) -> Result<User, Box<dyn Error>> {
+    let id = ObjectId::from_str(&id)?;
 
-    match collection
-        .find_one_and_update(doc! { "_id": id }, doc! { "$set": update_doc })
-        .return_document(ReturnDocument::After)
+    let user = match repos.users.update_role(id, payload.role).await? {
+        Some(user) => user,
+        None => return Err("No user found with the given id".into()),
+    };
+
+    repos.sessions.delete_all(id, None).await?;
+
+    Ok(user)
+}
+
+// =============================================================================================================================
+
+pub async fn seed_admin(
+    repos: &Repositories,
+    username: String,
+    email: String,
+    password: String,
+) -> Result<User, Box<dyn Error>> {
+    let payload = CreateUser {
+        username: username.trim().to_lowercase(),
+        email: email.trim().to_lowercase(),
+        password,
+        role: UserRole::Admin,
+        bio: "System administrator".to_string(),
+        avatar: None,
+        location: Location {
+            location_type: "Point".to_string(),
+            coordinates: [4.8156, 45.7107],
+        },
+    };
+    payload.validate()?;
+
+    let existing = match repos.users.find_by_email(&payload.email).await? {
+        Some(user) => user,
+        None => {
+            if repos
+                .users
+                .find_by_credential(&payload.username)
+                .await?
+                .is_some()
+            {
+                return Err("The username is already taken by another account".into());
+            }
+            return create_user(repos, payload).await;
+        }
+    };
+
+    // Re-running the command promotes the account and resets its password.
+    let id = existing.id.ok_or("The existing account has no id")?;
+    let password_hash = hash(&payload.password, DEFAULT_COST)?;
+    if repos
+        .users
+        .update_password(id, &password_hash)
         .await?
+        .is_none()
+        || repos
+            .users
+            .update_role(id, UserRole::Admin)
+            .await?
+            .is_none()
     {
+        return Err("Failed to update the admin account.".into());
+    }
+
+    match repos.users.set_verified_email(id, &payload.email).await? {
         Some(user) => Ok(user),
-        None => Err("Failed to update the current user.".into()),
+        None => Err("Failed to update the admin account.".into()),
     }
 }
 
 // =============================================================================================================================
 
-pub async fn delete_user_by_id(db: &Database, id: String) -> Result<User, Box<dyn Error>> {
-    let id = ObjectId::from_str(&id)?;
-    let collection: Collection<User> = db.collection(COLLECTION_NAME);
-    match collection.find_one_and_delete(doc! { "_id": id }).await? {
-        Some(user) => Ok(user),
-        None => Err("No user found with the given id".into()),
+fn encode_cursor(user: &User, key: &str) -> String {
+    let id = user.id.map(|id| id.to_hex()).unwrap_or_default();
+    BASE64URL_NOPAD.encode(format!("{}:{}", id, key).as_bytes())
+}
+
+fn decode_cursor(cursor: &str) -> Result<UserCursor, Box<dyn Error>> {
+    let decoded = BASE64URL_NOPAD
+        .decode(cursor.as_bytes())
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or("Invalid cursor")?;
+    let (id, key) = decoded.split_once(':').ok_or("Invalid cursor")?;
+
+    Ok(UserCursor {
+        id: ObjectId::from_str(id).map_err(|_| "Invalid cursor")?,
+        key: key.to_string(),
+    })
+}
+
+/// Checks the password of the signed-in user before a sensitive change.
+async fn confirm_password(
+    repos: &Repositories,
+    config: &Config,
+    id: &str,
+    password: String,
+) -> Result<User, Box<dyn Error>> {
+    let id = ObjectId::from_str(id)?;
+    let user = repos
+        .users
+        .find_by_id(id)
+        .await?
+        .ok_or("No user found with the given id")?;
+
+    let now = Utc::now();
+    auth_service::ensure_not_locked(repos, id, now).await?;
+    if !auth_service::verify_password(password, Some(user.password.clone())).await? {
+        return Err(auth_service::record_failed_login(
+            repos,
+            config,
+            id,
+            now,
+            Box::new(IncorrectPassword),
+        )
+        .await);
     }
+
+    Ok(user)
 }
 
 // =============================================================================================================================
+
+/// Returned when the current password given to confirm a change is wrong.
+#[derive(Debug)]
+pub struct IncorrectPassword;
+
+impl fmt::Display for IncorrectPassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The current password is incorrect")
+    }
+}
+
+impl Error for IncorrectPassword {}
+
+// =============================================================================================================================
@@ -0,0 +1,355 @@
+use crate::{
+    config::LocalStorageConfig,
+    models::media_model::ObjectMetadata,
+    storage::{PresignMethod, StorageBackend},
+    utils::sigv4,
+};
+use actix_web::web;
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
+
+// =============================================================================================================================
+
+const OBJECTS_DIR: &str = "objects";
+const METADATA_DIR: &str = "metadata";
+const MULTIPART_DIR: &str = "multipart";
+const CONTENT_TYPE_FILE: &str = "content-type";
+
+// =============================================================================================================================
+
+/// Stores objects on the local filesystem, for development without MinIO and for integration tests.
+/// Presigned URLs point at the `/api/files` routes and are signed with an HMAC of the method, key
+/// and expiry.
+pub struct LocalStorage {
+    root: PathBuf,
+    public_url: String,
+    secret: Vec<u8>,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>, public_url: &str, secret: &[u8]) -> Self {
+        Self {
+            root: root.into(),
+            public_url: public_url.trim_end_matches('/').to_string(),
+            secret: secret.to_vec(),
+        }
+    }
+
+    pub fn from_config(config: &LocalStorageConfig) -> Self {
+        Self::new(&config.path, &config.public_url, config.secret.as_bytes())
+    }
+
+    fn object_path(&self, key: &str) -> Result<PathBuf, Box<dyn Error>> {
+        Ok(self.root.join(OBJECTS_DIR).join(checked_key(key)?))
+    }
+
+    fn metadata_path(&self, key: &str) -> Result<PathBuf, Box<dyn Error>> {
+        Ok(self.root.join(METADATA_DIR).join(checked_key(key)?))
+    }
+
+    fn multipart_path(&self, upload_id: &str) -> Result<PathBuf, Box<dyn Error>> {
+        if upload_id.is_empty()
+            || !upload_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            return Err("Invalid upload id".into());
+        }
+        Ok(self.root.join(MULTIPART_DIR).join(upload_id))
+    }
+
+    fn signature(&self, method: PresignMethod, key: &str, expires: i64) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(format!("{}\n{}\n{}", method.as_str(), key, expires).as_bytes());
+        if let PresignMethod::Put {
+            content_type,
+            content_length,
+        } = method
+        {
+            mac.update(format!("\n{}\n{}", content_type, content_length).as_bytes());
+        }
+        mac
+    }
+}
+
+// =============================================================================================================================
+
+#[async_trait(?Send)]
+impl StorageBackend for LocalStorage {
+    fn object_url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_url, key)
+    }
+
+    fn object_key_from_url<'a>(&self, url: &'a str) -> Option<&'a str> {
+        url.strip_prefix(self.public_url.as_str())
+            .and_then(|path| path.strip_prefix('/'))
+            .filter(|key| !key.is_empty())
+    }
+
+    async fn put(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        let path = self.object_path(key)?;
+        let metadata_path = self.metadata_path(key)?;
+        let content_type = content_type.to_string();
+
+        web::block(move || {
+            write_atomically(&path, &data)?;
+            write_atomically(&metadata_path, content_type.as_bytes())
+        })
+        .await??;
+
+        Ok(self.object_url(key))
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let path = self.object_path(key)?;
+
+        match web::block(move || fs::read(path)).await? {
+            Ok(data) => Ok(data),
+            Err(e) if e.kind() == ErrorKind::NotFound => Err("File not found".into()),
+            Err(e) => Err(format!("Failed to download file: {}", e).into()),
+        }
+    }
+
+    async fn get_range(
+        &self,
+        key: &str,
+        start: u64,
+        length: u64,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let path = self.object_path(key)?;
+
+        let result = web::block(move || -> io::Result<Vec<u8>> {
+            let mut file = File::open(path)?;
+            file.seek(SeekFrom::Start(start))?;
+            let mut data = Vec::new();
+            file.take(length).read_to_end(&mut data)?;
+            Ok(data)
+        })
+        .await?;
+
+        match result {
+            Ok(data) => Ok(data),
+            Err(e) if e.kind() == ErrorKind::NotFound => Err("File not found".into()),
+            Err(e) => Err(format!("Failed to download file: {}", e).into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Box<dyn Error>> {
+        let path = self.object_path(key)?;
+        let metadata_path = self.metadata_path(key)?;
+
+        web::block(move || {
+            remove_if_exists(&path)?;
+            remove_if_exists(&metadata_path)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, Box<dyn Error>> {
+        let path = self.object_path(key)?;
+        let metadata_path = self.metadata_path(key)?;
+
+        let result = web::block(move || -> io::Result<ObjectMetadata> {
+            let size = fs::metadata(&path)?.len();
+            let content_type = fs::read_to_string(&metadata_path).unwrap_or_default();
+            Ok(ObjectMetadata { size, content_type })
+        })
+        .await?;
+
+        match result {
+            Ok(metadata) => Ok(metadata),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                Err("The file has not been uploaded".into())
+            }
+            Err(e) => Err(format!("Failed to inspect file: {}", e).into()),
+        }
+    }
+
+    fn presign(
+        &self,
+        method: PresignMethod,
+        key: &str,
+        expires_in: u64,
+    ) -> Result<String, Box<dyn Error>> {
+        checked_key(key)?;
+
+        let expires = Utc::now().timestamp() + expires_in as i64;
+        let signature = hex::encode(self.signature(method, key, expires).finalize().into_bytes());
+
+        Ok(format!(
+            "{}?expires={}&signature={}",
+            self.object_url(key),
+            expires,
+            signature
+        ))
+    }
+
+    fn verify_presigned(
+        &self,
+        method: PresignMethod,
+        key: &str,
+        expires: i64,
+        signature: &str,
+    ) -> bool {
+        if expires < Utc::now().timestamp() || checked_key(key).is_err() {
+            return false;
+        }
+
+        match hex::decode(signature) {
+            Ok(signature) => self
+                .signature(method, key, expires)
+                .verify_slice(&signature)
+                .is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    async fn create_multipart_upload(
+        &self,
+        key: &str,
+        content_type: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        checked_key(key)?;
+
+        let upload_id = Uuid::new_v4().to_string();
+        let path = self.multipart_path(&upload_id)?;
+        let content_type = content_type.to_string();
+
+        web::block(move || {
+            fs::create_dir_all(&path)?;
+            fs::write(path.join(CONTENT_TYPE_FILE), content_type)
+        })
+        .await??;
+
+        Ok(upload_id)
+    }
+
+    async fn upload_part(
+        &self,
+        _key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Vec<u8>,
+    ) -> Result<String, Box<dyn Error>> {
+        let path = self.multipart_path(upload_id)?;
+        let etag = format!("\"{}\"", sigv4::sha256_hex(&data));
+
+        web::block(move || {
+            if !path.is_dir() {
+                return Err(io::Error::new(
+                    ErrorKind::NotFound,
+                    "Multipart upload not found",
+                ));
+            }
+            write_atomically(&path.join(format!("part-{}", part_number)), &data)
+        })
+        .await??;
+
+        Ok(etag)
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[(i32, String)],
+    ) -> Result<String, Box<dyn Error>> {
+        let path = self.multipart_path(upload_id)?;
+        let object_path = self.object_path(key)?;
+        let metadata_path = self.metadata_path(key)?;
+        let parts = parts.to_vec();
+
+        web::block(move || -> Result<(), String> {
+            let mut data = Vec::new();
+            for (part_number, etag) in parts {
+                let part = fs::read(path.join(format!("part-{}", part_number)))
+                    .map_err(|_| format!("Part {} has not been uploaded", part_number))?;
+                if format!("\"{}\"", sigv4::sha256_hex(&part)) != etag {
+                    return Err(format!("Part {} does not match its ETag", part_number));
+                }
+                data.extend_from_slice(&part);
+            }
+
+            let content_type = fs::read_to_string(path.join(CONTENT_TYPE_FILE))
+                .map_err(|_| "Multipart upload not found".to_string())?;
+            write_atomically(&object_path, &data).map_err(|e| e.to_string())?;
+            write_atomically(&metadata_path, content_type.as_bytes()).map_err(|e| e.to_string())?;
+            fs::remove_dir_all(&path).map_err(|e| e.to_string())
+        })
+        .await??;
+
+        Ok(self.object_url(key))
+    }
+
+    async fn abort_multipart_upload(
+        &self,
+        _key: &str,
+        upload_id: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let path = self.multipart_path(upload_id)?;
+
+        web::block(move || match fs::remove_dir_all(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        })
+        .await??;
+
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
+
+/// Rejects keys that could escape the storage root.
+fn checked_key(key: &str) -> Result<&str, Box<dyn Error>> {
+    let valid = !key.is_empty()
+        && !key.contains('\\')
+        && key
+            .split('/')
+            .all(|segment| !segment.is_empty() && segment != "." && segment != "..");
+
+    if valid {
+        Ok(key)
+    } else {
+        Err("Invalid object key".into())
+    }
+}
+
+// =============================================================================================================================
+
+fn write_atomically(path: &Path, data: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp_path = path.with_extension(format!("{}.tmp", Uuid::new_v4().simple()));
+    fs::write(&temp_path, data)?;
+    fs::rename(&temp_path, path)
+}
+
+// =============================================================================================================================
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,123 @@
+use crate::{
+    config::{Config, StorageKind},
+    models::media_model::ObjectMetadata,
+};
+use async_trait::async_trait;
+use local_storage::LocalStorage;
+use s3_storage::S3Storage;
+use std::{error::Error, sync::Arc};
+
+pub mod local_storage;
+pub mod s3_storage;
+
+// =============================================================================================================================
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PresignMethod<'a> {
+    Get,
+    /// The upload is only accepted with exactly this `Content-Type` and `Content-Length`.
+    Put {
+        content_type: &'a str,
+        content_length: u64,
+    },
+}
+
+impl PresignMethod<'_> {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PresignMethod::Get => "GET",
+            PresignMethod::Put { .. } => "PUT",
+        }
+    }
+}
+
+// =============================================================================================================================
+
+/// Object storage used for every media file and export archive. Objects are addressed by key; the URL
+/// returned by `object_url` is what gets persisted in MongoDB and is never handed out to clients as-is,
+/// they only ever receive presigned URLs.
+#[async_trait(?Send)]
+pub trait StorageBackend: Send + Sync {
+    fn object_url(&self, key: &str) -> String;
+
+    fn object_key_from_url<'a>(&self, url: &'a str) -> Option<&'a str>;
+
+    async fn put(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<String, Box<dyn Error>>;
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, Box<dyn Error>>;
+
+    /// Reads at most `length` bytes from `start`, fewer when the object ends first.
+    async fn get_range(
+        &self,
+        key: &str,
+        start: u64,
+        length: u64,
+    ) -> Result<Vec<u8>, Box<dyn Error>>;
+
+    async fn delete(&self, key: &str) -> Result<(), Box<dyn Error>>;
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, Box<dyn Error>>;
+
+    fn presign(
+        &self,
+        method: PresignMethod,
+        key: &str,
+        expires_in: u64,
+    ) -> Result<String, Box<dyn Error>>;
+
+    /// Checks a presigned URL served by the API itself. Backends whose URLs point straight at the
+    /// object store (S3, MinIO) never accept them here.
+    fn verify_presigned(
+        &self,
+        _method: PresignMethod,
+        _key: &str,
+        _expires: i64,
+        _signature: &str,
+    ) -> bool {
+        false
+    }
+
+    async fn create_multipart_upload(
+        &self,
+        key: &str,
+        content_type: &str,
+    ) -> Result<String, Box<dyn Error>>;
+
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Vec<u8>,
+    ) -> Result<String, Box<dyn Error>>;
+
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[(i32, String)],
+    ) -> Result<String, Box<dyn Error>>;
+
+    async fn abort_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+    ) -> Result<(), Box<dyn Error>>;
+}
+
+// =============================================================================================================================
+
+/// Builds the backend selected by `storage.backend` (`STORAGE_BACKEND`).
+pub fn from_config(config: &Config) -> Arc<dyn StorageBackend> {
+    match config.storage.backend {
+        StorageKind::S3 => Arc::new(S3Storage::from_config(&config.storage.s3)),
+        StorageKind::Local => Arc::new(LocalStorage::from_config(&config.storage.local)),
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,390 @@
+use crate::{
+    config::S3Config,
+    models::media_model::ObjectMetadata,
+    storage::{PresignMethod, StorageBackend},
+    utils::sigv4::{self, SigV4Credentials},
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode, Url};
+use std::error::Error;
+
+// =============================================================================================================================
+
+/// S3-compatible object storage (MinIO in development), authenticated with AWS Signature V4.
+pub struct S3Storage {
+    server_url: String,
+    public_url: String,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    client: Client,
+}
+
+impl S3Storage {
+    pub fn from_config(config: &S3Config) -> Self {
+        Self {
+            server_url: config.server_url.clone(),
+            public_url: config
+                .public_url
+                .clone()
+                .unwrap_or_else(|| config.server_url.clone()),
+            bucket: config.bucket.clone(),
+            region: config.region.clone(),
+            access_key: config.access_key.clone(),
+            secret_key: config.secret_key.clone(),
+            client: Client::new(),
+        }
+    }
+
+    fn credentials(&self) -> SigV4Credentials<'_> {
+        SigV4Credentials {
+            access_key: &self.access_key,
+            secret_key: &self.secret_key,
+            region: &self.region,
+        }
+    }
+
+    async fn signed_request(
+        &self,
+        method: Method,
+        url: &str,
+        content_type: Option<&str>,
+        body: Vec<u8>,
+    ) -> Result<Response, Box<dyn Error>> {
+        Ok(self
+            .signed_builder(method, url, content_type, body)?
+            .send()
+            .await?)
+    }
+
+    fn signed_builder(
+        &self,
+        method: Method,
+        url: &str,
+        content_type: Option<&str>,
+        body: Vec<u8>,
+    ) -> Result<RequestBuilder, Box<dyn Error>> {
+        let url = Url::parse(url)?;
+        let payload_hash = sigv4::sha256_hex(&body);
+        let headers = sigv4::sign_request(
+            &self.credentials(),
+            method.as_str(),
+            &url,
+            &payload_hash,
+            Utc::now(),
+        );
+
+        let mut request = self.client.request(method, url).body(body);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        if let Some(content_type) = content_type {
+            request = request.header(reqwest::header::CONTENT_TYPE, content_type);
+        }
+
+        Ok(request)
+    }
+}
+
+// =============================================================================================================================
+
+#[async_trait(?Send)]
+impl StorageBackend for S3Storage {
+    fn object_url(&self, key: &str) -> String {
+        format!("{}/{}/{}", self.server_url, self.bucket, key)
+    }
+
+    fn object_key_from_url<'a>(&self, url: &'a str) -> Option<&'a str> {
+        url.strip_prefix(self.server_url.as_str())
+            .and_then(|path| path.strip_prefix(&format!("/{}/", self.bucket)))
+            .filter(|key| !key.is_empty())
+    }
+
+    async fn put(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        let url = self.object_url(key);
+
+        let response = self
+            .signed_request(Method::PUT, &url, Some(content_type), data)
+            .await?;
+
+        if response.status() != StatusCode::OK
+            && response.status() != StatusCode::CREATED
+            && response.status() != StatusCode::NO_CONTENT
+        {
+            return Err(format!(
+                "Failed to upload file: {} - {}",
+                response.status(),
+                response.text().await?
+            )
+            .into());
+        }
+
+        Ok(url)
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let response = self
+            .signed_request(Method::GET, &self.object_url(key), None, Vec::new())
+            .await?;
+
+        if response.status() != StatusCode::OK {
+            return Err(format!(
+                "Failed to download file: {} - {}",
+                response.status(),
+                response.text().await?
+            )
+            .into());
+        }
+
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    async fn get_range(
+        &self,
+        key: &str,
+        start: u64,
+        length: u64,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        if length == 0 {
+            return Ok(Vec::new());
+        }
+
+        let response = self
+            .signed_builder(Method::GET, &self.object_url(key), None, Vec::new())?
+            .header(
+                reqwest::header::RANGE,
+                format!("bytes={}-{}", start, start + length - 1),
+            )
+            .send()
+            .await?;
+
+        match response.status() {
+            StatusCode::PARTIAL_CONTENT => Ok(response.bytes().await?.to_vec()),
+            // Served whole by stores that ignore the range.
+            StatusCode::OK => {
+                let data = response.bytes().await?;
+                let start = (start as usize).min(data.len());
+                let end = start.saturating_add(length as usize).min(data.len());
+                Ok(data[start..end].to_vec())
+            }
+            StatusCode::RANGE_NOT_SATISFIABLE => Ok(Vec::new()),
+            status => Err(format!(
+                "Failed to download file: {} - {}",
+                status,
+                response.text().await?
+            )
+            .into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Box<dyn Error>> {
+        let response = self
+            .signed_request(Method::DELETE, &self.object_url(key), None, Vec::new())
+            .await?;
+
+        if response.status() != StatusCode::OK && response.status() != StatusCode::NO_CONTENT {
+            return Err(format!(
+                "Failed to delete file: {} - {}",
+                response.status(),
+                response.text().await?
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, Box<dyn Error>> {
+        let response = self
+            .signed_request(Method::HEAD, &self.object_url(key), None, Vec::new())
+            .await?;
+
+        match response.status() {
+            StatusCode::OK => {}
+            StatusCode::NOT_FOUND => return Err("The file has not been uploaded".into()),
+            status => return Err(format!("Failed to inspect file: {}", status).into()),
+        }
+
+        let size = response.content_length().unwrap_or(0);
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        Ok(ObjectMetadata { size, content_type })
+    }
+
+    fn presign(
+        &self,
+        method: PresignMethod,
+        key: &str,
+        expires_in: u64,
+    ) -> Result<String, Box<dyn Error>> {
+        let url = Url::parse(&format!("{}/{}/{}", self.public_url, self.bucket, key))?;
+        let headers = match method {
+            PresignMethod::Get => Vec::new(),
+            PresignMethod::Put {
+                content_type,
+                content_length,
+            } => vec![
+                ("content-length", content_length.to_string()),
+                ("content-type", content_type.to_string()),
+            ],
+        };
+        let presigned = sigv4::presign_url(
+            &self.credentials(),
+            method.as_str(),
+            &url,
+            &headers,
+            expires_in,
+            Utc::now(),
+        );
+        Ok(presigned.to_string())
+    }
+
+    async fn create_multipart_upload(
+        &self,
+        key: &str,
+        content_type: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut url = Url::parse(&self.object_url(key))?;
+        url.query_pairs_mut().append_key_only("uploads");
+
+        let response = self
+            .signed_request(Method::POST, url.as_str(), Some(content_type), Vec::new())
+            .await?;
+
+        if response.status() != StatusCode::OK {
+            return Err(format!(
+                "Failed to start multipart upload: {} - {}",
+                response.status(),
+                response.text().await?
+            )
+            .into());
+        }
+
+        let body = response.text().await?;
+        xml_value(&body, "UploadId")
+            .map(str::to_string)
+            .ok_or_else(|| "Invalid multipart upload response".into())
+    }
+
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Vec<u8>,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut url = Url::parse(&self.object_url(key))?;
+        url.query_pairs_mut()
+            .append_pair("partNumber", &part_number.to_string())
+            .append_pair("uploadId", upload_id);
+
+        let response = self
+            .signed_request(Method::PUT, url.as_str(), None, data)
+            .await?;
+
+        if response.status() != StatusCode::OK {
+            return Err(format!(
+                "Failed to upload part {}: {} - {}",
+                part_number,
+                response.status(),
+                response.text().await?
+            )
+            .into());
+        }
+
+        response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+            .ok_or_else(|| "Missing ETag in upload part response".into())
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[(i32, String)],
+    ) -> Result<String, Box<dyn Error>> {
+        let mut url = Url::parse(&self.object_url(key))?;
+        url.query_pairs_mut().append_pair("uploadId", upload_id);
+
+        let body = format!(
+            "<CompleteMultipartUpload>{}</CompleteMultipartUpload>",
+            parts
+                .iter()
+                .map(|(part_number, etag)| format!(
+                    "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                    part_number, etag
+                ))
+                .collect::<String>()
+        );
+
+        let response = self
+            .signed_request(
+                Method::POST,
+                url.as_str(),
+                Some("application/xml"),
+                body.into_bytes(),
+            )
+            .await?;
+
+        let status = response.status();
+        let text = response.text().await?;
+        // S3 may report a failed completion with a 200 status and an <Error> document.
+        if status != StatusCode::OK || xml_value(&text, "Code").is_some() {
+            return Err(
+                format!("Failed to complete multipart upload: {} - {}", status, text).into(),
+            );
+        }
+
+        Ok(self.object_url(key))
+    }
+
+    async fn abort_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut url = Url::parse(&self.object_url(key))?;
+        url.query_pairs_mut().append_pair("uploadId", upload_id);
+
+        let response = self
+            .signed_request(Method::DELETE, url.as_str(), None, Vec::new())
+            .await?;
+
+        if response.status() != StatusCode::NO_CONTENT && response.status() != StatusCode::NOT_FOUND
+        {
+            return Err(format!(
+                "Failed to abort multipart upload: {} - {}",
+                response.status(),
+                response.text().await?
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+// =============================================================================================================================
+
+fn xml_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(&xml[start..end])
+}
+
+// =============================================================================================================================
@@ -0,0 +1,86 @@
+use actix_cors::Cors;
+use actix_web::http::{Method, header::HeaderName};
+
+use crate::config::{Config, Environment};
+
+// =============================================================================================================================
+
+const LOOPBACK_HOSTS: [&str; 3] = ["localhost", "127.0.0.1", "[::1]"];
+
+// =============================================================================================================================
+
+/// Builds the CORS middleware from a configuration that already passed `Config::validate`. Origins are matched
+/// exactly; in development, loopback origins on any port are accepted too so local dev servers work out of the box.
+pub fn build_cors(config: &Config) -> Cors {
+    let cors_config = &config.cors;
+
+    let mut cors = Cors::default();
+    for origin in &cors_config.allowed_origins {
+        cors = cors.allowed_origin(origin);
+    }
+    if config.server.environment == Environment::Development {
+        cors = cors
+            .allowed_origin_fn(|origin, _req_head| origin.to_str().is_ok_and(is_loopback_origin));
+    }
+
+    cors = cors
+        .allowed_methods(
+            cors_config
+                .allowed_methods
+                .iter()
+                .filter_map(|method| Method::from_bytes(method.as_bytes()).ok()),
+        )
+        .allowed_headers(
+            cors_config
+                .allowed_headers
+                .iter()
+                .filter_map(|header| HeaderName::from_bytes(header.as_bytes()).ok()),
+        )
+        .max_age(cors_config.max_age_secs);
+    if cors_config.allow_credentials {
+        cors = cors.supports_credentials();
+    }
+
+    cors
+}
+
+// =============================================================================================================================
+
+/// One line summary of the effective policy, logged at startup.
+pub fn describe_cors(config: &Config) -> String {
+    let cors_config = &config.cors;
+    let loopback = match config.server.environment {
+        Environment::Development => " + loopback on any port",
+        Environment::Production => "",
+    };
+
+    format!(
+        "{:?} mode, origins [{}]{}, methods [{}], headers [{}], credentials {}, max age {}s",
+        config.server.environment,
+        cors_config.allowed_origins.join(", "),
+        loopback,
+        cors_config.allowed_methods.join(", "),
+        cors_config.allowed_headers.join(", "),
+        cors_config.allow_credentials,
+        cors_config.max_age_secs
+    )
+}
+
+// =============================================================================================================================
+
+fn is_loopback_origin(origin: &str) -> bool {
+    let Some(authority) = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"))
+    else {
+        return false;
+    };
+    let host = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => authority,
+    };
+
+    LOOPBACK_HOSTS.contains(&host)
+}
+
+// =============================================================================================================================
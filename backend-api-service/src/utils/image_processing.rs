@@ -0,0 +1,219 @@
+use image::{
+    DynamicImage, ImageError, ImageFormat, ImageReader, ImageResult, Limits,
+    codecs::{jpeg::JpegEncoder, png::PngEncoder},
+    error::{DecodingError, ImageFormatHint},
+    metadata::Orientation,
+};
+use std::io::Cursor;
+
+// =============================================================================================================================
+
+pub const FEED_MAX_DIMENSION: u32 = 1080;
+pub const THUMBNAIL_MAX_DIMENSION: u32 = 320;
+const JPEG_QUALITY: u8 = 85;
+
+// Decoding stops past these, so a small file declaring huge dimensions cannot exhaust the memory of the server.
+const MAX_DECODED_DIMENSION: u32 = 10_000;
+const MAX_DECODE_ALLOCATION: u64 = 256 * 1024 * 1024;
+
+// =============================================================================================================================
+
+pub struct ImageRendition {
+    pub data: Vec<u8>,
+    pub content_type: &'static str,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub struct ProcessedImage {
+    pub original: ImageRendition,
+    pub feed: ImageRendition,
+    pub thumbnail: ImageRendition,
+}
+
+impl ProcessedImage {
+    /// The bytes taken by the three renditions once stored.
+    pub fn size(&self) -> usize {
+        self.original.data.len() + self.feed.data.len() + self.thumbnail.data.len()
+    }
+}
+
+// =============================================================================================================================
+
+/// Decodes an uploaded image, rotates it upright according to its EXIF orientation and re-encodes it
+/// into the original, feed and thumbnail renditions. Re-encoding drops every metadata block (EXIF,
+/// GPS, XMP) from the stored files. GIFs keep their frames so they stay animated, but lose their
+/// comment, plain text and application extensions, which is where XMP hides in them.
+pub fn process_image(data: &[u8], orientation: Option<i32>) -> ImageResult<ProcessedImage> {
+    let format = image::guess_format(data)?;
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DECODED_DIMENSION);
+    limits.max_image_height = Some(MAX_DECODED_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOCATION);
+
+    let mut reader = ImageReader::with_format(Cursor::new(data), format);
+    reader.limits(limits);
+    let mut image = reader.decode()?;
+
+    if let Some(orientation) = orientation
+        .and_then(|orientation| u8::try_from(orientation).ok())
+        .and_then(Orientation::from_exif)
+    {
+        image.apply_orientation(orientation);
+    }
+
+    let original = if format == ImageFormat::Gif {
+        ImageRendition {
+            data: strip_gif_metadata(data)?,
+            content_type: "image/gif",
+            width: image.width(),
+            height: image.height(),
+        }
+    } else {
+        encode(&image)?
+    };
+
+    Ok(ProcessedImage {
+        original,
+        feed: encode(&downscale(&image, FEED_MAX_DIMENSION))?,
+        thumbnail: encode(&downscale(&image, THUMBNAIL_MAX_DIMENSION))?,
+    })
+}
+
+// =============================================================================================================================
+
+const GIF_EXTENSION: u8 = 0x21;
+const GIF_IMAGE_DESCRIPTOR: u8 = 0x2C;
+const GIF_TRAILER: u8 = 0x3B;
+const GIF_GRAPHIC_CONTROL_LABEL: u8 = 0xF9;
+const GIF_APPLICATION_LABEL: u8 = 0xFF;
+
+/// Application extensions that only tell how to play the animation.
+const GIF_LOOP_APPLICATIONS: [&[u8]; 2] = [b"NETSCAPE2.0", b"ANIMEXTS1.0"];
+
+/// Copies the GIF block by block, keeping the header, the color tables, the frames with their graphic control
+/// extensions and the loop count, and dropping every other extension and anything after the trailer.
+fn strip_gif_metadata(data: &[u8]) -> ImageResult<Vec<u8>> {
+    let mut gif = GifBlocks { data, position: 0 };
+    let mut stripped = Vec::with_capacity(data.len());
+
+    // Header and logical screen descriptor, then the global color table when the descriptor announces one.
+    let screen = gif.take(13)?;
+    stripped.extend_from_slice(screen);
+    stripped.extend_from_slice(gif.take(color_table_size(screen[10]))?);
+
+    loop {
+        match gif.take(1)?[0] {
+            GIF_EXTENSION => {
+                let label = gif.take(1)?[0];
+                let start = gif.position;
+                gif.skip_sub_blocks()?;
+                let body = &data[start..gif.position];
+
+                let keep = match label {
+                    GIF_GRAPHIC_CONTROL_LABEL => true,
+                    GIF_APPLICATION_LABEL => GIF_LOOP_APPLICATIONS
+                        .iter()
+                        .any(|application| body.get(1..12) == Some(*application)),
+                    _ => false,
+                };
+                if keep {
+                    stripped.extend_from_slice(&[GIF_EXTENSION, label]);
+                    stripped.extend_from_slice(body);
+                }
+            }
+            GIF_IMAGE_DESCRIPTOR => {
+                let descriptor = gif.take(9)?;
+                stripped.push(GIF_IMAGE_DESCRIPTOR);
+                stripped.extend_from_slice(descriptor);
+                stripped.extend_from_slice(gif.take(color_table_size(descriptor[8]))?);
+
+                // LZW minimum code size, then the image data sub-blocks.
+                let start = gif.position;
+                gif.take(1)?;
+                gif.skip_sub_blocks()?;
+                stripped.extend_from_slice(&data[start..gif.position]);
+            }
+            GIF_TRAILER => {
+                stripped.push(GIF_TRAILER);
+                return Ok(stripped);
+            }
+            _ => return Err(malformed_gif()),
+        }
+    }
+}
+
+fn color_table_size(flags: u8) -> usize {
+    match flags & 0x80 {
+        0 => 0,
+        _ => 3 << ((flags & 0x07) + 1),
+    }
+}
+
+struct GifBlocks<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> GifBlocks<'a> {
+    fn take(&mut self, length: usize) -> ImageResult<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + length)
+            .ok_or_else(malformed_gif)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    /// Skips sub-blocks up to and including the empty one that ends them.
+    fn skip_sub_blocks(&mut self) -> ImageResult<()> {
+        loop {
+            let length = self.take(1)?[0] as usize;
+            if length == 0 {
+                return Ok(());
+            }
+            self.take(length)?;
+        }
+    }
+}
+
+fn malformed_gif() -> ImageError {
+    ImageError::Decoding(DecodingError::new(
+        ImageFormatHint::Exact(ImageFormat::Gif),
+        "Malformed GIF block",
+    ))
+}
+
+// =============================================================================================================================
+
+fn downscale(image: &DynamicImage, max_dimension: u32) -> DynamicImage {
+    if image.width() <= max_dimension && image.height() <= max_dimension {
+        return image.clone();
+    }
+    image.thumbnail(max_dimension, max_dimension)
+}
+
+// =============================================================================================================================
+
+fn encode(image: &DynamicImage) -> ImageResult<ImageRendition> {
+    let mut data = Vec::new();
+
+    let content_type = if image.color().has_alpha() {
+        image.write_with_encoder(PngEncoder::new(&mut data))?;
+        "image/png"
+    } else {
+        let rgb = DynamicImage::ImageRgb8(image.to_rgb8());
+        rgb.write_with_encoder(JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY))?;
+        "image/jpeg"
+    };
+
+    Ok(ImageRendition {
+        data,
+        content_type,
+        width: image.width(),
+        height: image.height(),
+    })
+}
+
+// =============================================================================================================================
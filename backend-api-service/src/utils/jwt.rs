@@ -1,19 +1,270 @@
-use std::env;
-
-use actix_web::{HttpRequest, HttpResponse, http::header};
+use actix_web::{HttpRequest, HttpResponse, http::header, web};
 use chrono::{Duration, Utc};
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
-use once_cell::sync::Lazy;
+use data_encoding::{BASE64, BASE64URL_NOPAD};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, decode_header, encode,
+};
+use ring::{
+    rand::SystemRandom,
+    signature::{Ed25519KeyPair, KeyPair, RsaKeyPair, RsaPublicKeyComponents},
+};
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{fs, path::Path, sync::Arc};
 
-use crate::{models::user_model::UserRole, utils::api_response::ApiResponse};
+use crate::{
+    config::{AuthConfig, Config},
+    models::user_model::UserRole,
+    utils::api_response::ApiResponse,
+};
 
 // =============================================================================================================================
 
-pub static JWT_EXTERNAL_SIGNATURE: Lazy<Vec<u8>> = Lazy::new(|| {
-    let secret_str = env::var("JWT_SIGNATURE").expect("JWT_SIGNATURE not set");
-    secret_str.into_bytes()
-});
+const KEY_FILE_EXTENSION: &str = "pem";
+const PKCS8_LABEL: &str = "PRIVATE KEY";
+const PKCS1_LABEL: &str = "RSA PRIVATE KEY";
+
+// =============================================================================================================================
+
+/// A private key loaded from `<kid>.pem`, with the public half published in the JWKS.
+struct SigningKey {
+    kid: String,
+    algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    jwk: Value,
+}
+
+/// The keys tokens are signed and verified with. Every key verifies, only the active one signs, so a new key can be
+/// activated while tokens signed with the previous one stay valid until they expire.
+#[derive(Clone, Default)]
+pub struct JwtKeys {
+    keys: Arc<Vec<SigningKey>>,
+    active: usize,
+}
+
+impl JwtKeys {
+    /// Loads every `*.pem` file in `dir`, PKCS#8 Ed25519 (EdDSA) or RSA (RS256) keys, named after their key id.
+    /// `active` defaults to the greatest key id, so timestamped names activate the newest key.
+    pub fn load(dir: &str, active: Option<&str>) -> Result<Self, String> {
+        let entries = fs::read_dir(dir).map_err(|e| format!("Cannot read {}: {}", dir, e))?;
+
+        let mut keys = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(KEY_FILE_EXTENSION) {
+                continue;
+            }
+            let kid = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or_else(|| format!("{} is not a valid key id", path.display()))?
+                .to_string();
+            let pem = fs::read_to_string(&path)
+                .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+            keys.push(
+                SigningKey::from_pem(kid, &pem)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?,
+            );
+        }
+        keys.sort_by(|a, b| a.kid.cmp(&b.kid));
+
+        let active = match active {
+            Some(kid) => keys
+                .iter()
+                .position(|key| key.kid == kid)
+                .ok_or_else(|| format!("No key with id {:?} in {}", kid, dir))?,
+            None if keys.is_empty() => return Err(format!("No .pem key in {}", dir)),
+            None => keys.len() - 1,
+        };
+
+        Ok(Self {
+            keys: Arc::new(keys),
+            active,
+        })
+    }
+
+    /// A single Ed25519 key that lives as long as the process, for development without `JWT_KEYS_DIR`.
+    pub fn generate() -> Self {
+        let pem = generate_key_pem();
+        let kid = format!("ephemeral-{}", Utc::now().format("%Y%m%d%H%M%S"));
+        let key = SigningKey::from_pem(kid, &pem).expect("A generated key is valid");
+        Self {
+            keys: Arc::new(vec![key]),
+            active: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn active_kid(&self) -> Option<&str> {
+        self.keys.get(self.active).map(|key| key.kid.as_str())
+    }
+
+    /// The public keys as a JSON Web Key Set, served at `/.well-known/jwks.json`.
+    pub fn jwks(&self) -> Value {
+        json!({ "keys": self.keys.iter().map(|key| &key.jwk).collect::<Vec<_>>() })
+    }
+
+    fn signing_key(&self) -> Result<&SigningKey, String> {
+        self.keys
+            .get(self.active)
+            .ok_or_else(|| "No signing key is configured".to_string())
+    }
+
+    fn verifying_key(&self, token: &str) -> Result<&SigningKey, String> {
+        let header = decode_header(token).map_err(|e| e.to_string())?;
+        let kid = header.kid.ok_or("Token has no key id")?;
+        self.keys
+            .iter()
+            .find(|key| key.kid == kid && key.algorithm == header.alg)
+            .ok_or_else(|| format!("Unknown signing key {:?}", kid))
+    }
+}
+
+impl SigningKey {
+    fn from_pem(kid: String, pem: &str) -> Result<Self, String> {
+        let (label, der) = parse_pem(pem)?;
+
+        let (algorithm, encoding, decoding, mut jwk) = match label.as_str() {
+            PKCS8_LABEL if Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der).is_ok() => {
+                let pair =
+                    Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der).map_err(|e| e.to_string())?;
+                let x = BASE64URL_NOPAD.encode(pair.public_key().as_ref());
+                (
+                    Algorithm::EdDSA,
+                    EncodingKey::from_ed_der(&der),
+                    DecodingKey::from_ed_components(&x).map_err(|e| e.to_string())?,
+                    json!({ "kty": "OKP", "crv": "Ed25519", "x": x }),
+                )
+            }
+            PKCS8_LABEL | PKCS1_LABEL => {
+                let pair = match label.as_str() {
+                    PKCS8_LABEL => RsaKeyPair::from_pkcs8(&der),
+                    _ => RsaKeyPair::from_der(&der),
+                }
+                .map_err(|e| format!("Not an Ed25519 or RSA (2048 bits or more) key: {}", e))?;
+                let public = RsaPublicKeyComponents::<Vec<u8>>::from(pair.public());
+                let (n, e) = (
+                    BASE64URL_NOPAD.encode(&public.n),
+                    BASE64URL_NOPAD.encode(&public.e),
+                );
+                (
+                    Algorithm::RS256,
+                    EncodingKey::from_rsa_pem(pem.as_bytes()).map_err(|e| e.to_string())?,
+                    DecodingKey::from_rsa_components(&n, &e).map_err(|e| e.to_string())?,
+                    json!({ "kty": "RSA", "n": n, "e": e }),
+                )
+            }
+            _ => return Err(format!("Unsupported PEM block {:?}", label)),
+        };
+
+        jwk["kid"] = json!(kid);
+        jwk["alg"] = json!(algorithm);
+        jwk["use"] = json!("sig");
+
+        Ok(Self {
+            kid,
+            algorithm,
+            encoding,
+            decoding,
+            jwk,
+        })
+    }
+}
+
+// =============================================================================================================================
+
+/// A new PKCS#8 Ed25519 private key, PEM encoded, for the `generate-jwt-key` command.
+pub fn generate_key_pem() -> String {
+    let der = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+        .expect("The system random number generator is available");
+    let body = BASE64.encode(der.as_ref());
+    let lines = body
+        .as_bytes()
+        .chunks(64)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect::<Vec<_>>();
+
+    format!(
+        "-----BEGIN {label}-----\n{}\n-----END {label}-----\n",
+        lines.join("\n"),
+        label = PKCS8_LABEL
+    )
+}
+
+/// Writes a new key to `<dir>/<kid>.pem`, the key id being the current UTC time so it sorts after the existing keys.
+pub fn write_new_key(dir: &str) -> Result<String, String> {
+    let kid = Utc::now().format("%Y%m%d%H%M%S").to_string();
+    let path = Path::new(dir).join(format!("{}.{}", kid, KEY_FILE_EXTENSION));
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()));
+    }
+
+    fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}: {}", dir, e))?;
+    fs::write(&path, generate_key_pem())
+        .map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+    Ok(kid)
+}
+
+fn parse_pem(pem: &str) -> Result<(String, Vec<u8>), String> {
+    let pem = pem.trim();
+    let label = pem
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("-----BEGIN "))
+        .and_then(|line| line.strip_suffix("-----"))
+        .ok_or("Not a PEM file")?
+        .to_string();
+    let body = pem
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.starts_with("-----END "))
+        .map(str::trim)
+        .collect::<String>();
+    let der = BASE64
+        .decode(body.as_bytes())
+        .map_err(|e| format!("Invalid PEM body: {}", e))?;
+
+    Ok((label, der))
+}
+
+// =============================================================================================================================
+
+/// Adds the registered `iss` and `aud` claims to the token's own claims.
+#[derive(Serialize)]
+struct Registered<'a, T> {
+    #[serde(flatten)]
+    claims: &'a T,
+    iss: &'a str,
+    aud: &'a str,
+}
+
+fn sign<T: Serialize>(config: &AuthConfig, claims: &T) -> Result<String, String> {
+    let key = config.keys.signing_key()?;
+    let mut header = Header::new(key.algorithm);
+    header.kid = Some(key.kid.clone());
+
+    let claims = Registered {
+        claims,
+        iss: &config.issuer,
+        aud: &config.audience,
+    };
+    encode(&header, &claims, &key.encoding).map_err(|e| e.to_string())
+}
+
+fn verify<T: for<'de> Deserialize<'de>>(config: &AuthConfig, token: &str) -> Result<T, String> {
+    let key = config.keys.verifying_key(token)?;
+    let mut validation = Validation::new(key.algorithm);
+    validation.set_issuer(&[&config.issuer]);
+    validation.set_audience(&[&config.audience]);
+
+    decode::<T>(token, &key.decoding, &validation)
+        .map(|data| data.claims)
+        .map_err(|e| e.to_string())
+}
 
 // =============================================================================================================================
 
@@ -21,41 +272,67 @@ pub static JWT_EXTERNAL_SIGNATURE: Lazy<Vec<u8>> = Lazy::new(|| {
 pub struct ExternalClaims {
     pub user_id: String,
     pub role: UserRole,
+    /// The session the token was issued for, see `session_service`.
+    pub session_id: String,
     pub exp: i64,
 }
 
 // =============================================================================================================================
 
-pub fn encode_external_jwt(user_id: String, role: UserRole) -> Result<String, String> {
-    let signature = JWT_EXTERNAL_SIGNATURE.as_slice();
+pub fn encode_external_jwt(
+    config: &AuthConfig,
+    user_id: String,
+    role: UserRole,
+    session_id: String,
+    exp: i64,
+) -> Result<String, String> {
     let claims = ExternalClaims {
         user_id,
         role,
-        exp: (Utc::now() + Duration::minutes(60)).timestamp(),
+        session_id,
+        exp,
     };
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(signature),
-    )
-    .map_err(|e| e.to_string())
+    sign(config, &claims)
 }
 
 // =============================================================================================================================
 
-pub fn decode_external_jwt(token: &str) -> Result<ExternalClaims, String> {
-    let signature = JWT_EXTERNAL_SIGNATURE.as_slice();
-    decode::<ExternalClaims>(
-        token,
-        &DecodingKey::from_secret(signature),
-        &Validation::default(),
-    )
-    .map(|data| data.claims)
-    .map_err(|e| e.to_string())
+pub fn decode_external_jwt(config: &AuthConfig, token: &str) -> Result<ExternalClaims, String> {
+    verify(config, token)
 }
 
 // =============================================================================================================================
 
+const TWO_FACTOR_PURPOSE: &str = "two_factor";
+
+/// Proves the password step of a login on an account with two-factor authentication. Its claims differ from
+/// `ExternalClaims`, so it is never accepted as an access token, and an access token is never accepted as one.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChallengeClaims {
+    pub sub: String,
+    pub purpose: String,
+    pub exp: i64,
+}
+
+pub fn encode_challenge_jwt(config: &AuthConfig, user_id: String) -> Result<String, String> {
+    let claims = ChallengeClaims {
+        sub: user_id,
+        purpose: TWO_FACTOR_PURPOSE.to_string(),
+        exp: (Utc::now() + Duration::minutes(config.two_factor_challenge_minutes)).timestamp(),
+    };
+    sign(config, &claims)
+}
+
+pub fn decode_challenge_jwt(config: &AuthConfig, token: &str) -> Result<ChallengeClaims, String> {
+    let claims = verify::<ChallengeClaims>(config, token)?;
+
+    match claims.purpose == TWO_FACTOR_PURPOSE {
+        true => Ok(claims),
+        false => Err("Not a two-factor challenge".to_string()),
+    }
+}
+
+// =============================================================================================================================
 pub fn get_external_jwt(req: &HttpRequest) -> Result<ExternalClaims, String> {
     let auth_header = req
         .headers()
@@ -68,7 +345,11 @@ pub fn get_external_jwt(req: &HttpRequest) -> Result<ExternalClaims, String> {
         .strip_prefix("Bearer ")
         .ok_or("Invalid token format, expected Bearer")?;
 
-    decode_external_jwt(token)
+    let config = req
+        .app_data::<web::Data<Config>>()
+        .ok_or("Authentication is not configured")?;
+
+    decode_external_jwt(&config.auth, token)
 }
 
 // =============================================================================================================================
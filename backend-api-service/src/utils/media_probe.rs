@@ -0,0 +1,587 @@
+use crate::models::{media_model::MediaInfo, message_model::MediaType};
+
+// =============================================================================================================================
+
+const EBML_HEADER: u32 = 0x1A45_DFA3;
+const EBML_DOC_TYPE: u32 = 0x4282;
+const EBML_SEGMENT: u32 = 0x1853_8067;
+const EBML_INFO: u32 = 0x1549_A966;
+const EBML_TIMECODE_SCALE: u32 = 0x2A_D7B1;
+const EBML_DURATION: u32 = 0x4489;
+const EBML_TRACKS: u32 = 0x1654_AE6B;
+const EBML_TRACK_ENTRY: u32 = 0xAE;
+const EBML_VIDEO: u32 = 0xE0;
+const EBML_PIXEL_WIDTH: u32 = 0xB0;
+const EBML_PIXEL_HEIGHT: u32 = 0xBA;
+
+const EXIF_ORIENTATION_TAG: u16 = 0x0112;
+
+pub const STREAM_HEAD_LIMIT: usize = 64 * 1024;
+pub const STREAM_MOOV_LIMIT: u64 = 4 * 1024 * 1024;
+
+// =============================================================================================================================
+
+/// Detects the real format of a media file from its magic bytes and extracts the metadata the
+/// container exposes (dimensions, duration, orientation). Returns `None` for unsupported formats.
+pub fn probe(data: &[u8]) -> Option<MediaInfo> {
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        let (dimensions, orientation) = jpeg_metadata(data);
+        return Some(image_info("image/jpeg", dimensions, orientation));
+    }
+
+    if data.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        return Some(image_info("image/png", png_dimensions(data), None));
+    }
+
+    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        let dimensions = le_u16(data, 6)
+            .zip(le_u16(data, 8))
+            .map(|(width, height)| (width as u32, height as u32));
+        return Some(image_info("image/gif", dimensions, None));
+    }
+
+    if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        return Some(image_info("image/webp", webp_dimensions(data), None));
+    }
+
+    if data.get(4..8) == Some(b"ftyp") {
+        return mp4_info(data);
+    }
+
+    if be_u32(data, 0) == Some(EBML_HEADER) {
+        return webm_info(data);
+    }
+
+    None
+}
+
+// =============================================================================================================================
+
+/// Incremental counterpart of `probe` for uploads that are streamed to storage without being buffered.
+/// It keeps the head of the file and, for MP4/QuickTime, the `moov` box wherever it sits in the file,
+/// which is everything `probe` reads for videos.
+#[derive(Default)]
+pub struct StreamProbe {
+    head: Vec<u8>,
+    offset: u64,
+    box_end: u64,
+    box_header: Vec<u8>,
+    moov: Vec<u8>,
+    moov_end: u64,
+    malformed: bool,
+}
+
+impl StreamProbe {
+    pub fn push(&mut self, mut chunk: &[u8]) {
+        let head_room = STREAM_HEAD_LIMIT.saturating_sub(self.head.len());
+        self.head
+            .extend_from_slice(&chunk[..head_room.min(chunk.len())]);
+
+        if self.head.len() >= 8 && !self.is_mp4() {
+            return;
+        }
+
+        while !chunk.is_empty() && !self.malformed {
+            if self.offset < self.box_end {
+                let take = (self.box_end - self.offset).min(chunk.len() as u64) as usize;
+                if self.offset < self.moov_end {
+                    self.moov.extend_from_slice(&chunk[..take]);
+                }
+                self.offset += take as u64;
+                chunk = &chunk[take..];
+                continue;
+            }
+
+            let header_length = match be_u32(&self.box_header, 0) {
+                Some(1) => 16,
+                _ => 8,
+            };
+            if self.box_header.len() < header_length {
+                let take = (header_length - self.box_header.len()).min(chunk.len());
+                self.box_header.extend_from_slice(&chunk[..take]);
+                self.offset += take as u64;
+                chunk = &chunk[take..];
+                continue;
+            }
+
+            let start = self.offset - header_length as u64;
+            let size = match be_u32(&self.box_header, 0) {
+                Some(0) => u64::MAX - start,
+                Some(1) => be_u64(&self.box_header, 8).unwrap_or_default(),
+                size => size.unwrap_or_default() as u64,
+            };
+            if size < header_length as u64 {
+                self.malformed = true;
+                break;
+            }
+
+            self.box_end = start.saturating_add(size);
+            if &self.box_header[4..8] == b"moov" && size <= STREAM_MOOV_LIMIT {
+                self.moov = self.box_header.clone();
+                self.moov_end = self.box_end;
+            }
+            self.box_header.clear();
+        }
+    }
+
+    pub fn finish(self) -> Option<MediaInfo> {
+        if !self.is_mp4() {
+            return probe(&self.head);
+        }
+
+        probe_mp4(&self.head, &self.moov)
+    }
+
+    fn is_mp4(&self) -> bool {
+        is_mp4(&self.head)
+    }
+}
+
+// =============================================================================================================================
+
+/// Whether the head of a file is that of an MP4/QuickTime file, whose `moov` box may sit anywhere in it.
+pub fn is_mp4(head: &[u8]) -> bool {
+    head.get(4..8) == Some(b"ftyp")
+}
+
+/// Probes an MP4/QuickTime file from its head, which starts with the `ftyp` box, and its `moov` box.
+pub fn probe_mp4(head: &[u8], moov: &[u8]) -> Option<MediaInfo> {
+    let ftyp_size = be_u32(head, 0)? as usize;
+    let mut data = head.get(..ftyp_size)?.to_vec();
+    data.extend_from_slice(moov);
+    probe(&data)
+}
+
+/// The name and size of the top-level MP4 box whose header (at most 16 bytes) starts `header`, `remaining` being
+/// the bytes left in the file from there.
+pub fn box_header(header: &[u8], remaining: u64) -> Option<([u8; 4], u64)> {
+    let name: [u8; 4] = header.get(4..8)?.try_into().ok()?;
+    let (header_length, size) = match be_u32(header, 0)? {
+        0 => (8, remaining),
+        1 => (16, be_u64(header, 8)?),
+        size => (8, size as u64),
+    };
+
+    (size >= header_length).then_some((name, size))
+}
+
+// =============================================================================================================================
+
+/// Lowercases a `Content-Type` header value, drops its parameters and maps common aliases to the
+/// type `probe` reports.
+pub fn normalize_content_type(content_type: &str) -> String {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    match essence.as_str() {
+        "image/jpg" | "image/pjpeg" => "image/jpeg".to_string(),
+        "video/x-m4v" => "video/mp4".to_string(),
+        _ => essence,
+    }
+}
+
+// =============================================================================================================================
+
+fn image_info(
+    content_type: &str,
+    dimensions: Option<(u32, u32)>,
+    orientation: Option<i32>,
+) -> MediaInfo {
+    MediaInfo {
+        content_type: content_type.to_string(),
+        media_type: MediaType::Image,
+        width: dimensions.map(|(width, _)| width as i32),
+        height: dimensions.map(|(_, height)| height as i32),
+        duration: None,
+        orientation,
+    }
+}
+
+// =============================================================================================================================
+
+fn jpeg_metadata(data: &[u8]) -> (Option<(u32, u32)>, Option<i32>) {
+    let mut dimensions = None;
+    let mut orientation = None;
+    let mut i = 2;
+
+    while i + 4 <= data.len() && dimensions.is_none() {
+        if data[i] != 0xFF {
+            break;
+        }
+
+        let marker = data[i + 1];
+        match marker {
+            0xFF => {
+                i += 1;
+                continue;
+            }
+            0x01 | 0xD0..=0xD8 => {
+                i += 2;
+                continue;
+            }
+            0xD9 | 0xDA => break,
+            _ => {}
+        }
+
+        let length = match be_u16(data, i + 2) {
+            Some(length) if length >= 2 => length as usize,
+            _ => break,
+        };
+        let segment = match data.get(i + 4..i + 2 + length) {
+            Some(segment) => segment,
+            None => break,
+        };
+
+        match marker {
+            0xE1 if segment.starts_with(b"Exif\0\0") => {
+                orientation = exif_orientation(&segment[6..]);
+            }
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                dimensions = be_u16(segment, 3)
+                    .zip(be_u16(segment, 1))
+                    .map(|(width, height)| (width as u32, height as u32));
+            }
+            _ => {}
+        }
+
+        i += 2 + length;
+    }
+
+    (dimensions, orientation)
+}
+
+// =============================================================================================================================
+
+fn exif_orientation(tiff: &[u8]) -> Option<i32> {
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let read_u16 = |offset: usize| {
+        if little_endian {
+            le_u16(tiff, offset)
+        } else {
+            be_u16(tiff, offset)
+        }
+    };
+    let read_u32 = |offset: usize| {
+        if little_endian {
+            le_u32(tiff, offset)
+        } else {
+            be_u32(tiff, offset)
+        }
+    };
+
+    if read_u16(2)? != 42 {
+        return None;
+    }
+
+    let ifd = read_u32(4)? as usize;
+    let entries = read_u16(ifd)? as usize;
+
+    (0..entries)
+        .map(|index| ifd + 2 + index * 12)
+        .find(|&entry| read_u16(entry) == Some(EXIF_ORIENTATION_TAG))
+        .and_then(|entry| read_u16(entry + 8))
+        .filter(|orientation| (1..=8).contains(orientation))
+        .map(|orientation| orientation as i32)
+}
+
+// =============================================================================================================================
+
+fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    Some((be_u32(data, 16)?, be_u32(data, 20)?))
+}
+
+// =============================================================================================================================
+
+fn webp_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    match data.get(12..16)? {
+        b"VP8 " => {
+            if data.get(23..26)? != [0x9D, 0x01, 0x2A] {
+                return None;
+            }
+            let width = le_u16(data, 26)? & 0x3FFF;
+            let height = le_u16(data, 28)? & 0x3FFF;
+            Some((width as u32, height as u32))
+        }
+        b"VP8L" => {
+            let bits = le_u32(data, 21)?;
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        b"VP8X" => Some((le_u24(data, 24)? + 1, le_u24(data, 27)? + 1)),
+        _ => None,
+    }
+}
+
+// =============================================================================================================================
+
+fn mp4_info(data: &[u8]) -> Option<MediaInfo> {
+    let ftyp = find_box(data, b"ftyp")?;
+    let content_type = match ftyp.get(0..4)? {
+        b"qt  " => "video/quicktime",
+        b"M4A " | b"M4B " | b"heic" | b"heix" | b"mif1" | b"avif" => return None,
+        _ => "video/mp4",
+    };
+
+    let mut info = MediaInfo {
+        content_type: content_type.to_string(),
+        media_type: MediaType::Video,
+        width: None,
+        height: None,
+        duration: None,
+        orientation: None,
+    };
+
+    let moov = match find_box(data, b"moov") {
+        Some(moov) => moov,
+        None => return Some(info),
+    };
+
+    if let Some(mvhd) = find_box(moov, b"mvhd") {
+        let (timescale, duration) = match mvhd.first()? {
+            1 => (be_u32(mvhd, 20)?, be_u64(mvhd, 24)?),
+            _ => (be_u32(mvhd, 12)?, be_u32(mvhd, 16)? as u64),
+        };
+        if timescale > 0 {
+            info.duration = Some(duration as f64 / timescale as f64);
+        }
+    }
+
+    for (name, trak) in boxes(moov) {
+        if name != b"trak" {
+            continue;
+        }
+        let tkhd = match find_box(trak, b"tkhd") {
+            Some(tkhd) if tkhd.len() >= 84 => tkhd,
+            _ => continue,
+        };
+
+        let width = (be_u32(tkhd, tkhd.len() - 8)? >> 16) as i32;
+        let height = (be_u32(tkhd, tkhd.len() - 4)? >> 16) as i32;
+        if width == 0 || height == 0 {
+            continue;
+        }
+
+        let matrix = tkhd.len() - 44;
+        let a = be_u32(tkhd, matrix)? as i32;
+        let b = be_u32(tkhd, matrix + 4)? as i32;
+        info.orientation = match (a.signum(), b.signum()) {
+            (0, 1) => Some(6),
+            (-1, 0) => Some(3),
+            (0, -1) => Some(8),
+            _ => Some(1),
+        };
+        info.width = Some(width);
+        info.height = Some(height);
+        break;
+    }
+
+    Some(info)
+}
+
+// =============================================================================================================================
+
+fn boxes(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut offset = 0;
+
+    std::iter::from_fn(move || {
+        let size = be_u32(data, offset)? as usize;
+        let name = data.get(offset + 4..offset + 8)?;
+
+        let (header, size) = match size {
+            0 => (8, data.len() - offset),
+            1 => (16, usize::try_from(be_u64(data, offset + 8)?).ok()?),
+            size => (8, size),
+        };
+        if size < header {
+            return None;
+        }
+
+        let payload = data.get(offset + header..offset.checked_add(size)?)?;
+        offset += size;
+        Some((name, payload))
+    })
+}
+
+// =============================================================================================================================
+
+fn find_box<'a>(data: &'a [u8], name: &[u8; 4]) -> Option<&'a [u8]> {
+    boxes(data)
+        .find(|(box_name, _)| *box_name == name)
+        .map(|(_, payload)| payload)
+}
+
+// =============================================================================================================================
+
+fn webm_info(data: &[u8]) -> Option<MediaInfo> {
+    let header = ebml_child(data, EBML_HEADER)?;
+    let doc_type = ebml_child(header, EBML_DOC_TYPE)?;
+    if doc_type != b"webm" {
+        return None;
+    }
+
+    let mut info = MediaInfo {
+        content_type: "video/webm".to_string(),
+        media_type: MediaType::Video,
+        width: None,
+        height: None,
+        duration: None,
+        orientation: None,
+    };
+
+    let segment = match ebml_child(data, EBML_SEGMENT) {
+        Some(segment) => segment,
+        None => return Some(info),
+    };
+
+    for (id, element) in ebml_elements(segment) {
+        match id {
+            EBML_INFO => {
+                let scale = ebml_child(element, EBML_TIMECODE_SCALE)
+                    .map(ebml_uint)
+                    .unwrap_or(1_000_000);
+                info.duration = ebml_child(element, EBML_DURATION)
+                    .and_then(ebml_float)
+                    .map(|duration| duration * scale as f64 / 1_000_000_000.0);
+            }
+            EBML_TRACKS => {
+                let video = ebml_elements(element)
+                    .filter(|(id, _)| *id == EBML_TRACK_ENTRY)
+                    .find_map(|(_, entry)| ebml_child(entry, EBML_VIDEO));
+                if let Some(video) = video {
+                    info.width = ebml_child(video, EBML_PIXEL_WIDTH).map(|w| ebml_uint(w) as i32);
+                    info.height = ebml_child(video, EBML_PIXEL_HEIGHT).map(|h| ebml_uint(h) as i32);
+                }
+            }
+            _ => {}
+        }
+
+        if info.duration.is_some() && info.width.is_some() {
+            break;
+        }
+    }
+
+    Some(info)
+}
+
+// =============================================================================================================================
+
+fn ebml_elements(data: &[u8]) -> impl Iterator<Item = (u32, &[u8])> {
+    let mut offset = 0;
+
+    std::iter::from_fn(move || {
+        let (id, id_length) = read_vint(data, offset, false)?;
+        let (size, size_length) = read_vint(data, offset + id_length, true)?;
+        let start = offset + id_length + size_length;
+
+        let unknown_size = size == (1u64 << (7 * size_length)) - 1;
+        let end = if unknown_size {
+            data.len()
+        } else {
+            start
+                .checked_add(usize::try_from(size).ok()?)?
+                .min(data.len())
+        };
+
+        let payload = data.get(start..end)?;
+        offset = end;
+        Some((id as u32, payload))
+    })
+}
+
+// =============================================================================================================================
+
+fn ebml_child(data: &[u8], id: u32) -> Option<&[u8]> {
+    ebml_elements(data)
+        .find(|(element_id, _)| *element_id == id)
+        .map(|(_, payload)| payload)
+}
+
+// =============================================================================================================================
+
+fn read_vint(data: &[u8], offset: usize, strip_marker: bool) -> Option<(u64, usize)> {
+    let first = *data.get(offset)?;
+    if first == 0 {
+        return None;
+    }
+
+    let length = first.leading_zeros() as usize + 1;
+    let bytes = data.get(offset..offset + length)?;
+
+    let mut value = if strip_marker {
+        (first & 0xFFu8.checked_shr(length as u32).unwrap_or(0)) as u64
+    } else {
+        first as u64
+    };
+    for byte in &bytes[1..] {
+        value = (value << 8) | *byte as u64;
+    }
+
+    Some((value, length))
+}
+
+// =============================================================================================================================
+
+fn ebml_uint(data: &[u8]) -> u64 {
+    data.iter()
+        .take(8)
+        .fold(0, |value, byte| (value << 8) | *byte as u64)
+}
+
+// =============================================================================================================================
+
+fn ebml_float(data: &[u8]) -> Option<f64> {
+    match data.len() {
+        4 => Some(f32::from_be_bytes(data.try_into().ok()?) as f64),
+        8 => Some(f64::from_be_bytes(data.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+// =============================================================================================================================
+
+fn be_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn be_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+fn le_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn le_u24(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 3)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+fn le_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+// =============================================================================================================================
@@ -1,3 +1,11 @@
 pub mod api_response;
+pub mod cors;
+pub mod image_processing;
 pub mod jwt;
+pub mod media_probe;
+pub mod oidc;
+pub mod rate_limit;
+pub mod session;
+pub mod sigv4;
+pub mod totp;
 pub mod utils_fn;
@@ -0,0 +1,267 @@
+use crate::{config::OidcProviderConfig, models::oidc_model::OidcClaims};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, Validation, decode, decode_header,
+    jwk::{Jwk, JwkSet},
+};
+use reqwest::{Client, Url};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{Arc, RwLock},
+    time,
+};
+
+// =============================================================================================================================
+
+const DISCOVERY_PATH: &str = "/.well-known/openid-configuration";
+const HTTP_TIMEOUT_SECS: u64 = 10;
+// A token signed with an unknown key refreshes the provider's keys, at most this often.
+const JWKS_REFRESH_SECS: i64 = 60;
+
+// =============================================================================================================================
+
+/// The parts of the provider's discovery document the authorization code flow needs.
+#[derive(Deserialize, Clone)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+struct CachedProvider {
+    metadata: ProviderMetadata,
+    jwks: JwkSet,
+    fetched_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    aud: Value,
+    azp: Option<String>,
+    email: Option<String>,
+    /// A string ("true") for some providers.
+    email_verified: Option<Value>,
+    name: Option<String>,
+    preferred_username: Option<String>,
+}
+
+// =============================================================================================================================
+
+/// Talks to the OpenID Connect providers and caches their discovery document and signing keys, per issuer. Shared
+/// between workers as `web::Data<OidcClient>`.
+pub struct OidcClient {
+    http: Client,
+    cache: RwLock<HashMap<String, Arc<CachedProvider>>>,
+}
+
+impl Default for OidcClient {
+    fn default() -> Self {
+        Self {
+            http: Client::builder()
+                .timeout(time::Duration::from_secs(HTTP_TIMEOUT_SECS))
+                .build()
+                .expect("The HTTP client configuration is valid"),
+            cache: RwLock::default(),
+        }
+    }
+}
+
+impl OidcClient {
+    pub async fn metadata(
+        &self,
+        provider: &OidcProviderConfig,
+    ) -> Result<ProviderMetadata, Box<dyn Error>> {
+        Ok(self.provider(provider, false).await?.metadata.clone())
+    }
+
+    /// The URL of the provider's sign-in page, with the PKCE challenge (S256) and the nonce the ID token must carry.
+    pub fn authorization_url(
+        metadata: &ProviderMetadata,
+        provider: &OidcProviderConfig,
+        state: &str,
+        nonce: &str,
+        code_challenge: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut url = Url::parse(&metadata.authorization_endpoint)?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &provider.client_id)
+            .append_pair("redirect_uri", &provider.redirect_uri)
+            .append_pair("scope", &provider.scopes.join(" "))
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", "S256");
+        Ok(url.into())
+    }
+
+    /// Redeems the authorization code at the token endpoint and returns the ID token.
+    pub async fn exchange_code(
+        &self,
+        provider: &OidcProviderConfig,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        let metadata = self.metadata(provider).await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", provider.redirect_uri.as_str()),
+            ("client_id", provider.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(secret) = &provider.client_secret {
+            form.push(("client_secret", secret));
+        }
+
+        let response: TokenResponse = self
+            .http
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        match (response.id_token, response.error) {
+            (Some(id_token), None) => Ok(id_token),
+            (_, Some(error)) => Err(format!(
+                "The provider rejected the code: {} {}",
+                error,
+                response.error_description.unwrap_or_default()
+            )
+            .trim_end()
+            .into()),
+            (None, None) => Err("The provider returned no ID token".into()),
+        }
+    }
+
+    /// Checks the ID token's signature against the provider's keys, its issuer, audience, expiry and nonce.
+    pub async fn verify_id_token(
+        &self,
+        provider: &OidcProviderConfig,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<OidcClaims, Box<dyn Error>> {
+        let header = decode_header(id_token)?;
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err("ID tokens must be signed with the provider's private key".into());
+        }
+
+        let mut cached = self.provider(provider, false).await?;
+        let key = match find_key(&cached.jwks, header.kid.as_deref()) {
+            Some(key) => key,
+            None => {
+                // The provider may have rotated its keys since they were cached.
+                cached = self.provider(provider, true).await?;
+                find_key(&cached.jwks, header.kid.as_deref())
+                    .ok_or("The ID token is signed with an unknown key")?
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&provider.issuer]);
+        validation.set_audience(&[&provider.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        let claims =
+            decode::<IdTokenClaims>(id_token, &DecodingKey::from_jwk(key)?, &validation)?.claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err("The ID token was not issued for this sign-in".into());
+        }
+        let audiences = claims.aud.as_array().map_or(1, Vec::len);
+        if audiences > 1 && claims.azp.as_deref() != Some(provider.client_id.as_str()) {
+            return Err("The ID token was issued to another client".into());
+        }
+
+        Ok(OidcClaims {
+            subject: claims.sub,
+            email: claims.email.map(|email| email.trim().to_lowercase()),
+            email_verified: matches!(claims.email_verified, Some(Value::Bool(true)))
+                || matches!(&claims.email_verified, Some(Value::String(value)) if value == "true"),
+            name: claims.preferred_username.or(claims.name),
+        })
+    }
+
+    /// The cached discovery document and keys, fetched on first use. `refresh_keys` fetches the keys again unless
+    /// they were fetched recently.
+    async fn provider(
+        &self,
+        provider: &OidcProviderConfig,
+        refresh_keys: bool,
+    ) -> Result<Arc<CachedProvider>, Box<dyn Error>> {
+        let cached = self.cache.read().unwrap().get(&provider.issuer).cloned();
+        let metadata = match cached {
+            Some(cached)
+                if !refresh_keys
+                    || Utc::now() - cached.fetched_at < Duration::seconds(JWKS_REFRESH_SECS) =>
+            {
+                return Ok(cached);
+            }
+            Some(cached) => cached.metadata.clone(),
+            None => {
+                let url = format!(
+                    "{}{}",
+                    provider.issuer.trim_end_matches('/'),
+                    DISCOVERY_PATH
+                );
+                let metadata: ProviderMetadata = self.get_json(&url).await?;
+                if metadata.issuer != provider.issuer {
+                    return Err(format!(
+                        "The provider reports the issuer {:?}, expected {:?}",
+                        metadata.issuer, provider.issuer
+                    )
+                    .into());
+                }
+                metadata
+            }
+        };
+
+        let jwks = self.get_json(&metadata.jwks_uri).await?;
+        let fresh = Arc::new(CachedProvider {
+            metadata,
+            jwks,
+            fetched_at: Utc::now(),
+        });
+        self.cache
+            .write()
+            .unwrap()
+            .insert(provider.issuer.clone(), fresh.clone());
+        Ok(fresh)
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, Box<dyn Error>> {
+        let response = self.http.get(url).send().await?.error_for_status()?;
+        Ok(response.json().await?)
+    }
+}
+
+// =============================================================================================================================
+
+/// Tokens without a `kid` are accepted from providers that publish a single key.
+fn find_key<'a>(jwks: &'a JwkSet, kid: Option<&str>) -> Option<&'a Jwk> {
+    match kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+}
+
+// =============================================================================================================================
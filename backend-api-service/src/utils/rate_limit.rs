@@ -0,0 +1,202 @@
+use actix_web::{
+    Error, HttpRequest, HttpResponse,
+    body::EitherBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::header,
+    web,
+};
+use futures_util::future::LocalBoxFuture;
+use std::{
+    collections::HashMap,
+    future::{Ready, ready},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::{Config, RateBudget, RateLimitConfig},
+    utils::{api_response::ApiResponse, jwt::get_external_jwt},
+};
+
+// =============================================================================================================================
+
+// Expired windows are only swept once the table grows past this size.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+// =============================================================================================================================
+
+/// Groups of routes sharing a budget from `RateLimitConfig`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Budget {
+    Login,
+    Register,
+    FriendRequest,
+    Upload,
+    Email,
+}
+
+impl Budget {
+    fn limits(self, config: &RateLimitConfig) -> RateBudget {
+        match self {
+            Budget::Login => config.login,
+            Budget::Register => config.register,
+            Budget::FriendRequest => config.friend_requests,
+            Budget::Upload => config.uploads,
+            Budget::Email => config.emails,
+        }
+    }
+}
+
+// =============================================================================================================================
+
+struct Window {
+    started_at: Instant,
+    length: Duration,
+    count: u32,
+}
+
+/// Fixed-window request counters, shared by every worker through `web::Data<RateLimiter>`. Counters live in
+/// process, so each instance of the service enforces its budgets on its own.
+#[derive(Default)]
+pub struct RateLimiter {
+    windows: Mutex<HashMap<(Budget, String), Window>>,
+}
+
+impl RateLimiter {
+    /// Counts one request against every key and returns, when one of them is over budget, the seconds until its
+    /// window resets.
+    pub fn hit(&self, budget: Budget, limits: RateBudget, keys: &[String]) -> Option<u64> {
+        let now = Instant::now();
+        let length = Duration::from_secs(limits.window_secs);
+        let mut windows = self.windows.lock().unwrap();
+
+        if windows.len() > PRUNE_THRESHOLD {
+            windows.retain(|_, window| now.duration_since(window.started_at) < window.length);
+        }
+
+        let mut retry_after = None;
+        for key in keys {
+            let window = windows
+                .entry((budget, key.clone()))
+                .or_insert_with(|| Window {
+                    started_at: now,
+                    length,
+                    count: 0,
+                });
+            if now.duration_since(window.started_at) >= window.length {
+                *window = Window {
+                    started_at: now,
+                    length,
+                    count: 0,
+                };
+            }
+
+            window.count = window.count.saturating_add(1);
+            if window.count > limits.requests {
+                let remaining = window.length - now.duration_since(window.started_at);
+                let secs = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+                retry_after = retry_after.max(Some(secs));
+            }
+        }
+
+        retry_after
+    }
+}
+
+// =============================================================================================================================
+
+/// The address of the client, from `X-Forwarded-For` when the proxy in front of the service is trusted.
+pub fn client_ip(req: &HttpRequest, config: &RateLimitConfig) -> Option<String> {
+    if config.trust_forwarded_for {
+        req.connection_info()
+            .realip_remote_addr()
+            .map(str::to_string)
+    } else {
+        req.peer_addr().map(|addr| addr.ip().to_string())
+    }
+}
+
+// =============================================================================================================================
+
+/// Route middleware enforcing a budget per client IP and, when the request carries a valid token, per user. Does
+/// nothing when the application has no `RateLimiter` or rate limiting is disabled.
+pub struct RateLimit(Budget);
+
+impl RateLimit {
+    pub fn new(budget: Budget) -> Self {
+        Self(budget)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service,
+            budget: self.0,
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: S,
+    budget: Budget,
+}
+
+impl<S> RateLimitMiddleware<S> {
+    fn retry_after(&self, req: &ServiceRequest) -> Option<u64> {
+        let config = req.app_data::<web::Data<Config>>()?;
+        let limiter = req.app_data::<web::Data<RateLimiter>>()?;
+        let rate_limits = &config.rate_limits;
+        if !rate_limits.enabled {
+            return None;
+        }
+
+        let ip = client_ip(req.request(), rate_limits);
+        let mut keys = vec![format!("ip:{}", ip.as_deref().unwrap_or("unknown"))];
+        if let Ok(claims) = get_external_jwt(req.request()) {
+            keys.push(format!("user:{}", claims.user_id));
+        }
+
+        limiter.hit(self.budget, self.budget.limits(rate_limits), &keys)
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if let Some(retry_after) = self.retry_after(&req) {
+            let res = ApiResponse::error(
+                "Too many requests",
+                format!("Try again in {} seconds", retry_after),
+            );
+            let response = HttpResponse::TooManyRequests()
+                .insert_header((header::RETRY_AFTER, retry_after))
+                .json(res);
+            return Box::pin(ready(Ok(req.into_response(response).map_into_right_body())));
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) })
+    }
+}
+
+// =============================================================================================================================
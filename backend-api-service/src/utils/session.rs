@@ -0,0 +1,137 @@
+use actix_web::{
+    Error, HttpRequest, HttpResponse,
+    body::EitherBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::header,
+    web,
+};
+use bson::oid::ObjectId;
+use futures_util::future::LocalBoxFuture;
+use std::{
+    future::{Ready, ready},
+    rc::Rc,
+};
+
+use crate::{
+    config::Config,
+    models::{
+        audit_model::Actor,
+        session_model::{ClientInfo, DeviceInfo},
+    },
+    repositories::Repositories,
+    services::session_service,
+    utils::{api_response::ApiResponse, jwt::get_external_jwt, rate_limit::client_ip},
+};
+
+// =============================================================================================================================
+
+const MAX_DEVICE_NAME_CHARS: usize = 100;
+const MAX_PLATFORM_CHARS: usize = 30;
+
+// =============================================================================================================================
+
+/// Describes the client signing in, from what it says about itself and the request.
+pub fn client_info(req: &HttpRequest, device: DeviceInfo) -> ClientInfo {
+    let clean = |value: Option<String>, max: usize| {
+        value
+            .map(|value| value.trim().chars().take(max).collect::<String>())
+            .filter(|value| !value.is_empty())
+    };
+
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    ClientInfo {
+        device_name: clean(device.device_name, MAX_DEVICE_NAME_CHARS)
+            .or_else(|| clean(user_agent, MAX_DEVICE_NAME_CHARS))
+            .unwrap_or_else(|| "Unknown device".to_string()),
+        platform: clean(device.platform, MAX_PLATFORM_CHARS),
+        ip: request_ip(req),
+    }
+}
+
+/// The signed-in user making the request, as recorded in the audit log.
+pub fn actor(req: &HttpRequest, user_id: &str) -> Actor {
+    Actor {
+        user_id: ObjectId::parse_str(user_id).ok(),
+        ip: request_ip(req),
+    }
+}
+
+fn request_ip(req: &HttpRequest) -> Option<String> {
+    req.app_data::<web::Data<Config>>()
+        .and_then(|config| client_ip(req, &config.rate_limits))
+}
+
+// =============================================================================================================================
+
+/// Rejects requests whose token is valid but belongs to a revoked session. Requests without a valid token go
+/// through untouched, handlers reject them where authentication is required.
+pub struct SessionGuard;
+
+impl<S, B> Transform<S, ServiceRequest> for SessionGuard
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = SessionGuardMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SessionGuardMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct SessionGuardMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for SessionGuardMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let claims = get_external_jwt(req.request()).ok();
+            let repos = req.app_data::<web::Data<Repositories>>().cloned();
+
+            if let (Some(claims), Some(repos)) = (claims, repos) {
+                let active = session_service::is_active(&repos, &claims)
+                    .await
+                    .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+                if !active {
+                    let res = ApiResponse::error(
+                        "The user must be authenticated.",
+                        "The session has been revoked",
+                    );
+                    let response = HttpResponse::Unauthorized().json(res);
+                    return Ok(req.into_response(response).map_into_right_body());
+                }
+            }
+
+            service
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,252 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+
+// =============================================================================================================================
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+const SERVICE: &str = "s3";
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+// =============================================================================================================================
+
+pub struct SigV4Credentials<'a> {
+    pub access_key: &'a str,
+    pub secret_key: &'a str,
+    pub region: &'a str,
+}
+
+// =============================================================================================================================
+
+/// Builds an AWS Signature V4 query-string presigned URL, as accepted by S3 and MinIO.
+/// The `host` header is always signed; the request must also carry each of `headers` with exactly the given value.
+pub fn presign_url(
+    credentials: &SigV4Credentials,
+    method: &str,
+    url: &Url,
+    headers: &[(&str, String)],
+    expires_in: u64,
+    now: DateTime<Utc>,
+) -> Url {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let scope = credential_scope(credentials, now);
+
+    let mut signed: Vec<(String, String)> = headers
+        .iter()
+        .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+        .collect();
+    signed.push(("host".to_string(), host_header(url)));
+    signed.sort();
+    let canonical_headers: String = signed
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .collect();
+    let signed_headers = signed
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let mut query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    query.push(("X-Amz-Algorithm".to_string(), ALGORITHM.to_string()));
+    query.push((
+        "X-Amz-Credential".to_string(),
+        format!("{}/{}", credentials.access_key, scope),
+    ));
+    query.push(("X-Amz-Date".to_string(), amz_date.clone()));
+    query.push(("X-Amz-Expires".to_string(), expires_in.to_string()));
+    query.push(("X-Amz-SignedHeaders".to_string(), signed_headers.clone()));
+
+    let canonical_query = canonical_query_string(&query);
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        canonical_uri(url),
+        canonical_query,
+        canonical_headers,
+        signed_headers,
+        UNSIGNED_PAYLOAD
+    );
+
+    let signature = sign(credentials, now, &amz_date, &scope, &canonical_request);
+
+    let mut presigned = url.clone();
+    presigned.set_query(Some(&format!(
+        "{}&X-Amz-Signature={}",
+        canonical_query, signature
+    )));
+    presigned
+}
+
+// =============================================================================================================================
+
+/// Computes the headers (`Authorization`, `x-amz-date`, `x-amz-content-sha256`) that authenticate a
+/// server-side request with AWS Signature V4.
+pub fn sign_request(
+    credentials: &SigV4Credentials,
+    method: &str,
+    url: &Url,
+    payload_hash: &str,
+    now: DateTime<Utc>,
+) -> Vec<(&'static str, String)> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let scope = credential_scope(credentials, now);
+
+    let query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical_request = format!(
+        "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        method,
+        canonical_uri(url),
+        canonical_query_string(&query),
+        host_header(url),
+        payload_hash,
+        amz_date,
+        signed_headers,
+        payload_hash
+    );
+
+    let signature = sign(credentials, now, &amz_date, &scope, &canonical_request);
+    let authorization = format!(
+        "{} Credential={}/{}, SignedHeaders={}, Signature={}",
+        ALGORITHM, credentials.access_key, scope, signed_headers, signature
+    );
+
+    vec![
+        ("Authorization", authorization),
+        ("x-amz-date", amz_date),
+        ("x-amz-content-sha256", payload_hash.to_string()),
+    ]
+}
+
+// =============================================================================================================================
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+// =============================================================================================================================
+
+fn sign(
+    credentials: &SigV4Credentials,
+    now: DateTime<Utc>,
+    amz_date: &str,
+    scope: &str,
+    canonical_request: &str,
+) -> String {
+    let string_to_sign = format!(
+        "{}\n{}\n{}\n{}",
+        ALGORITHM,
+        amz_date,
+        scope,
+        sha256_hex(canonical_request.as_bytes())
+    );
+
+    let date_key = hmac_sha256(
+        format!("AWS4{}", credentials.secret_key).as_bytes(),
+        now.format("%Y%m%d").to_string().as_bytes(),
+    );
+    let region_key = hmac_sha256(&date_key, credentials.region.as_bytes());
+    let service_key = hmac_sha256(&region_key, SERVICE.as_bytes());
+    let signing_key = hmac_sha256(&service_key, b"aws4_request");
+
+    hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()))
+}
+
+// =============================================================================================================================
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+// =============================================================================================================================
+
+fn credential_scope(credentials: &SigV4Credentials, now: DateTime<Utc>) -> String {
+    format!(
+        "{}/{}/{}/aws4_request",
+        now.format("%Y%m%d"),
+        credentials.region,
+        SERVICE
+    )
+}
+
+// =============================================================================================================================
+
+fn host_header(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    }
+}
+
+// =============================================================================================================================
+
+fn canonical_uri(url: &Url) -> String {
+    url.path()
+        .split('/')
+        .map(|segment| {
+            let decoded = percent_decode(segment);
+            uri_encode(&decoded)
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// =============================================================================================================================
+
+fn canonical_query_string(query: &[(String, String)]) -> String {
+    let mut encoded: Vec<(String, String)> = query
+        .iter()
+        .map(|(key, value)| (uri_encode(key), uri_encode(value)))
+        .collect();
+    encoded.sort();
+
+    encoded
+        .into_iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+// =============================================================================================================================
+
+fn uri_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+// =============================================================================================================================
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && let Ok(hex) = std::str::from_utf8(&bytes[i + 1..i + 3])
+            && let Ok(byte) = u8::from_str_radix(hex, 16)
+        {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// =============================================================================================================================
@@ -0,0 +1,100 @@
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::error::Error;
+
+// =============================================================================================================================
+
+// RFC 6238 defaults, the only parameters authenticator apps reliably support.
+const DIGITS: usize = 6;
+const STEP_SECS: i64 = 30;
+const SECRET_BYTES: usize = 20;
+// Codes from the previous and next step are accepted to absorb clock drift.
+const ALLOWED_DRIFT_STEPS: i64 = 1;
+
+// =============================================================================================================================
+
+/// A new random secret, base32 encoded as authenticator apps expect it.
+pub fn generate_secret() -> Result<String, Box<dyn Error>> {
+    let mut secret = [0u8; SECRET_BYTES];
+    getrandom::fill(&mut secret).map_err(|e| format!("Failed to generate a secret: {}", e))?;
+    Ok(BASE32_NOPAD.encode(&secret))
+}
+
+// =============================================================================================================================
+
+/// The `otpauth://` URI shown as a QR code to enrol the secret in an authenticator app.
+pub fn provisioning_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(issuer),
+        percent_encode(account),
+        secret,
+        percent_encode(issuer),
+        DIGITS,
+        STEP_SECS
+    )
+}
+
+// =============================================================================================================================
+
+/// The time step a Unix timestamp falls in.
+pub fn step_at(unix_time: i64) -> i64 {
+    unix_time.div_euclid(STEP_SECS)
+}
+
+/// The code of a time step (HOTP of the step counter, RFC 4226).
+pub fn code_at(secret: &str, step: i64) -> Result<String, Box<dyn Error>> {
+    let key = BASE32_NOPAD
+        .decode(secret.as_bytes())
+        .map_err(|_| "Invalid TOTP secret")?;
+    let mut mac = Hmac::<Sha1>::new_from_slice(&key)?;
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    Ok(format!(
+        "{:0width$}",
+        binary % 10u32.pow(DIGITS as u32),
+        width = DIGITS
+    ))
+}
+
+/// Returns the time step `code` belongs to, when it is valid at `unix_time`.
+pub fn verify(secret: &str, code: &str, unix_time: i64) -> Result<Option<i64>, Box<dyn Error>> {
+    let code = code.trim();
+    if code.len() != DIGITS || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(None);
+    }
+
+    let current = step_at(unix_time);
+    for step in current - ALLOWED_DRIFT_STEPS..=current + ALLOWED_DRIFT_STEPS {
+        if code_at(secret, step)? == code {
+            return Ok(Some(step));
+        }
+    }
+    Ok(None)
+}
+
+// =============================================================================================================================
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+// =============================================================================================================================
@@ -46,3 +46,23 @@ where
 }
 
 // =============================================================================================================================
+
+pub fn trim_lowercase_option<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = Option::<String>::deserialize(deserializer)?;
+    Ok(s.map(|s| s.trim().to_lowercase()))
+}
+
+// =============================================================================================================================
+
+pub fn trim_option<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = Option::<String>::deserialize(deserializer)?;
+    Ok(s.map(|s| s.trim().to_string()))
+}
+
+// =============================================================================================================================
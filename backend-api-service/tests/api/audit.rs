@@ -0,0 +1,206 @@
+use crate::common::{PASSWORD, TestApp, TestUser, bearer, call, object_id};
+use actix_http::Request;
+use actix_web::{
+    Error,
+    dev::{Service, ServiceResponse},
+    http::StatusCode,
+    test::TestRequest,
+};
+use backend_api_service::models::{
+    audit_model::AuditFilter, message_model::Message, user_model::UserRole,
+};
+use chrono::{Duration, Utc};
+use serde_json::{Value, json};
+
+// =============================================================================================================================
+
+async fn events(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    admin: &TestUser,
+    query: &str,
+) -> Vec<Value> {
+    let (status, body) = call(
+        app,
+        TestRequest::get()
+            .uri(&format!("/api/audit/events?{}", query))
+            .insert_header(bearer(&admin.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body["data"].as_array().unwrap().clone()
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn logins_and_failed_logins_are_audited() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let admin = ctx.create_user("admin", UserRole::Admin).await;
+    let user = ctx.create_user("alice", UserRole::User).await;
+    let ip = "203.0.113.7:41000".parse().unwrap();
+
+    for (credential, password) in [("alice", "wrong-password-123"), ("nobody", PASSWORD)] {
+        let (status, _) = call(
+            &app,
+            TestRequest::post()
+                .uri("/api/auth/login")
+                .peer_addr(ip)
+                .set_json(json!({ "credential": credential, "password": password })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/auth/login")
+            .peer_addr(ip)
+            .set_json(json!({ "credential": "alice", "password": PASSWORD })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/audit/events")
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let failed = events(&app, &admin, "action=LoginFailed").await;
+    assert_eq!(failed.len(), 2);
+    assert_eq!(failed[0]["details"], "Unknown account");
+    assert_eq!(failed[0]["target_id"], Value::Null);
+    assert_eq!(failed[1]["details"], "Wrong password");
+    assert_eq!(object_id(&failed[1]["target_id"]), user.id.to_hex());
+    assert_eq!(failed[1]["actor_id"], Value::Null);
+    assert_eq!(failed[1]["ip"], "203.0.113.7");
+
+    let logins = events(
+        &app,
+        &admin,
+        &format!("action=Login&actor_id={}", user.id.to_hex()),
+    )
+    .await;
+    // The test sign-in of the fixture, then the login above.
+    assert_eq!(logins.len(), 2);
+    assert_eq!(logins[0]["ip"], "203.0.113.7");
+    assert_eq!(object_id(&logins[0]["target_id"]), user.id.to_hex());
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn admin_changes_and_deletions_are_audited() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let admin = ctx.create_user("admin", UserRole::Admin).await;
+    let user = ctx.create_user("alice", UserRole::User).await;
+    let friend = ctx.create_user("bob", UserRole::User).await;
+    let user_uri = format!("/api/users/{}", user.id.to_hex());
+
+    let (status, _) = call(
+        &app,
+        TestRequest::patch()
+            .uri(&user_uri)
+            .insert_header(bearer(&admin.token))
+            .set_json(json!({ "bio": "Edited by the admins" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let message = ctx
+        .repos
+        .messages
+        .insert(Message {
+            id: None,
+            content: "Hello".to_string(),
+            sender_id: user.id,
+            recipient_id: friend.id,
+            is_group: false,
+            media: None,
+            read: false,
+        })
+        .await
+        .unwrap();
+    let message_id = message.id.unwrap().to_hex();
+    let (status, _) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&format!("/api/messages/{}", message_id))
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    // Last, since the role change revokes the sessions of the user.
+    let (status, _) = call(
+        &app,
+        TestRequest::put()
+            .uri(&format!("{}/role", user_uri))
+            .insert_header(bearer(&admin.token))
+            .set_json(json!({ "role": "Admin" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let by_admin = events(&app, &admin, &format!("actor_id={}", admin.id.to_hex())).await;
+    let actions: Vec<&str> = by_admin
+        .iter()
+        .map(|event| event["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(actions, ["RoleChanged", "UserUpdated", "Login"]);
+    assert_eq!(by_admin[0]["details"], "Role set to Admin");
+    assert_eq!(by_admin[1]["details"], "bio");
+    assert_eq!(object_id(&by_admin[1]["target_id"]), user.id.to_hex());
+
+    let deleted = events(&app, &admin, &format!("target_id={}", message_id)).await;
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0]["action"], "MessageDeleted");
+    assert_eq!(object_id(&deleted[0]["actor_id"]), user.id.to_hex());
+
+    // Pages follow each other through the id of the last event.
+    let first = events(&app, &admin, "limit=2").await;
+    assert_eq!(first.len(), 2);
+    let next = events(
+        &app,
+        &admin,
+        &format!("limit=2&before={}", object_id(&first[1]["_id"])),
+    )
+    .await;
+    assert_eq!(next[0]["action"], "UserUpdated");
+
+    let from =
+        (Utc::now() + Duration::minutes(1)).to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    assert!(
+        events(&app, &admin, &format!("from={}", from))
+            .await
+            .is_empty()
+    );
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn audit_events_expire_after_the_retention_period() {
+    let mut ctx = TestApp::new().await;
+    ctx.create_user("alice", UserRole::User).await;
+    ctx.config.audit.retention_days = 0;
+    ctx.create_user("bob", UserRole::User).await;
+
+    let events = ctx
+        .repos
+        .audit_events
+        .find(&AuditFilter::default(), 10)
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 2);
+    assert!(events[0].expires_at.is_none());
+    let expires_at = events[1].expires_at.unwrap();
+    assert!(expires_at > Utc::now() + Duration::days(364));
+    assert!(expires_at <= Utc::now() + Duration::days(365));
+}
+
+// =============================================================================================================================
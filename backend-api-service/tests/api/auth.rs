@@ -0,0 +1,283 @@
+use crate::common::{PASSWORD, TestApp, bearer, call, link_token};
+use actix_web::{http::StatusCode, test::TestRequest};
+use backend_api_service::{
+    config::AuthConfig,
+    models::user_model::UserRole,
+    utils::jwt::{JwtKeys, encode_external_jwt},
+};
+use chrono::{Duration, Utc};
+use serde_json::json;
+
+// =============================================================================================================================
+
+fn registration(username: &str) -> serde_json::Value {
+    json!({
+        "username": username,
+        "email": format!("{}@snapshoot.test", username),
+        "password": PASSWORD,
+        "bio": "Hello from the tests",
+        "location": { "type": "Point", "coordinates": [4.8156, 45.7107] }
+    })
+}
+
+fn token_signed_with(config: &AuthConfig, exp: i64) -> String {
+    encode_external_jwt(
+        config,
+        bson::oid::ObjectId::new().to_hex(),
+        UserRole::Admin,
+        bson::oid::ObjectId::new().to_hex(),
+        exp,
+    )
+    .unwrap()
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn health_check_is_public() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+
+    let (status, body) = call(&app, TestRequest::get().uri("/api/health")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["success"], true);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn register_requires_email_verification_before_login() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let email = "alice@snapshoot.test";
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/auth/register")
+            .set_json(registration("alice")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["verified"], false);
+    assert!(body["data"].get("token").is_none());
+
+    let login = || {
+        TestRequest::post()
+            .uri("/api/auth/login")
+            .set_json(json!({ "credential": "alice", "password": PASSWORD }))
+    };
+    let (status, _) = call(&app, login()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Asking for a new link invalidates the first one.
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/auth/resend-verification")
+            .set_json(json!({ "email": email })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let emails = ctx.emails_to(email);
+    assert_eq!(emails.len(), 2);
+    let first = link_token(&emails[0], "/verify-email");
+    let second = link_token(&emails[1], "/verify-email");
+
+    let verify = |token: &str| {
+        TestRequest::post()
+            .uri("/api/auth/verify-email")
+            .set_json(json!({ "token": token }))
+    };
+    let (status, _) = call(&app, verify(&first)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = call(&app, verify(&second)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/users/me")
+            .insert_header(bearer(&token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["username"], "alice");
+    assert_eq!(body["data"]["role"], "User");
+    assert_eq!(body["data"]["verified"], true);
+
+    let (status, _) = call(&app, verify(&second)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = call(&app, login()).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn register_rejects_invalid_payloads() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+
+    let mut payload = registration("bob");
+    payload["password"] = json!("short");
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/auth/register")
+            .set_json(payload),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body["success"], false);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/auth/register")
+            .set_json(json!({ "username": "bob" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "Deserialization error");
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn login_accepts_username_or_email() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("carol", UserRole::User).await;
+
+    for credential in [&user.username, &user.email] {
+        let (status, body) = call(
+            &app,
+            TestRequest::post()
+                .uri("/api/auth/login")
+                .set_json(json!({ "credential": credential, "password": PASSWORD })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert!(body["data"]["token"].is_string());
+    }
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn login_rejects_bad_credentials() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("dave", UserRole::User).await;
+
+    for (credential, password) in [
+        (user.username.as_str(), "wrong-password-123"),
+        ("nobody", PASSWORD),
+    ] {
+        let (status, body) = call(
+            &app,
+            TestRequest::post()
+                .uri("/api/auth/login")
+                .set_json(json!({ "credential": credential, "password": password })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(body.get("data").is_none());
+    }
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn protected_routes_reject_missing_or_invalid_tokens() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let expired = token_signed_with(
+        &ctx.config.auth,
+        (Utc::now() - Duration::hours(1)).timestamp(),
+    );
+    let mut other_keys = ctx.config.auth.clone();
+    other_keys.keys = JwtKeys::generate();
+    let forged = token_signed_with(&other_keys, (Utc::now() + Duration::hours(1)).timestamp());
+
+    let (status, _) = call(&app, TestRequest::get().uri("/api/auth/me")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/auth/me")
+            .insert_header(("Authorization", format!("Token {}", expired))),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    for token in [expired, forged, "garbage".to_string()] {
+        let (status, body) = call(
+            &app,
+            TestRequest::get()
+                .uri("/api/auth/me")
+                .insert_header(bearer(&token)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["success"], false);
+    }
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn password_reset_tokens_work_once() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("frank", UserRole::User).await;
+    let new_password = "a-brand-new-password";
+
+    for email in ["nobody@snapshoot.test", user.email.as_str()] {
+        let (status, _) = call(
+            &app,
+            TestRequest::post()
+                .uri("/api/auth/forgot-password")
+                .set_json(json!({ "email": email })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    assert!(ctx.emails_to("nobody@snapshoot.test").is_empty());
+    let emails = ctx.emails_to(&user.email);
+    assert_eq!(emails.len(), 1);
+    let token = link_token(&emails[0], "/reset-password");
+
+    let reset = |password: &str| {
+        TestRequest::post()
+            .uri("/api/auth/reset-password")
+            .set_json(json!({ "token": token, "password": password }))
+    };
+    let (status, _) = call(&app, reset("short")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = call(&app, reset(new_password)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, _) = call(&app, reset("yet-another-password")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    for (password, expected) in [
+        (PASSWORD, StatusCode::INTERNAL_SERVER_ERROR),
+        (new_password, StatusCode::OK),
+    ] {
+        let (status, _) = call(
+            &app,
+            TestRequest::post()
+                .uri("/api/auth/login")
+                .set_json(json!({ "credential": user.username, "password": password })),
+        )
+        .await;
+        assert_eq!(status, expected);
+    }
+}
+
+// =============================================================================================================================
@@ -0,0 +1,388 @@
+use actix_http::Request;
+use actix_web::{
+    App, Error,
+    dev::{Service, ServiceResponse},
+    http::{StatusCode, header},
+    middleware::Compat,
+    test::{self, TestRequest},
+    web,
+};
+use backend_api_service::{
+    config::{Config, MailerKind, StorageKind},
+    controllers::routes,
+    db::Db,
+    extractor::deserialize_error_extractor,
+    mailer::{self, Mailer},
+    models::{
+        friend_model::{Friend, FriendStatus},
+        message_model::{Media, MediaType},
+        session_model::ClientInfo,
+        story_model::{Location, Story},
+        user_model::{self, AccountStatus, User, UserRole},
+    },
+    repositories::Repositories,
+    services::session_service,
+    storage::{self, StorageBackend},
+    utils::{cors::build_cors, jwt::JwtKeys, oidc::OidcClient, rate_limit::RateLimiter},
+};
+use bson::oid::ObjectId;
+use chrono::{Duration, Utc};
+use image::{ImageFormat, Rgb, RgbImage};
+use mongodb::{Client, Database};
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::{env, fs, io::Cursor, path::PathBuf, sync::Arc, thread};
+use uuid::Uuid;
+
+// =============================================================================================================================
+
+pub const PASSWORD: &str = "correct-horse-battery";
+
+const TEST_DATABASE_URL: &str = "TEST_DATABASE_URL";
+// Never contacted: without TEST_DATABASE_URL everything runs on the in-memory repositories.
+const UNREACHABLE_DATABASE_URL: &str = "mongodb://127.0.0.1:9/?serverSelectionTimeoutMS=100";
+const STORAGE_PUBLIC_URL: &str = "http://localhost:8080/api/files";
+const STORAGE_SECRET: &str = "integration-test-secret";
+const MAIL_DIR: &str = "mail";
+const MULTIPART_BOUNDARY: &str = "snapshoot-test-boundary";
+
+// Hashing with the default cost for every seeded user would make the suite needlessly slow.
+static PASSWORD_HASH: Lazy<String> = Lazy::new(|| bcrypt::hash(PASSWORD, 4).unwrap());
+
+// =============================================================================================================================
+
+pub struct TestUser {
+    pub id: ObjectId,
+    pub username: String,
+    pub email: String,
+    pub token: String,
+}
+
+// =============================================================================================================================
+
+/// Everything the application needs, isolated per test: in-memory repositories by default, or a
+/// fresh database on the server named by `TEST_DATABASE_URL`. Files
+/// go to a temporary directory through the local storage backend, emails to the file mailer next to them.
+pub struct TestApp {
+    pub config: Config,
+    pub db: Database,
+    pub repos: Repositories,
+    pub storage: Arc<dyn StorageBackend>,
+    pub mailer: Arc<dyn Mailer>,
+    database_url: Option<String>,
+    storage_root: PathBuf,
+}
+
+impl TestApp {
+    pub async fn new() -> Self {
+        let database_url = env::var(TEST_DATABASE_URL)
+            .ok()
+            .filter(|url| !url.trim().is_empty());
+        let storage_root = env::temp_dir().join(format!("snapshoot-test-{}", Uuid::new_v4()));
+
+        let mut config = Config::default();
+        config.database.url = database_url
+            .clone()
+            .unwrap_or_else(|| UNREACHABLE_DATABASE_URL.to_string());
+        config.database.name = format!("snapshoot_test_{}", Uuid::new_v4().simple());
+        config.auth.keys = JwtKeys::generate();
+        config.storage.backend = StorageKind::Local;
+        config.storage.local.path = storage_root.to_string_lossy().into_owned();
+        config.storage.local.public_url = STORAGE_PUBLIC_URL.to_string();
+        config.storage.local.secret = STORAGE_SECRET.to_string();
+        config.mail.backend = MailerKind::File;
+        config.mail.file.path = storage_root.join(MAIL_DIR).to_string_lossy().into_owned();
+        assert_eq!(config.validate(), Vec::<String>::new());
+
+        let db = Db::init(&config.database)
+            .await
+            .expect("Invalid test database URL");
+
+        let repos = match database_url {
+            Some(_) => {
+                Db::migrate(&db)
+                    .await
+                    .expect("Failed to migrate the test database");
+                Repositories::mongo(&db)
+            }
+            None => Repositories::in_memory(),
+        };
+
+        let storage = storage::from_config(&config);
+        let mailer = mailer::from_config(&config.mail).unwrap();
+
+        Self {
+            config,
+            db,
+            repos,
+            storage,
+            mailer,
+            database_url,
+            storage_root,
+        }
+    }
+
+    pub async fn service(
+        &self,
+    ) -> impl Service<Request, Response = ServiceResponse, Error = Error> + use<> {
+        test::init_service(
+            App::new()
+                // Boxes the body the CORS middleware wraps responses in, so tests see plain `ServiceResponse`s.
+                .wrap(Compat::new(build_cors(&self.config)))
+                .app_data(web::Data::new(self.config.clone()))
+                .app_data(web::Data::new(RateLimiter::default()))
+                .app_data(web::Data::new(OidcClient::default()))
+                .app_data(web::Data::new(self.repos.clone()))
+                .app_data(web::Data::from(self.storage.clone()))
+                .app_data(web::Data::from(self.mailer.clone()))
+                .configure(routes)
+                .app_data(deserialize_error_extractor()),
+        )
+        .await
+    }
+
+    pub async fn create_user(&self, username: &str, role: UserRole) -> TestUser {
+        self.create_user_at(username, role, [4.8156, 45.7107]).await
+    }
+
+    pub async fn create_user_at(
+        &self,
+        username: &str,
+        role: UserRole,
+        coordinates: [f64; 2],
+    ) -> TestUser {
+        let email = format!("{}@snapshoot.test", username);
+        let user = self
+            .repos
+            .users
+            .insert(User {
+                id: None,
+                username: username.to_string(),
+                email: email.clone(),
+                password: PASSWORD_HASH.clone(),
+                role,
+                bio: "Integration test user".to_string(),
+                avatar: None,
+                location: user_model::Location {
+                    location_type: "Point".to_string(),
+                    coordinates,
+                },
+                verified: true,
+                status: AccountStatus::Active,
+                suspended_until: None,
+            })
+            .await
+            .unwrap();
+        let id = user.id.unwrap();
+
+        TestUser {
+            id,
+            username: username.to_string(),
+            email,
+            token: self.sign_in(&user).await,
+        }
+    }
+
+    /// Starts a new session for the user and returns its access token.
+    pub async fn sign_in(&self, user: &User) -> String {
+        let client = ClientInfo {
+            device_name: "Integration tests".to_string(),
+            platform: None,
+            ip: None,
+        };
+        session_service::start(&self.repos, &self.config, user, client)
+            .await
+            .unwrap()
+            .token
+    }
+
+    /// Emails written by the file mailer to `address`, oldest first.
+    pub fn emails_to(&self, address: &str) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(self.storage_root.join(MAIL_DIR)) else {
+            return Vec::new();
+        };
+        let mut paths: Vec<PathBuf> = entries.map(|entry| entry.unwrap().path()).collect();
+        paths.sort();
+
+        paths
+            .into_iter()
+            .map(|path| fs::read_to_string(path).unwrap())
+            .filter(|email| email.lines().any(|line| line == format!("To: {}", address)))
+            .collect()
+    }
+
+    /// Sends and accepts a friend request through the repositories.
+    pub async fn befriend(&self, user: &TestUser, friend: &TestUser) {
+        let request = self
+            .repos
+            .friends
+            .insert(Friend {
+                id: None,
+                status: FriendStatus::Pending,
+                user_id: user.id,
+                friend_id: friend.id,
+            })
+            .await
+            .unwrap();
+        self.repos
+            .friends
+            .accept(request.id.unwrap(), friend.id)
+            .await
+            .unwrap();
+    }
+
+    /// Stores a story whose media is a plain storage object, so deleting it needs no media record.
+    pub async fn create_story(
+        &self,
+        user: &TestUser,
+        coordinates: [f64; 2],
+        expires_in: Duration,
+    ) -> Story {
+        let key = format!("stories/{}.jpg", Uuid::new_v4());
+        self.repos
+            .stories
+            .insert(Story {
+                id: None,
+                user_id: user.id,
+                location: Location {
+                    location_type: "Point".to_string(),
+                    coordinates,
+                },
+                media: Media {
+                    id: None,
+                    media_type: MediaType::Image,
+                    url: self.storage.object_url(&key),
+                    feed_url: None,
+                    thumbnail_url: None,
+                    duration: None,
+                },
+                expires_at: Utc::now() + expires_in,
+            })
+            .await
+            .unwrap()
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.storage_root);
+
+        if let Some(url) = self.database_url.clone() {
+            let name = self.db.name().to_string();
+            // The test runtime is shutting down, so the database is dropped from a client of its own.
+            let _ = thread::spawn(move || {
+                actix_web::rt::System::new().block_on(async move {
+                    if let Ok(client) = Client::with_uri_str(&url).await {
+                        let _ = client.database(&name).drop().await;
+                    }
+                })
+            })
+            .join();
+        }
+    }
+}
+
+// =============================================================================================================================
+
+pub fn bearer(token: &str) -> (header::HeaderName, String) {
+    (header::AUTHORIZATION, format!("Bearer {}", token))
+}
+
+// =============================================================================================================================
+
+/// Sends the request and returns the status with the JSON body (`Value::Null` when there is none).
+pub async fn call(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    request: TestRequest,
+) -> (StatusCode, Value) {
+    let response = test::call_service(app, request.to_request()).await;
+    let status = response.status();
+    let body = test::read_body(response).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+// =============================================================================================================================
+
+/// The token of the `{path}?token=...` link in an email.
+pub fn link_token(email: &str, path: &str) -> String {
+    let marker = format!("{}?token=", path);
+    let start = email
+        .find(&marker)
+        .unwrap_or_else(|| panic!("no {} link in: {}", path, email))
+        + marker.len();
+    email[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect()
+}
+
+// =============================================================================================================================
+
+/// Hex ids come back as plain strings for `_id` fields and as `{ "$oid": ... }` elsewhere.
+pub fn object_id(value: &Value) -> String {
+    value
+        .as_str()
+        .or_else(|| value["$oid"].as_str())
+        .unwrap_or_else(|| panic!("not an ObjectId: {}", value))
+        .to_string()
+}
+
+// =============================================================================================================================
+
+/// The path of a URL served by the local storage backend, which tests call the application with.
+pub fn local_path(url: &str) -> String {
+    let path = url
+        .strip_prefix(STORAGE_PUBLIC_URL)
+        .unwrap_or_else(|| panic!("not a local storage URL: {}", url));
+    format!("/api/files{}", path)
+}
+
+// =============================================================================================================================
+
+/// A small opaque PNG, which the application accepts as an image upload.
+pub fn png(width: u32, height: u32) -> Vec<u8> {
+    let image = RgbImage::from_fn(width, height, |x, y| Rgb([x as u8, y as u8, 128]));
+    let mut data = Cursor::new(Vec::new());
+    image.write_to(&mut data, ImageFormat::Png).unwrap();
+    data.into_inner()
+}
+
+// =============================================================================================================================
+
+/// A `multipart/form-data` body with the text fields and a `file` part, ready for `set_payload`.
+pub fn multipart(
+    fields: &[(&str, &str)],
+    file: (&str, &[u8]),
+) -> ((header::HeaderName, String), Vec<u8>) {
+    let (content_type, data) = file;
+    let mut body = Vec::new();
+
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                MULTIPART_BOUNDARY, name, value
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"upload\"\r\nContent-Type: {}\r\n\r\n",
+            MULTIPART_BOUNDARY, content_type
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", MULTIPART_BOUNDARY).as_bytes());
+
+    (
+        (
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY),
+        ),
+        body,
+    )
+}
+
+// =============================================================================================================================
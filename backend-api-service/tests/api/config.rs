@@ -0,0 +1,139 @@
+use crate::common::{PASSWORD, TestApp, call};
+use actix_web::{http::StatusCode, test::TestRequest};
+use backend_api_service::{
+    config::{Config, StorageKind},
+    models::user_model::UserRole,
+    utils::jwt::decode_external_jwt,
+};
+use chrono::Utc;
+use serde_json::json;
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn toml_settings_override_the_defaults() {
+    let config = Config::from_toml(
+        r#"
+        [server]
+        bind_address = "127.0.0.1:9090"
+
+        [database]
+        url = "mongodb://localhost:27017"
+        name = "snapshoot_staging"
+
+        [auth]
+        keys_dir = "/etc/snapshoot/jwt-keys"
+        token_lifetime_minutes = 15
+
+        [cors]
+        allowed_origins = ["https://app.snapshoot.example", "capacitor://localhost"]
+
+        [uploads]
+        max_video_duration_secs = 15.0
+
+        [storage]
+        backend = "local"
+
+        [storage.local]
+        path = "/var/lib/snapshoot"
+        secret = "a-long-enough-secret"
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.server.bind_address, "127.0.0.1:9090");
+    assert_eq!(config.database.name, "snapshoot_staging");
+    assert_eq!(config.auth.token_lifetime_minutes, 15);
+    assert_eq!(config.cors.allowed_origins.len(), 2);
+    assert_eq!(config.uploads.max_video_duration_secs, 15.0);
+    assert_eq!(config.uploads.max_video_size_mb, 10);
+    assert_eq!(config.stories.lifetime_hours, 24);
+    assert!(config.storage.backend == StorageKind::Local);
+    assert_eq!(config.storage.local.path, "/var/lib/snapshoot");
+    assert_eq!(
+        config.storage.local.public_url,
+        "http://localhost:8080/api/files"
+    );
+    assert_eq!(config.validate(), Vec::<String>::new());
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn unknown_or_mistyped_settings_are_rejected() {
+    let error = Config::from_toml("[server]\nport = 8080\n").err().unwrap();
+    assert!(error.contains("unknown field `port`"), "{}", error);
+
+    let error = Config::from_toml("[stories]\nlifetime_hours = \"a day\"\n")
+        .err()
+        .unwrap();
+    assert!(error.contains("lifetime_hours"), "{}", error);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn validation_reports_every_invalid_setting() {
+    let mut config = Config::default();
+    config.server.bind_address = "localhost".to_string();
+    config.cors.allowed_origins = vec!["https://app.snapshoot.example/".to_string()];
+    config.uploads.max_image_size_mb = 0;
+    config.stories.lifetime_hours = -1;
+    config.auth.audience = String::new();
+
+    let errors = config.validate();
+    for setting in [
+        "BIND_ADDRESS",
+        "DATABASE_URL",
+        "JWT_AUDIENCE",
+        "CORS_ALLOWED_ORIGINS",
+        "UPLOAD_MAX_IMAGE_SIZE_MB",
+        "STORY_LIFETIME_HOURS",
+        "MINIO_SERVER_URL",
+        "MINIO_ROOT_USER",
+        "MINIO_ROOT_PASSWORD",
+    ] {
+        assert!(
+            errors.iter().any(|error| error.starts_with(setting)),
+            "{} not reported in {:?}",
+            setting,
+            errors
+        );
+    }
+    assert_eq!(errors.len(), 9, "{:?}", errors);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn tokens_use_the_configured_lifetime() {
+    let mut ctx = TestApp::new().await;
+    ctx.config.auth.token_lifetime_minutes = 5;
+    let app = ctx.service().await;
+    let user = ctx.create_user("shortlived", UserRole::User).await;
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/auth/login")
+            .set_json(json!({ "credential": user.username, "password": PASSWORD })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let token = body["data"]["token"].as_str().unwrap();
+    let claims = decode_external_jwt(&ctx.config.auth, token).unwrap();
+    let remaining = claims.exp - Utc::now().timestamp();
+    assert!((290..=300).contains(&remaining), "{}", remaining);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn example_file_is_a_valid_configuration() {
+    let config =
+        Config::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/config.example.toml")).unwrap();
+    assert_eq!(config.validate(), Vec::<String>::new());
+}
+
+// =============================================================================================================================
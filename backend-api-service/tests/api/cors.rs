@@ -0,0 +1,156 @@
+use crate::common::TestApp;
+use actix_web::{
+    http::{StatusCode, header},
+    test::{self, TestRequest},
+};
+use backend_api_service::config::{Config, Environment};
+
+// =============================================================================================================================
+
+/// Returns the status and the `Access-Control-Allow-Origin` header of a preflight request from `origin`.
+async fn preflight(ctx: &TestApp, origin: &str) -> (StatusCode, Option<String>) {
+    let app = ctx.service().await;
+    let request = TestRequest::default()
+        .method(actix_web::http::Method::OPTIONS)
+        .uri("/api/auth/me")
+        .insert_header((header::ORIGIN, origin))
+        .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+        .to_request();
+
+    match test::try_call_service(&app, request).await {
+        Ok(response) => (
+            response.status(),
+            response
+                .headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .map(|value| value.to_str().unwrap().to_string()),
+        ),
+        Err(e) => (e.as_response_error().status_code(), None),
+    }
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn mobile_app_origins_are_allowed_with_credentials() {
+    let mut ctx = TestApp::new().await;
+    ctx.config.server.environment = Environment::Production;
+    let app = ctx.service().await;
+
+    for origin in [
+        "capacitor://localhost",
+        "ionic://localhost",
+        "http://localhost:8100",
+    ] {
+        let response = test::call_service(
+            &app,
+            TestRequest::get()
+                .uri("/api/health")
+                .insert_header((header::ORIGIN, origin))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(
+            headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            origin
+        );
+        assert_eq!(
+            headers
+                .get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS)
+                .unwrap(),
+            "true"
+        );
+    }
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn production_only_accepts_listed_origins() {
+    let mut ctx = TestApp::new().await;
+    ctx.config.server.environment = Environment::Production;
+
+    for origin in [
+        "http://evil.example",
+        "http://localhost:5173",
+        "capacitor://evil.example",
+    ] {
+        let (status, allowed) = preflight(&ctx, origin).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", origin);
+        assert_eq!(allowed, None, "{}", origin);
+    }
+
+    let (status, allowed) = preflight(&ctx, "capacitor://localhost").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(allowed.as_deref(), Some("capacitor://localhost"));
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn development_also_accepts_loopback_origins_on_any_port() {
+    let ctx = TestApp::new().await;
+
+    let (status, allowed) = preflight(&ctx, "http://localhost:5173").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(allowed.as_deref(), Some("http://localhost:5173"));
+
+    let (status, _) = preflight(&ctx, "http://evil.example").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn wildcard_and_malformed_cors_settings_are_rejected() {
+    let mut config = Config::from_toml(
+        r#"
+        [server]
+        environment = "production"
+
+        [database]
+        url = "mongodb://localhost:27017"
+
+        [auth]
+        keys_dir = "/etc/snapshoot/jwt-keys"
+
+        [cors]
+        allowed_origins = ["*", "http://*", "capacitor://localhost"]
+        allowed_methods = ["GET", "NOT A METHOD"]
+        allowed_headers = ["Authorization", "Bad Header"]
+
+        [storage]
+        backend = "local"
+
+        [storage.local]
+        secret = "a-long-enough-secret"
+        "#,
+    )
+    .unwrap();
+
+    let errors = config.validate();
+    assert_eq!(
+        errors
+            .iter()
+            .filter(|error| error.contains("is a wildcard"))
+            .count(),
+        2,
+        "{:?}",
+        errors
+    );
+    assert!(errors.iter().any(|e| e.starts_with("CORS_ALLOWED_METHODS")));
+    assert!(errors.iter().any(|e| e.starts_with("CORS_ALLOWED_HEADERS")));
+    assert_eq!(errors.len(), 4, "{:?}", errors);
+
+    config.cors.allowed_origins.clear();
+    config.cors.allowed_methods = vec!["GET".to_string()];
+    config.cors.allowed_headers.clear();
+    assert_eq!(
+        config.validate(),
+        vec!["CORS_ALLOWED_ORIGINS must list at least one origin in production".to_string()]
+    );
+}
+
+// =============================================================================================================================
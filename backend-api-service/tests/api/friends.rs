@@ -0,0 +1,407 @@
+use crate::common::{TestApp, TestUser, bearer, call, object_id};
+use actix_http::Request;
+use actix_web::{
+    Error,
+    dev::{Service, ServiceResponse},
+    http::StatusCode,
+    test::TestRequest,
+};
+use backend_api_service::models::{
+    friend_model::{Friend, FriendStatus},
+    group_model::Group,
+    user_model::{AccountStatus, UserRole},
+};
+use serde_json::{Value, json};
+
+// =============================================================================================================================
+
+async fn search(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    user: &TestUser,
+    query: &str,
+) -> Vec<String> {
+    let (status, body) = call(
+        app,
+        TestRequest::get()
+            .uri(&format!("/api/friends/search?{}", query))
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|user| user["username"].as_str().unwrap().to_string())
+        .collect()
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn friend_requests_must_be_accepted_by_the_recipient() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let bob = ctx.create_user("bob", UserRole::User).await;
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!("/api/friends/request/{}", bob.id.to_hex()))
+            .insert_header(bearer(&alice.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["status"], "Pending");
+    let request_id = object_id(&body["data"]["_id"]);
+
+    let (_, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/friends/requests")
+            .insert_header(bearer(&alice.token)),
+    )
+    .await;
+    assert_eq!(body["data"], json!([]));
+
+    let (_, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/friends/requests")
+            .insert_header(bearer(&bob.token)),
+    )
+    .await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+
+    // The sender cannot accept on the recipient's behalf.
+    let (status, _) = call(
+        &app,
+        TestRequest::patch()
+            .uri(&format!("/api/friends/accept/{}", request_id))
+            .insert_header(bearer(&alice.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let (_, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/friends")
+            .insert_header(bearer(&alice.token)),
+    )
+    .await;
+    assert_eq!(body["data"], json!([]));
+
+    let (status, body) = call(
+        &app,
+        TestRequest::patch()
+            .uri(&format!("/api/friends/accept/{}", request_id))
+            .insert_header(bearer(&bob.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["status"], "Accepted");
+
+    for (user, friend) in [(&alice, "bob"), (&bob, "alice")] {
+        let (status, body) = call(
+            &app,
+            TestRequest::get()
+                .uri("/api/friends")
+                .insert_header(bearer(&user.token)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["username"], friend);
+    }
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn duplicate_and_self_friend_requests_are_rejected() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let bob = ctx.create_user("bob", UserRole::User).await;
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!("/api/friends/request/{}", alice.id.to_hex()))
+            .insert_header(bearer(&alice.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!("/api/friends/request/{}", bob.id.to_hex()))
+            .insert_header(bearer(&alice.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Neither side can open a second request once one exists.
+    for (sender, recipient) in [(&alice, &bob), (&bob, &alice)] {
+        let (status, body) = call(
+            &app,
+            TestRequest::post()
+                .uri(&format!("/api/friends/request/{}", recipient.id.to_hex()))
+                .insert_header(bearer(&sender.token)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            body["error"],
+            "Friend request already exists or users are already friends"
+        );
+    }
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn users_can_be_found_by_email_or_id() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let bob = ctx.create_user("bob", UserRole::User).await;
+
+    for payload in [
+        json!({ "email": bob.email }),
+        json!({ "user_id": bob.id.to_hex() }),
+    ] {
+        let (status, body) = call(
+            &app,
+            TestRequest::post()
+                .uri("/api/friends/find")
+                .insert_header(bearer(&alice.token))
+                .set_json(payload),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["username"], "bob");
+    }
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/friends/find")
+            .insert_header(bearer(&alice.token))
+            .set_json(json!({ "email": "nobody@snapshoot.test" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/friends/find")
+            .insert_header(bearer(&alice.token))
+            .set_json(json!({})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/friends/find")
+            .set_json(json!({ "email": bob.email })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn either_friend_can_end_the_friendship() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let bob = ctx.create_user("bob", UserRole::User).await;
+    ctx.befriend(&alice, &bob).await;
+
+    let (status, _) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&format!("/api/friends/{}", alice.id.to_hex()))
+            .insert_header(bearer(&bob.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/friends")
+            .insert_header(bearer(&alice.token)),
+    )
+    .await;
+    assert_eq!(body["data"], json!([]));
+
+    let (status, _) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&format!("/api/friends/{}", bob.id.to_hex()))
+            .insert_header(bearer(&alice.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn users_are_found_by_username_prefix_then_by_similarity() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let zoe = ctx.create_user("zoe", UserRole::User).await;
+    ctx.create_user("zoey", UserRole::User).await;
+    ctx.create_user("alice", UserRole::User).await;
+    ctx.create_user("alicia", UserRole::User).await;
+    ctx.create_user("malice", UserRole::User).await;
+    ctx.create_user("bob", UserRole::User).await;
+    let banned = ctx.create_user("alibaba", UserRole::User).await;
+    ctx.repos
+        .users
+        .update_status(banned.id, AccountStatus::Banned, None)
+        .await
+        .unwrap();
+
+    // The search is lowercased like usernames; "malice" shares too little with "ali" to be a fuzzy match.
+    assert_eq!(search(&app, &zoe, "q=ALI").await, ["alice", "alicia"]);
+    let fuzzy = search(&app, &zoe, "q=alise").await;
+    assert_eq!(fuzzy.first().map(String::as_str), Some("alice"));
+    assert!(!fuzzy.contains(&"malice".to_string()));
+    assert!(!fuzzy.contains(&"alibaba".to_string()));
+
+    assert_eq!(search(&app, &zoe, "q=zo").await, ["zoey"]);
+    assert_eq!(search(&app, &zoe, "q=ali&limit=1").await, ["alice"]);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/friends/search?q=a")
+            .insert_header(bearer(&zoe.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn suggestions_rank_mutual_friends_shared_groups_and_proximity() {
+    const PARIS: [f64; 2] = [2.3522, 48.8566];
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let bob = ctx.create_user_at("bob", UserRole::User, PARIS).await;
+    let carol = ctx.create_user_at("carol", UserRole::User, PARIS).await;
+    let dave = ctx.create_user_at("dave", UserRole::User, PARIS).await;
+    // About five kilometres from Alice.
+    ctx.create_user_at("erin", UserRole::User, [4.8500, 45.7500])
+        .await;
+    let frank = ctx.create_user("frank", UserRole::User).await;
+    let mallory = ctx.create_user("mallory", UserRole::User).await;
+    ctx.create_user_at("zed", UserRole::User, PARIS).await;
+
+    ctx.befriend(&alice, &bob).await;
+    ctx.befriend(&bob, &carol).await;
+    ctx.befriend(&bob, &mallory).await;
+    ctx.repos
+        .users
+        .update_status(mallory.id, AccountStatus::Banned, None)
+        .await
+        .unwrap();
+    ctx.repos
+        .friends
+        .insert(Friend {
+            id: None,
+            status: FriendStatus::Pending,
+            user_id: alice.id,
+            friend_id: frank.id,
+        })
+        .await
+        .unwrap();
+    ctx.repos
+        .groups
+        .insert(Group {
+            id: None,
+            name: "Climbing".to_string(),
+            creator_id: alice.id,
+            members: vec![alice.id, bob.id, dave.id],
+        })
+        .await
+        .unwrap();
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/friends/suggestions")
+            .insert_header(bearer(&alice.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let suggestions = body["data"].as_array().unwrap();
+    let names: Vec<&str> = suggestions
+        .iter()
+        .map(|suggestion| suggestion["user"]["username"].as_str().unwrap())
+        .collect();
+    // Friends, pending requests, banned and unrelated users are left out.
+    assert_eq!(names, ["carol", "dave", "erin"]);
+    assert_eq!(suggestions[0]["mutual_friends"], 1);
+    assert_eq!(suggestions[0]["distance_km"], Value::Null);
+    assert_eq!(suggestions[1]["shared_groups"], 1);
+    let distance = suggestions[2]["distance_km"].as_u64().unwrap();
+    assert!((5..=6).contains(&distance), "{}", distance);
+
+    let (_, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/friends/suggestions?limit=1")
+            .insert_header(bearer(&alice.token)),
+    )
+    .await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn search_and_suggestions_only_expose_public_profiles() {
+    const PARIS: [f64; 2] = [2.3522, 48.8566];
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user_at("alice", UserRole::User, PARIS).await;
+    let bob = ctx.create_user_at("bob", UserRole::User, PARIS).await;
+    let carol = ctx.create_user_at("carol", UserRole::User, PARIS).await;
+    ctx.befriend(&alice, &bob).await;
+    ctx.befriend(&bob, &carol).await;
+
+    for uri in ["/api/friends/search?q=car", "/api/friends/suggestions"] {
+        let (status, body) = call(
+            &app,
+            TestRequest::get()
+                .uri(uri)
+                .insert_header(bearer(&alice.token)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let entries = body["data"].as_array().unwrap();
+        assert_eq!(entries.len(), 1, "{}", body);
+        let user = entries[0].get("user").unwrap_or(&entries[0]);
+        assert_eq!(user["username"], "carol");
+        assert_eq!(user["_id"], carol.id.to_hex());
+        for key in ["password", "email", "location", "status"] {
+            assert!(user.get(key).is_none(), "{} exposed by {}", key, uri);
+        }
+        assert!(entries[0].get("distance_meters").is_none());
+    }
+}
+
+// =============================================================================================================================
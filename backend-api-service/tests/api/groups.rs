@@ -0,0 +1,283 @@
+use crate::common::{TestApp, TestUser, bearer, call, object_id};
+use actix_http::Request;
+use actix_web::{Error, dev::ServiceResponse};
+use actix_web::{dev::Service, http::StatusCode, test::TestRequest};
+use backend_api_service::models::user_model::UserRole;
+use serde_json::{Value, json};
+
+// =============================================================================================================================
+
+async fn create_group(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    creator: &TestUser,
+    members: &[&TestUser],
+) -> String {
+    let members: Vec<String> = members.iter().map(|member| member.id.to_hex()).collect();
+    let (status, body) = call(
+        app,
+        TestRequest::post()
+            .uri("/api/groups")
+            .insert_header(bearer(&creator.token))
+            .set_json(json!({ "name": "Climbing crew", "members": members })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    object_id(&body["data"]["_id"])
+}
+
+fn member_ids(group: &Value) -> Vec<String> {
+    group["members"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(object_id)
+        .collect()
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn groups_are_only_visible_to_their_members() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let owner = ctx.create_user("owner", UserRole::User).await;
+    let member = ctx.create_user("member", UserRole::User).await;
+    let outsider = ctx.create_user("outsider", UserRole::User).await;
+    let group_id = create_group(&app, &owner, &[&member]).await;
+
+    for user in [&owner, &member] {
+        let (status, body) = call(
+            &app,
+            TestRequest::get()
+                .uri(&format!("/api/groups/{}", group_id))
+                .insert_header(bearer(&user.token)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            member_ids(&body["data"]),
+            [owner.id.to_hex(), member.id.to_hex()]
+        );
+
+        let (_, body) = call(
+            &app,
+            TestRequest::get()
+                .uri("/api/groups")
+                .insert_header(bearer(&user.token)),
+        )
+        .await;
+        assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    }
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri(&format!("/api/groups/{}", group_id))
+            .insert_header(bearer(&outsider.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(body.get("data").is_none());
+
+    let (_, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/groups")
+            .insert_header(bearer(&outsider.token)),
+    )
+    .await;
+    assert_eq!(body["data"], json!([]));
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn only_members_can_rename_a_group() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let owner = ctx.create_user("owner", UserRole::User).await;
+    let member = ctx.create_user("member", UserRole::User).await;
+    let outsider = ctx.create_user("outsider", UserRole::User).await;
+    let group_id = create_group(&app, &owner, &[&member]).await;
+
+    let (status, _) = call(
+        &app,
+        TestRequest::put()
+            .uri(&format!("/api/groups/{}", group_id))
+            .insert_header(bearer(&outsider.token))
+            .set_json(json!({ "name": "Hijacked" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::put()
+            .uri(&format!("/api/groups/{}", group_id))
+            .insert_header(bearer(&member.token))
+            .set_json(json!({ "name": "x" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::put()
+            .uri(&format!("/api/groups/{}", group_id))
+            .insert_header(bearer(&member.token))
+            .set_json(json!({ "name": "Bouldering crew" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["name"], "Bouldering crew");
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn only_the_creator_can_add_members() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let owner = ctx.create_user("owner", UserRole::User).await;
+    let member = ctx.create_user("member", UserRole::User).await;
+    let newcomer = ctx.create_user("newcomer", UserRole::User).await;
+    let group_id = create_group(&app, &owner, &[&member]).await;
+    let uri = format!("/api/groups/{}/members", group_id);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri(&uri)
+            .insert_header(bearer(&member.token))
+            .set_json(json!({ "members": [newcomer.id.to_hex()] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri(&uri)
+            .insert_header(bearer(&owner.token))
+            .set_json(
+                json!({ "members": [newcomer.id.to_hex(), member.id.to_hex(), "not-an-id"] }),
+            ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        member_ids(&body["data"]),
+        [owner.id.to_hex(), member.id.to_hex(), newcomer.id.to_hex()]
+    );
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn members_can_leave_but_only_the_creator_removes_others() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let owner = ctx.create_user("owner", UserRole::User).await;
+    let first = ctx.create_user("first", UserRole::User).await;
+    let second = ctx.create_user("second", UserRole::User).await;
+    let group_id = create_group(&app, &owner, &[&first, &second]).await;
+    let member_uri =
+        |user: &TestUser| format!("/api/groups/{}/members/{}", group_id, user.id.to_hex());
+
+    let (status, body) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&member_uri(&second))
+            .insert_header(bearer(&first.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+        body["error"],
+        "Only the group creator can remove other members"
+    );
+
+    let (status, body) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&member_uri(&owner))
+            .insert_header(bearer(&owner.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+        body["error"],
+        "The creator cannot be removed from the group"
+    );
+
+    let (status, _) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&member_uri(&first))
+            .insert_header(bearer(&first.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&member_uri(&second))
+            .insert_header(bearer(&owner.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(member_ids(&body["data"]), [owner.id.to_hex()]);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn only_the_creator_can_delete_a_group() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let owner = ctx.create_user("owner", UserRole::User).await;
+    let member = ctx.create_user("member", UserRole::User).await;
+    let group_id = create_group(&app, &owner, &[&member]).await;
+
+    let (status, _) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&format!("/api/groups/{}", group_id))
+            .insert_header(bearer(&member.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&format!("/api/groups/{}?dry_run=true", group_id))
+            .insert_header(bearer(&owner.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["message"], "Dry run, the group was not deleted");
+    assert_eq!(body["data"]["groups"], 1);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&format!("/api/groups/{}", group_id))
+            .insert_header(bearer(&owner.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["message"], "Group deleted successfully");
+
+    let (status, _) = call(
+        &app,
+        TestRequest::get()
+            .uri(&format!("/api/groups/{}", group_id))
+            .insert_header(bearer(&owner.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+// =============================================================================================================================
@@ -0,0 +1,79 @@
+use crate::common::{TestApp, bearer, call};
+use actix_web::{http::StatusCode, test::TestRequest};
+use backend_api_service::models::user_model::UserRole;
+use serde_json::json;
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn updated_location_drives_nearby_users() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let viewer = ctx
+        .create_user_at("viewer", UserRole::User, [2.3522, 48.8566])
+        .await;
+    ctx.create_user_at("neighbour", UserRole::User, [4.8800, 45.7719])
+        .await;
+    ctx.create_user_at("faraway", UserRole::User, [-0.5792, 44.8378])
+        .await;
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/location/update")
+            .insert_header(bearer(&viewer.token))
+            .set_json(json!({
+                "location": { "type": "Point", "coordinates": [4.8357, 45.7640] }
+            })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(
+        body["data"]["location"]["coordinates"],
+        json!([4.8357, 45.764])
+    );
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/location/nearby/users?longitude=4.8357&latitude=45.7640&radius=10000")
+            .insert_header(bearer(&viewer.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let usernames: Vec<&str> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|user| user["username"].as_str().unwrap())
+        .collect();
+    assert_eq!(usernames, ["neighbour"]);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn location_routes_require_authentication() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/location/update")
+            .set_json(json!({
+                "location": { "type": "Point", "coordinates": [4.8357, 45.7640] }
+            })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::get().uri("/api/location/nearby/users?longitude=4.8&latitude=45.7"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+// =============================================================================================================================
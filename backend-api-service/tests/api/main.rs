@@ -0,0 +1,19 @@
+mod audit;
+mod auth;
+mod common;
+mod config;
+mod cors;
+mod friends;
+mod groups;
+mod jwks;
+mod locations;
+mod media;
+mod messages;
+mod moderation;
+mod oidc;
+mod rate_limits;
+mod sessions;
+mod sigv4;
+mod stories;
+mod two_factor;
+mod users;
@@ -0,0 +1,953 @@
+use crate::common::{TestApp, TestUser, bearer, call, local_path, multipart, object_id, png};
+use actix_http::Request;
+use actix_web::{
+    Error,
+    dev::{Service, ServiceResponse},
+    http::{StatusCode, header},
+    test::{self, TestRequest},
+};
+use backend_api_service::{
+    config::Config,
+    models::{
+        media_model::{MediaInfo, PendingUpload},
+        message_model::MediaType,
+        user_model::UserRole,
+    },
+    services::{media_service, upload_service},
+    utils::image_processing,
+};
+use chrono::{Duration, Utc};
+use futures_util::future::join;
+use image::{ImageFormat, Rgb, RgbImage};
+use serde_json::{Value, json};
+use std::{fs, io::Cursor, path::Path};
+
+// =============================================================================================================================
+
+async fn upload_to_library(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    user: &TestUser,
+    data: &[u8],
+) -> (StatusCode, Value) {
+    let (content_type, body) = multipart(&[], ("image/png", data));
+    call(
+        app,
+        TestRequest::post()
+            .uri("/api/media")
+            .insert_header(bearer(&user.token))
+            .insert_header(content_type)
+            .set_payload(body),
+    )
+    .await
+}
+
+/// Uploads `data` through a presigned URL and returns its key, ready to be confirmed.
+async fn presigned_upload(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    user: &TestUser,
+    content_type: &str,
+    data: Vec<u8>,
+) -> String {
+    let (status, body) = call(
+        app,
+        TestRequest::post()
+            .uri("/api/uploads/presign")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({ "content_type": content_type, "size": data.len() })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let key = body["data"]["key"].as_str().unwrap().to_string();
+
+    let (status, body) = call(
+        app,
+        TestRequest::put()
+            .uri(&local_path(body["data"]["upload_url"].as_str().unwrap()))
+            .insert_header((header::CONTENT_TYPE, content_type))
+            .insert_header((header::CONTENT_LENGTH, data.len()))
+            .set_payload(data),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    key
+}
+
+async fn confirm_upload(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    user: &TestUser,
+    key: &str,
+) -> (StatusCode, Value) {
+    call(
+        app,
+        TestRequest::post()
+            .uri("/api/uploads/confirm")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({ "key": key })),
+    )
+    .await
+}
+
+async fn upload_and_confirm(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    user: &TestUser,
+    content_type: &str,
+    data: Vec<u8>,
+) -> (StatusCode, Value) {
+    let key = presigned_upload(app, user, content_type, data).await;
+    confirm_upload(app, user, &key).await
+}
+
+/// An MP4 file with `mdat_size` bytes of media data followed by its `moov` box, as most encoders write them.
+fn mp4(duration_secs: u32, mdat_size: usize) -> Vec<u8> {
+    let mp4_box = |name: &[u8], payload: &[u8]| {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(name);
+        data.extend_from_slice(payload);
+        data
+    };
+
+    let mut mvhd = vec![0; 100];
+    mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+    mvhd[16..20].copy_from_slice(&(duration_secs * 1000).to_be_bytes());
+
+    let mut data = mp4_box(b"ftyp", b"isom\0\0\x02\0isom");
+    data.extend(mp4_box(b"mdat", &vec![0; mdat_size]));
+    data.extend(mp4_box(b"moov", &mp4_box(b"mvhd", &mvhd)));
+    data
+}
+
+/// Files under the local storage root, whatever their key.
+/// A JPEG with an EXIF block that points at a GPS IFD holding a latitude, as phone cameras write them.
+fn jpeg_with_gps() -> Vec<u8> {
+    let image = RgbImage::from_fn(32, 24, |x, y| Rgb([x as u8, y as u8, 64]));
+    let mut jpeg = Cursor::new(Vec::new());
+    image.write_to(&mut jpeg, ImageFormat::Jpeg).unwrap();
+    let jpeg = jpeg.into_inner();
+
+    let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+    // IFD0: one GPSInfo entry pointing at the GPS IFD right after it.
+    tiff.extend_from_slice(&[0x01, 0x00, 0x25, 0x88, 0x04, 0x00, 0x01, 0x00, 0x00, 0x00]);
+    tiff.extend_from_slice(&26u32.to_le_bytes());
+    tiff.extend_from_slice(&[0; 4]);
+    // GPS IFD: GPSLatitudeRef "N".
+    tiff.extend_from_slice(&[0x01, 0x00, 0x01, 0x00, 0x02, 0x00, 0x02, 0x00, 0x00, 0x00]);
+    tiff.extend_from_slice(b"N\0\0\0");
+    tiff.extend_from_slice(&[0; 4]);
+
+    let mut segment = b"Exif\0\0".to_vec();
+    segment.extend_from_slice(&tiff);
+    let mut data = jpeg[..2].to_vec();
+    data.extend_from_slice(&[0xFF, 0xE1]);
+    data.extend_from_slice(&(segment.len() as u16 + 2).to_be_bytes());
+    data.extend_from_slice(&segment);
+    data.extend_from_slice(&jpeg[2..]);
+    data
+}
+
+/// A GIF carrying a comment and an XMP packet next to its frame.
+fn gif_with_metadata() -> Vec<u8> {
+    let image = RgbImage::from_fn(16, 16, |x, y| Rgb([x as u8 * 16, y as u8 * 16, 0]));
+    let mut gif = Cursor::new(Vec::new());
+    image.write_to(&mut gif, ImageFormat::Gif).unwrap();
+    let mut data = gif.into_inner();
+    let trailer = data.pop().unwrap();
+
+    data.extend_from_slice(&[0x21, 0xFE, 13]);
+    data.extend_from_slice(b"Lyon 45.76 N,");
+    data.push(0);
+    data.extend_from_slice(&[0x21, 0xFF, 11]);
+    data.extend_from_slice(b"XMP DataXMP");
+    data.push(23);
+    data.extend_from_slice(b"<exif:GPSLatitude>45.76");
+    data.push(0);
+    data.push(trailer);
+    data
+}
+
+fn stored_objects(ctx: &TestApp) -> usize {
+    fn count(dir: &Path) -> usize {
+        fs::read_dir(dir).map_or(0, |entries| {
+            entries
+                .flatten()
+                .map(|entry| match entry.path().is_dir() {
+                    true => count(&entry.path()),
+                    false => 1,
+                })
+                .sum()
+        })
+    }
+    count(&Path::new(&ctx.config.storage.local.path).join("objects"))
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn library_media_is_private_to_its_owner_until_deleted() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let owner = ctx.create_user("owner", UserRole::User).await;
+    let other = ctx.create_user("other", UserRole::User).await;
+
+    let (status, body) = upload_to_library(&app, &owner, &png(64, 48)).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["width"], 64);
+    assert_eq!(body["data"]["height"], 48);
+    assert!(body["data"]["thumbnail_url"].is_string());
+    let media_id = object_id(&body["data"]["_id"]);
+
+    let record = ctx
+        .repos
+        .media
+        .find_by_owner(owner.id)
+        .await
+        .unwrap()
+        .remove(0);
+    let mut stored = 0;
+    for url in [
+        Some(&record.url),
+        record.feed_url.as_ref(),
+        record.thumbnail_url.as_ref(),
+    ] {
+        let key = ctx.storage.object_key_from_url(url.unwrap()).unwrap();
+        stored += ctx.storage.get(key).await.unwrap().len() as i64;
+    }
+    assert_eq!(record.size, stored);
+    let media_uri = format!("/api/media/{}", media_id);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::get()
+            .uri(&media_uri)
+            .insert_header(bearer(&other.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri(&media_uri)
+            .insert_header(bearer(&owner.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let download = local_path(body["data"]["url"].as_str().unwrap());
+
+    let response = test::call_service(&app, TestRequest::get().uri(&download).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(header::CONTENT_TYPE).unwrap(),
+        "image/jpeg"
+    );
+
+    let (status, _) = call(&app, TestRequest::get().uri(&format!("{}x", download))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&media_uri)
+            .insert_header(bearer(&owner.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, _) = call(&app, TestRequest::get().uri(&download)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn uploads_must_match_their_declared_type() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("mallory", UserRole::User).await;
+
+    let (content_type, body) = multipart(&[], ("image/jpeg", &png(8, 8)));
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/media")
+            .insert_header(bearer(&user.token))
+            .insert_header(content_type)
+            .set_payload(body),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+
+    let (content_type, body) = multipart(&[], ("application/pdf", b"%PDF-1.7"));
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/media")
+            .insert_header(bearer(&user.token))
+            .insert_header(content_type)
+            .set_payload(body),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert!(
+        ctx.repos
+            .media
+            .find_by_owner(user.id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn stored_images_carry_no_metadata() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("alice", UserRole::User).await;
+    let data = jpeg_with_gps();
+    assert!(data.windows(4).any(|window| window == b"Exif"));
+
+    let (content_type, body) = multipart(&[], ("image/jpeg", &data));
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/media")
+            .insert_header(bearer(&user.token))
+            .insert_header(content_type)
+            .set_payload(body),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+
+    let record = ctx
+        .repos
+        .media
+        .find_by_owner(user.id)
+        .await
+        .unwrap()
+        .remove(0);
+    for url in [
+        Some(&record.url),
+        record.feed_url.as_ref(),
+        record.thumbnail_url.as_ref(),
+    ] {
+        let key = ctx.storage.object_key_from_url(url.unwrap()).unwrap();
+        let stored = ctx.storage.get(key).await.unwrap();
+        assert!(!stored.windows(4).any(|window| window == b"Exif"));
+        assert!(!stored.windows(2).any(|window| window == [0xFF, 0xE1]));
+    }
+}
+
+// =============================================================================================================================
+
+#[test]
+fn gifs_lose_their_comments_and_xmp() {
+    let data = gif_with_metadata();
+    assert!(image::load_from_memory(&data).is_ok());
+
+    let original = image_processing::process_image(&data, None)
+        .unwrap()
+        .original;
+    assert_eq!(original.content_type, "image/gif");
+    assert_eq!((original.width, original.height), (16, 16));
+    for leak in [&b"Lyon"[..], b"XMP", b"GPSLatitude"] {
+        assert!(
+            !original
+                .data
+                .windows(leak.len())
+                .any(|window| window == leak)
+        );
+    }
+    let decoded = image::load_from_memory(&original.data).unwrap();
+    assert_eq!(
+        decoded.to_rgb8(),
+        image::load_from_memory(&data).unwrap().to_rgb8()
+    );
+}
+
+// =============================================================================================================================
+
+#[test]
+fn images_too_large_to_decode_safely_are_rejected() {
+    assert!(image_processing::process_image(&png(20_000, 1), None).is_err());
+    assert!(image_processing::process_image(&png(2_000, 1), None).is_ok());
+}
+
+// =============================================================================================================================
+
+#[test]
+fn videos_need_a_valid_duration_within_the_limit() {
+    let config = Config::default();
+    let video = |duration: Option<f64>| MediaInfo {
+        content_type: "video/mp4".to_string(),
+        media_type: MediaType::Video,
+        width: Some(640),
+        height: Some(360),
+        duration,
+        orientation: None,
+    };
+
+    assert!(media_service::check_media(&config, "video/mp4", &video(Some(5.0)), 1024).is_ok());
+    for duration in [
+        None,
+        Some(f64::NAN),
+        Some(f64::INFINITY),
+        Some(-1.0),
+        Some(600.0),
+    ] {
+        assert!(
+            media_service::check_media(&config, "video/mp4", &video(duration), 1024).is_err(),
+            "{:?}",
+            duration
+        );
+    }
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn presigned_uploads_are_confirmed_once() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("alice", UserRole::User).await;
+    let other = ctx.create_user("bob", UserRole::User).await;
+    let image = png(32, 32);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/uploads/presign")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({ "content_type": "image/png", "size": image.len() })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["method"], "PUT");
+    assert_eq!(body["data"]["size"], image.len());
+    let key = body["data"]["key"].as_str().unwrap().to_string();
+    let upload = local_path(body["data"]["upload_url"].as_str().unwrap());
+    let put = |uri: &str, content_type: &str, data: Vec<u8>| {
+        TestRequest::put()
+            .uri(uri)
+            .insert_header((header::CONTENT_TYPE, content_type))
+            .insert_header((header::CONTENT_LENGTH, data.len()))
+            .set_payload(data)
+    };
+
+    // The URL only takes the declared type and size.
+    let mut padded = image.clone();
+    padded.extend_from_slice(&[0; 1024]);
+    for request in [
+        put(&format!("{}0", upload), "image/png", image.clone()),
+        put(&upload, "image/gif", image.clone()),
+        put(&upload, "image/png", padded),
+        TestRequest::put()
+            .uri(&upload)
+            .insert_header((header::CONTENT_TYPE, "image/png"))
+            .insert_header((header::CONTENT_LENGTH, image.len()))
+            .set_payload(vec![0; image.len() * 2]),
+    ] {
+        let (status, _) = call(&app, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    let (status, body) = call(&app, put(&upload, "image/png", image)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let confirm = |token: &str| {
+        TestRequest::post()
+            .uri("/api/uploads/confirm")
+            .insert_header(bearer(token))
+            .set_json(json!({ "key": key }))
+    };
+
+    let (status, _) = call(&app, confirm(&other.token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = call(&app, confirm(&user.token)).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["media"]["width"], 32);
+    let (status, _) = call(
+        &app,
+        TestRequest::get().uri(&local_path(body["data"]["download_url"].as_str().unwrap())),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = call(&app, confirm(&user.token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        ctx.repos.media.find_by_owner(user.id).await.unwrap().len(),
+        1
+    );
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn presigned_uploads_follow_the_configured_size_limits() {
+    let mut ctx = TestApp::new().await;
+    ctx.config.uploads.max_video_size_mb = 12;
+    let app = ctx.service().await;
+    let user = ctx.create_user("alice", UserRole::User).await;
+    let video = mp4(3, 11 * 1024 * 1024);
+
+    let (status, body) = upload_and_confirm(&app, &user, "video/mp4", video.clone()).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+
+    // A URL signed under the raised limit is refused once the limit is lowered again.
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/uploads/presign")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({ "content_type": "video/mp4", "size": video.len() })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let upload = local_path(body["data"]["upload_url"].as_str().unwrap());
+
+    ctx.config.uploads.max_video_size_mb = 10;
+    let app = ctx.service().await;
+    let (status, body) = call(
+        &app,
+        TestRequest::put()
+            .uri(&upload)
+            .insert_header((header::CONTENT_TYPE, "video/mp4"))
+            .insert_header((header::CONTENT_LENGTH, video.len()))
+            .set_payload(video),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "Videos must be under 10MB");
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn resumable_uploads_complete_once_every_part_is_received() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("carol", UserRole::User).await;
+    let other = ctx.create_user("dave", UserRole::User).await;
+    let image = png(40, 30);
+
+    let create_session = || {
+        TestRequest::post()
+            .uri("/api/uploads/sessions")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({ "content_type": "image/png", "size": image.len() }))
+    };
+
+    let (status, body) = call(&app, create_session()).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["total_parts"], 1);
+    let session_uri = format!(
+        "/api/uploads/sessions/{}",
+        body["data"]["id"].as_str().unwrap()
+    );
+
+    let (status, _) = call(
+        &app,
+        TestRequest::get()
+            .uri(&session_uri)
+            .insert_header(bearer(&other.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!("{}/complete", session_uri))
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::put()
+            .uri(&format!("{}/parts/1", session_uri))
+            .insert_header(bearer(&user.token))
+            .set_payload(image.clone()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["uploaded_parts"], json!([1]));
+    assert_eq!(body["data"]["received"], image.len());
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!("{}/complete", session_uri))
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["media"]["width"], 40);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::get()
+            .uri(&session_uri)
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = call(&app, create_session()).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let session_uri = format!(
+        "/api/uploads/sessions/{}",
+        body["data"]["id"].as_str().unwrap()
+    );
+
+    let (status, body) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&session_uri)
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(
+        ctx.repos.media.find_by_owner(user.id).await.unwrap().len(),
+        1
+    );
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn failed_completions_leave_the_upload_to_the_sweeper() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("frank", UserRole::User).await;
+    let image = png(40, 30);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/uploads/sessions")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({ "content_type": "image/png", "size": image.len() })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let session_uri = format!(
+        "/api/uploads/sessions/{}",
+        body["data"]["id"].as_str().unwrap()
+    );
+    let key = body["data"]["key"].as_str().unwrap().to_string();
+    let (status, body) = call(
+        &app,
+        TestRequest::put()
+            .uri(&format!("{}/parts/1", session_uri))
+            .insert_header(bearer(&user.token))
+            .set_payload(image),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    ctx.repos
+        .storage_quotas
+        .save(UserRole::User, 1, Utc::now())
+        .await
+        .unwrap();
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!("{}/complete", session_uri))
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+
+    let pending = ctx
+        .repos
+        .pending_uploads
+        .find_by_owner(user.id)
+        .await
+        .unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].key, key);
+    assert!(pending[0].confirming_until.is_none());
+    assert!(
+        ctx.repos
+            .pending_uploads
+            .find_expired(pending[0].expires_at)
+            .await
+            .unwrap()
+            .iter()
+            .any(|upload| upload.key == pending[0].key)
+    );
+    assert!(
+        ctx.repos
+            .upload_sessions
+            .find_by_owner(user.id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn uploads_beyond_the_quota_are_rejected() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("erin", UserRole::User).await;
+    let admin = ctx.create_user("root", UserRole::Admin).await;
+
+    let (status, body) = upload_to_library(&app, &user, &png(16, 16)).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let used = body["data"]["size"].as_i64().unwrap();
+
+    let (status, body) = call(
+        &app,
+        TestRequest::put()
+            .uri("/api/storage/quotas/User")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({ "quota_bytes": used + 10 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::put()
+            .uri("/api/storage/quotas/User")
+            .insert_header(bearer(&admin.token))
+            .set_json(json!({ "quota_bytes": used + 10 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/users/me/storage")
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["used_bytes"], used);
+    assert_eq!(body["data"]["reserved_bytes"], 0);
+    assert_eq!(body["data"]["remaining_bytes"], 10);
+    assert_eq!(body["data"]["breakdown"]["library"]["count"], 1);
+
+    let (status, body) = upload_to_library(&app, &user, &png(16, 16)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/uploads/presign")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({ "content_type": "image/png", "size": 1024 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(
+        ctx.repos.media.find_by_owner(user.id).await.unwrap().len(),
+        1
+    );
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn confirmed_videos_are_probed_without_being_downloaded() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("gina", UserRole::User).await;
+
+    let data = mp4(3, 200 * 1024);
+    ctx.storage
+        .put("probe/sample.mp4", data.clone(), "video/mp4")
+        .await
+        .unwrap();
+    let range = ctx
+        .storage
+        .get_range("probe/sample.mp4", 4, 4)
+        .await
+        .unwrap();
+    assert_eq!(range, b"ftyp");
+    let tail = ctx
+        .storage
+        .get_range("probe/sample.mp4", data.len() as u64 - 2, 10)
+        .await
+        .unwrap();
+    assert_eq!(tail, data[data.len() - 2..]);
+
+    // The moov box sits past the head, behind the media data.
+    let (status, body) = upload_and_confirm(&app, &user, "video/mp4", data).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["media"]["duration"], 3.0);
+
+    let before = stored_objects(&ctx);
+    let (status, body) = upload_and_confirm(&app, &user, "video/mp4", mp4(60, 200 * 1024)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(stored_objects(&ctx), before);
+    assert_eq!(
+        ctx.repos.media.find_by_owner(user.id).await.unwrap().len(),
+        1
+    );
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn failed_library_uploads_leave_nothing_behind() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("hank", UserRole::User).await;
+
+    // A valid file part followed by a field over the size limit, so the form fails once the media is stored.
+    let (content_type, file_part) = multipart(&[], ("image/png", &png(16, 16)));
+    let boundary = content_type.1.rsplit('=').next().unwrap().to_string();
+    let (_, oversized) = multipart(&[("caption", &"a".repeat(8 * 1024))], ("image/png", b""));
+    let mut body = file_part[..file_part.len() - format!("--{}--\r\n", boundary).len()].to_vec();
+    body.extend_from_slice(&oversized);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/media")
+            .insert_header(bearer(&user.token))
+            .insert_header(content_type)
+            .set_payload(body),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert!(
+        ctx.repos
+            .media
+            .find_by_owner(user.id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(stored_objects(&ctx), 0);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn confirmed_images_that_cannot_be_processed_are_deleted() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("ivan", UserRole::User).await;
+
+    // Passes the sniff and the size limit, but is too wide to decode safely.
+    let (status, body) = upload_and_confirm(&app, &user, "image/png", png(20_000, 1)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert!(
+        ctx.repos
+            .media
+            .find_by_owner(user.id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(stored_objects(&ctx), 0);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn concurrent_confirmations_register_an_upload_once() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("judy", UserRole::User).await;
+
+    let key = presigned_upload(&app, &user, "video/mp4", mp4(3, 1024)).await;
+    let ((first, _), (second, _)) = join(
+        confirm_upload(&app, &user, &key),
+        confirm_upload(&app, &user, &key),
+    )
+    .await;
+
+    let mut statuses = [first, second];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::CREATED, StatusCode::BAD_REQUEST]);
+    assert_eq!(
+        ctx.repos.media.find_by_owner(user.id).await.unwrap().len(),
+        1
+    );
+    assert!(ctx.storage.head(&key).await.is_ok());
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn unconfirmed_uploads_hold_quota_until_swept() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("kate", UserRole::User).await;
+    ctx.repos
+        .storage_quotas
+        .save(UserRole::User, 2048, Utc::now())
+        .await
+        .unwrap();
+
+    let presign = |size: usize| {
+        TestRequest::post()
+            .uri("/api/uploads/presign")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({ "content_type": "image/png", "size": size }))
+    };
+    let (status, body) = call(&app, presign(1500)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = call(&app, presign(1000)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/users/me/storage")
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["used_bytes"], 0);
+    assert_eq!(body["data"]["reserved_bytes"], 1500);
+    assert_eq!(body["data"]["remaining_bytes"], 548);
+
+    let stale_key = format!("{}/stale.png", user.id.to_hex());
+    ctx.storage
+        .put(&stale_key, png(8, 8), "image/png")
+        .await
+        .unwrap();
+    ctx.repos
+        .pending_uploads
+        .insert(PendingUpload {
+            id: None,
+            owner_id: user.id,
+            key: stale_key.clone(),
+            size: 100,
+            expires_at: Utc::now() - Duration::minutes(1),
+            confirming_until: None,
+        })
+        .await
+        .unwrap();
+
+    let swept = upload_service::sweep_expired(&ctx.repos, ctx.storage.as_ref())
+        .await
+        .unwrap();
+    assert_eq!(swept, 1);
+    assert!(ctx.storage.head(&stale_key).await.is_err());
+    let (status, body) = confirm_upload(&app, &user, &stale_key).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+
+    // The upload presigned first has not expired, so it still holds its share of the quota.
+    let (status, body) = call(&app, presign(1000)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    let (status, body) = call(&app, presign(500)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+// =============================================================================================================================
@@ -0,0 +1,291 @@
+use crate::common::{TestApp, bearer, call, multipart, object_id, png};
+use actix_web::{http::StatusCode, test::TestRequest};
+use backend_api_service::models::user_model::UserRole;
+use serde_json::{Value, json};
+
+// =============================================================================================================================
+
+fn contents(body: &Value) -> Vec<&str> {
+    body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|message| message["content"].as_str().unwrap())
+        .collect()
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn direct_messages_require_friendship() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let bob = ctx.create_user("bob", UserRole::User).await;
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!("/api/messages/{}", bob.id.to_hex()))
+            .insert_header(bearer(&alice.token))
+            .set_json(json!({ "content": "Hi stranger" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body["error"], "Recipient is not in your friends list");
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!("/api/messages/{}", alice.id.to_hex()))
+            .insert_header(bearer(&alice.token))
+            .set_json(json!({ "content": "Note to self" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body["error"], "Cannot send message to yourself");
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn friends_share_a_paginated_conversation() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let bob = ctx.create_user("bob", UserRole::User).await;
+    ctx.befriend(&alice, &bob).await;
+
+    for (sender, recipient, content) in [
+        (&alice, &bob, "first"),
+        (&bob, &alice, "second"),
+        (&alice, &bob, "third"),
+    ] {
+        let (status, body) = call(
+            &app,
+            TestRequest::post()
+                .uri(&format!("/api/messages/{}", recipient.id.to_hex()))
+                .insert_header(bearer(&sender.token))
+                .set_json(json!({ "content": content })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+    }
+
+    for (user, other) in [(&alice, &bob), (&bob, &alice)] {
+        let (status, body) = call(
+            &app,
+            TestRequest::get()
+                .uri(&format!("/api/messages/{}", other.id.to_hex()))
+                .insert_header(bearer(&user.token)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(contents(&body), ["third", "second", "first"]);
+    }
+
+    let (_, body) = call(
+        &app,
+        TestRequest::get()
+            .uri(&format!(
+                "/api/messages/{}?limit=1&offset=1",
+                bob.id.to_hex()
+            ))
+            .insert_header(bearer(&alice.token)),
+    )
+    .await;
+    assert_eq!(contents(&body), ["second"]);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn group_messages_are_limited_to_members() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let owner = ctx.create_user("owner", UserRole::User).await;
+    let member = ctx.create_user("member", UserRole::User).await;
+    let outsider = ctx.create_user("outsider", UserRole::User).await;
+
+    let (_, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/groups")
+            .insert_header(bearer(&owner.token))
+            .set_json(json!({ "name": "Crew", "members": [member.id.to_hex()] })),
+    )
+    .await;
+    let uri = format!("/api/messages/groups/{}", object_id(&body["data"]["_id"]));
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri(&uri)
+            .insert_header(bearer(&outsider.token))
+            .set_json(json!({ "content": "Let me in" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body["error"], "Group not found or user is not a member");
+
+    let (status, _) = call(
+        &app,
+        TestRequest::get()
+            .uri(&uri)
+            .insert_header(bearer(&outsider.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri(&uri)
+            .insert_header(bearer(&member.token))
+            .set_json(json!({ "content": "Hello crew" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri(&uri)
+            .insert_header(bearer(&owner.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(contents(&body), ["Hello crew"]);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn only_the_sender_can_delete_a_message() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let bob = ctx.create_user("bob", UserRole::User).await;
+    ctx.befriend(&alice, &bob).await;
+
+    let (_, body) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!("/api/messages/{}", bob.id.to_hex()))
+            .insert_header(bearer(&alice.token))
+            .set_json(json!({ "content": "Oops" })),
+    )
+    .await;
+    let uri = format!("/api/messages/{}", object_id(&body["data"]["_id"]));
+
+    let (status, body) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&uri)
+            .insert_header(bearer(&bob.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body["error"], "Message not found or user is not the sender");
+
+    let (status, _) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&uri)
+            .insert_header(bearer(&alice.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = call(
+        &app,
+        TestRequest::get()
+            .uri(&format!("/api/messages/{}", alice.id.to_hex()))
+            .insert_header(bearer(&bob.token)),
+    )
+    .await;
+    assert_eq!(body["data"], json!([]));
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn messages_can_carry_an_upload() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let bob = ctx.create_user("bob", UserRole::User).await;
+    let stranger = ctx.create_user("stranger", UserRole::User).await;
+    ctx.befriend(&alice, &bob).await;
+
+    let send = |uri: String, token: &str| {
+        let (content_type, form) =
+            multipart(&[("text_content", "Look")], ("image/png", &png(20, 20)));
+        TestRequest::post()
+            .uri(&uri)
+            .insert_header(bearer(token))
+            .insert_header(content_type)
+            .set_payload(form)
+    };
+
+    let (status, body) = call(
+        &app,
+        send(
+            format!("/api/messages/{}/media", bob.id.to_hex()),
+            &alice.token,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["content"], "Look");
+    assert_eq!(body["data"]["media"]["media_type"], "Image");
+
+    let (status, _) = call(
+        &app,
+        send(
+            format!("/api/messages/{}/media", bob.id.to_hex()),
+            &stranger.token,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(
+        ctx.repos
+            .media
+            .find_by_owner(stranger.id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    let (_, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/groups")
+            .insert_header(bearer(&alice.token))
+            .set_json(json!({ "name": "Crew", "members": [bob.id.to_hex()] })),
+    )
+    .await;
+    let group_uri = format!(
+        "/api/messages/groups/{}/media",
+        object_id(&body["data"]["_id"])
+    );
+
+    let (status, body) = call(&app, send(group_uri.clone(), &bob.token)).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["is_group"], true);
+    assert!(body["data"]["media"]["url"].is_string());
+
+    let (status, _) = call(&app, send(group_uri, &stranger.token)).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(
+        ctx.repos
+            .media
+            .find_by_owner(stranger.id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+// =============================================================================================================================
@@ -0,0 +1,387 @@
+use crate::common::{PASSWORD, TestApp, TestUser, bearer, call, object_id};
+use actix_http::Request;
+use actix_web::{
+    Error,
+    dev::{Service, ServiceResponse},
+    http::StatusCode,
+    test::TestRequest,
+};
+use backend_api_service::models::{message_model::Message, user_model::UserRole};
+use chrono::{Duration, Utc};
+use serde_json::{Value, json};
+
+// =============================================================================================================================
+
+const LYON: [f64; 2] = [4.8357, 45.7640];
+
+async fn report(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    reporter: &TestUser,
+    target: &str,
+    target_id: String,
+) -> (StatusCode, Value) {
+    call(
+        app,
+        TestRequest::post()
+            .uri("/api/reports")
+            .insert_header(bearer(&reporter.token))
+            .set_json(json!({ "target": target, "target_id": target_id, "reason": "Offensive" })),
+    )
+    .await
+}
+
+async fn login_status(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    user: &TestUser,
+) -> StatusCode {
+    call(
+        app,
+        TestRequest::post()
+            .uri("/api/auth/login")
+            .set_json(json!({ "credential": user.username, "password": PASSWORD })),
+    )
+    .await
+    .0
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn removing_a_reported_story_deletes_its_media() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let admin = ctx.create_user("admin", UserRole::Admin).await;
+    let poster = ctx.create_user("poster", UserRole::User).await;
+    let viewer = ctx.create_user("viewer", UserRole::User).await;
+    let other = ctx.create_user("other", UserRole::User).await;
+
+    let story = ctx.create_story(&poster, LYON, Duration::hours(1)).await;
+    let story_id = story.id.unwrap().to_hex();
+    let key = ctx
+        .storage
+        .object_key_from_url(&story.media.url)
+        .unwrap()
+        .to_string();
+    ctx.storage
+        .put(&key, b"jpeg".to_vec(), "image/jpeg")
+        .await
+        .unwrap();
+
+    let (status, body) = report(&app, &viewer, "Story", story_id.clone()).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let report_id = object_id(&body["data"]["_id"]);
+    let (status, _) = report(&app, &other, "Story", story_id.clone()).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/moderation/reports")
+            .insert_header(bearer(&viewer.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/moderation/reports")
+            .insert_header(bearer(&admin.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+    assert_eq!(body["data"][0]["reason"], "Offensive");
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!("/api/moderation/reports/{}/resolve", report_id))
+            .insert_header(bearer(&admin.token))
+            .set_json(json!({ "remove_content": true, "note": "Graphic content" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["status"], "Resolved");
+
+    assert!(
+        ctx.repos
+            .stories
+            .find_by_id(story.id.unwrap())
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert!(ctx.storage.get(&key).await.is_err());
+
+    // The other report of the story went with it.
+    let (_, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/moderation/reports")
+            .insert_header(bearer(&admin.token)),
+    )
+    .await;
+    assert_eq!(body["data"], json!([]));
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!("/api/moderation/reports/{}/dismiss", report_id))
+            .insert_header(bearer(&admin.token))
+            .set_json(json!({})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri(&format!("/api/moderation/actions?target_id={}", story_id))
+            .insert_header(bearer(&admin.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let actions = body["data"].as_array().unwrap();
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0]["action"], "RemoveContent");
+    assert_eq!(actions[0]["reason"], "Graphic content");
+    assert_eq!(object_id(&actions[0]["moderator_id"]), admin.id.to_hex());
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn users_can_only_report_what_they_can_see() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let bob = ctx.create_user("bob", UserRole::User).await;
+    let carol = ctx.create_user("carol", UserRole::User).await;
+
+    let message = ctx
+        .repos
+        .messages
+        .insert(Message {
+            id: None,
+            content: "Rude words".to_string(),
+            sender_id: alice.id,
+            recipient_id: bob.id,
+            is_group: false,
+            media: None,
+            read: false,
+        })
+        .await
+        .unwrap();
+    let message_id = message.id.unwrap().to_hex();
+
+    let (status, _) = report(&app, &carol, "Message", message_id.clone()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = report(&app, &alice, "Message", message_id.clone()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = report(&app, &bob, "User", bob.id.to_hex()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = report(&app, &bob, "Story", message_id.clone()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Expired stories stay visible to the friends of their author only.
+    ctx.befriend(&alice, &bob).await;
+    let story_id = ctx
+        .create_story(&alice, LYON, -Duration::hours(1))
+        .await
+        .id
+        .unwrap()
+        .to_hex();
+    let (status, _) = report(&app, &carol, "Story", story_id.clone()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, body) = report(&app, &bob, "Story", story_id).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+
+    let (status, body) = report(&app, &bob, "Message", message_id.clone()).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(
+        object_id(&body["data"]["target_user_id"]),
+        alice.id.to_hex()
+    );
+    let (status, _) = report(&app, &bob, "Message", message_id).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = report(&app, &bob, "User", alice.id.to_hex()).await;
+    assert_eq!(status, StatusCode::CREATED);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn suspended_users_are_signed_out_until_reinstated() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let admin = ctx.create_user("admin", UserRole::Admin).await;
+    let other_admin = ctx.create_user("root", UserRole::Admin).await;
+    let user = ctx.create_user("alice", UserRole::User).await;
+    let suspend_uri = format!("/api/moderation/users/{}/suspend", user.id.to_hex());
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri(&suspend_uri)
+            .insert_header(bearer(&admin.token))
+            .set_json(json!({ "until": Utc::now() - Duration::hours(1), "reason": "Spam" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!(
+                "/api/moderation/users/{}/ban",
+                other_admin.id.to_hex()
+            ))
+            .insert_header(bearer(&admin.token))
+            .set_json(json!({ "reason": "Coup" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri(&suspend_uri)
+            .insert_header(bearer(&admin.token))
+            .set_json(json!({ "until": Utc::now() + Duration::days(7), "reason": "Spam" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["status"], "Suspended");
+
+    let (status, _) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/auth/me")
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(login_status(&app, &user).await, StatusCode::FORBIDDEN);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!(
+                "/api/moderation/users/{}/reinstate",
+                user.id.to_hex()
+            ))
+            .insert_header(bearer(&admin.token))
+            .set_json(json!({ "reason": "Appeal accepted" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(login_status(&app, &user).await, StatusCode::OK);
+
+    let (_, body) = call(
+        &app,
+        TestRequest::get()
+            .uri(&format!(
+                "/api/moderation/actions?target_id={}",
+                user.id.to_hex()
+            ))
+            .insert_header(bearer(&admin.token)),
+    )
+    .await;
+    let actions: Vec<&str> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|action| action["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(actions, ["ReinstateUser", "SuspendUser"]);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn banned_users_stories_disappear_from_nearby() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let admin = ctx.create_user("admin", UserRole::Admin).await;
+    let viewer = ctx.create_user("viewer", UserRole::User).await;
+    let poster = ctx.create_user("poster", UserRole::User).await;
+    let banned = ctx.create_user("troll", UserRole::User).await;
+
+    let kept = ctx.create_story(&poster, LYON, Duration::hours(1)).await;
+    ctx.create_story(&banned, LYON, Duration::hours(1)).await;
+    let nearby_uri = format!(
+        "/api/stories/nearby?longitude={}&latitude={}",
+        LYON[0], LYON[1]
+    );
+
+    let (_, body) = call(
+        &app,
+        TestRequest::get()
+            .uri(&nearby_uri)
+            .insert_header(bearer(&viewer.token)),
+    )
+    .await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!("/api/moderation/users/{}/ban", banned.id.to_hex()))
+            .insert_header(bearer(&admin.token))
+            .set_json(json!({ "reason": "Repeated harassment" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = call(
+        &app,
+        TestRequest::get()
+            .uri(&nearby_uri)
+            .insert_header(bearer(&viewer.token)),
+    )
+    .await;
+    let ids: Vec<String> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|story| object_id(&story["_id"]))
+        .collect();
+    assert_eq!(ids, [kept.id.unwrap().to_hex()]);
+    assert_eq!(login_status(&app, &banned).await, StatusCode::FORBIDDEN);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn strangers_can_report_the_nearby_stories_they_see() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let poster = ctx.create_user("poster", UserRole::User).await;
+    let stranger = ctx.create_user("stranger", UserRole::User).await;
+    let story = ctx.create_story(&poster, LYON, Duration::hours(1)).await;
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri(&format!(
+                "/api/stories/nearby?longitude={}&latitude={}",
+                LYON[0], LYON[1]
+            ))
+            .insert_header(bearer(&stranger.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let story_id = object_id(&body["data"][0]["_id"]);
+    assert_eq!(story_id, story.id.unwrap().to_hex());
+
+    let (status, body) = report(&app, &stranger, "Story", story_id).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(
+        object_id(&body["data"]["target_user_id"]),
+        poster.id.to_hex()
+    );
+}
+
+// =============================================================================================================================
@@ -0,0 +1,387 @@
+use crate::common::{PASSWORD, TestApp, bearer, call};
+use actix_http::Request;
+use actix_web::{
+    App, Error, HttpResponse, HttpServer,
+    dev::{Service, ServiceResponse},
+    http::StatusCode,
+    test::TestRequest,
+    web,
+};
+use backend_api_service::{
+    config::OidcProviderConfig,
+    models::user_model::UserRole,
+    utils::jwt::{JwtKeys, generate_key_pem},
+};
+use chrono::{Duration, Utc};
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use reqwest::Url;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    env, fs,
+    net::TcpListener,
+    sync::{Arc, Mutex},
+};
+use uuid::Uuid;
+
+// =============================================================================================================================
+
+const PROVIDER: &str = "mock";
+const CLIENT_ID: &str = "snapshoot-tests";
+const REDIRECT_URI: &str = "snapshoot://oidc/callback";
+const KEY_ID: &str = "mock-key";
+
+// =============================================================================================================================
+
+struct IssuedCode {
+    code_challenge: String,
+    claims: Value,
+}
+
+struct MockState {
+    issuer: String,
+    encoding_key: EncodingKey,
+    jwks: Value,
+    codes: Mutex<HashMap<String, IssuedCode>>,
+}
+
+/// An OpenID Connect provider on a local port: discovery, keys and a token endpoint that checks PKCE. Users "sign
+/// in" through `approve`, which issues the code the provider would redirect back with.
+struct MockProvider {
+    state: Arc<MockState>,
+}
+
+impl MockProvider {
+    fn start() -> Self {
+        let dir = env::temp_dir().join(format!("snapshoot-oidc-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let pem = generate_key_pem();
+        fs::write(dir.join(format!("{}.pem", KEY_ID)), &pem).unwrap();
+        let jwks = JwtKeys::load(dir.to_str().unwrap(), None).unwrap().jwks();
+        fs::remove_dir_all(dir).ok();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let state = Arc::new(MockState {
+            issuer: format!("http://{}", listener.local_addr().unwrap()),
+            encoding_key: EncodingKey::from_ed_pem(pem.as_bytes()).unwrap(),
+            jwks,
+            codes: Mutex::default(),
+        });
+
+        let data = web::Data::from(state.clone());
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(data.clone())
+                .route(
+                    "/.well-known/openid-configuration",
+                    web::get().to(discovery),
+                )
+                .route("/jwks", web::get().to(jwks_endpoint))
+                .route("/token", web::post().to(token))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        actix_web::rt::spawn(server);
+
+        Self { state }
+    }
+
+    fn config(&self) -> OidcProviderConfig {
+        OidcProviderConfig {
+            issuer: self.state.issuer.clone(),
+            client_id: CLIENT_ID.to_string(),
+            redirect_uri: REDIRECT_URI.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The claims of an ID token for `authorization_url`, as the provider would issue them.
+    fn claims(&self, authorization_url: &str, subject: &str, email: &str, verified: bool) -> Value {
+        json!({
+            "iss": self.state.issuer,
+            "aud": CLIENT_ID,
+            "sub": subject,
+            "exp": (Utc::now() + Duration::minutes(5)).timestamp(),
+            "iat": Utc::now().timestamp(),
+            "nonce": query_param(authorization_url, "nonce"),
+            "email": email,
+            "email_verified": verified,
+            "name": "Jane Doe",
+        })
+    }
+
+    /// Signs the user in at the provider and returns the code of the redirect.
+    fn approve(&self, authorization_url: &str, claims: Value) -> String {
+        assert_eq!(query_param(authorization_url, "client_id"), CLIENT_ID);
+        assert_eq!(query_param(authorization_url, "redirect_uri"), REDIRECT_URI);
+        assert_eq!(
+            query_param(authorization_url, "code_challenge_method"),
+            "S256"
+        );
+
+        let code = Uuid::new_v4().simple().to_string();
+        self.state.codes.lock().unwrap().insert(
+            code.clone(),
+            IssuedCode {
+                code_challenge: query_param(authorization_url, "code_challenge"),
+                claims,
+            },
+        );
+        code
+    }
+}
+
+async fn discovery(state: web::Data<MockState>) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "issuer": state.issuer,
+        "authorization_endpoint": format!("{}/authorize", state.issuer),
+        "token_endpoint": format!("{}/token", state.issuer),
+        "jwks_uri": format!("{}/jwks", state.issuer),
+    }))
+}
+
+async fn jwks_endpoint(state: web::Data<MockState>) -> HttpResponse {
+    HttpResponse::Ok().json(&state.jwks)
+}
+
+async fn token(
+    state: web::Data<MockState>,
+    form: web::Form<HashMap<String, String>>,
+) -> HttpResponse {
+    let issued = state.codes.lock().unwrap().remove(&form["code"]);
+    let verifier_challenge =
+        BASE64URL_NOPAD.encode(&Sha256::digest(form["code_verifier"].as_bytes()));
+
+    match issued {
+        Some(issued)
+            if issued.code_challenge == verifier_challenge && form["client_id"] == CLIENT_ID =>
+        {
+            let mut header = Header::new(Algorithm::EdDSA);
+            header.kid = Some(KEY_ID.to_string());
+            let id_token = encode(&header, &issued.claims, &state.encoding_key).unwrap();
+            HttpResponse::Ok().json(json!({ "id_token": id_token, "token_type": "Bearer" }))
+        }
+        _ => HttpResponse::BadRequest().json(json!({ "error": "invalid_grant" })),
+    }
+}
+
+fn query_param(url: &str, name: &str) -> String {
+    Url::parse(url)
+        .unwrap()
+        .query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+        .unwrap_or_else(|| panic!("no {} in {}", name, url))
+}
+
+// =============================================================================================================================
+
+async fn authorize(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+) -> (String, String) {
+    let (status, body) = call(
+        app,
+        TestRequest::post().uri(&format!("/api/auth/oidc/{}/authorize", PROVIDER)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    (
+        body["data"]["authorization_url"]
+            .as_str()
+            .unwrap()
+            .to_string(),
+        body["data"]["state"].as_str().unwrap().to_string(),
+    )
+}
+
+async fn callback(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    code: &str,
+    state: &str,
+) -> (StatusCode, Value) {
+    call(
+        app,
+        TestRequest::post()
+            .uri(&format!("/api/auth/oidc/{}/callback", PROVIDER))
+            .set_json(json!({ "code": code, "state": state, "device_name": "Jane's phone" })),
+    )
+    .await
+}
+
+async fn me(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    token: &str,
+) -> Value {
+    let (status, body) = call(
+        app,
+        TestRequest::get()
+            .uri("/api/users/me")
+            .insert_header(bearer(token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body["data"].clone()
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn a_new_identity_signs_up_and_signs_in_again() {
+    let provider = MockProvider::start();
+    let mut ctx = TestApp::new().await;
+    ctx.config
+        .oidc
+        .providers
+        .insert(PROVIDER.to_string(), provider.config());
+    let app = ctx.service().await;
+
+    let (status, body) = call(&app, TestRequest::get().uri("/api/auth/oidc/providers")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"], json!([PROVIDER]));
+
+    let (url, state) = authorize(&app).await;
+    assert!(url.starts_with(&format!("{}/authorize?", provider.state.issuer)));
+    assert_eq!(query_param(&url, "state"), state);
+    let code = provider.approve(
+        &url,
+        provider.claims(&url, "sub-1", "Jane@Example.com", true),
+    );
+
+    let (status, body) = callback(&app, &code, &state).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let user = me(&app, body["data"]["token"].as_str().unwrap()).await;
+    assert_eq!(user["email"], "jane@example.com");
+    assert_eq!(user["username"], "jane doe");
+    assert_eq!(user["verified"], true);
+
+    // The same subject signs in to the same account, even with another email at the provider.
+    let (url, state) = authorize(&app).await;
+    let code = provider.approve(
+        &url,
+        provider.claims(&url, "sub-1", "jane@elsewhere.com", false),
+    );
+    let (status, body) = callback(&app, &code, &state).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(
+        me(&app, body["data"]["token"].as_str().unwrap()).await["_id"],
+        user["_id"]
+    );
+
+    // The account has no password.
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/auth/login")
+            .set_json(json!({ "credential": "jane@example.com", "password": PASSWORD })),
+    )
+    .await;
+    assert_ne!(status, StatusCode::OK);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn identities_link_to_accounts_by_verified_email_only() {
+    let provider = MockProvider::start();
+    let mut ctx = TestApp::new().await;
+    ctx.config
+        .oidc
+        .providers
+        .insert(PROVIDER.to_string(), provider.config());
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+
+    let (url, state) = authorize(&app).await;
+    let code = provider.approve(&url, provider.claims(&url, "sub-2", &alice.email, false));
+    let (status, _) = callback(&app, &code, &state).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(
+        ctx.repos
+            .identities
+            .find(PROVIDER, "sub-2")
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    let (url, state) = authorize(&app).await;
+    let code = provider.approve(&url, provider.claims(&url, "sub-2", &alice.email, true));
+    let (status, body) = callback(&app, &code, &state).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let user = me(&app, body["data"]["token"].as_str().unwrap()).await;
+    assert_eq!(user["_id"], alice.id.to_hex());
+
+    // The password keeps working for the linked account.
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/auth/login")
+            .set_json(json!({ "credential": "alice", "password": PASSWORD })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn forged_or_replayed_callbacks_are_rejected() {
+    let provider = MockProvider::start();
+    let mut ctx = TestApp::new().await;
+    ctx.config
+        .oidc
+        .providers
+        .insert(PROVIDER.to_string(), provider.config());
+    let app = ctx.service().await;
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post().uri("/api/auth/oidc/unknown/authorize"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Unknown state.
+    let (url, _) = authorize(&app).await;
+    let code = provider.approve(
+        &url,
+        provider.claims(&url, "sub-3", "bob@example.com", true),
+    );
+    let (status, _) = callback(&app, &code, "not-the-state").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // ID tokens for another sign-in, another client or another issuer.
+    for (claim, value) in [
+        ("nonce", json!("another-nonce")),
+        ("aud", json!("another-client")),
+        ("iss", json!("https://issuer.example")),
+        (
+            "exp",
+            json!((Utc::now() - Duration::minutes(5)).timestamp()),
+        ),
+    ] {
+        let (url, state) = authorize(&app).await;
+        let mut claims = provider.claims(&url, "sub-3", "bob@example.com", true);
+        claims[claim] = value;
+        let code = provider.approve(&url, claims);
+        let (status, body) = callback(&app, &code, &state).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{}: {}", claim, body);
+    }
+
+    // A state works once.
+    let (url, state) = authorize(&app).await;
+    let code = provider.approve(
+        &url,
+        provider.claims(&url, "sub-3", "bob@example.com", true),
+    );
+    let (status, _) = callback(&app, &code, &state).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = callback(&app, &code, &state).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+// =============================================================================================================================
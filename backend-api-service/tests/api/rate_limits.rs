@@ -0,0 +1,168 @@
+use crate::common::{PASSWORD, TestApp, bearer, call};
+use actix_web::{
+    http::{StatusCode, header},
+    test::{self, TestRequest},
+};
+use backend_api_service::{config::RateBudget, models::user_model::UserRole};
+use chrono::{Duration, Utc};
+use serde_json::json;
+
+// =============================================================================================================================
+
+fn login(credential: &str, password: &str) -> TestRequest {
+    TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(json!({ "credential": credential, "password": password }))
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn login_attempts_are_limited_per_ip() {
+    let mut ctx = TestApp::new().await;
+    ctx.config.auth.lockout_threshold = 0;
+    ctx.config.rate_limits.login = RateBudget {
+        requests: 3,
+        window_secs: 60,
+    };
+    let app = ctx.service().await;
+    let first_ip = "203.0.113.7:50000".parse().unwrap();
+
+    for _ in 0..3 {
+        let (status, _) = call(
+            &app,
+            login("nobody", "wrong-password-123").peer_addr(first_ip),
+        )
+        .await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let response = test::call_service(
+        &app,
+        login("nobody", "wrong-password-123")
+            .peer_addr(first_ip)
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after), "{}", retry_after);
+
+    let (status, _) = call(
+        &app,
+        login("nobody", "wrong-password-123").peer_addr("198.51.100.4:50000".parse().unwrap()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn friend_requests_are_limited_per_user_across_ips() {
+    let mut ctx = TestApp::new().await;
+    ctx.config.rate_limits.friend_requests = RateBudget {
+        requests: 2,
+        window_secs: 3600,
+    };
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let recipients = [
+        ctx.create_user("bob", UserRole::User).await,
+        ctx.create_user("carol", UserRole::User).await,
+        ctx.create_user("dave", UserRole::User).await,
+    ];
+
+    let mut statuses = Vec::new();
+    for (index, recipient) in recipients.iter().enumerate() {
+        let (status, _) = call(
+            &app,
+            TestRequest::post()
+                .uri(&format!("/api/friends/request/{}", recipient.id.to_hex()))
+                .peer_addr(format!("203.0.113.{}:50000", index + 1).parse().unwrap())
+                .insert_header(bearer(&alice.token)),
+        )
+        .await;
+        statuses.push(status);
+    }
+
+    assert_eq!(
+        statuses,
+        [
+            StatusCode::OK,
+            StatusCode::OK,
+            StatusCode::TOO_MANY_REQUESTS
+        ]
+    );
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn rate_limits_can_be_disabled() {
+    let mut ctx = TestApp::new().await;
+    ctx.config.auth.lockout_threshold = 0;
+    ctx.config.rate_limits.enabled = false;
+    ctx.config.rate_limits.login = RateBudget {
+        requests: 1,
+        window_secs: 60,
+    };
+    let app = ctx.service().await;
+
+    for _ in 0..3 {
+        let (status, _) = call(&app, login("nobody", "wrong-password-123")).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn accounts_are_locked_after_repeated_failed_logins() {
+    let mut ctx = TestApp::new().await;
+    ctx.config.rate_limits.enabled = false;
+    ctx.config.auth.lockout_threshold = 3;
+    let app = ctx.service().await;
+    let user = ctx.create_user("erin", UserRole::User).await;
+
+    // A successful login resets the count.
+    for password in ["wrong-password-123", "wrong-password-123", PASSWORD] {
+        call(&app, login(&user.username, password)).await;
+    }
+
+    for _ in 0..2 {
+        let (status, _) = call(&app, login(&user.username, "wrong-password-123")).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    let (status, body) = call(&app, login(&user.email, "wrong-password-123")).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS, "{}", body);
+
+    // Locked accounts reject the right password as well.
+    let (status, body) = call(&app, login(&user.username, PASSWORD)).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(body.get("data").is_none());
+
+    // Unknown credentials never report a lock, so they cannot be told apart from existing accounts this way.
+    for _ in 0..4 {
+        let (status, _) = call(&app, login("nobody", "wrong-password-123")).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    // Once the lock has expired, the right password works again.
+    ctx.repos
+        .login_attempts
+        .record_failure(user.id, 1, Utc::now() - Duration::minutes(1))
+        .await
+        .unwrap();
+    let (status, body) = call(&app, login(&user.username, PASSWORD)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+// =============================================================================================================================
@@ -0,0 +1,249 @@
+use crate::common::{PASSWORD, TestApp, bearer, call};
+use actix_http::Request;
+use actix_web::{
+    Error,
+    dev::{Service, ServiceResponse},
+    http::{StatusCode, header},
+    test::TestRequest,
+};
+use backend_api_service::{models::user_model::UserRole, utils::jwt::encode_external_jwt};
+use bson::oid::ObjectId;
+use chrono::{Duration, Utc};
+use serde_json::json;
+
+// =============================================================================================================================
+
+async fn login(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    device_name: &str,
+    platform: &str,
+) -> String {
+    let (status, body) = call(
+        app,
+        TestRequest::post().uri("/api/auth/login").set_json(json!({
+            "credential": "alice",
+            "password": PASSWORD,
+            "device_name": device_name,
+            "platform": platform
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body["data"]["token"].as_str().unwrap().to_string()
+}
+
+async fn me_status(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    token: &str,
+) -> StatusCode {
+    call(
+        app,
+        TestRequest::get()
+            .uri("/api/users/me")
+            .insert_header(bearer(token)),
+    )
+    .await
+    .0
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn logins_are_listed_as_sessions() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+
+    let phone = login(&app, "Alice's phone", "ios").await;
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/auth/login")
+            .insert_header((header::USER_AGENT, "Firefox on Linux"))
+            .set_json(json!({ "credential": "alice", "password": PASSWORD })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/auth/sessions")
+            .insert_header(bearer(&phone)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let sessions = body["data"].as_array().unwrap();
+    // The session of `create_user`, the phone and the browser.
+    assert_eq!(sessions.len(), 3);
+
+    let phone_session = sessions
+        .iter()
+        .find(|session| session["device_name"] == "Alice's phone")
+        .unwrap();
+    assert_eq!(phone_session["platform"], "ios");
+    assert_eq!(phone_session["current"], true);
+    assert!(phone_session["created_at"].is_string());
+    assert!(phone_session["last_used"].is_string());
+
+    let browser_session = sessions
+        .iter()
+        .find(|session| session["device_name"] == "Firefox on Linux")
+        .unwrap();
+    assert_eq!(browser_session["current"], false);
+
+    // Sessions of other users are not listed.
+    let (_, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/auth/sessions")
+            .insert_header(bearer(&ctx.create_user("bob", UserRole::User).await.token)),
+    )
+    .await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    assert_eq!(me_status(&app, &alice.token).await, StatusCode::OK);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn revoked_sessions_reject_their_token() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let bob = ctx.create_user("bob", UserRole::User).await;
+    let phone = login(&app, "Alice's phone", "android").await;
+    let laptop = login(&app, "Alice's laptop", "web").await;
+
+    let (_, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/auth/sessions")
+            .insert_header(bearer(&laptop)),
+    )
+    .await;
+    let phone_id = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|session| session["device_name"] == "Alice's phone")
+        .unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Another user cannot revoke it.
+    let (status, _) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&format!("/api/auth/sessions/{}", phone_id))
+            .insert_header(bearer(&bob.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(me_status(&app, &phone).await, StatusCode::OK);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&format!("/api/auth/sessions/{}", phone_id))
+            .insert_header(bearer(&laptop)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(me_status(&app, &phone).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(me_status(&app, &laptop).await, StatusCode::OK);
+
+    // Revoking the other sessions keeps the current one.
+    let (status, body) = call(
+        &app,
+        TestRequest::delete()
+            .uri("/api/auth/sessions")
+            .insert_header(bearer(&laptop)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["revoked"], 1);
+    assert_eq!(
+        me_status(&app, &alice.token).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(me_status(&app, &laptop).await, StatusCode::OK);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/auth/logout")
+            .insert_header(bearer(&laptop)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(me_status(&app, &laptop).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(me_status(&app, &bob.token).await, StatusCode::OK);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn tokens_without_a_known_session_are_rejected() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+
+    let token = encode_external_jwt(
+        &ctx.config.auth,
+        alice.id.to_hex(),
+        UserRole::User,
+        ObjectId::new().to_hex(),
+        (Utc::now() + Duration::minutes(5)).timestamp(),
+    )
+    .unwrap();
+    assert_eq!(me_status(&app, &token).await, StatusCode::UNAUTHORIZED);
+
+    // A session only works with the token of its own user.
+    let bob = ctx.create_user("bob", UserRole::User).await;
+    let session_id = ctx
+        .repos
+        .sessions
+        .find_active(alice.id, Utc::now())
+        .await
+        .unwrap()[0]
+        .id;
+    let token = encode_external_jwt(
+        &ctx.config.auth,
+        bob.id.to_hex(),
+        UserRole::User,
+        session_id.to_hex(),
+        (Utc::now() + Duration::minutes(5)).timestamp(),
+    )
+    .unwrap();
+    assert_eq!(me_status(&app, &token).await, StatusCode::UNAUTHORIZED);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn changing_the_password_signs_out_other_sessions() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let phone = login(&app, "Alice's phone", "ios").await;
+
+    let (status, _) = call(
+        &app,
+        TestRequest::put()
+            .uri("/api/users/me/password")
+            .insert_header(bearer(&alice.token))
+            .set_json(json!({
+                "current_password": PASSWORD,
+                "new_password": "another-long-password"
+            })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    assert_eq!(me_status(&app, &phone).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(me_status(&app, &alice.token).await, StatusCode::OK);
+}
+
+// =============================================================================================================================
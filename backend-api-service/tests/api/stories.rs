@@ -0,0 +1,303 @@
+use crate::common::{TestApp, bearer, call, multipart, object_id, png};
+use actix_web::{http::StatusCode, test::TestRequest};
+use backend_api_service::models::user_model::UserRole;
+use chrono::Duration;
+use serde_json::{Value, json};
+
+// =============================================================================================================================
+
+const LYON: [f64; 2] = [4.8357, 45.7640];
+const VILLEURBANNE: [f64; 2] = [4.8800, 45.7719];
+const PARIS: [f64; 2] = [2.3522, 48.8566];
+
+fn story_ids(body: &Value) -> Vec<String> {
+    body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|story| object_id(&story["_id"]))
+        .collect()
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn feed_lists_active_stories_from_friends_only() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let viewer = ctx.create_user("viewer", UserRole::User).await;
+    let friend = ctx.create_user("friend", UserRole::User).await;
+    let stranger = ctx.create_user("stranger", UserRole::User).await;
+    ctx.befriend(&friend, &viewer).await;
+
+    let live = ctx.create_story(&friend, LYON, Duration::hours(1)).await;
+    let newer = ctx.create_story(&friend, LYON, Duration::hours(20)).await;
+    ctx.create_story(&friend, LYON, Duration::hours(-1)).await;
+    ctx.create_story(&stranger, LYON, Duration::hours(1)).await;
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/stories")
+            .insert_header(bearer(&viewer.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        story_ids(&body),
+        [newer.id.unwrap().to_hex(), live.id.unwrap().to_hex()]
+    );
+
+    let (_, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/stories")
+            .insert_header(bearer(&stranger.token)),
+    )
+    .await;
+    assert_eq!(body["data"], json!([]));
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn nearby_stories_are_filtered_by_radius() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let viewer = ctx.create_user("viewer", UserRole::User).await;
+    let poster = ctx.create_user("poster", UserRole::User).await;
+
+    let close = ctx
+        .create_story(&poster, VILLEURBANNE, Duration::hours(1))
+        .await;
+    ctx.create_story(&poster, PARIS, Duration::hours(1)).await;
+    ctx.create_story(&poster, LYON, Duration::hours(-1)).await;
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri(&format!(
+                "/api/stories/nearby?longitude={}&latitude={}&radius=10000",
+                LYON[0], LYON[1]
+            ))
+            .insert_header(bearer(&viewer.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(story_ids(&body), [close.id.unwrap().to_hex()]);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/stories/nearby?longitude=4.8")
+            .insert_header(bearer(&viewer.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn expired_stories_stay_visible_to_the_owner_and_friends() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let owner = ctx.create_user("owner", UserRole::User).await;
+    let friend = ctx.create_user("friend", UserRole::User).await;
+    let stranger = ctx.create_user("stranger", UserRole::User).await;
+    ctx.befriend(&owner, &friend).await;
+
+    let expired = ctx.create_story(&owner, LYON, Duration::hours(-1)).await;
+    let uri = format!("/api/stories/{}", expired.id.unwrap().to_hex());
+
+    for user in [&owner, &friend] {
+        let (status, body) = call(
+            &app,
+            TestRequest::get()
+                .uri(&uri)
+                .insert_header(bearer(&user.token)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(object_id(&body["data"]["user_id"]), owner.id.to_hex());
+    }
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri(&uri)
+            .insert_header(bearer(&stranger.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body["error"], "Story not found or you don't have access");
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn only_the_owner_can_delete_a_story() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let owner = ctx.create_user("owner", UserRole::User).await;
+    let other = ctx.create_user("other", UserRole::User).await;
+
+    let story = ctx.create_story(&owner, LYON, Duration::hours(1)).await;
+    let uri = format!("/api/stories/{}", story.id.unwrap().to_hex());
+
+    let (status, body) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&uri)
+            .insert_header(bearer(&other.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body["error"], "Story not found or user is not the creator");
+
+    let (status, body) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&uri)
+            .insert_header(bearer(&owner.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::get()
+            .uri(&uri)
+            .insert_header(bearer(&owner.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn stories_reference_library_media_until_deleted() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let owner = ctx.create_user("owner", UserRole::User).await;
+    let other = ctx.create_user("other", UserRole::User).await;
+
+    let (content_type, form) = multipart(&[], ("image/png", &png(24, 24)));
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/media")
+            .insert_header(bearer(&owner.token))
+            .insert_header(content_type)
+            .set_payload(form),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let media_id = object_id(&body["data"]["_id"]);
+    let story = json!({
+        "media_id": media_id,
+        "location": { "type": "Point", "coordinates": LYON }
+    });
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/stories")
+            .insert_header(bearer(&other.token))
+            .set_json(&story),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/stories")
+            .insert_header(bearer(&owner.token))
+            .set_json(&story),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(object_id(&body["data"]["media"]["id"]), media_id);
+    let story_uri = format!("/api/stories/{}", object_id(&body["data"]["_id"]));
+
+    let (status, _) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&format!("/api/media/{}", media_id))
+            .insert_header(bearer(&owner.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        ctx.repos.media.find_by_owner(owner.id).await.unwrap().len(),
+        1
+    );
+
+    let (status, _) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&story_uri)
+            .insert_header(bearer(&owner.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        ctx.repos
+            .media
+            .find_by_owner(owner.id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn stories_can_be_posted_with_an_upload() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let owner = ctx.create_user("owner", UserRole::User).await;
+    let uri = format!(
+        "/api/stories/media?type=Point&coordinates=[{},{}]",
+        LYON[0], LYON[1]
+    );
+
+    let (content_type, form) = multipart(&[], ("image/png", &png(24, 24)));
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri(&uri)
+            .insert_header(bearer(&owner.token))
+            .insert_header(content_type)
+            .set_payload(form),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["location"]["coordinates"], json!(LYON));
+    assert!(body["data"]["media"]["thumbnail_url"].is_string());
+
+    let media = ctx.repos.media.find_by_owner(owner.id).await.unwrap();
+    assert_eq!(media.len(), 1);
+    assert!(!media[0].in_library);
+
+    let (content_type, form) = multipart(&[], ("video/mp4", &png(24, 24)));
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri(&uri)
+            .insert_header(bearer(&owner.token))
+            .insert_header(content_type)
+            .set_payload(form),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        ctx.repos.media.find_by_owner(owner.id).await.unwrap().len(),
+        1
+    );
+}
+
+// =============================================================================================================================
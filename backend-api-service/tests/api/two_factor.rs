@@ -0,0 +1,327 @@
+use crate::common::{PASSWORD, TestApp, TestUser, bearer, call, link_token};
+use actix_http::Request;
+use actix_web::{
+    Error,
+    dev::{Service, ServiceResponse},
+    http::StatusCode,
+    test::TestRequest,
+};
+use backend_api_service::{models::user_model::UserRole, utils::totp};
+use chrono::Utc;
+use serde_json::{Value, json};
+
+// =============================================================================================================================
+
+fn code(secret: &str, steps_ahead: i64) -> String {
+    totp::code_at(secret, totp::step_at(Utc::now().timestamp()) + steps_ahead).unwrap()
+}
+
+/// Enrols and confirms two-factor authentication with the current code. Returns the secret and the recovery codes.
+async fn enable_two_factor(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    user: &TestUser,
+) -> (String, Vec<String>) {
+    let (status, body) = call(
+        app,
+        TestRequest::post()
+            .uri("/api/auth/2fa/enroll")
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let secret = body["data"]["secret"].as_str().unwrap().to_string();
+    assert!(
+        body["data"]["provisioning_uri"]
+            .as_str()
+            .unwrap()
+            .starts_with(&format!(
+                "otpauth://totp/Snapshoot:{}?secret={}",
+                user.username, secret
+            ))
+    );
+
+    let (status, body) = call(
+        app,
+        TestRequest::post()
+            .uri("/api/auth/2fa/confirm")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({ "code": code(&secret, 0) })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let recovery_codes = body["data"]["recovery_codes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|code| code.as_str().unwrap().to_string())
+        .collect();
+
+    (secret, recovery_codes)
+}
+
+async fn login_challenge(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    user: &TestUser,
+) -> String {
+    let (status, body) = call(
+        app,
+        TestRequest::post()
+            .uri("/api/auth/login")
+            .set_json(json!({ "credential": user.username, "password": PASSWORD })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["two_factor_required"], true);
+    assert!(body["data"].get("token").is_none());
+    body["data"]["challenge_token"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+async fn verify(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    challenge_token: &str,
+    code: &str,
+) -> (StatusCode, Value) {
+    call(
+        app,
+        TestRequest::post()
+            .uri("/api/auth/2fa/verify")
+            .set_json(json!({ "challenge_token": challenge_token, "code": code })),
+    )
+    .await
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn totp_codes_match_the_rfc_test_vector() {
+    // RFC 6238 appendix B, SHA-1 secret "12345678901234567890", truncated to six digits.
+    let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    assert_eq!(totp::code_at(secret, totp::step_at(59)).unwrap(), "287082");
+    assert_eq!(totp::verify(secret, "287082", 59).unwrap(), Some(1));
+    assert_eq!(totp::verify(secret, "287082", 59 + 30 * 5).unwrap(), None);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn login_with_two_factor_requires_a_code() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+
+    let (secret, recovery_codes) = enable_two_factor(&app, &alice).await;
+    assert_eq!(recovery_codes.len(), 10);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/auth/2fa")
+            .insert_header(bearer(&alice.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["enabled"], true);
+    assert_eq!(body["data"]["recovery_codes_left"], 10);
+
+    let challenge_token = login_challenge(&app, &alice).await;
+
+    // The challenge is not an access token.
+    let (status, _) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/users/me")
+            .insert_header(bearer(&challenge_token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = verify(&app, &challenge_token, "000000").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // The code used to confirm the enrolment cannot be replayed, the next one is accepted.
+    let (status, _) = verify(&app, &challenge_token, &code(&secret, 0)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = verify(&app, &challenge_token, &code(&secret, 1)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let token = body["data"]["token"].as_str().unwrap();
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/users/me")
+            .insert_header(bearer(token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["username"], "alice");
+
+    let (status, _) = verify(&app, &challenge_token, &code(&secret, 1)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn recovery_codes_work_once_and_can_disable_two_factor() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let (_, recovery_codes) = enable_two_factor(&app, &alice).await;
+
+    let challenge_token = login_challenge(&app, &alice).await;
+    let (status, _) = verify(&app, &challenge_token, &recovery_codes[0].to_uppercase()).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = verify(&app, &challenge_token, &recovery_codes[0]).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::delete()
+            .uri("/api/auth/2fa")
+            .insert_header(bearer(&alice.token))
+            .set_json(json!({ "code": recovery_codes[1] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/auth/login")
+            .set_json(json!({ "credential": "alice", "password": PASSWORD })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["data"]["token"].is_string());
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn wrong_codes_count_towards_the_lockout() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let (secret, _) = enable_two_factor(&app, &alice).await;
+    let challenge_token = login_challenge(&app, &alice).await;
+
+    for _ in 1..ctx.config.auth.lockout_threshold {
+        let (status, _) = verify(&app, &challenge_token, "000000").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+    let (status, _) = verify(&app, &challenge_token, "000000").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    let (status, _) = verify(&app, &challenge_token, &code(&secret, 1)).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn wrong_codes_when_disabling_count_towards_the_lockout() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let (secret, _) = enable_two_factor(&app, &alice).await;
+
+    let disable = |code: String| {
+        TestRequest::delete()
+            .uri("/api/auth/2fa")
+            .insert_header(bearer(&alice.token))
+            .set_json(json!({ "code": code }))
+    };
+
+    for _ in 1..ctx.config.auth.lockout_threshold {
+        let (status, _) = call(&app, disable("000000".into())).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+    let (status, _) = call(&app, disable("000000".into())).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    let (status, _) = call(&app, disable(code(&secret, 1))).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/auth/2fa")
+            .insert_header(bearer(&alice.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["enabled"], true);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn malformed_codes_are_rejected() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let bob = ctx.create_user("bob", UserRole::User).await;
+    enable_two_factor(&app, &alice).await;
+    enable_two_factor(&app, &bob).await;
+    let challenge_token = login_challenge(&app, &alice).await;
+
+    // Ten bytes but five characters, and ten characters that are not ASCII.
+    for code in ["ééééé", "ééééé-ééééé", "", "-----"] {
+        let (status, body) = verify(&app, &challenge_token, code).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+    }
+
+    let (status, body) = call(
+        &app,
+        TestRequest::delete()
+            .uri("/api/auth/2fa")
+            .insert_header(bearer(&bob.token))
+            .set_json(json!({ "code": "ééééé" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn email_verification_links_ask_for_the_second_factor() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let alice = ctx.create_user("alice", UserRole::User).await;
+    let (secret, _) = enable_two_factor(&app, &alice).await;
+    let new_email = "alice.new@snapshoot.test";
+
+    let (status, body) = call(
+        &app,
+        TestRequest::put()
+            .uri("/api/users/me/email")
+            .insert_header(bearer(&alice.token))
+            .set_json(json!({ "current_password": PASSWORD, "email": new_email })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+
+    let token = link_token(&ctx.emails_to(new_email)[0], "/verify-email");
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/auth/verify-email")
+            .set_json(json!({ "token": token })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["two_factor_required"], true);
+    assert!(body["data"].get("token").is_none());
+
+    let challenge_token = body["data"]["challenge_token"].as_str().unwrap();
+    let (status, body) = verify(&app, challenge_token, &code(&secret, 1)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body["data"]["token"].is_string());
+}
+
+// =============================================================================================================================
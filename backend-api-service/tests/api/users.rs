@@ -0,0 +1,973 @@
+use crate::common::{
+    PASSWORD, TestApp, TestUser, bearer, call, link_token, local_path, multipart, object_id, png,
+};
+use actix_http::Request;
+use actix_web::{
+    Error,
+    dev::{Service, ServiceResponse},
+    http::{StatusCode, header},
+    test::{self, TestRequest},
+};
+use backend_api_service::{
+    models::{
+        auth_model::{AuthToken, TokenPurpose},
+        export_model::{DataExport, ExportStatus},
+        media_model::PendingUpload,
+        moderation_model::ReportStatus,
+        two_factor_model::TwoFactor,
+        user_model::{AccountStatus, UserRole},
+    },
+    services::export_service,
+};
+use bson::oid::ObjectId;
+use chrono::{Duration, Utc};
+use serde_json::{Value, json};
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+// =============================================================================================================================
+
+fn user_payload(username: &str, role: &str) -> Value {
+    json!({
+        "username": username,
+        "email": format!("{}@snapshoot.test", username),
+        "password": PASSWORD,
+        "role": role,
+        "bio": "Updated by the tests",
+        "avatar": null,
+        "location": { "type": "Point", "coordinates": [2.3522, 48.8566] }
+    })
+}
+
+/// Requests an export and waits for the background task to build it.
+async fn ready_export(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    user: &TestUser,
+) -> Value {
+    let (status, body) = call(
+        app,
+        TestRequest::post()
+            .uri("/api/users/me/export")
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+    let export_uri = format!(
+        "/api/users/me/export/{}",
+        body["data"]["id"].as_str().unwrap()
+    );
+
+    let mut export = Value::Null;
+    for _ in 0..100 {
+        let (status, body) = call(
+            app,
+            TestRequest::get()
+                .uri(&export_uri)
+                .insert_header(bearer(&user.token)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        export = body["data"].clone();
+        if export["status"] != "Pending" {
+            break;
+        }
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(export["status"], "Ready", "{}", export);
+    export
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn admin_routes_reject_regular_users() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("mallory", UserRole::User).await;
+    let victim = ctx.create_user("victim", UserRole::User).await;
+    let victim_uri = format!("/api/users/{}", victim.id.to_hex());
+
+    let requests = [
+        TestRequest::get().uri("/api/users"),
+        TestRequest::post()
+            .uri("/api/users")
+            .set_json(user_payload("intruder", "Admin")),
+        TestRequest::patch()
+            .uri(&victim_uri)
+            .set_json(json!({ "username": "owned" })),
+        TestRequest::put()
+            .uri(&format!("{}/role", victim_uri))
+            .set_json(json!({ "role": "Admin" })),
+        TestRequest::delete().uri(&victim_uri),
+    ];
+
+    for request in requests {
+        let (status, body) = call(&app, request.insert_header(bearer(&user.token))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["message"], "Access denied: insufficient role");
+    }
+
+    let stored = ctx
+        .repos
+        .users
+        .find_by_id(victim.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.role, UserRole::User);
+    assert_eq!(stored.username, "victim");
+    assert!(
+        ctx.repos
+            .users
+            .find_by_email("intruder@snapshoot.test")
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn admin_routes_reject_anonymous_requests() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+
+    let (status, _) = call(&app, TestRequest::get().uri("/api/users")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/users")
+            .set_json(user_payload("anonymous", "Admin")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn admin_can_list_create_and_update_users() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let admin = ctx.create_user("root", UserRole::Admin).await;
+    ctx.create_user("erin", UserRole::User).await;
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/users")
+            .insert_header(bearer(&admin.token))
+            .set_json(user_payload("frank", "User")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let frank_id = body["data"]["_id"].as_str().unwrap().to_string();
+    assert_ne!(body["data"]["password"], PASSWORD);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::patch()
+            .uri(&format!("/api/users/{}", frank_id))
+            .insert_header(bearer(&admin.token))
+            .set_json(json!({ "username": "franky" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["username"], "franky");
+    assert_eq!(body["data"]["bio"], "Updated by the tests");
+
+    let (status, body) = call(
+        &app,
+        TestRequest::put()
+            .uri(&format!("/api/users/{}/role", frank_id))
+            .insert_header(bearer(&admin.token))
+            .set_json(json!({ "role": "Admin" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["role"], "Admin");
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/users")
+            .insert_header(bearer(&admin.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let mut usernames: Vec<&str> = body["data"]["users"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|user| user["username"].as_str().unwrap())
+        .collect();
+    usernames.sort();
+    assert_eq!(usernames, ["erin", "franky", "root"]);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn changing_the_role_of_a_user_revokes_their_sessions() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let admin = ctx.create_user("root", UserRole::Admin).await;
+    let demoted = ctx.create_user("ops", UserRole::Admin).await;
+
+    let (status, body) = call(
+        &app,
+        TestRequest::put()
+            .uri(&format!("/api/users/{}/role", demoted.id.to_hex()))
+            .insert_header(bearer(&admin.token))
+            .set_json(json!({ "role": "User" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["role"], "User");
+
+    for uri in ["/api/users", "/api/users/me"] {
+        let (status, body) = call(
+            &app,
+            TestRequest::get()
+                .uri(uri)
+                .insert_header(bearer(&demoted.token)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+    }
+
+    let user = ctx
+        .repos
+        .users
+        .find_by_id(demoted.id)
+        .await
+        .unwrap()
+        .unwrap();
+    let demoted = ctx.sign_in(&user).await;
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/users")
+            .insert_header(bearer(&demoted)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/users/me")
+            .insert_header(bearer(&demoted)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn admins_page_through_filtered_users() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let admin = ctx.create_user("root", UserRole::Admin).await;
+    for username in ["alice", "albert", "alfred", "bob"] {
+        ctx.create_user(username, UserRole::User).await;
+    }
+    let suspended = ctx.create_user("carol", UserRole::User).await;
+    ctx.repos
+        .users
+        .update_status(suspended.id, AccountStatus::Suspended, None)
+        .await
+        .unwrap();
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/auth/register")
+            .set_json(json!({
+                "username": "alan",
+                "email": "alan@snapshoot.test",
+                "password": PASSWORD,
+                "bio": "Hello from the tests",
+                "location": { "type": "Point", "coordinates": [4.8156, 45.7107] }
+            })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let list = |query: String| {
+        call(
+            &app,
+            TestRequest::get()
+                .uri(&format!("/api/users?{}", query))
+                .insert_header(bearer(&admin.token)),
+        )
+    };
+    let usernames = |body: &Value| -> Vec<String> {
+        body["data"]["users"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|user| user["username"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let (status, body) = list("search=AL&sort=Username&order=Asc&limit=2".to_string()).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(usernames(&body), ["alan", "albert"]);
+    assert_eq!(body["data"]["total"], 4);
+    assert_eq!(
+        body["data"]["counts"],
+        json!({ "users": 7, "admins": 1, "unverified": 1, "suspended": 1, "banned": 0 })
+    );
+
+    let cursor = body["data"]["next_cursor"].as_str().unwrap().to_string();
+    let (_, body) = list(format!(
+        "search=AL&sort=Username&order=Asc&limit=2&cursor={}",
+        cursor
+    ))
+    .await;
+    assert_eq!(usernames(&body), ["alfred", "alice"]);
+    assert_eq!(body["data"]["next_cursor"], Value::Null);
+
+    // Newest first by default.
+    let (_, body) = list("verified=false".to_string()).await;
+    assert_eq!(usernames(&body), ["alan"]);
+    let (_, body) = list("status=Suspended".to_string()).await;
+    assert_eq!(usernames(&body), ["carol"]);
+    let (_, body) = list("role=User&status=Active&limit=3".to_string()).await;
+    assert_eq!(usernames(&body), ["alan", "bob", "alfred"]);
+    assert_eq!(body["data"]["total"], 5);
+
+    let (status, _) = list("cursor=not-a-cursor".to_string()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn users_can_read_profiles_and_update_their_own() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("grace", UserRole::User).await;
+    let other = ctx.create_user("heidi", UserRole::User).await;
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri(&format!("/api/users/{}", other.id.to_hex()))
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["username"], "heidi");
+
+    let (status, _) = call(
+        &app,
+        TestRequest::get().uri(&format!("/api/users/{}", other.id.to_hex())),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Fields outside the profile, like the role, are ignored.
+    let (status, body) = call(
+        &app,
+        TestRequest::patch()
+            .uri("/api/users/me")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({ "bio": "Updated by the tests", "role": "Admin" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["bio"], "Updated by the tests");
+    assert_eq!(body["data"]["username"], "grace");
+    assert_eq!(body["data"]["role"], "User");
+
+    let (status, _) = call(
+        &app,
+        TestRequest::patch()
+            .uri("/api/users/me")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({ "username": "heidi" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/users/me")
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["bio"], "Updated by the tests");
+    assert_eq!(body["data"]["role"], "User");
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn changing_the_password_requires_the_current_one() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("ivan", UserRole::User).await;
+    let new_password = "another-long-password";
+
+    let change = |current_password: &str| {
+        TestRequest::put()
+            .uri("/api/users/me/password")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({ "current_password": current_password, "new_password": new_password }))
+    };
+
+    let (status, _) = call(&app, change("not-the-password")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, body) = call(&app, change(PASSWORD)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let stored = ctx.repos.users.find_by_id(user.id).await.unwrap().unwrap();
+    assert!(bcrypt::verify(new_password, &stored.password).unwrap());
+
+    let login = |password: &str| {
+        TestRequest::post()
+            .uri("/api/auth/login")
+            .set_json(json!({ "credential": "ivan", "password": password }))
+    };
+    let (status, _) = call(&app, login(PASSWORD)).await;
+    assert_ne!(status, StatusCode::OK);
+    let (status, _) = call(&app, login(new_password)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn changing_the_email_verifies_the_new_address_first() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("judy", UserRole::User).await;
+    ctx.create_user("karl", UserRole::User).await;
+    let new_email = "judy.new@snapshoot.test";
+
+    let change = |email: &str, current_password: &str| {
+        TestRequest::put()
+            .uri("/api/users/me/email")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({ "current_password": current_password, "email": email }))
+    };
+
+    let (status, _) = call(&app, change(new_email, "not-the-password")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = call(&app, change("karl@snapshoot.test", PASSWORD)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = call(&app, change(new_email, PASSWORD)).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+    assert_eq!(body["data"]["email"], new_email);
+
+    // The account keeps its address until the link is opened.
+    let stored = ctx.repos.users.find_by_id(user.id).await.unwrap().unwrap();
+    assert_eq!(stored.email, user.email);
+
+    let emails = ctx.emails_to(new_email);
+    assert_eq!(emails.len(), 1);
+    let token = link_token(&emails[0], "/verify-email");
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/auth/verify-email")
+            .set_json(json!({ "token": token })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let stored = ctx.repos.users.find_by_id(user.id).await.unwrap().unwrap();
+    assert_eq!(stored.email, new_email);
+    assert!(stored.verified);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn unknown_or_malformed_user_ids_are_errors() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("ivan", UserRole::User).await;
+
+    for id in ["not-an-id", "000000000000000000000000"] {
+        let (status, body) = call(
+            &app,
+            TestRequest::get()
+                .uri(&format!("/api/users/{}", id))
+                .insert_header(bearer(&user.token)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["success"], false);
+    }
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn account_deletion_cascades_to_everything_the_user_owns() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("judy", UserRole::User).await;
+    let friend = ctx.create_user("friend", UserRole::User).await;
+    let admin = ctx.create_user("root", UserRole::Admin).await;
+    ctx.befriend(&user, &friend).await;
+
+    let (content_type, form) = multipart(&[], ("image/png", &png(24, 24)));
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/media")
+            .insert_header(bearer(&user.token))
+            .insert_header(content_type)
+            .set_payload(form),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/stories")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({
+                "media_id": object_id(&body["data"]["_id"]),
+                "location": { "type": "Point", "coordinates": [4.8357, 45.7640] }
+            })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+
+    let (content_type, form) = multipart(&[], ("image/png", &png(16, 16)));
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!("/api/messages/{}/media", friend.id.to_hex()))
+            .insert_header(bearer(&user.token))
+            .insert_header(content_type)
+            .set_payload(form),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+
+    let mut groups = Vec::new();
+    for (creator, member) in [(&user, &friend), (&friend, &user)] {
+        let (status, body) = call(
+            &app,
+            TestRequest::post()
+                .uri("/api/groups")
+                .insert_header(bearer(&creator.token))
+                .set_json(json!({ "name": "Crew", "members": [member.id.to_hex()] })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        groups.push(ObjectId::parse_str(object_id(&body["data"]["_id"])).unwrap());
+    }
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri(&format!("/api/messages/groups/{}", groups[0].to_hex()))
+            .insert_header(bearer(&friend.token))
+            .set_json(json!({ "content": "Hello crew" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/users/me/storage")
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["breakdown"]["stories"]["count"], 1);
+    assert_eq!(body["data"]["breakdown"]["direct_messages"]["count"], 1);
+
+    let (status, body) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/uploads/sessions")
+            .insert_header(bearer(&user.token))
+            .set_json(json!({ "content_type": "video/mp4", "size": 1024 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    ready_export(&app, &user).await;
+    let archive_key = ctx
+        .repos
+        .exports
+        .find_by_user(user.id)
+        .await
+        .unwrap()
+        .into_iter()
+        .find_map(|export| export.archive_url)
+        .map(|url| ctx.storage.object_key_from_url(&url).unwrap().to_string())
+        .unwrap();
+    ctx.repos
+        .two_factor
+        .save(TwoFactor {
+            user_id: user.id,
+            secret: "JBSWY3DPEHPK3PXP".to_string(),
+            enabled: true,
+            recovery_codes: Vec::new(),
+            last_used_step: None,
+        })
+        .await
+        .unwrap();
+    ctx.repos
+        .auth_tokens
+        .replace(AuthToken {
+            token_hash: "hash".to_string(),
+            user_id: user.id,
+            purpose: TokenPurpose::PasswordReset,
+            email: user.email.clone(),
+            expires_at: Utc::now() + Duration::hours(1),
+        })
+        .await
+        .unwrap();
+    ctx.repos
+        .login_attempts
+        .record_failure(user.id, 5, Utc::now())
+        .await
+        .unwrap();
+    let pending_key = format!("uploads/{}/pending.png", user.id.to_hex());
+    ctx.storage
+        .put(&pending_key, png(8, 8), "image/png")
+        .await
+        .unwrap();
+    ctx.repos
+        .pending_uploads
+        .insert(PendingUpload {
+            id: None,
+            owner_id: user.id,
+            key: pending_key.clone(),
+            size: 0,
+            expires_at: Utc::now() + Duration::hours(1),
+            confirming_until: None,
+        })
+        .await
+        .unwrap();
+    for (reporter, target) in [(&user, &friend), (&friend, &user)] {
+        let (status, body) = call(
+            &app,
+            TestRequest::post()
+                .uri("/api/reports")
+                .insert_header(bearer(&reporter.token))
+                .set_json(json!({
+                    "target": "User",
+                    "target_id": target.id.to_hex(),
+                    "reason": "Offensive"
+                })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+    }
+
+    let (status, body) = call(
+        &app,
+        TestRequest::delete()
+            .uri("/api/users/me?dry_run=true")
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let report = &body["data"];
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(report["users"], 1);
+    assert_eq!(report["friendships"], 1);
+    assert_eq!(report["groups"], 1);
+    assert_eq!(report["group_memberships"], 1);
+    assert_eq!(report["messages"], 2);
+    assert_eq!(report["stories"], 1);
+    assert_eq!(report["sessions"], 1);
+    assert_eq!(report["two_factor"], 1);
+    assert_eq!(report["auth_tokens"], 1);
+    assert_eq!(report["login_attempts"], 1);
+    assert_eq!(report["upload_sessions"], 1);
+    assert_eq!(report["pending_uploads"], 1);
+    assert_eq!(report["exports"], 1);
+    assert_eq!(report["reports"], 2);
+    assert_eq!(body["message"], "Dry run, the user was not deleted");
+    assert_eq!(report["media"].as_array().unwrap().len(), 4);
+    assert!(ctx.repos.users.find_by_id(user.id).await.unwrap().is_some());
+    assert_eq!(
+        ctx.repos.media.find_by_owner(user.id).await.unwrap().len(),
+        2
+    );
+
+    let (status, body) = call(
+        &app,
+        TestRequest::delete()
+            .uri(&format!("/api/users/{}", user.id.to_hex()))
+            .insert_header(bearer(&admin.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["messages"], 2);
+    assert_eq!(body["data"]["media_failures"], json!([]));
+
+    assert!(ctx.repos.users.find_by_id(user.id).await.unwrap().is_none());
+    assert!(
+        ctx.repos
+            .exports
+            .find_by_user(user.id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(ctx.storage.head(&archive_key).await.is_err());
+    assert!(ctx.storage.head(&pending_key).await.is_err());
+    assert!(
+        ctx.repos
+            .pending_uploads
+            .find_by_owner(user.id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        ctx.repos
+            .reports
+            .find_by_status(ReportStatus::Open)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        ctx.repos
+            .upload_sessions
+            .find_by_owner(user.id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(ctx.repos.two_factor.find(user.id).await.unwrap().is_none());
+    assert!(
+        ctx.repos
+            .login_attempts
+            .find(user.id)
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        ctx.repos
+            .media
+            .find_by_owner(user.id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        ctx.repos
+            .messages
+            .find_by_sender(user.id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        ctx.repos
+            .stories
+            .find_by_user(user.id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        ctx.repos
+            .friends
+            .find_involving(&[friend.id])
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        ctx.repos
+            .groups
+            .find_by_id(groups[0])
+            .await
+            .unwrap()
+            .is_none()
+    );
+    let remaining = ctx
+        .repos
+        .groups
+        .find_by_id(groups[1])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(remaining.members, [friend.id]);
+
+    let (status, _) = call(
+        &app,
+        TestRequest::get()
+            .uri("/api/users/me")
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn users_can_download_an_export_of_their_data() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("kim", UserRole::User).await;
+    let other = ctx.create_user("lee", UserRole::User).await;
+    ctx.befriend(&user, &other).await;
+
+    let (content_type, form) = multipart(&[], ("image/png", &png(24, 24)));
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/api/media")
+            .insert_header(bearer(&user.token))
+            .insert_header(content_type)
+            .set_payload(form),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let export = ready_export(&app, &user).await;
+    let (status, _) = call(
+        &app,
+        TestRequest::get()
+            .uri(&format!(
+                "/api/users/me/export/{}",
+                export["id"].as_str().unwrap()
+            ))
+            .insert_header(bearer(&other.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let download_uri = export["download_url"].as_str().unwrap().to_string();
+
+    let (status, _) = call(&app, TestRequest::get().uri(&format!("{}x", download_uri))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let response =
+        test::call_service(&app, TestRequest::get().uri(&download_uri).to_request()).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    let location = response.headers().get(header::LOCATION).unwrap();
+    let response = test::call_service(
+        &app,
+        TestRequest::get()
+            .uri(&local_path(location.to_str().unwrap()))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(header::CONTENT_TYPE).unwrap(),
+        "application/zip"
+    );
+    let archive = test::read_body(response).await;
+    let mut archive = ZipArchive::new(Cursor::new(archive.to_vec())).unwrap();
+    let names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    for name in [
+        "profile.json",
+        "friendships.json",
+        "groups.json",
+        "messages.json",
+        "stories.json",
+    ] {
+        assert!(names.iter().any(|file| file == name), "{:?}", names);
+    }
+    assert!(
+        names.iter().any(|file| file.starts_with("media/")),
+        "{:?}",
+        names
+    );
+
+    let mut profile = String::new();
+    archive
+        .by_name("profile.json")
+        .unwrap()
+        .read_to_string(&mut profile)
+        .unwrap();
+    assert!(profile.contains("kim@snapshoot.test"));
+}
+
+// =============================================================================================================================
+
+#[actix_web::test]
+async fn abandoned_and_expired_exports_are_cleaned_up() {
+    let ctx = TestApp::new().await;
+    let app = ctx.service().await;
+    let user = ctx.create_user("max", UserRole::User).await;
+
+    let abandoned = ctx
+        .repos
+        .exports
+        .insert(DataExport {
+            id: None,
+            user_id: user.id,
+            status: ExportStatus::Pending,
+            archive_url: None,
+            download_token: None,
+            error: None,
+            created_at: Utc::now() - Duration::hours(2),
+            expires_at: None,
+        })
+        .await
+        .unwrap();
+
+    let export = ready_export(&app, &user).await;
+    let (status, body) = call(
+        &app,
+        TestRequest::get()
+            .uri(&format!(
+                "/api/users/me/export/{}",
+                abandoned.id.unwrap().to_hex()
+            ))
+            .insert_header(bearer(&user.token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["status"], "Failed");
+
+    let ready = ctx
+        .repos
+        .exports
+        .find_for_user(object_id(&export["id"]).parse().unwrap(), user.id)
+        .await
+        .unwrap()
+        .unwrap();
+    let archive_url = ready.archive_url.unwrap();
+    ctx.repos
+        .exports
+        .mark_ready(
+            ready.id.unwrap(),
+            &archive_url,
+            &ready.download_token.unwrap(),
+            Utc::now() - Duration::minutes(1),
+        )
+        .await
+        .unwrap();
+
+    let swept = export_service::sweep_expired(&ctx.repos, ctx.storage.as_ref())
+        .await
+        .unwrap();
+    assert_eq!(swept, 1);
+    let archive_key = ctx.storage.object_key_from_url(&archive_url).unwrap();
+    assert!(ctx.storage.get(archive_key).await.is_err());
+    assert_eq!(
+        ctx.repos.exports.find_by_user(user.id).await.unwrap().len(),
+        1
+    );
+}
+
+// =============================================================================================================================
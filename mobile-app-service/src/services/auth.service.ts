@@ -454,7 +454,7 @@ export const updateUserProfile = async (userData: Partial<User>): Promise<User>
       }
 
       const response = await fetch(`${API_URL}/users/me`, {
-        method: "PUT",
+        method: "PATCH",
         headers: {
           ...(token ? { Authorization: `Bearer ${token}` } : {}),
           "Content-Type": "application/json",